@@ -1,7 +1,88 @@
-use std::error::Error;
+use prost::Message;
+use prost_types::{field_descriptor_proto::Type, DescriptorProto, FileDescriptorSet};
+use std::{env, error::Error, fmt::Write, fs, path::PathBuf};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    prost_build::compile_protos(&["proto/messages.proto"], &["proto/"])?;
+    // Keep the compiled descriptor next to the generated code, `protocol::describe` embeds it
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    let descriptor_path = out_dir.join("messages.bin");
+    prost_build::Config::new()
+        .file_descriptor_set_path(&descriptor_path)
+        // With the `serde` feature messages convert to and from JSON, TOML and the like; fields
+        // missing from the input keep their protobuf defaults
+        .type_attribute(".", "#[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]")
+        .message_attribute(".", "#[cfg_attr(feature = \"serde\", serde(default))]")
+        .compile_protos(&["proto/messages.proto"], &["proto/"])?;
+
+    // Every request in ClientMessage gets a typed client method
+    let set = FileDescriptorSet::decode(&fs::read(&descriptor_path)?[..])?;
+    fs::write(out_dir.join("client_stubs.rs"), client_stubs(&set)?)?;
 
     Ok(())
 }
+
+// Generate the `ClientStubs` trait from the ClientMessage and ServerMessage oneofs
+fn client_stubs(set: &FileDescriptorSet) -> Result<String, Box<dyn Error>> {
+    let messages: Vec<&DescriptorProto> = set.file.iter().flat_map(|file| &file.message_type).collect();
+    let find = |name: &str| {
+        messages
+            .iter()
+            .find(|message| message.name() == name)
+            .ok_or_else(|| format!("{} missing from the schema", name))
+    };
+    let requests = oneof_arms(find("ClientMessage")?);
+    let responses = oneof_arms(find("ServerMessage")?);
+
+    let mut code = String::new();
+    writeln!(code, "/// Typed client methods, one per request in `ClientMessage`, generated from the schema")?;
+    writeln!(code, "pub trait ClientStubs {{")?;
+    writeln!(code, "    /// Sends one request and returns the server's reply")?;
+    writeln!(code, "    fn call(&mut self, message: client_message::Message) -> std::io::Result<ServerMessage>;")?;
+    for (field, variant, request) in &requests {
+        // A request is answered with the same type (echo) or with its `...Response` counterpart
+        let counterpart = request.strip_suffix("Request").map(|name| format!("{}Response", name));
+        let response = responses
+            .iter()
+            .find(|(_, _, response)| response == request || Some(response) == counterpart.as_ref());
+        writeln!(code)?;
+        match response {
+            Some((_, response_variant, response)) => {
+                writeln!(code, "    /// Sends `request` and waits for the `{}`", response)?;
+                writeln!(code, "    fn {}(&mut self, request: {}) -> std::io::Result<{}> {{", field, request, response)?;
+                writeln!(code, "        match self.call(client_message::Message::{}(request))?.message {{", variant)?;
+                writeln!(code, "            Some(server_message::Message::{}(response)) => Ok(response),", response_variant)?;
+                writeln!(code, "            other => Err(unexpected_response(other)),")?;
+                writeln!(code, "        }}")?;
+            }
+            None => {
+                writeln!(code, "    /// Sends `request` and returns the server's reply")?;
+                writeln!(code, "    fn {}(&mut self, request: {}) -> std::io::Result<ServerMessage> {{", field, request)?;
+                writeln!(code, "        self.call(client_message::Message::{}(request))", variant)?;
+            }
+        }
+        writeln!(code, "    }}")?;
+    }
+    writeln!(code, "}}")?;
+    Ok(code)
+}
+
+// Field name, prost variant name and message type of every message arm of a oneof
+fn oneof_arms(message: &DescriptorProto) -> Vec<(String, String, String)> {
+    message
+        .field
+        .iter()
+        .filter(|field| field.oneof_index.is_some() && field.r#type() == Type::Message)
+        .map(|field| {
+            let variant = field
+                .name()
+                .split('_')
+                .map(|part| {
+                    let mut chars = part.chars();
+                    chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+                })
+                .collect();
+            let type_name = field.type_name().rsplit('.').next().unwrap_or_default().to_string();
+            (field.name().to_string(), variant, type_name)
+        })
+        .collect()
+}
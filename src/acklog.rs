@@ -0,0 +1,232 @@
+// Import necessary modules and crates
+#[cfg(feature = "storage")]
+use crate::hex::{from_hex, to_hex}; // Log file lines
+use crate::message::ServerMessage; // Responses stored for replay
+#[cfg(feature = "storage")]
+use prost::Message; // Protobuf message encoding/decoding
+use std::{
+    collections::{HashMap, VecDeque}, // Entries and their insertion order
+    io,
+};
+#[cfg(feature = "storage")]
+use log::warn; // Logging macros
+#[cfg(feature = "storage")]
+use std::{
+    fs::{self, File, OpenOptions}, // Log file handling
+    io::{BufRead, BufReader, ErrorKind, Write}, // Log file reading and writing
+    path::{Path, PathBuf}, // Log file location
+};
+
+// Number of commands remembered before the oldest ones are forgotten
+const DEFAULT_CAPACITY: usize = 10_000;
+
+// Command id of the entries written by `probe`, no client sends a NUL in its command ids
+#[cfg(feature = "storage")]
+const PROBE_COMMAND_ID: &str = "\0probe";
+
+/// Stale entries a persistent log may hold before it is rewritten with only the remembered commands, see
+/// `AckLog::open_compacting_after`. The file then never holds more than twice the remembered commands
+pub const DEFAULT_COMPACT_AFTER: usize = DEFAULT_CAPACITY;
+
+/// Responses of completed commands by command id, so a retried command is answered without running it again
+#[derive(Debug)]
+pub struct AckLog {
+    entries: HashMap<String, ServerMessage>, // Response of each completed command
+    order: VecDeque<String>, // Command ids oldest first, for eviction
+    capacity: usize, // Maximum number of remembered commands
+    discarded: usize, // Torn entries dropped when the log was opened
+    #[cfg(feature = "storage")]
+    file: Option<(PathBuf, File)>, // Append-only log the entries are persisted to
+    #[cfg(feature = "storage")]
+    lines: usize, // Entries in the file, forgotten ones included
+    #[cfg(feature = "storage")]
+    compact_after: usize, // Stale entries the file may hold before it is rewritten
+    #[cfg(feature = "storage")]
+    torn: bool, // A failed write left part of an entry that couldn't be cut off, the file is rewritten first
+}
+
+impl AckLog {
+    /// Creates a log kept only in memory, it doesn't survive a restart
+    pub fn in_memory() -> Self {
+        AckLog {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: DEFAULT_CAPACITY,
+            discarded: 0,
+            #[cfg(feature = "storage")]
+            file: None,
+            #[cfg(feature = "storage")]
+            lines: 0,
+            #[cfg(feature = "storage")]
+            compact_after: DEFAULT_COMPACT_AFTER,
+            #[cfg(feature = "storage")]
+            torn: false,
+        }
+    }
+
+    /// Opens or creates a persistent log at `path`, loading the commands completed before a restart. A last
+    /// entry cut off by a crash is dropped, see `discarded`; corruption anywhere else is an error
+    #[cfg(feature = "storage")]
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::open_compacting_after(path, DEFAULT_COMPACT_AFTER)
+    }
+
+    /// Like `open`, rewriting the file with only the remembered commands whenever it holds more than `entries`
+    /// stale ones, of forgotten commands or older responses of re-recorded ones, so it doesn't grow while the
+    /// server runs
+    #[cfg(feature = "storage")]
+    pub fn open_compacting_after(path: &Path, entries: usize) -> io::Result<Self> {
+        let mut log = Self::in_memory();
+        log.compact_after = entries;
+        match File::open(path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+                let mut line = String::new();
+                let mut index = 0;
+                while reader.read_line(&mut line)? > 0 {
+                    index += 1;
+                    // Entries are synced before the command is answered, so a torn last line was never
+                    // acknowledged and the client retries it
+                    if !line.ends_with('\n') {
+                        warn!("Dropping torn entry at line {} of acknowledgement log {}", index, path.display());
+                        log.discarded += 1;
+                        break;
+                    }
+                    let (id, response) = parse_line(line.trim_end()).ok_or_else(|| {
+                        io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("Corrupt acknowledgement log {} at line {}", path.display(), index),
+                        )
+                    })?;
+                    log.insert(id, response);
+                    line.clear();
+                }
+            }
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        // Rewrite the file with only the retained entries, so it doesn't grow across restarts
+        let file = log.compact(path)?;
+        log.file = Some((path.to_path_buf(), file));
+        Ok(log)
+    }
+
+    // Rewrite the file at `path` with only the remembered entries and open it for appending
+    #[cfg(feature = "storage")]
+    fn compact(&mut self, path: &Path) -> io::Result<File> {
+        let compacted = path.with_extension("compact");
+        {
+            let mut file = File::create(&compacted)?;
+            for id in &self.order {
+                file.write_all(format_line(id, &self.entries[id]).as_bytes())?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&compacted, path)?;
+        self.lines = self.order.len();
+        self.torn = false;
+        OpenOptions::new().append(true).open(path)
+    }
+
+    /// Returns the stored response of a completed command
+    pub fn get(&self, command_id: &str) -> Option<&ServerMessage> {
+        self.entries.get(command_id)
+    }
+
+    /// Records the response of a completed command, persisting it before returning. A file with too many
+    /// stale entries is compacted afterwards. The command ran, so it is remembered even if persisting fails;
+    /// a failed write leaves no partial entry in the file
+    pub fn record(&mut self, command_id: &str, response: &ServerMessage) -> io::Result<()> {
+        self.insert(command_id.to_string(), response.clone());
+        #[cfg(feature = "storage")]
+        if self.torn {
+            if let Some(path) = self.file.as_ref().map(|(path, _)| path.clone()) {
+                let file = self.compact(&path)?;
+                self.file = Some((path, file));
+                // The entry is in the rewritten file already
+                return Ok(());
+            }
+        }
+        #[cfg(feature = "storage")]
+        if let Some((path, file)) = self.file.as_mut() {
+            let length = file.metadata()?.len();
+            let written = file
+                .write_all(format_line(command_id, response).as_bytes())
+                .and_then(|()| file.sync_data());
+            if let Err(e) = written {
+                // Cut the partial entry off, so later entries aren't appended behind it
+                if let Err(truncate) = file.set_len(length).and_then(|()| file.sync_data()) {
+                    warn!("Failed to cut a partial entry off acknowledgement log {}: {}", path.display(), truncate);
+                    self.torn = true;
+                }
+                return Err(e);
+            }
+            self.lines += 1;
+        }
+        #[cfg(feature = "storage")]
+        if self.lines.saturating_sub(self.order.len()) > self.compact_after {
+            if let Some(path) = self.file.as_ref().map(|(path, _)| path.clone()) {
+                let file = self.compact(&path)?;
+                self.file = Some((path, file));
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends an entry under a command id of its own and syncs it, to check the storage still takes writes.
+    /// Each probe replaces the previous one, compaction removes the old lines
+    #[cfg(feature = "storage")]
+    pub fn probe(&mut self) -> io::Result<()> {
+        self.record(PROBE_COMMAND_ID, &ServerMessage::default())
+    }
+
+    /// Number of entries in the persistent file, including those of forgotten or re-recorded commands
+    /// until the next compaction; 0 for a log kept in memory
+    #[cfg(feature = "storage")]
+    pub fn file_entries(&self) -> usize {
+        self.lines
+    }
+
+    /// Number of torn entries dropped when the log was opened, at most one after a crash mid-write
+    pub fn discarded(&self) -> usize {
+        self.discarded
+    }
+
+    /// Number of remembered commands
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no command is remembered
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Add an entry, forgetting the oldest one when over capacity
+    fn insert(&mut self, command_id: String, response: ServerMessage) {
+        if self.entries.insert(command_id.clone(), response).is_none() {
+            self.order.push_back(command_id);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+// One entry per line: hex command id, a space and the hex encoded response
+#[cfg(feature = "storage")]
+fn format_line(command_id: &str, response: &ServerMessage) -> String {
+    format!("{} {}\n", to_hex(command_id.as_bytes()), to_hex(&response.encode_to_vec()))
+}
+
+// Parse a line written by `format_line`
+#[cfg(feature = "storage")]
+fn parse_line(line: &str) -> Option<(String, ServerMessage)> {
+    let (id, response) = line.split_once(' ')?;
+    let id = String::from_utf8(from_hex(id)?).ok()?;
+    let response = ServerMessage::decode(&from_hex(response)?[..]).ok()?;
+    Some((id, response))
+}
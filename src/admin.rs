@@ -0,0 +1,156 @@
+// Import necessary modules and crates
+use crate::health::escape_json; // Reasons in the JSON state and errors
+use crate::http::{self, Response}; // Shared HTTP loop
+use crate::server::Server; // Server the operator controls
+use std::{
+    env, io,
+    net::TcpListener, // Admin endpoint
+    path::PathBuf, // Capture files
+    sync::atomic::AtomicBool, // Running flag of the server
+    time::Duration, // Maintenance retry delay and capture length
+};
+
+// Length of a capture started without `duration_ms`
+const DEFAULT_CAPTURE: Duration = Duration::from_secs(60);
+
+// Operator request to the admin endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AdminRequest {
+    State, // GET /state
+    EnterMaintenance { retry_after: Duration, reason: String }, // POST /maintenance?retry_after_ms=...&reason=...
+    LeaveMaintenance, // DELETE /maintenance
+    EnterReadOnly { reason: String }, // POST /read-only?reason=...
+    LeaveReadOnly, // DELETE /read-only
+    StartCapture { peer: String, duration: Duration }, // POST /capture?peer=...&duration_ms=...
+    StopCapture { peer: String }, // DELETE /capture?peer=...
+}
+
+// Serve the admin requests for `server` on `listener` until `is_running` turns false
+pub(crate) fn serve_http(listener: TcpListener, is_running: &AtomicBool, server: &Server) -> io::Result<()> {
+    http::serve("Admin", listener, is_running, |method, target| match parse(method, target) {
+        Ok(request) => respond(server, request),
+        Err(response) => response,
+    })
+}
+
+// Take the action of `request` and answer with the resulting state
+fn respond(server: &Server, request: AdminRequest) -> Response {
+    match request {
+        AdminRequest::State => {}
+        AdminRequest::EnterMaintenance { retry_after, reason } => server.enter_maintenance(retry_after, &reason),
+        AdminRequest::LeaveMaintenance => server.leave_maintenance(),
+        AdminRequest::EnterReadOnly { reason } => server.enter_read_only(&reason),
+        AdminRequest::LeaveReadOnly => server.leave_read_only(),
+        AdminRequest::StartCapture { peer, duration } => {
+            if let Err(e) = server.capture_connection(&peer, capture_path(&peer), duration) {
+                let detail = format!("Failed to capture {}: {}", peer, e);
+                return ("500 Internal Server Error", "application/json", error_json(&detail));
+            }
+        }
+        AdminRequest::StopCapture { peer } => {
+            server.stop_capture(&peer);
+        }
+    }
+    ("200 OK", "application/json", state_json(server))
+}
+
+// File a capture started over the admin endpoint goes to, in the temporary directory and named after the peer,
+// so the endpoint can't be used to write anywhere else
+fn capture_path(peer: &str) -> PathBuf {
+    let name: String = peer.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '_' }).collect();
+    env::temp_dir().join(format!("capture-{}.log", name))
+}
+
+// Modes of the server as a JSON object
+fn state_json(server: &Server) -> String {
+    let read_only = match server.read_only_reason() {
+        Some(reason) => format!("\"{}\"", escape_json(&reason)),
+        None => "null".to_string(),
+    };
+    let captures: Vec<String> =
+        server.captured_connections().iter().map(|peer| format!("\"{}\"", escape_json(peer))).collect();
+    format!(
+        "{{\"maintenance\":{},\"read_only\":{},\"captures\":[{}]}}",
+        server.in_maintenance(),
+        read_only,
+        captures.join(",")
+    )
+}
+
+// Request for a method and target such as "POST /maintenance?reason=firmware", or the error to answer with
+fn parse(method: &str, target: &str) -> Result<AdminRequest, Response> {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| decode(value))
+    };
+    match (method, path) {
+        ("GET", "/state") => Ok(AdminRequest::State),
+        ("POST", "/maintenance") => {
+            let retry_after_ms = match param("retry_after_ms") {
+                Some(value) => value.parse().map_err(|_| bad_request("retry_after_ms must be a number"))?,
+                None => 0,
+            };
+            Ok(AdminRequest::EnterMaintenance {
+                retry_after: Duration::from_millis(retry_after_ms),
+                reason: param("reason").unwrap_or_default(),
+            })
+        }
+        ("DELETE", "/maintenance") => Ok(AdminRequest::LeaveMaintenance),
+        ("POST", "/read-only") => Ok(AdminRequest::EnterReadOnly {
+            reason: param("reason").unwrap_or_else(|| "entered by an operator".to_string()),
+        }),
+        ("DELETE", "/read-only") => Ok(AdminRequest::LeaveReadOnly),
+        ("POST", "/capture") => {
+            let duration = match param("duration_ms") {
+                Some(value) => {
+                    let duration_ms = value.parse().map_err(|_| bad_request("duration_ms must be a number"))?;
+                    Duration::from_millis(duration_ms)
+                }
+                None => DEFAULT_CAPTURE,
+            };
+            let peer = param("peer").ok_or_else(|| bad_request("peer is required"))?;
+            Ok(AdminRequest::StartCapture { peer, duration })
+        }
+        ("DELETE", "/capture") => Ok(AdminRequest::StopCapture {
+            peer: param("peer").ok_or_else(|| bad_request("peer is required"))?,
+        }),
+        _ => Err(http::not_found()),
+    }
+}
+
+// Decode a percent-encoded query value, '+' stands for a space
+fn decode(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let hex = tail
+            .get(..2)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (byte, hex) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(if byte == b'+' { b' ' } else { byte });
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// Answer for a request with invalid parameters
+fn bad_request(message: &str) -> Response {
+    ("400 Bad Request", "application/json", error_json(message))
+}
+
+// JSON body of an error answer
+fn error_json(message: &str) -> String {
+    format!("{{\"error\":\"{}\"}}", escape_json(message))
+}
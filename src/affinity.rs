@@ -0,0 +1,85 @@
+// Import necessary modules and crates
+use crate::config::ThreadPriority; // Priority settings from the server config
+use log::warn; // Logging macros
+use std::io;
+
+/// Restricts the calling thread to the given CPU cores
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+pub fn pin_current_thread(cores: &[usize]) -> io::Result<()> {
+    // SAFETY: cpu_set_t is a plain bit mask, zeroed is the empty set and CPU_SET only writes inside it
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for &core in cores {
+            if core >= libc::CPU_SETSIZE as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("CPU core {} is out of range", core),
+                ));
+            }
+            libc::CPU_SET(core, &mut set);
+        }
+        // A pid of 0 means the calling thread
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Restricts the calling thread to the given CPU cores
+#[cfg(not(all(feature = "affinity", target_os = "linux")))]
+pub fn pin_current_thread(_cores: &[usize]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "CPU pinning needs the `affinity` feature on Linux",
+    ))
+}
+
+/// Changes the scheduling priority of the calling thread
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+pub fn set_current_thread_priority(priority: ThreadPriority) -> io::Result<()> {
+    let result = match priority {
+        // SAFETY: plain syscalls on the calling thread, no pointers besides the local sched_param
+        ThreadPriority::Nice(nice) => unsafe {
+            // On Linux the nice value is per thread, addressed by its kernel thread id
+            libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, nice)
+        },
+        ThreadPriority::RealTime(priority) => unsafe {
+            // Some libcs (musl) have extra reserved fields, so start from zero rather than a literal
+            let mut param: libc::sched_param = std::mem::zeroed();
+            param.sched_priority = priority;
+            libc::sched_setscheduler(0, libc::SCHED_FIFO, &param)
+        },
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Changes the scheduling priority of the calling thread
+#[cfg(not(all(feature = "affinity", target_os = "linux")))]
+pub fn set_current_thread_priority(_priority: ThreadPriority) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Thread priorities need the `affinity` feature on Linux",
+    ))
+}
+
+// Apply the pinning and priority settings for one thread, failures are logged and the thread keeps running
+pub(crate) fn configure_current_thread(role: &str, cores: &[usize], priority: Option<ThreadPriority>) {
+    if !cores.is_empty() {
+        if let Err(e) = pin_current_thread(cores) {
+            warn!("Failed to pin {} thread to cores {:?}: {}", role, cores, e);
+        }
+    }
+    if let Some(priority) = priority {
+        if let Err(e) = set_current_thread_priority(priority) {
+            warn!("Failed to set {} thread priority to {:?}: {}", role, priority, e);
+        }
+    }
+}
@@ -0,0 +1,29 @@
+// Import necessary modules and crates
+use embedded_recruitment_task::conformance; // The check battery
+use std::{env, process, time::Duration};
+
+// Wait for each response when no timeout is given on the command line
+const DEFAULT_TIMEOUT_MS: u64 = 2000;
+
+// Check the server at the address given as the first argument, exiting with 1 if it fails any check
+fn main() {
+    let mut args = env::args().skip(1);
+    let Some(addr) = args.next() else {
+        eprintln!("Usage: conformance <host:port> [timeout_ms]");
+        process::exit(2);
+    };
+    let timeout_ms = match args.next().map(|arg| arg.parse()) {
+        None => DEFAULT_TIMEOUT_MS,
+        Some(Ok(timeout_ms)) => timeout_ms,
+        Some(Err(e)) => {
+            eprintln!("Invalid timeout: {}", e);
+            process::exit(2);
+        }
+    };
+
+    let report = conformance::run(&addr, Duration::from_millis(timeout_ms));
+    println!("{}", report);
+    if !report.passed() {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,22 @@
+// Import necessary modules and crates
+use embedded_recruitment_task::server::Server; // The server itself
+use std::{env, process};
+
+// Address served when none is given on the command line
+const DEFAULT_ADDR: &str = "0.0.0.0:8080";
+
+// Serve on the address given as the only argument until the process is killed
+fn main() {
+    let addr = env::args().nth(1).unwrap_or_else(|| DEFAULT_ADDR.to_string());
+    let server = match Server::new(&addr) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Failed to start server on {}: {}", addr, e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = server.run() {
+        eprintln!("Server on {} failed: {}", addr, e);
+        process::exit(1);
+    }
+}
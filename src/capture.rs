@@ -0,0 +1,107 @@
+// Import necessary modules and crates
+use crate::hex::to_hex; // Captured frames
+use log::{info, warn}; // Logging macros
+use std::{
+    collections::HashMap, // Active captures by peer address
+    fmt,
+    fs::{File, OpenOptions}, // Capture files
+    io::{self, Write},
+    path::{Path, PathBuf}, // Capture file locations
+    sync::{
+        atomic::{AtomicUsize, Ordering}, // Lets connections skip the lock while nothing is captured
+        Mutex,
+    },
+    time::Instant, // End of a capture
+};
+
+/// Direction of a captured frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Received, // Sent by the client
+    Sent, // Sent by the server
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Direction::Received => "in",
+            Direction::Sent => "out",
+        })
+    }
+}
+
+// Capture of one connection
+#[derive(Debug)]
+struct Capture {
+    file: File, // Appended one line per frame
+    path: PathBuf, // For logs
+    until: Instant, // The capture stops with the first frame after this
+}
+
+/// Frames of selected connections hex-dumped to files for a limited time, so one misbehaving device can be
+/// debugged without verbose logging for every connection. Each frame is one line: the server's wall-clock
+/// time in microseconds since the Unix epoch, `in` or `out`, and the encoded message in hex without its
+/// length prefix
+#[derive(Debug, Default)]
+pub(crate) struct Captures {
+    active: AtomicUsize, // Number of captures, checked before taking the lock
+    by_peer: Mutex<HashMap<String, Capture>>, // Captures by peer address, e.g. "192.168.1.20:50312"
+}
+
+impl Captures {
+    // Capture the frames of `peer` into `path`, appending, until `until`. Replaces an earlier capture of it
+    pub(crate) fn start(&self, peer: &str, path: &Path, until: Instant) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let capture = Capture {
+            file,
+            path: path.to_path_buf(),
+            until,
+        };
+        if self.by_peer.lock().unwrap().insert(peer.to_string(), capture).is_none() {
+            self.active.fetch_add(1, Ordering::SeqCst);
+        }
+        info!("Capturing the frames of {} into {}", peer, path.display());
+        Ok(())
+    }
+
+    // Stop capturing `peer`, returns whether it was captured
+    pub(crate) fn stop(&self, peer: &str) -> bool {
+        let Some(capture) = self.by_peer.lock().unwrap().remove(peer) else {
+            return false;
+        };
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        info!("Stopped capturing {} into {}", peer, capture.path.display());
+        true
+    }
+
+    // Append a frame of `peer` to its capture, if it has one that hasn't run out
+    pub(crate) fn record(&self, peer: &str, direction: Direction, frame: &[u8], now: Instant, wall_us: u64) {
+        if self.active.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut by_peer = self.by_peer.lock().unwrap();
+        let Some(capture) = by_peer.get_mut(peer) else {
+            return;
+        };
+        let ended = if now >= capture.until {
+            info!("Capture of {} into {} ran out", peer, capture.path.display());
+            true
+        } else if let Err(e) = writeln!(capture.file, "{} {} {}", wall_us, direction, to_hex(frame)) {
+            warn!("Stopping the capture of {} into {}: {}", peer, capture.path.display(), e);
+            true
+        } else {
+            false
+        };
+        if ended {
+            by_peer.remove(peer);
+            self.active.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // Peers captured now
+    pub(crate) fn peers(&self) -> Vec<String> {
+        let mut peers: Vec<String> = self.by_peer.lock().unwrap().keys().cloned().collect();
+        peers.sort();
+        peers
+    }
+}
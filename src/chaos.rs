@@ -0,0 +1,106 @@
+// Import necessary modules and crates
+use crate::message::client_message; // Requests a fault may be injected into
+use std::{
+    fmt,
+    sync::{Arc, Mutex}, // Shared injector list and generator state
+    time::Duration, // Injected delays
+};
+
+/// Misbehaviour injected into the handling of one request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    Delay(Duration), // Handle the request normally, but only after sleeping this long
+    Drop, // Discard the request without any response, as if the frame was lost
+    InternalError, // Answer with an `INTERNAL` ErrorResponse instead of handling the request
+}
+
+/// Decides which requests misbehave, see `Server::add_fault_injector`
+pub trait FaultInjector: Send + Sync {
+    /// Returns the fault to inject into `request`, `None` handles it normally
+    fn inject(&self, request: &client_message::Message) -> Option<Fault>;
+}
+
+/// Share of requests affected by each fault of `RandomFaults`, each between 0.0 and 1.0
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FaultRates {
+    pub drop: f64, // Requests discarded without a response
+    pub error: f64, // Requests answered with an `INTERNAL` error
+    pub delay: f64, // Requests handled late
+    pub delay_for: Duration, // How late delayed requests are handled
+}
+
+/// Injects faults at random with the configured rates, reproducibly for the same seed
+#[derive(Debug)]
+pub struct RandomFaults {
+    rates: FaultRates, // Share of requests per fault
+    state: Mutex<u64>, // Xorshift generator state, never zero
+}
+
+impl RandomFaults {
+    /// Creates an injector drawing from a generator seeded with `seed`
+    pub fn new(rates: FaultRates, seed: u64) -> Self {
+        RandomFaults {
+            rates,
+            // Xorshift sticks at zero, any other seed works
+            state: Mutex::new(seed.max(1)),
+        }
+    }
+
+    // Next uniformly distributed value in [0, 1)
+    fn next_unit(&self) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        // The top 53 bits fill the mantissa of an f64
+        (*state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl FaultInjector for RandomFaults {
+    fn inject(&self, _request: &client_message::Message) -> Option<Fault> {
+        // One draw per request, the rates are consecutive slices of [0, 1)
+        let draw = self.next_unit();
+        let rates = &self.rates;
+        if draw < rates.drop {
+            Some(Fault::Drop)
+        } else if draw < rates.drop + rates.error {
+            Some(Fault::InternalError)
+        } else if draw < rates.drop + rates.error + rates.delay {
+            Some(Fault::Delay(rates.delay_for))
+        } else {
+            None
+        }
+    }
+}
+
+// Injectors registered on one server, consulted in registration order
+#[derive(Default)]
+pub(crate) struct FaultInjectors {
+    injectors: Mutex<Vec<Arc<dyn FaultInjector>>>,
+}
+
+impl FaultInjectors {
+    pub(crate) fn add(&self, injector: impl FaultInjector + 'static) {
+        self.injectors.lock().unwrap().push(Arc::new(injector));
+    }
+
+    pub(crate) fn clear(&self) {
+        self.injectors.lock().unwrap().clear();
+    }
+
+    // The fault of the first injector that asks for one
+    pub(crate) fn pick(&self, request: &client_message::Message) -> Option<Fault> {
+        // Cloned out so injectors run without holding the lock
+        let injectors = self.injectors.lock().unwrap().clone();
+        injectors.iter().find_map(|injector| injector.inject(request))
+    }
+}
+
+impl fmt::Debug for FaultInjectors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultInjectors")
+            .field("count", &self.injectors.lock().unwrap().len())
+            .finish()
+    }
+}
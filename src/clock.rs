@@ -0,0 +1,90 @@
+// Import necessary modules and crates
+use std::{
+    fmt,
+    sync::{Arc, Mutex}, // Shared virtual time
+    thread,
+    time::{Duration, Instant, SystemTime}, // Time handling
+};
+
+/// Source of time for timeouts and periodic work, so they can run on virtual time in tests
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Current time
+    fn now(&self) -> Instant;
+
+    /// Waits until `duration` has passed on this clock
+    fn sleep(&self, duration: Duration);
+
+    /// Current wall-clock time, for state compared between processes such as lease expiry
+    fn wall(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// The real monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Clock that only moves when told to. Sleeping advances it instead of blocking, so code under test
+/// runs through any timeout instantly and always observes the same times
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant, // Real time the clock was created, virtual time counts from here
+    wall_start: SystemTime, // Wall-clock time the clock was created
+    elapsed: Mutex<Duration>, // Virtual time passed since `start`
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    /// Creates a clock standing at the current time
+    pub fn new() -> Self {
+        ManualClock {
+            start: Instant::now(),
+            wall_start: SystemTime::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Moves the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// Virtual time passed since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+
+    fn wall(&self) -> SystemTime {
+        self.wall_start + self.elapsed()
+    }
+}
+
+/// Shared handle of the real clock, the default of everything taking a clock
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
@@ -0,0 +1,269 @@
+use crate::acklog; // Default acknowledgement log compaction
+use log::LevelFilter; // Log file verbosity
+use std::{path::PathBuf, time::Duration}; // Location of persistent state, time handling
+
+/// Longest a dynamic message handler may take by default, so a hung handler can't hold its connection thread
+pub const DEFAULT_HANDLER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Scheduling priority applied to a server thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadPriority {
+    Nice(i32), // Regular time-sharing scheduling with this nice value (-20 highest, 19 lowest)
+    RealTime(i32), // SCHED_FIFO real-time scheduling with this priority (1 lowest, 99 highest)
+}
+
+/// OS-level TCP keepalive, probes the peer from the kernel without any application traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpKeepalive {
+    pub idle: Duration, // Silence before the first keepalive packet
+    pub interval: Duration, // Time between unanswered keepalive packets
+    pub retries: u32, // Unanswered packets before the kernel drops the connection
+}
+
+/// Dead connection detection, a half-open connection is closed at most `probe_after + probe_timeout` after
+/// the last byte received from it, or after `idle + interval * retries` by the kernel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LivenessConfig {
+    pub tcp_keepalive: Option<TcpKeepalive>, // Kernel keepalive, needs the `keepalive` feature on Linux or Windows
+    pub probe_after: Option<Duration>, // Send a LivenessProbe after this much silence, `None` disables probes
+    pub probe_timeout: Duration, // Close the connection if nothing arrives this long after a probe
+}
+
+impl Default for LivenessConfig {
+    fn default() -> Self {
+        LivenessConfig {
+            tcp_keepalive: None,
+            probe_after: None,
+            probe_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// How many protocol violations of each kind a connection may commit within `window` before it is closed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViolationPolicy {
+    pub window: Duration, // Violations older than this are forgotten
+    pub max_decode_failures: u32, // Frames that are not a valid ClientMessage
+    pub max_unknown_messages: u32, // Messages of a type this server doesn't know, or without content
+    pub max_oversized_frames: u32, // Frames above the size limit, skipped without being read into memory
+}
+
+impl Default for ViolationPolicy {
+    fn default() -> Self {
+        ViolationPolicy {
+            window: Duration::from_secs(60),
+            max_decode_failures: 5,
+            max_unknown_messages: 5,
+            max_oversized_frames: 0,
+        }
+    }
+}
+
+/// Estimated memory a server may use before it sheds load, for gateways with little RAM and no swap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryBudget {
+    pub limit: usize, // Bytes of buffers, connections and caches above which load is shed
+    pub connection_cost: usize, // Estimated bytes per connection besides its buffers, mostly its thread stack
+    pub large_request: usize, // Frames at least this large are rejected while over the limit
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        MemoryBudget {
+            limit: 64 * 1024 * 1024,
+            connection_cost: 64 * 1024,
+            large_request: 4 * 1024,
+        }
+    }
+}
+
+/// Early rejection of data requests while handlers are slow or requests queue up. Control requests such
+/// as health checks are never rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadShedding {
+    pub latency_threshold: Duration, // Average handler latency above which requests are shed
+    pub queue_threshold: usize, // Requests queued across all connections above which requests are shed
+    pub max_reject_percent: u32, // Share of data requests rejected at twice a threshold or more
+}
+
+impl Default for LoadShedding {
+    fn default() -> Self {
+        LoadShedding {
+            latency_threshold: Duration::from_millis(50),
+            queue_threshold: 64,
+            max_reject_percent: 90,
+        }
+    }
+}
+
+/// Stall detection for the accept loops and connection threads, so a supervisor can restart a wedged process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    pub timeout: Duration, // Time without progress after which a component counts as stalled
+    pub check_interval: Duration, // Time between checks, also the interval of systemd notifications
+    pub notify_systemd: bool, // Send `WATCHDOG=1` to systemd after every check finding no stall
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            timeout: Duration::from_secs(10),
+            check_interval: Duration::from_secs(2),
+            notify_systemd: true,
+        }
+    }
+}
+
+/// On-disk log with rotation, for hosts without a log daemon. A rotated file becomes `<path>.1`, older ones
+/// move up to `<path>.<keep>` and anything beyond is deleted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFileConfig {
+    pub path: PathBuf, // Current log file
+    pub level: LevelFilter, // Most verbose level written
+    pub max_size: Option<u64>, // Rotate before the file would exceed this many bytes
+    pub max_age: Option<Duration>, // Rotate once the file is this old
+    pub keep: usize, // Rotated files kept, 0 deletes the file on rotation
+}
+
+impl LogFileConfig {
+    /// Logs at info level to `path`, rotating at 10 MiB and keeping 5 old files
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        LogFileConfig {
+            path: path.into(),
+            level: LevelFilter::Info,
+            max_size: Some(10 * 1024 * 1024),
+            max_age: None,
+            keep: 5,
+        }
+    }
+}
+
+/// Upstream responses of one request type kept by a gateway, to answer it during outages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachePolicy {
+    pub message_type: String, // Request type whose responses are kept, e.g. "HealthRequest"; only read-only requests belong here
+    pub ttl: Duration, // Oldest response still served while the upstream server is unreachable
+}
+
+/// Forwarding of selected request types to an upstream server, for site gateways fronting a central one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayConfig {
+    pub upstream: String, // Address of the upstream server, e.g. "central.example.com:8080"
+    pub message_types: Vec<String>, // Requests forwarded by type name, e.g. "AddRequest"; the rest are handled locally
+    pub timeout: Duration, // Longest wait to connect to the upstream server and for each of its responses
+    pub max_connections: usize, // Upstream connections open at most, further requests wait up to `timeout` for one
+    pub failure_threshold: u32, // Consecutive failed forwards that open the circuit breaker
+    pub open_for: Duration, // How long an open breaker rejects requests before letting one probe through
+    pub cache: Vec<CachePolicy>, // Request types answered from earlier upstream responses when forwarding fails
+}
+
+impl GatewayConfig {
+    /// Forwards the given request types to `upstream` over up to 8 connections, waiting up to 5 seconds
+    /// for it; 5 failures in a row stop forwarding for 10 seconds. Nothing is cached
+    pub fn new(upstream: impl Into<String>, message_types: &[&str]) -> Self {
+        GatewayConfig {
+            upstream: upstream.into(),
+            message_types: message_types.iter().map(|kind| kind.to_string()).collect(),
+            timeout: Duration::from_secs(5),
+            max_connections: 8,
+            failure_threshold: 5,
+            open_for: Duration::from_secs(10),
+            cache: Vec::new(),
+        }
+    }
+}
+
+/// Operator-written Rhai script run on the requests of one type and their responses, see `scripting::Scripts`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptHook {
+    pub message_type: String, // Request type the script applies to, e.g. "EchoMessage"
+    pub path: PathBuf, // Script file, read once when the server is created
+}
+
+/// Sandboxed WebAssembly handler of dynamic messages, see `wasm::WasmHandler`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmHandlerConfig {
+    pub path: PathBuf, // Module file, binary or text format, compiled once when the server is created
+    pub message_types: Vec<String>, // Full names of the dynamic message types routed to the module
+    pub fuel: u64, // Instructions one call may run, about one unit each
+    pub max_memory: usize, // Bytes of linear memory one call may use
+    pub timeout: Option<Duration>, // Longest one call may take, `None` for `ServerConfig::handler_timeout`
+}
+
+/// Configuration for a server instance, start from `ServerConfig::default()` and override what you need
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub acceptor_cores: Vec<usize>, // CPU cores the accept loop may run on, empty for no pinning
+    pub worker_cores: Vec<usize>, // CPU cores connection threads may run on, empty for no pinning
+    pub acceptor_priority: Option<ThreadPriority>, // Priority of the accept loop thread
+    pub worker_priority: Option<ThreadPriority>, // Priority of connection threads
+    pub control_cores: Vec<usize>, // CPU cores of the scheduler and HTTP endpoint threads, empty for no pinning
+    pub control_priority: Option<ThreadPriority>, // Priority of the scheduler and HTTP endpoint threads
+    pub listen_backlog: Option<u32>, // Pending connection queue length, `None` keeps the std default
+    pub acceptors: usize, // Number of SO_REUSEPORT listeners, each with its own accept loop thread
+    pub healthz_addr: Option<String>, // Address of the HTTP `/healthz` endpoint, needs the `healthz` feature
+    pub status_addr: Option<String>, // Address of the HTTP status page, keep it on localhost, needs the `status-page` feature
+    pub admin_addr: Option<String>, // Address of the HTTP admin endpoint, it has no authentication so keep it on localhost, needs the `admin` feature
+    pub ack_log_path: Option<PathBuf>, // File persisting completed command ids across restarts (`storage` feature), `None` keeps them in memory
+    pub ack_log_compact_after: usize, // Stale entries of forgotten commands the acknowledgement log file may hold before it is rewritten
+    pub require_sequence: bool, // Reject requests without a sequence number, not only replayed ones
+    pub violation_policy: ViolationPolicy, // When a misbehaving connection gets closed
+    pub liveness: LivenessConfig, // How silent and half-open connections are detected
+    pub slow_request_threshold: Option<Duration>, // Log and count requests that take longer to decode and handle, `None` disables the check
+    pub event_capacity: usize, // Recent events kept for `Server::recent_events` and RecentEventsRequest
+    pub log_file: Option<LogFileConfig>, // Install a process-wide logger writing to a rotated file (`storage` feature), `None` leaves logging to the application
+    pub inherit_listeners: Option<PathBuf>, // Unix socket of a running server to take the listeners from instead of binding, needs the `handoff` feature
+    pub poll_interval: Duration, // Sleep between accept polls, zero busy-polls for the lowest accept latency at the cost of a core per acceptor
+    pub gateway: Option<GatewayConfig>, // Forward selected requests to an upstream server, `None` handles everything locally
+    pub watchdog: Option<WatchdogConfig>, // Report accept loops and connection threads that stop making progress, `None` disables the watchdog
+    pub memory_budget: Option<MemoryBudget>, // Reject new connections and large requests while over budget, `None` never sheds load
+    pub load_shedding: Option<LoadShedding>, // Reject some data requests while handlers are slow, `None` serves every request
+    pub resync_buffer: usize, // Bytes of recently sent messages each connection keeps for ResyncRequest, 0 keeps none
+    pub delta_baselines: usize, // Large requests each connection keeps as baselines for DeltaRequest, 0 keeps none
+    pub content_store: usize, // Bytes of large requests the server keeps for OfferRequest, 0 keeps none
+    pub scripts: Vec<ScriptHook>, // Scripts transforming requests and responses by type, needs the `scripting` feature
+    pub plugin_dir: Option<PathBuf>, // Directory of plugin libraries loaded at startup, needs the `plugins` feature on Unix
+    pub wasm_handlers: Vec<WasmHandlerConfig>, // Sandboxed handlers of dynamic messages, needs the `wasm` feature
+    pub handler_timeout: Option<Duration>, // Longest a dynamic message handler may take unless routed with its own limit, `None` for no limit
+    pub routes_path: Option<PathBuf>, // Routing file read at startup and by `Server::reload_routes`, see `routing::RoutingTable`
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            acceptor_cores: Vec::new(),
+            worker_cores: Vec::new(),
+            acceptor_priority: None,
+            worker_priority: None,
+            control_cores: Vec::new(),
+            control_priority: None,
+            listen_backlog: None,
+            acceptors: 1,
+            healthz_addr: None,
+            status_addr: None,
+            admin_addr: None,
+            ack_log_path: None,
+            ack_log_compact_after: acklog::DEFAULT_COMPACT_AFTER,
+            require_sequence: false,
+            violation_policy: ViolationPolicy::default(),
+            liveness: LivenessConfig::default(),
+            slow_request_threshold: None,
+            event_capacity: 256,
+            log_file: None,
+            inherit_listeners: None,
+            poll_interval: Duration::from_millis(100),
+            gateway: None,
+            watchdog: None,
+            memory_budget: None,
+            load_shedding: None,
+            resync_buffer: 0,
+            delta_baselines: 0,
+            content_store: 0,
+            scripts: Vec::new(),
+            plugin_dir: None,
+            wasm_handlers: Vec::new(),
+            handler_timeout: Some(DEFAULT_HANDLER_TIMEOUT),
+            routes_path: None,
+        }
+    }
+}
@@ -0,0 +1,320 @@
+// Import necessary modules and crates
+use crate::frame::{Frame, WireHeader}; // Frame header parsing
+use crate::message::{
+    server_message, BenchRequest, ClientMessage, EchoMessage, ErrorCode, HealthStatus, LivenessProbeAck, Metadata,
+    ServerMessage,
+}; // Requests sent and responses checked
+use crate::protocol::MAX_MESSAGE_SIZE; // Largest frame a server must accept
+use crate::selftest::CheckResult; // Outcome of one check
+use prost::Message; // Protobuf message encoding/decoding
+use std::{
+    fmt,
+    io::{self, Read, Write}, // I/O operations
+    net::{TcpStream, ToSocketAddrs}, // Networking
+    thread,
+    time::{Duration, Instant}, // Time handling
+};
+
+// Bytes read from the server at once
+const READ_CHUNK: usize = 4096;
+
+// Failure of a receive the server didn't answer within the timeout
+const NO_RESPONSE: &str = "Server did not respond in time";
+
+// A check of the battery, run on its own connection
+type Check = fn(&mut Connection) -> CheckResult;
+
+// Every check in the order they run
+const CHECKS: [(&str, Check); 11] = [
+    ("echo", check_echo),
+    ("add", check_add),
+    ("health", check_health),
+    ("trace_id", check_trace_id),
+    ("pipelining", check_pipelining),
+    ("split_frame", check_split_frame),
+    ("large_message", check_large_message),
+    ("oversized_frame", check_oversized_frame),
+    ("undecodable_frame", check_undecodable_frame),
+    ("empty_message", check_empty_message),
+    ("error_response", check_error_response),
+];
+
+/// Result of one conformance check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceCheck {
+    pub name: String,
+    pub result: CheckResult, // What was verified, or why the server failed the check
+    pub duration: Duration, // Time the check took, including connecting
+}
+
+/// Results of a conformance run against one server, printed as a pass/fail report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceReport {
+    pub addr: String, // Server that was checked
+    pub checks: Vec<ConformanceCheck>, // In the order they ran
+}
+
+impl ConformanceReport {
+    /// Whether the server passed every check
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.result.is_ok())
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Conformance of {}", self.addr)?;
+        for check in &self.checks {
+            let (verdict, detail) = match &check.result {
+                Ok(detail) => ("PASS", detail),
+                Err(reason) => ("FAIL", reason),
+            };
+            writeln!(f, "{} {:<18} {:>6} ms  {}", verdict, check.name, check.duration.as_millis(), detail)?;
+        }
+        let failed = self.checks.iter().filter(|check| check.result.is_err()).count();
+        write!(f, "{} of {} checks passed", self.checks.len() - failed, self.checks.len())
+    }
+}
+
+/// Runs the conformance battery against the server at `addr`. Every check uses a new connection, so a
+/// server closing one after a protocol error doesn't fail the others; `timeout` bounds each wait
+pub fn run(addr: &str, timeout: Duration) -> ConformanceReport {
+    let checks = CHECKS
+        .iter()
+        .map(|&(name, check)| {
+            let started = Instant::now();
+            let result = Connection::open(addr, timeout).and_then(|mut connection| check(&mut connection));
+            ConformanceCheck {
+                name: name.to_string(),
+                result,
+                duration: started.elapsed(),
+            }
+        })
+        .collect();
+    ConformanceReport {
+        addr: addr.to_string(),
+        checks,
+    }
+}
+
+fn check_echo(connection: &mut Connection) -> CheckResult {
+    connection.send(&ClientMessage::echo("conformance"))?;
+    connection.expect_echo("conformance")?;
+    Ok("EchoMessage returned unchanged".to_string())
+}
+
+fn check_add(connection: &mut Connection) -> CheckResult {
+    connection.send(&ClientMessage::add(-7, 12))?;
+    match connection.receive()?.message {
+        Some(server_message::Message::AddResponse(response)) if response.result == 5 => {
+            Ok("AddRequest answered with the sum".to_string())
+        }
+        other => Err(format!("Expected AddResponse of 5, got {:?}", other)),
+    }
+}
+
+fn check_health(connection: &mut Connection) -> CheckResult {
+    connection.send(&ClientMessage::health())?;
+    match connection.receive()?.message {
+        Some(server_message::Message::HealthResponse(response)) if response.status() != HealthStatus::Unspecified => {
+            Ok(format!("HealthResponse with status {:?}", response.status()))
+        }
+        other => Err(format!("Expected HealthResponse with a status, got {:?}", other)),
+    }
+}
+
+fn check_trace_id(connection: &mut Connection) -> CheckResult {
+    let request = ClientMessage {
+        metadata: Some(Metadata {
+            trace_id: "conformance-trace".to_string(),
+            ..Metadata::default()
+        }),
+        ..ClientMessage::echo("traced")
+    };
+    connection.send(&request)?;
+    let response = connection.receive()?;
+    match response.metadata {
+        Some(metadata) if metadata.trace_id == "conformance-trace" => Ok("Trace id returned".to_string()),
+        other => Err(format!("Expected the request's trace id in the response metadata, got {:?}", other)),
+    }
+}
+
+fn check_pipelining(connection: &mut Connection) -> CheckResult {
+    // Several requests in one write must be answered one by one, in order
+    let mut bytes = Vec::new();
+    for a in 0..3 {
+        bytes.extend(ClientMessage::add(a, 100).encode_length_delimited_to_vec());
+    }
+    connection.write(&bytes)?;
+    for a in 0..3 {
+        match connection.receive()?.message {
+            Some(server_message::Message::AddResponse(response)) if response.result == a + 100 => {}
+            other => return Err(format!("Expected AddResponse of {} in order, got {:?}", a + 100, other)),
+        }
+    }
+    Ok("3 pipelined requests answered in order".to_string())
+}
+
+fn check_split_frame(connection: &mut Connection) -> CheckResult {
+    // A frame arriving in pieces, the header byte by byte, must be reassembled
+    let content = "x".repeat(300);
+    let bytes = ClientMessage::echo(content.clone()).encode_length_delimited_to_vec();
+    let header_len = WireHeader::parse(&bytes).ok().flatten().map_or(1, |(_, len)| len);
+    let middle = header_len + (bytes.len() - header_len) / 2;
+    let mut pieces: Vec<&[u8]> = bytes[..header_len].chunks(1).collect();
+    pieces.extend([&bytes[header_len..middle], &bytes[middle..]]);
+    for piece in pieces {
+        connection.write(piece)?;
+        thread::sleep(Duration::from_millis(20));
+    }
+    connection.expect_echo(&content)?;
+    Ok(format!("Frame of {} bytes sent in pieces was reassembled", bytes.len()))
+}
+
+fn check_large_message(connection: &mut Connection) -> CheckResult {
+    // Close to the size limit, leaving room for the metadata the response carries
+    let content = "L".repeat(MAX_MESSAGE_SIZE - 256);
+    let request = ClientMessage::echo(content.clone());
+    connection.send(&request)?;
+    connection.expect_echo(&content)?;
+    Ok(format!("Message of {} bytes echoed", request.encoded_len()))
+}
+
+fn check_oversized_frame(connection: &mut Connection) -> CheckResult {
+    // The header alone first: a server may reject the frame and close at once, or wait to skip its payload
+    let mut header = Vec::new();
+    prost::encode_length_delimiter(MAX_MESSAGE_SIZE + 1, &mut header).map_err(|e| e.to_string())?;
+    connection.write(&header)?;
+    match connection.receive() {
+        Ok(ServerMessage {
+            message: Some(server_message::Message::ErrorResponse(error)),
+            ..
+        }) if error.code() == ErrorCode::ProtocolViolation => {
+            return Ok(format!("Rejected with PROTOCOL_VIOLATION: {}", error.message));
+        }
+        Ok(other) => return Err(format!("Unexpected response to an oversized frame: {:?}", other.message)),
+        Err(e) if e == NO_RESPONSE => {}
+        Err(e) => return Err(e),
+    }
+
+    // Skipping the payload must leave the connection usable
+    connection.write(&vec![0; MAX_MESSAGE_SIZE + 1])?;
+    connection.send(&ClientMessage::echo("after oversized"))?;
+    connection.expect_echo("after oversized")?;
+    Ok(format!("Frame of {} bytes skipped, connection still usable", MAX_MESSAGE_SIZE + 1))
+}
+
+fn check_undecodable_frame(connection: &mut Connection) -> CheckResult {
+    // A truncated varint field can't be decoded as any message
+    connection.write(&[2, 0x08, 0xff])?;
+    connection.send(&ClientMessage::echo("after garbage"))?;
+    connection.expect_echo("after garbage")?;
+    Ok("Undecodable frame skipped, connection still usable".to_string())
+}
+
+fn check_empty_message(connection: &mut Connection) -> CheckResult {
+    // A message without content gets no regular response, and an unsolicited probe ack none at all
+    connection.send(&ClientMessage::default())?;
+    connection.send(&LivenessProbeAck { id: 1 }.into())?;
+    connection.send(&ClientMessage::echo("after empty"))?;
+    connection.expect_echo("after empty")?;
+    Ok("Empty message and unsolicited probe ack skipped".to_string())
+}
+
+fn check_error_response(connection: &mut Connection) -> CheckResult {
+    // A bench far above any sensible limit must be refused with an error code, not served or ignored
+    let request = BenchRequest {
+        payload_size: u32::MAX,
+        count: u32::MAX,
+    };
+    connection.send(&request.into())?;
+    match connection.receive()?.message {
+        Some(server_message::Message::ErrorResponse(error)) if error.code() == ErrorCode::InvalidRequest => {
+            Ok(format!("Rejected with INVALID_REQUEST: {}", error.message))
+        }
+        other => Err(format!("Expected ErrorResponse with INVALID_REQUEST, got {:?}", other)),
+    }
+}
+
+// Connection to the server under test, answering its liveness probes on the way
+struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>, // Received bytes not yet decoded
+}
+
+impl Connection {
+    fn open(addr: &str, timeout: Duration) -> Result<Self, String> {
+        let socket_addr = addr
+            .to_socket_addrs()
+            .map_err(|e| format!("Failed to resolve {}: {}", addr, e))?
+            .next()
+            .ok_or_else(|| format!("{} resolves to no address", addr))?;
+        let stream = TcpStream::connect_timeout(&socket_addr, timeout)
+            .map_err(|e| format!("Failed to connect to {}: {}", addr, e))?;
+        stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+        stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        Ok(Connection {
+            stream,
+            buffer: Vec::new(),
+        })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.stream.write_all(bytes).map_err(|e| format!("Failed to send: {}", e))
+    }
+
+    fn send(&mut self, message: &ClientMessage) -> Result<(), String> {
+        self.write(&message.encode_length_delimited_to_vec())
+    }
+
+    // The next message that isn't a liveness probe
+    fn receive(&mut self) -> Result<ServerMessage, String> {
+        loop {
+            match WireHeader::next_frame(&self.buffer).map_err(|e| format!("Server sent a bad frame: {}", e))? {
+                Some(Frame::Complete { start, end }) => {
+                    let message = ServerMessage::decode(&self.buffer[start..end])
+                        .map_err(|e| format!("Server sent an undecodable message: {}", e))?;
+                    self.buffer.drain(..end);
+                    match message.message {
+                        Some(server_message::Message::LivenessProbe(probe)) => {
+                            self.send(&LivenessProbeAck { id: probe.id }.into())?;
+                        }
+                        _ => return Ok(message),
+                    }
+                    continue;
+                }
+                Some(Frame::Oversized { payload_len, .. }) => {
+                    return Err(format!("Server sent a frame of {} bytes, above the size limit", payload_len));
+                }
+                None => {}
+            }
+
+            let filled = self.buffer.len();
+            self.buffer.resize(filled + READ_CHUNK, 0);
+            let read = self.stream.read(&mut self.buffer[filled..]);
+            self.buffer.truncate(filled + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(0) => return Err("Server closed the connection".to_string()),
+                Ok(_) => {}
+                Err(ref e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    return Err(NO_RESPONSE.to_string());
+                }
+                Err(e) => return Err(format!("Failed to receive: {}", e)),
+            }
+        }
+    }
+
+    // Wait for the echo of `content`, error responses to earlier frames are allowed before it
+    fn expect_echo(&mut self, content: &str) -> Result<(), String> {
+        loop {
+            match self.receive()?.message {
+                Some(server_message::Message::EchoMessage(EchoMessage { content: echoed })) if echoed == content => {
+                    return Ok(())
+                }
+                Some(server_message::Message::ErrorResponse(_)) => {}
+                other => return Err(format!("Expected the echo, got {:?}", other)),
+            }
+        }
+    }
+}
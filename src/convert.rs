@@ -0,0 +1,200 @@
+// Import necessary modules and crates
+#[cfg(feature = "dedup")]
+use crate::dedup; // Offered requests
+#[cfg(feature = "delta")]
+use crate::delta; // Delta requests
+use crate::message::*; // Every payload of the ClientMessage and ServerMessage oneofs
+use prost::Message; // Protobuf message encoding
+
+// `From` each payload type into its oneof and into the enclosing message, without metadata
+macro_rules! oneof_from {
+    ($message:ident, $oneof:ident: $($variant:ident),+ $(,)?) => {
+        $(
+            impl From<$variant> for $oneof::Message {
+                fn from(payload: $variant) -> Self {
+                    $oneof::Message::$variant(payload)
+                }
+            }
+
+            impl From<$variant> for $message {
+                fn from(payload: $variant) -> Self {
+                    $message {
+                        message: Some(payload.into()),
+                        metadata: None,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+oneof_from!(ClientMessage, client_message:
+    EchoMessage,
+    AddRequest,
+    HealthRequest,
+    SelfTestRequest,
+    CommandStatusRequest,
+    BenchRequest,
+    StatsRequest,
+    LivenessProbeAck,
+    RecentEventsRequest,
+    GetSchemaRequest,
+    DynamicMessage,
+    ResyncRequest,
+    DeltaRequest,
+    OfferRequest,
+);
+
+oneof_from!(ServerMessage, server_message:
+    EchoMessage,
+    AddResponse,
+    HealthResponse,
+    SelfTestResponse,
+    ErrorResponse,
+    CommandStatusResponse,
+    BenchResponse,
+    BenchPayload,
+    StatsResponse,
+    LivenessProbe,
+    RecentEventsResponse,
+    GetSchemaResponse,
+    DynamicMessage,
+    GoAway,
+    ResyncResponse,
+    OfferResponse,
+    MaintenanceMode,
+);
+
+impl ClientMessage {
+    /// Echo request for `content`
+    pub fn echo(content: impl Into<String>) -> Self {
+        EchoMessage { content: content.into() }.into()
+    }
+
+    /// Request for the sum of `a` and `b`
+    pub fn add(a: i32, b: i32) -> Self {
+        AddRequest { a, b }.into()
+    }
+
+    /// Request for the server health
+    pub fn health() -> Self {
+        HealthRequest {}.into()
+    }
+
+    /// Request running the server self-tests
+    pub fn self_test() -> Self {
+        SelfTestRequest {}.into()
+    }
+
+    /// Query whether the command `command_id` was applied
+    pub fn command_status(command_id: impl Into<String>) -> Self {
+        CommandStatusRequest { command_id: command_id.into() }.into()
+    }
+
+    /// Request streaming `count` payloads of `payload_size` bytes
+    pub fn bench(payload_size: u32, count: u32) -> Self {
+        BenchRequest { payload_size, count }.into()
+    }
+
+    /// Request for the counters of this connection
+    pub fn stats() -> Self {
+        StatsRequest {}.into()
+    }
+
+    /// Request for up to `limit` recent server events, 0 for all kept
+    pub fn recent_events(limit: u32) -> Self {
+        RecentEventsRequest { limit }.into()
+    }
+
+    /// Request for the schema the server was built with
+    pub fn get_schema() -> Self {
+        GetSchemaRequest {}.into()
+    }
+
+    /// Request for the messages sent after `last_seen_seq` on this connection
+    pub fn resync(last_seen_seq: u64) -> Self {
+        ResyncRequest { last_seen_seq }.into()
+    }
+
+    /// `message` sent as the difference to `baseline`, a request the server answered before
+    #[cfg(feature = "delta")]
+    pub fn delta(baseline: &client_message::Message, message: &client_message::Message) -> Self {
+        let baseline = encode_payload(baseline);
+        DeltaRequest {
+            baseline: delta::fingerprint(&baseline),
+            delta: delta::diff(&baseline, &encode_payload(message)),
+        }
+        .into()
+    }
+
+    /// Offer of `message` by its hash, the server asks for the message itself if it hasn't got it
+    #[cfg(feature = "dedup")]
+    pub fn offer(message: &client_message::Message) -> Self {
+        OfferRequest {
+            sha256: dedup::content_hash(&encode_payload(message)).to_vec(),
+        }
+        .into()
+    }
+}
+
+impl ServerMessage {
+    /// Echo of `content`
+    pub fn echo(content: impl Into<String>) -> Self {
+        EchoMessage { content: content.into() }.into()
+    }
+
+    /// Sum of an AddRequest
+    pub fn add(result: i32) -> Self {
+        AddResponse { result }.into()
+    }
+
+    /// Rejection with `code` and a human readable `message`
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        ErrorResponse::new(code, message).into()
+    }
+}
+
+impl DynamicMessage {
+    /// Encoded `payload` of the message type `type_name`, a full protobuf name
+    pub fn new(type_name: impl Into<String>, payload: Vec<u8>) -> Self {
+        DynamicMessage {
+            type_name: type_name.into(),
+            payload,
+        }
+    }
+}
+
+impl ErrorCode {
+    /// Whether the same request may succeed when sent again later, so a client should keep it and retry.
+    /// Other errors are final answers to the request
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::Internal
+                | ErrorCode::UpstreamUnavailable
+                | ErrorCode::Overloaded
+                | ErrorCode::ReadOnly
+                | ErrorCode::HandlerBusy
+        )
+    }
+}
+
+impl ErrorResponse {
+    /// Error with `code` and a human readable `message`
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ErrorResponse {
+            code: code as i32,
+            message: message.into(),
+        }
+    }
+}
+
+// Encoding of a request without metadata, the form deltas and offers refer to
+#[cfg(any(feature = "delta", feature = "dedup"))]
+fn encode_payload(message: &client_message::Message) -> Vec<u8> {
+    ClientMessage {
+        message: Some(message.clone()),
+        metadata: None,
+    }
+    .encode_to_vec()
+}
@@ -0,0 +1,83 @@
+// Import necessary modules and crates
+use sha2::{Digest, Sha256}; // Content hashes clients can't forge collisions for
+use std::collections::{HashMap, VecDeque}; // Stored content and its use order
+
+/// SHA-256 naming a request in an offer, taken over its encoding without metadata
+pub type ContentHash = [u8; 32];
+
+/// Hash of `encoded` as used in offers
+pub fn content_hash(encoded: &[u8]) -> ContentHash {
+    Sha256::digest(encoded).into()
+}
+
+/// Large requests by content hash, shared by every connection of a server, so a payload many devices send,
+/// such as a firmware image, is uploaded once. The least recently used are forgotten beyond `limit` bytes
+#[derive(Debug, Default)]
+pub struct ContentStore {
+    limit: usize, // Most bytes of content kept
+    bytes: usize, // Bytes of content kept now
+    entries: HashMap<ContentHash, Vec<u8>>, // Encoded requests without metadata
+    order: VecDeque<ContentHash>, // Hashes least recently used first
+}
+
+impl ContentStore {
+    /// Creates a store keeping at most `limit` bytes, 0 keeps nothing
+    pub fn new(limit: usize) -> Self {
+        ContentStore {
+            limit,
+            ..ContentStore::default()
+        }
+    }
+
+    /// Keeps `encoded`, unless it alone is over the limit, and returns its hash
+    pub fn insert(&mut self, encoded: Vec<u8>) -> ContentHash {
+        let hash = content_hash(&encoded);
+        if encoded.len() > self.limit || self.touch(&hash) {
+            return hash;
+        }
+        self.bytes += encoded.len();
+        self.entries.insert(hash, encoded);
+        self.order.push_back(hash);
+        while self.bytes > self.limit {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.bytes -= self.entries.remove(&oldest).map_or(0, |content| content.len());
+        }
+        hash
+    }
+
+    /// The content with `hash`, counting as a use
+    pub fn get(&mut self, hash: &[u8]) -> Option<&[u8]> {
+        let hash: ContentHash = hash.try_into().ok()?;
+        if !self.touch(&hash) {
+            return None;
+        }
+        self.entries.get(&hash).map(Vec::as_slice)
+    }
+
+    /// Bytes of content kept, counted against the server's memory budget
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Number of requests kept
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether nothing is kept
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Move `hash` to the most recently used end, returns whether it is kept
+    fn touch(&mut self, hash: &ContentHash) -> bool {
+        let Some(position) = self.order.iter().position(|kept| kept == hash) else {
+            return false;
+        };
+        self.order.remove(position);
+        self.order.push_back(*hash);
+        true
+    }
+}
@@ -0,0 +1,93 @@
+// Import necessary modules and crates
+use prost::encoding::{decode_varint, encode_varint}; // Instruction headers and offsets
+use std::{
+    collections::HashMap, // Baseline blocks by content
+    io::{self, ErrorKind}, // Invalid delta errors
+};
+
+// Bytes a match must span before it is copied rather than sent. Shorter matches would cost about as much
+// in instructions as they save
+const BLOCK: usize = 16;
+
+/// Fingerprint naming a baseline in a delta request, 64-bit FNV-1a of its bytes
+pub fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Instructions rebuilding `target` from `baseline`. Runs of `target` found in `baseline` are copied,
+/// wherever they are, and the bytes in between are sent as they are. Each instruction starts with a varint
+/// of its length shifted left by one, the low bit set for a copy; a copy is followed by the varint offset
+/// in the baseline, an insert by its bytes
+pub fn diff(baseline: &[u8], target: &[u8]) -> Vec<u8> {
+    // Aligned baseline blocks, target blocks are looked up at every offset
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    for (index, block) in baseline.chunks_exact(BLOCK).enumerate() {
+        blocks.entry(block).or_insert(index * BLOCK);
+    }
+
+    let mut delta = Vec::new();
+    let mut literal_start = 0;
+    let mut position = 0;
+    while position + BLOCK <= target.len() {
+        let Some(&found) = blocks.get(&target[position..position + BLOCK]) else {
+            position += 1;
+            continue;
+        };
+        // Grow the match backwards over unsent bytes and forwards as far as both agree
+        let (mut start, mut source) = (position, found);
+        while start > literal_start && source > 0 && target[start - 1] == baseline[source - 1] {
+            start -= 1;
+            source -= 1;
+        }
+        let agree = |end: usize| baseline.get(source + end - start) == Some(&target[end]);
+        let mut end = position + BLOCK;
+        while end < target.len() && agree(end) {
+            end += 1;
+        }
+
+        push_insert(&mut delta, &target[literal_start..start]);
+        encode_varint((((end - start) as u64) << 1) | 1, &mut delta);
+        encode_varint(source as u64, &mut delta);
+        position = end;
+        literal_start = end;
+    }
+    push_insert(&mut delta, &target[literal_start..]);
+    delta
+}
+
+/// Rebuilds the target of `delta` from `baseline`. A target longer than `max_len`, a copy outside the
+/// baseline or a truncated instruction is an error
+pub fn apply(baseline: &[u8], mut delta: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+    let invalid = |detail: &str| io::Error::new(ErrorKind::InvalidData, format!("Invalid delta: {}", detail));
+    let mut target = Vec::new();
+    while !delta.is_empty() {
+        let header = decode_varint(&mut delta).map_err(|_| invalid("truncated instruction"))?;
+        let len = usize::try_from(header >> 1).map_err(|_| invalid("length out of range"))?;
+        if len > max_len.saturating_sub(target.len()) {
+            return Err(invalid("target too large"));
+        }
+        if header & 1 == 0 {
+            let bytes = delta.get(..len).ok_or_else(|| invalid("truncated insert"))?;
+            target.extend_from_slice(bytes);
+            delta = &delta[len..];
+        } else {
+            let offset = decode_varint(&mut delta).map_err(|_| invalid("truncated copy"))?;
+            let bytes = usize::try_from(offset)
+                .ok()
+                .and_then(|offset| baseline.get(offset..offset.checked_add(len)?))
+                .ok_or_else(|| invalid("copy outside the baseline"))?;
+            target.extend_from_slice(bytes);
+        }
+    }
+    Ok(target)
+}
+
+// Append an instruction inserting `bytes`, nothing if there are none
+fn push_insert(delta: &mut Vec<u8>, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        encode_varint((bytes.len() as u64) << 1, delta);
+        delta.extend_from_slice(bytes);
+    }
+}
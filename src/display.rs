@@ -0,0 +1,166 @@
+// Import necessary modules and crates
+use crate::message::{client_message, server_message, ClientMessage, DynamicMessage, ServerMessage};
+use std::fmt;
+
+/// Characters of a string shown before it is cut
+pub const MAX_SHOWN_CHARS: usize = 64;
+
+/// Shows a string quoted, cut after `MAX_SHOWN_CHARS` characters with its full length noted
+#[derive(Debug, Clone, Copy)]
+pub struct Truncated<'a>(pub &'a str);
+
+impl fmt::Display for Truncated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.char_indices().nth(MAX_SHOWN_CHARS) {
+            Some((cut, _)) => write!(f, "{:?}... ({} bytes)", &self.0[..cut], self.0.len()),
+            None => write!(f, "{:?}", self.0),
+        }
+    }
+}
+
+// Pass-through messages in either direction, their payload is opaque here
+struct Dynamic<'a>(&'a DynamicMessage);
+
+impl fmt::Display for Dynamic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DynamicMessage {{ type_name: {}, payload: <{} bytes> }}",
+            Truncated(&self.0.type_name),
+            self.0.payload.len()
+        )
+    }
+}
+
+// Requests are short apart from echo content, which is cut
+impl fmt::Display for client_message::Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            client_message::Message::EchoMessage(echo) => {
+                write!(f, "EchoMessage {{ content: {} }}", Truncated(&echo.content))
+            }
+            client_message::Message::AddRequest(request) => write!(f, "{:?}", request),
+            client_message::Message::HealthRequest(request) => write!(f, "{:?}", request),
+            client_message::Message::SelfTestRequest(request) => write!(f, "{:?}", request),
+            client_message::Message::CommandStatusRequest(request) => {
+                write!(f, "CommandStatusRequest {{ command_id: {} }}", Truncated(&request.command_id))
+            }
+            client_message::Message::BenchRequest(request) => write!(f, "{:?}", request),
+            client_message::Message::StatsRequest(request) => write!(f, "{:?}", request),
+            client_message::Message::LivenessProbeAck(ack) => write!(f, "{:?}", ack),
+            client_message::Message::RecentEventsRequest(request) => write!(f, "{:?}", request),
+            client_message::Message::GetSchemaRequest(request) => write!(f, "{:?}", request),
+            client_message::Message::DynamicMessage(message) => fmt::Display::fmt(&Dynamic(message), f),
+            client_message::Message::ResyncRequest(request) => write!(f, "{:?}", request),
+            client_message::Message::OfferRequest(request) => {
+                let hash: String = request.sha256.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+                write!(f, "OfferRequest {{ sha256: {}... }}", hash)
+            }
+            client_message::Message::DeltaRequest(request) => write!(
+                f,
+                "DeltaRequest {{ baseline: {:016x}, delta: {} bytes }}",
+                request.baseline,
+                request.delta.len()
+            ),
+        }
+    }
+}
+
+// Binary payloads are reduced to their size and lists to their length
+impl fmt::Display for server_message::Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            server_message::Message::EchoMessage(echo) => {
+                write!(f, "EchoMessage {{ content: {} }}", Truncated(&echo.content))
+            }
+            server_message::Message::AddResponse(response) => write!(f, "{:?}", response),
+            server_message::Message::HealthResponse(response) => write!(
+                f,
+                "HealthResponse {{ status: {:?}, connections: {}, queue_depth: {}, last_error: {}, uptime_secs: {} }}",
+                response.status(),
+                response.connections,
+                response.queue_depth,
+                Truncated(&response.last_error),
+                response.uptime_secs
+            ),
+            server_message::Message::SelfTestResponse(response) => {
+                let failed = response.checks.iter().filter(|check| !check.passed).count();
+                write!(
+                    f,
+                    "SelfTestResponse {{ passed: {}, checks: {}, failed: {} }}",
+                    response.passed,
+                    response.checks.len(),
+                    failed
+                )
+            }
+            server_message::Message::ErrorResponse(error) => {
+                write!(f, "ErrorResponse {{ code: {:?}, message: {} }}", error.code(), Truncated(&error.message))
+            }
+            server_message::Message::CommandStatusResponse(response) => write!(
+                f,
+                "CommandStatusResponse {{ command_id: {}, status: {:?} }}",
+                Truncated(&response.command_id),
+                response.status()
+            ),
+            server_message::Message::BenchResponse(response) => write!(f, "{:?}", response),
+            server_message::Message::BenchPayload(payload) => {
+                write!(f, "BenchPayload {{ index: {}, data: <{} bytes> }}", payload.index, payload.data.len())
+            }
+            server_message::Message::StatsResponse(response) => write!(
+                f,
+                "StatsResponse {{ messages_received: {}, messages_sent: {}, bytes_received: {}, bytes_sent: {}, \
+                 average_latency_us: {}, last_error: {}, connected_secs: {}, read_window: {} }}",
+                response.messages_received,
+                response.messages_sent,
+                response.bytes_received,
+                response.bytes_sent,
+                response.average_latency_us,
+                Truncated(&response.last_error),
+                response.connected_secs,
+                response.read_window
+            ),
+            server_message::Message::LivenessProbe(probe) => write!(f, "{:?}", probe),
+            server_message::Message::RecentEventsResponse(response) => write!(
+                f,
+                "RecentEventsResponse {{ events: {}, dropped: {} }}",
+                response.events.len(),
+                response.dropped
+            ),
+            server_message::Message::GetSchemaResponse(response) => write!(
+                f,
+                "GetSchemaResponse {{ file_descriptor_set: <{} bytes> }}",
+                response.file_descriptor_set.len()
+            ),
+            server_message::Message::DynamicMessage(message) => fmt::Display::fmt(&Dynamic(message), f),
+            server_message::Message::GoAway(go_away) => write!(f, "{:?}", go_away),
+            server_message::Message::OfferResponse(response) => write!(f, "{:?}", response),
+            server_message::Message::MaintenanceMode(maintenance) => write!(f, "{:?}", maintenance),
+            server_message::Message::ResyncResponse(response) => write!(
+                f,
+                "ResyncResponse {{ messages: {}, complete: {} }}",
+                response.messages.len(),
+                response.complete
+            ),
+        }
+    }
+}
+
+/// Shows the payload only, the trace id is already part of every log line
+impl fmt::Display for ClientMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => fmt::Display::fmt(message, f),
+            None => f.write_str("empty ClientMessage"),
+        }
+    }
+}
+
+/// Shows the payload only, the trace id is already part of every log line
+impl fmt::Display for ServerMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => fmt::Display::fmt(message, f),
+            None => f.write_str("empty ServerMessage"),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Import necessary modules and crates
+pub use crate::message::{Event, EventKind}; // Events are kept in their wire representation
+use crate::clock::{self, Clock}; // Event timestamps, replaceable by a disciplined or simulated clock
+use std::{
+    collections::VecDeque, // Ring buffer
+    sync::{Arc, Mutex},
+    time::UNIX_EPOCH,
+};
+
+/// Ring buffer of the most recent significant events, the oldest is overwritten once it is full
+#[derive(Debug)]
+pub struct EventLog {
+    events: Mutex<(VecDeque<Event>, u64)>, // Events oldest first, and how many were overwritten
+    capacity: usize, // Events kept, 0 keeps none
+    clock: Arc<dyn Clock>, // Stamps the events with its wall-clock time
+}
+
+impl EventLog {
+    /// Creates a buffer keeping the last `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self::with_clock(capacity, clock::system())
+    }
+
+    /// Creates a buffer stamping its events with the wall-clock time of `clock`
+    pub fn with_clock(capacity: usize, clock: Arc<dyn Clock>) -> Self {
+        EventLog {
+            events: Mutex::new((VecDeque::with_capacity(capacity), 0)),
+            capacity,
+            clock,
+        }
+    }
+
+    /// Records an event happening now
+    pub fn record(&self, kind: EventKind, detail: impl Into<String>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let event = Event {
+            kind: kind as i32,
+            at_us: self
+                .clock
+                .wall()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_micros() as u64)
+                .unwrap_or(0),
+            detail: detail.into(),
+        };
+        let mut events = self.events.lock().unwrap();
+        if events.0.len() == self.capacity {
+            events.0.pop_front();
+            events.1 += 1;
+        }
+        events.0.push_back(event);
+    }
+
+    /// Returns up to `limit` of the most recent events newest first, all kept events if `limit` is 0
+    pub fn recent(&self, limit: usize) -> Vec<Event> {
+        let events = self.events.lock().unwrap();
+        let limit = if limit == 0 { events.0.len() } else { limit };
+        events.0.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Number of events overwritten because the buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.events.lock().unwrap().1
+    }
+}
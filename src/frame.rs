@@ -0,0 +1,76 @@
+// Import necessary modules and crates
+use crate::protocol::{MAX_MESSAGE_SIZE, MAX_PREFIX_LEN}; // Frame limits
+use std::io::{self, ErrorKind}; // I/O errors
+
+/// Header of a frame on the wire, the payload length as an unsigned protobuf varint. `MAX_PAYLOAD`
+/// is the largest payload the reading side accepts, longer frames are reported as oversized. Flags, a
+/// channel and a CRC are not on the wire yet; they need a new wire version that clients can negotiate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header<const MAX_PAYLOAD: usize> {
+    pub payload_len: usize, // Bytes of protobuf payload following the header
+}
+
+/// Header with the limit both sides of this protocol use
+pub type WireHeader = Header<MAX_MESSAGE_SIZE>;
+
+/// Frame found at the start of a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame {
+    Complete { start: usize, end: usize }, // Payload bounds of a fully received frame
+    Oversized { prefix_len: usize, payload_len: usize }, // Frame above the size limit, to be skipped
+}
+
+impl<const MAX_PAYLOAD: usize> Header<MAX_PAYLOAD> {
+    /// Header for a payload of `payload_len` bytes
+    pub fn new(payload_len: usize) -> Self {
+        Header { payload_len }
+    }
+
+    /// Encoded size of this header, 1 to `MAX_PREFIX_LEN` bytes
+    pub fn encoded_len(&self) -> usize {
+        prost::length_delimiter_len(self.payload_len)
+    }
+
+    /// Writes the header to the start of `out` and returns how many bytes it took
+    pub fn encode(&self, out: &mut [u8; MAX_PREFIX_LEN]) -> usize {
+        prost::encode_length_delimiter(self.payload_len, &mut &mut out[..])
+            .expect("A length prefix is at most 10 bytes");
+        self.encoded_len()
+    }
+
+    /// Reads the header at the start of `buffer`, with its encoded length. `None` until the header is
+    /// complete; a varint running past `MAX_PREFIX_LEN` bytes is an error
+    pub fn parse(buffer: &[u8]) -> io::Result<Option<(Self, usize)>> {
+        let prefix_len = match buffer.iter().take(MAX_PREFIX_LEN).position(|byte| byte & 0x80 == 0) {
+            Some(position) => position + 1,
+            None if buffer.len() < MAX_PREFIX_LEN => return Ok(None),
+            None => return Err(io::Error::new(ErrorKind::InvalidData, "Invalid frame length prefix")),
+        };
+        let payload_len = prost::decode_length_delimiter(&buffer[..prefix_len])
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        Ok(Some((Header { payload_len }, prefix_len)))
+    }
+
+    /// Finds the first frame in `buffer`. `None` until it has fully arrived, except that an oversized
+    /// frame is reported as soon as its header is complete
+    pub fn next_frame(buffer: &[u8]) -> io::Result<Option<Frame>> {
+        let Some((header, prefix_len)) = Self::parse(buffer)? else {
+            return Ok(None);
+        };
+        if header.payload_len > MAX_PAYLOAD {
+            return Ok(Some(Frame::Oversized {
+                prefix_len,
+                payload_len: header.payload_len,
+            }));
+        }
+
+        // Wait for the rest of the payload
+        if buffer.len() < prefix_len + header.payload_len {
+            return Ok(None);
+        }
+        Ok(Some(Frame::Complete {
+            start: prefix_len,
+            end: prefix_len + header.payload_len,
+        }))
+    }
+}
@@ -0,0 +1,385 @@
+// Import necessary modules and crates
+use crate::clock::Clock; // Times the open circuit breaker
+use crate::config::{CachePolicy, GatewayConfig}; // Upstream address, forwarded request types, breaker and cache settings
+use crate::frame::{Frame, WireHeader}; // Frame header encoding and parsing
+use crate::message::{server_message, ClientMessage, LivenessProbeAck, ServerMessage}; // Forwarded messages
+use crate::protocol::MAX_PREFIX_LEN; // Longest frame header
+use crate::socket; // Timeout classification
+use log::{debug, info, warn}; // Logging macros
+use prost::Message; // Protobuf message encoding/decoding
+use std::{
+    collections::HashMap, // Cached responses
+    fmt,
+    io::{self, ErrorKind, Read, Write}, // I/O operations
+    net::{TcpStream, ToSocketAddrs}, // Networking
+    sync::{Arc, Condvar, Mutex}, // Connection pool and breaker state shared by all connections
+    time::{Duration, Instant}, // Time handling
+};
+
+// Bytes read from the upstream server at once
+const READ_CHUNK: usize = 4096;
+
+/// Blocking connection to another server of this protocol, answering its liveness probes itself
+#[derive(Debug)]
+pub struct Upstream {
+    addr: String, // Address connected to, for errors and logs
+    stream: TcpStream,
+    buffer: Vec<u8>, // Received bytes not yet decoded
+    going_away: bool, // Whether the upstream server announced that it is shutting down
+}
+
+impl Upstream {
+    /// Connects to `addr`, trying every address it resolves to; `timeout` bounds the connect and each
+    /// wait for a response
+    pub fn connect(addr: &str, timeout: Duration) -> io::Result<Self> {
+        let mut last_error = None;
+        for socket_addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&socket_addr, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+                    info!("Connected to upstream server {}", addr);
+                    return Ok(Upstream {
+                        addr: addr.to_string(),
+                        stream,
+                        buffer: Vec::new(),
+                        going_away: false,
+                    });
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| io::Error::new(ErrorKind::InvalidInput, "Could not resolve to any address")))
+    }
+
+    /// Address of the upstream server
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// Whether the upstream server sent a `GoAway`, the connection should not be used for new requests
+    pub fn is_going_away(&self) -> bool {
+        self.going_away
+    }
+
+    /// Sends `request` and returns the next response that isn't a liveness probe or `GoAway`
+    pub fn call(&mut self, request: &ClientMessage) -> io::Result<ServerMessage> {
+        self.send(request)?;
+        loop {
+            let response = self.receive()?;
+            match &response.message {
+                Some(server_message::Message::LivenessProbe(probe)) => {
+                    debug!("Answering liveness probe {} of upstream server {}", probe.id, self.addr);
+                    self.send(&LivenessProbeAck { id: probe.id }.into())?;
+                }
+                Some(server_message::Message::GoAway(_)) => {
+                    info!("Upstream server {} is shutting down, closing the connection after this request", self.addr);
+                    self.going_away = true;
+                }
+                _ => return Ok(response),
+            }
+        }
+    }
+
+    // Write one length-prefixed message
+    fn send(&mut self, message: &ClientMessage) -> io::Result<()> {
+        let mut header = [0u8; MAX_PREFIX_LEN];
+        let header_len = WireHeader::new(message.encoded_len()).encode(&mut header);
+        let mut frame = header[..header_len].to_vec();
+        message.encode(&mut frame).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        self.stream.write_all(&frame)
+    }
+
+    // Read until a complete frame arrived and decode it
+    fn receive(&mut self) -> io::Result<ServerMessage> {
+        loop {
+            match WireHeader::next_frame(&self.buffer)? {
+                Some(Frame::Complete { start, end }) => {
+                    let response = ServerMessage::decode(&self.buffer[start..end])
+                        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+                    self.buffer.drain(..end);
+                    return response;
+                }
+                Some(Frame::Oversized { payload_len, .. }) => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Upstream response of {} bytes is over the size limit", payload_len),
+                    ));
+                }
+                None => {}
+            }
+
+            let filled = self.buffer.len();
+            self.buffer.resize(filled + READ_CHUNK, 0);
+            let read = self.stream.read(&mut self.buffer[filled..]);
+            self.buffer.truncate(filled + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(0) => return Err(io::Error::new(ErrorKind::UnexpectedEof, "Upstream server closed the connection")),
+                Ok(_) => {}
+                Err(ref e) if socket::is_timeout(e) => {
+                    return Err(io::Error::new(ErrorKind::TimedOut, "Upstream server did not respond in time"));
+                }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+// Why a request could not be forwarded
+#[derive(Debug)]
+pub(crate) enum ForwardError {
+    CircuitOpen, // Rejected without trying, the upstream server failed too often recently
+    Failed(io::Error), // Connecting, sending or receiving failed
+}
+
+impl fmt::Display for ForwardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForwardError::CircuitOpen => f.write_str("circuit breaker open after repeated failures"),
+            ForwardError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// State of the circuit breaker in front of the upstream server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Breaker {
+    Closed { failures: u32 }, // Forwarding, counting consecutive failures
+    Open { until: Instant }, // Rejecting every request until then
+    HalfOpen, // One probe request is in flight, its outcome closes or reopens the breaker
+}
+
+// Most responses a gateway caches, expired ones are evicted first and then the oldest
+const MAX_CACHED: usize = 1024;
+
+// Upstream responses by encoded request payload, with the time they were received
+#[derive(Debug, Default)]
+struct ResponseCache {
+    entries: HashMap<Vec<u8>, (Instant, server_message::Message)>,
+}
+
+impl ResponseCache {
+    fn insert(&mut self, key: Vec<u8>, now: Instant, response: server_message::Message, policies: &[CachePolicy]) {
+        if self.entries.len() >= MAX_CACHED && !self.entries.contains_key(&key) {
+            // The longest TTL bounds what any entry could still be served for
+            let longest = policies.iter().map(|policy| policy.ttl).max().unwrap_or_default();
+            self.entries.retain(|_, (received, _)| now.duration_since(*received) <= longest);
+            if self.entries.len() >= MAX_CACHED {
+                let oldest = self.entries.iter().min_by_key(|(_, (received, _))| *received).map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+        self.entries.insert(key, (now, response));
+    }
+
+    // Estimated bytes held by the cached requests and responses
+    fn bytes(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|(key, (_, response))| key.len() + response.encoded_len())
+            .sum()
+    }
+
+    // A response no older than `ttl`, with its age
+    fn get(&self, key: &[u8], now: Instant, ttl: Duration) -> Option<(Duration, server_message::Message)> {
+        let (received, response) = self.entries.get(key)?;
+        let age = now.duration_since(*received);
+        (age <= ttl).then(|| (age, response.clone()))
+    }
+}
+
+/// Response to a forwarded request
+#[derive(Debug)]
+pub(crate) struct Forwarded {
+    pub(crate) message: server_message::Message,
+    pub(crate) stale: Option<Duration>, // Age of a cached response served because forwarding failed
+}
+
+// Upstream connections, bounded by `max_connections`
+#[derive(Debug, Default)]
+struct Pool {
+    idle: Vec<Upstream>, // Connected and not in use by any request
+    open: usize, // Connections in use, idle or being established
+}
+
+// Forwarding of the configured request types over a pool of upstream connections, behind a circuit breaker
+#[derive(Debug)]
+pub(crate) struct Gateway {
+    config: GatewayConfig,
+    clock: Arc<dyn Clock>, // Times the open breaker
+    breaker: Mutex<Breaker>,
+    pool: Mutex<Pool>,
+    returned: Condvar, // Wakes requests waiting for a connection when one is returned or closed
+    cache: Mutex<ResponseCache>, // Responses of the request types with a cache policy
+}
+
+impl Gateway {
+    pub(crate) fn new(config: GatewayConfig, clock: Arc<dyn Clock>) -> Self {
+        Gateway {
+            config,
+            clock,
+            breaker: Mutex::new(Breaker::Closed { failures: 0 }),
+            pool: Mutex::new(Pool::default()),
+            returned: Condvar::new(),
+            cache: Mutex::new(ResponseCache::default()),
+        }
+    }
+
+    // Whether requests of this type go upstream; bench requests measure the local link and never do
+    pub(crate) fn forwards(&self, kind: &str) -> bool {
+        kind != "BenchRequest" && self.config.message_types.iter().any(|forwarded| forwarded == kind)
+    }
+
+    pub(crate) fn upstream(&self) -> &str {
+        &self.config.upstream
+    }
+
+    // Estimated bytes held by the response cache, counted against the server's memory budget
+    pub(crate) fn cached_bytes(&self) -> usize {
+        self.cache.lock().unwrap().bytes()
+    }
+
+    // Send a request of type `kind` upstream and return the payload of its response. If that fails and
+    // the type has a cache policy, the last response to the same request is returned while it is fresh enough
+    pub(crate) fn forward(&self, kind: &str, request: &ClientMessage) -> Result<Forwarded, ForwardError> {
+        let ttl = self.config.cache.iter().find(|policy| policy.message_type == kind).map(|policy| policy.ttl);
+        // Requests are cached by payload, the metadata differs between otherwise identical requests
+        let key = ttl.map(|_| {
+            ClientMessage {
+                message: request.message.clone(),
+                metadata: None,
+            }
+            .encode_to_vec()
+        });
+
+        let result = if self.admit() {
+            let result = self.call(request);
+            self.settle(result.is_ok());
+            result.map_err(ForwardError::Failed)
+        } else {
+            Err(ForwardError::CircuitOpen)
+        };
+
+        let now = self.clock.now();
+        match (result, key, ttl) {
+            (Ok(message), Some(key), _) => {
+                // Errors are not worth repeating during an outage
+                if !matches!(message, server_message::Message::ErrorResponse(_)) {
+                    let mut cache = self.cache.lock().unwrap();
+                    cache.insert(key, now, message.clone(), &self.config.cache);
+                }
+                Ok(Forwarded { message, stale: None })
+            }
+            (Ok(message), None, _) => Ok(Forwarded { message, stale: None }),
+            (Err(e), Some(key), Some(ttl)) => match self.cache.lock().unwrap().get(&key, now, ttl) {
+                Some((age, message)) => {
+                    info!("Upstream server {} unavailable ({}), answering {} from the cache", self.config.upstream, e, kind);
+                    Ok(Forwarded {
+                        message,
+                        stale: Some(age),
+                    })
+                }
+                None => Err(e),
+            },
+            (Err(e), _, _) => Err(e),
+        }
+    }
+
+    // Whether the breaker lets a request through, an expired open breaker lets exactly one probe through
+    fn admit(&self) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+        match *breaker {
+            Breaker::Closed { .. } => true,
+            Breaker::Open { until } if self.clock.now() >= until => {
+                info!("Probing upstream server {} after the circuit breaker cooled down", self.config.upstream);
+                *breaker = Breaker::HalfOpen;
+                true
+            }
+            Breaker::Open { .. } | Breaker::HalfOpen => false,
+        }
+    }
+
+    // Record the outcome of a forwarded request
+    fn settle(&self, succeeded: bool) {
+        let mut breaker = self.breaker.lock().unwrap();
+        if succeeded {
+            if !matches!(*breaker, Breaker::Closed { .. }) {
+                info!("Upstream server {} is reachable again, closing the circuit breaker", self.config.upstream);
+            }
+            *breaker = Breaker::Closed { failures: 0 };
+            return;
+        }
+
+        let failures = match *breaker {
+            Breaker::Closed { failures } => failures + 1,
+            // A failed probe, or a request admitted before the breaker opened
+            Breaker::HalfOpen | Breaker::Open { .. } => self.config.failure_threshold,
+        };
+        *breaker = if failures >= self.config.failure_threshold {
+            warn!(
+                "Opening the circuit breaker of upstream server {} for {:?}",
+                self.config.upstream, self.config.open_for
+            );
+            Breaker::Open {
+                until: self.clock.now() + self.config.open_for,
+            }
+        } else {
+            Breaker::Closed { failures }
+        };
+    }
+
+    // Forward over a pooled connection, a failed connection is closed instead of returned
+    fn call(&self, request: &ClientMessage) -> io::Result<server_message::Message> {
+        let mut upstream = self.checkout()?;
+        match upstream.call(request) {
+            Ok(response) => {
+                // A draining upstream server still answered, the next request gets a new connection
+                if upstream.is_going_away() {
+                    self.discard();
+                } else {
+                    self.pool.lock().unwrap().idle.push(upstream);
+                    self.returned.notify_one();
+                }
+                response
+                    .message
+                    .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Empty upstream response"))
+            }
+            Err(e) => {
+                self.discard();
+                Err(e)
+            }
+        }
+    }
+
+    // Take an idle connection, open a new one below the limit, or wait for one to be returned
+    fn checkout(&self) -> io::Result<Upstream> {
+        let deadline = Instant::now() + self.config.timeout;
+        let mut pool = self.pool.lock().unwrap();
+        loop {
+            if let Some(upstream) = pool.idle.pop() {
+                return Ok(upstream);
+            }
+            if pool.open < self.config.max_connections.max(1) {
+                pool.open += 1;
+                drop(pool);
+                // Connect without holding the lock, other requests keep using the pool meanwhile
+                return Upstream::connect(&self.config.upstream, self.config.timeout).inspect_err(|_| self.discard());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(ErrorKind::TimedOut, "No upstream connection became free in time"));
+            }
+            pool = self.returned.wait_timeout(pool, deadline - now).unwrap().0;
+        }
+    }
+
+    // Forget a connection that was closed or never established
+    fn discard(&self) {
+        self.pool.lock().unwrap().open -= 1;
+        self.returned.notify_one();
+    }
+}
@@ -0,0 +1,167 @@
+// Import necessary modules and crates
+use std::{io, net::TcpListener, path::Path};
+#[cfg(all(feature = "handoff", unix))]
+use std::time::Duration;
+
+// How long the new process keeps trying to reach the old one
+#[cfg(all(feature = "handoff", unix))]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Most listeners sent in one handoff, one per acceptor
+#[cfg(all(feature = "handoff", unix))]
+const MAX_LISTENERS: usize = 64;
+
+/// Waits on a Unix socket at `path` for the next server process and passes it `listeners` with SCM_RIGHTS
+#[cfg(all(feature = "handoff", unix))]
+pub fn send_listeners(path: &Path, listeners: &[TcpListener]) -> io::Result<()> {
+    use log::info;
+    use std::os::{fd::AsRawFd, unix::net::UnixListener};
+
+    if listeners.is_empty() || listeners.len() > MAX_LISTENERS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Can hand off 1 to {} listeners, not {}", MAX_LISTENERS, listeners.len()),
+        ));
+    }
+
+    // A socket file left over from an earlier handoff would make bind fail
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let control = UnixListener::bind(path)?;
+    info!("Waiting for the next server process on {}", path.display());
+    let (stream, _) = control.accept()?;
+    let _ = std::fs::remove_file(path);
+
+    let fds: Vec<libc::c_int> = listeners.iter().map(|listener| listener.as_raw_fd()).collect();
+    // The single data byte carries the listener count, the descriptors travel as ancillary data
+    let mut count = [fds.len() as u8];
+    let mut iov = libc::iovec {
+        iov_base: count.as_mut_ptr() as *mut libc::c_void,
+        iov_len: count.len(),
+    };
+    let fds_len = std::mem::size_of_val(fds.as_slice()) as libc::c_uint;
+    // SAFETY: CMSG_SPACE only computes a size
+    let mut control_buffer = vec![0u8; unsafe { libc::CMSG_SPACE(fds_len) } as usize];
+    // SAFETY: msghdr is plain data, all-zero is a valid value
+    let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control_buffer.as_mut_ptr() as *mut libc::c_void;
+    header.msg_controllen = control_buffer.len() as _;
+
+    // SAFETY: the control buffer was sized with CMSG_SPACE for exactly these descriptors, so the first
+    // header and its data fit; iov and the buffers outlive the sendmsg call
+    let sent = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&header);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len) as _;
+        std::ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut libc::c_int, fds.len());
+        libc::sendmsg(stream.as_raw_fd(), &header, 0)
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    info!("Handed {} listeners to the next server process", fds.len());
+    Ok(())
+}
+
+/// Connects to the Unix socket at `path` of the running server and receives its listeners
+#[cfg(all(feature = "handoff", unix))]
+pub fn receive_listeners(path: &Path) -> io::Result<Vec<TcpListener>> {
+    use std::os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::net::UnixStream,
+    };
+    use std::time::Instant;
+
+    // The old process may still be getting ready to hand off
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    let stream = loop {
+        match UnixStream::connect(path) {
+            Ok(stream) => break stream,
+            Err(e) if Instant::now() >= deadline => return Err(e),
+            Err(_) => std::thread::sleep(Duration::from_millis(50)),
+        }
+    };
+
+    let mut count = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: count.as_mut_ptr() as *mut libc::c_void,
+        iov_len: count.len(),
+    };
+    let fds_len = (MAX_LISTENERS * std::mem::size_of::<libc::c_int>()) as libc::c_uint;
+    // SAFETY: CMSG_SPACE only computes a size
+    let mut control_buffer = vec![0u8; unsafe { libc::CMSG_SPACE(fds_len) } as usize];
+    // SAFETY: msghdr is plain data, all-zero is a valid value
+    let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control_buffer.as_mut_ptr() as *mut libc::c_void;
+    header.msg_controllen = control_buffer.len() as _;
+
+    // SAFETY: header points to buffers that live until the call returns; received descriptors are close on exec
+    #[cfg(target_os = "linux")]
+    let flags = libc::MSG_CMSG_CLOEXEC;
+    #[cfg(not(target_os = "linux"))]
+    let flags = 0;
+    let received = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut header, flags) };
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if received == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Server closed the handoff socket without sending listeners",
+        ));
+    }
+
+    let mut listeners = Vec::new();
+    // SAFETY: the kernel filled in the control buffer, the CMSG macros walk it within msg_controllen;
+    // every received descriptor is new to this process and owned by the listener wrapping it
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&header);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data_len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                let data = libc::CMSG_DATA(cmsg) as *const libc::c_int;
+                for index in 0..data_len / std::mem::size_of::<libc::c_int>() {
+                    let fd = std::ptr::read_unaligned(data.add(index));
+                    listeners.push(TcpListener::from(OwnedFd::from_raw_fd(fd)));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&header, cmsg);
+        }
+    }
+
+    if header.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Handoff carried too many listeners"));
+    }
+    if listeners.len() != count[0] as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Expected {} listeners, received {}", count[0], listeners.len()),
+        ));
+    }
+    Ok(listeners)
+}
+
+/// Waits on a Unix socket at `path` for the next server process and passes it `listeners` with SCM_RIGHTS
+#[cfg(not(all(feature = "handoff", unix)))]
+pub fn send_listeners(_path: &Path, _listeners: &[TcpListener]) -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// Connects to the Unix socket at `path` of the running server and receives its listeners
+#[cfg(not(all(feature = "handoff", unix)))]
+pub fn receive_listeners(_path: &Path) -> io::Result<Vec<TcpListener>> {
+    Err(unsupported())
+}
+
+// Error returned when listener handoff is not compiled in
+#[cfg(not(all(feature = "handoff", unix)))]
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "Listener handoff needs the `handoff` feature on Unix")
+}
@@ -0,0 +1,89 @@
+// Import necessary modules and crates
+pub use crate::message::HealthStatus; // Overall status, shared with the wire protocol
+use crate::message::HealthResponse;
+use std::time::Duration; // Time handling
+
+/// Snapshot of the server internals reported by health checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    pub status: HealthStatus, // Serving, Degraded after a recent error, or Draining while shutting down
+    pub connections: usize, // Currently connected clients
+    pub queue_depth: usize, // Requests decoded but not yet answered
+    pub last_error: Option<String>, // Most recent error, if any was recorded
+    pub uptime: Duration, // Time since the server was created
+}
+
+impl HealthReport {
+    /// Converts the report into its wire representation
+    pub fn to_response(&self) -> HealthResponse {
+        HealthResponse {
+            status: self.status as i32,
+            connections: self.connections as u32,
+            queue_depth: self.queue_depth as u32,
+            last_error: self.last_error.clone().unwrap_or_default(),
+            uptime_secs: self.uptime.as_secs(),
+        }
+    }
+
+    /// Renders the report as a JSON object, as served on `/healthz`
+    pub fn to_json(&self) -> String {
+        let status = match self.status {
+            HealthStatus::Serving => "serving",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Draining => "draining",
+            HealthStatus::Maintenance => "maintenance",
+            HealthStatus::Unspecified => "unspecified",
+        };
+        let last_error = match &self.last_error {
+            Some(error) => format!("\"{}\"", escape_json(error)),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"status\":\"{}\",\"connections\":{},\"queue_depth\":{},\"last_error\":{},\"uptime_secs\":{}}}",
+            status,
+            self.connections,
+            self.queue_depth,
+            last_error,
+            self.uptime.as_secs()
+        )
+    }
+}
+
+// Escape a string for use inside a JSON string literal
+pub(crate) fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Serve `GET /healthz` on `listener` until `is_running` turns false. A draining server answers 503, so
+// load balancers stop sending it new clients
+#[cfg(feature = "healthz")]
+pub(crate) fn serve_http(
+    listener: std::net::TcpListener,
+    is_running: &std::sync::atomic::AtomicBool,
+    report: impl Fn() -> HealthReport,
+) -> std::io::Result<()> {
+    crate::http::serve("Health", listener, is_running, |method, path| match (method, path) {
+        ("GET", "/healthz") => {
+            let report = report();
+            let status = if matches!(report.status, HealthStatus::Draining | HealthStatus::Maintenance) {
+                "503 Service Unavailable"
+            } else {
+                "200 OK"
+            };
+            (status, "application/json", report.to_json())
+        }
+        _ => crate::http::not_found(),
+    })
+}
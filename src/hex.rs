@@ -0,0 +1,18 @@
+// Hex encoding of bytes, used by the files that store binary data as text lines
+
+// Lowercase hex encoding of `bytes`, two digits per byte
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Bytes of a string written by `to_hex`, `None` if it isn't valid hex
+#[cfg(feature = "storage")]
+pub(crate) fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,71 @@
+// Import necessary modules and crates
+use log::{info, warn}; // Logging macros
+use std::{
+    io::{self, ErrorKind, Read, Write}, // I/O operations
+    net::TcpListener, // Networking
+    sync::atomic::{AtomicBool, Ordering}, // Running flag of the server
+    thread,
+    time::Duration, // Time handling
+};
+
+// Answer to a request: status line, content type and body
+pub(crate) type Response = (&'static str, &'static str, String);
+
+// Serve requests on `listener` until `is_running` turns false, `respond` maps a method and target to the response
+pub(crate) fn serve(
+    name: &str,
+    listener: TcpListener,
+    is_running: &AtomicBool,
+    respond: impl Fn(&str, &str) -> Response,
+) -> io::Result<()> {
+    info!("{} endpoint listening on {}", name, listener.local_addr()?);
+    // Poll like the protocol accept loop so the endpoint stops with the server
+    listener.set_nonblocking(true)?;
+
+    while is_running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((mut stream, _)) => {
+                // Requests are tiny, a slow or silent peer must not hold up the loop for long
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+                let mut request = [0; 1024];
+                let bytes_read = match stream.read(&mut request) {
+                    Ok(bytes_read) => bytes_read,
+                    Err(e) => {
+                        warn!("Failed to read {} request: {}", name, e);
+                        continue;
+                    }
+                };
+
+                // Only the request line matters, e.g. "GET /healthz HTTP/1.1"
+                let request = String::from_utf8_lossy(&request[..bytes_read]);
+                let mut parts = request.split_whitespace();
+                let (status, content_type, body) = match (parts.next(), parts.next()) {
+                    (Some(method), Some(target)) => respond(method, target),
+                    _ => not_found(),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    content_type,
+                    body.len(),
+                    body
+                );
+                if let Err(e) = stream.write_all(response.as_bytes()) {
+                    warn!("Failed to answer {} request: {}", name, e);
+                }
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => warn!("Error accepting {} request: {}", name, e),
+        }
+    }
+
+    Ok(())
+}
+
+// Answer for paths an endpoint does not serve
+pub(crate) fn not_found() -> Response {
+    ("404 Not Found", "application/json", "{\"error\":\"not found\"}".to_string())
+}
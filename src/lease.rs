@@ -0,0 +1,145 @@
+// Import necessary modules and crates
+use crate::clock::{self, Clock}; // Lease expiry, replaceable in tests
+use log::{error, info, warn}; // Logging macros
+use std::{
+    fs,
+    io::{self, ErrorKind}, // Lease file errors
+    path::{Path, PathBuf}, // Lease file location
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH}, // Expiry shared between nodes as wall-clock time
+};
+
+/// Lease on a file in storage shared by the nodes of a cluster, e.g. an NFS mount. At most one node
+/// holds it until it expires, so cluster-wide jobs run on a single node and move to another when it dies
+#[derive(Debug, Clone)]
+pub struct Lease {
+    path: PathBuf, // Lease file, the same path on every node
+    holder: String, // Name of this node, unique in the cluster
+    ttl: Duration, // How long an acquired or renewed lease stays held
+    clock: Arc<dyn Clock>, // Decides when the lease expires
+}
+
+impl Lease {
+    /// Creates a handle on the lease at `path` for the node `holder`; nothing is acquired yet
+    pub fn new(path: &Path, holder: &str, ttl: Duration) -> Self {
+        Self::with_clock(path, holder, ttl, clock::system())
+    }
+
+    /// Creates a handle timing expiry by `clock`. Nodes compare wall-clock times, so their clocks
+    /// must agree to well within `ttl`
+    pub fn with_clock(path: &Path, holder: &str, ttl: Duration, clock: Arc<dyn Clock>) -> Self {
+        Lease {
+            path: path.to_path_buf(),
+            holder: holder.to_string(),
+            ttl,
+            clock,
+        }
+    }
+
+    /// Acquires the lease if it is free or expired, or renews it if this node holds it already.
+    /// Returns whether this node holds the lease now
+    pub fn try_acquire(&self) -> io::Result<bool> {
+        let now = self.clock.wall();
+        if let Some((holder, expires)) = self.read(&self.path)? {
+            if holder != self.holder && expires > now {
+                return Ok(false);
+            }
+
+            // Move the lease file out of the way under a name of this node's own. Only one node's rename
+            // finds the file, the others see it gone and back off
+            let taken = self.own_file("taken");
+            match fs::rename(&self.path, &taken) {
+                Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+                result => result?,
+            }
+            // Another node may have taken the lease over between the read and the rename, give it back then
+            let lost = match self.read(&taken)? {
+                Some((holder, expires)) => holder != self.holder && expires > now,
+                None => false,
+            };
+            if lost {
+                match fs::hard_link(&taken, &self.path) {
+                    Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {}
+                    result => result?,
+                }
+                fs::remove_file(&taken)?;
+                return Ok(false);
+            }
+            fs::remove_file(&taken)?;
+        }
+
+        // Write the claim beside the lease and link it in place. Linking fails if the lease file exists, so of
+        // the nodes finding the lease free only one gets it, and readers never see half a file
+        let claim = self.own_file("claim");
+        let expires = (now + self.ttl).duration_since(UNIX_EPOCH).unwrap_or_default();
+        fs::write(&claim, format!("{}\n{}\n", self.holder, expires.as_millis()))?;
+        let linked = fs::hard_link(&claim, &self.path);
+        fs::remove_file(&claim)?;
+        match linked {
+            Ok(()) => Ok(true),
+            Err(ref e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The node holding the lease, `None` when it is free or expired
+    pub fn holder(&self) -> io::Result<Option<String>> {
+        let now = self.clock.wall();
+        Ok(self.read(&self.path)?.filter(|(_, expires)| *expires > now).map(|(holder, _)| holder))
+    }
+
+    /// Gives the lease up if this node holds it, so another node can take over without waiting for expiry
+    pub fn release(&self) -> io::Result<()> {
+        match self.read(&self.path)? {
+            Some((holder, _)) if holder == self.holder => match fs::remove_file(&self.path) {
+                Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                result => result,
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Wraps `job` so it only runs on the node holding the lease. Every call renews the lease or tries
+    /// to take it over, so schedule the job at an interval well below `ttl`
+    pub fn guard(self, mut job: impl FnMut() + Send + 'static) -> impl FnMut() + Send + 'static {
+        let mut leading = false;
+        move || match self.try_acquire() {
+            Ok(true) => {
+                if !leading {
+                    info!("{} acquired lease {}", self.holder, self.path.display());
+                    leading = true;
+                }
+                job();
+            }
+            Ok(false) => {
+                if leading {
+                    warn!("{} lost lease {}", self.holder, self.path.display());
+                    leading = false;
+                }
+            }
+            Err(e) => {
+                error!("Failed to renew lease {}: {}", self.path.display(), e);
+                leading = false;
+            }
+        }
+    }
+
+    // File beside the lease that only this node uses, `kind` tells its purpose
+    fn own_file(&self, kind: &str) -> PathBuf {
+        let node: String = self.holder.chars().filter(char::is_ascii_alphanumeric).collect();
+        self.path.with_extension(format!("{}-{}", kind, node))
+    }
+
+    // Holder and expiry in the lease file at `path`, `None` when there is no file
+    fn read(&self, path: &Path) -> io::Result<Option<(String, SystemTime)>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let corrupt = || io::Error::new(ErrorKind::InvalidData, format!("Corrupt lease file {}", path.display()));
+        let (holder, expires) = content.split_once('\n').ok_or_else(corrupt)?;
+        let expires: u64 = expires.trim_end().parse().map_err(|_| corrupt())?;
+        Ok(Some((holder.to_string(), UNIX_EPOCH + Duration::from_millis(expires))))
+    }
+}
@@ -1,4 +1,64 @@
+pub mod acklog;
+#[cfg(feature = "admin")]
+mod admin;
+pub mod affinity;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+mod capture;
+pub mod clock;
+pub mod config;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+pub mod convert;
+#[cfg(feature = "dedup")]
+pub mod dedup;
+#[cfg(feature = "delta")]
+pub mod delta;
+pub mod display;
+pub mod events;
+pub mod frame;
+#[cfg(feature = "gateway")]
+pub mod gateway;
+pub mod handoff;
+pub mod health;
+mod hex;
+#[cfg(any(feature = "healthz", feature = "status-page", feature = "admin"))]
+mod http;
+#[cfg(feature = "storage")]
+pub mod lease;
+#[cfg(feature = "storage")]
+pub mod logfile;
+pub mod message_stats;
+pub mod metrics;
+#[cfg(feature = "storage")]
+pub mod outbox;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod pool;
+pub mod protocol;
+#[cfg(feature = "reflect")]
+pub mod reflect;
+pub mod registry;
+#[cfg(feature = "routing")]
+pub mod routing;
+pub mod scheduler;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod selftest;
 pub mod server;
+pub mod shedding;
+#[cfg(feature = "snmp")]
+pub mod snmp;
+pub mod socket;
+pub mod startup;
+pub mod status;
+pub mod stubs;
+#[cfg(feature = "vectors")]
+pub mod vectors;
+pub mod violations;
+pub mod watchdog;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub mod message {
     include!(concat!(env!("OUT_DIR"), "/messages.rs"));
@@ -0,0 +1,177 @@
+// Import necessary modules and crates
+use crate::config::LogFileConfig; // Location, level and rotation policy
+use log::{Log, Metadata, Record}; // Logger interface
+use std::{
+    fs::{self, File, OpenOptions}, // Log file handling
+    io::{self, ErrorKind, Write}, // I/O operations
+    path::PathBuf, // Rotated file names
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH}, // Timestamps and file age
+};
+
+/// Log file that rotates itself by size and age, keeping a bounded number of old files
+#[derive(Debug)]
+pub struct RotatingFile {
+    config: LogFileConfig, // Location and rotation policy
+    file: File, // Current file, opened for appending
+    size: u64, // Bytes in the current file
+    opened: SystemTime, // When the current file was started, for age-based rotation
+}
+
+impl RotatingFile {
+    /// Opens or creates the log file, appending to what an earlier run wrote
+    pub fn open(config: &LogFileConfig) -> io::Result<Self> {
+        if let Some(dir) = config.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&config.path)?;
+        let metadata = file.metadata()?;
+        Ok(RotatingFile {
+            config: config.clone(),
+            file,
+            size: metadata.len(),
+            // Creation time isn't available everywhere, then the age counts from this run
+            opened: metadata.created().unwrap_or_else(|_| SystemTime::now()),
+        })
+    }
+
+    /// Appends `line`, rotating first if it would push the file past its size or age limit
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let too_big = self
+            .config
+            .max_size
+            .is_some_and(|max_size| self.size > 0 && self.size + line.len() as u64 > max_size);
+        let too_old = self
+            .config
+            .max_age
+            .is_some_and(|max_age| self.opened.elapsed().is_ok_and(|age| age >= max_age));
+        if too_big || too_old {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    // Shift the old files up by one, drop the oldest, and start a new current file
+    fn rotate(&mut self) -> io::Result<()> {
+        let path = &self.config.path;
+        let numbered = |index: usize| {
+            let mut name = path.clone().into_os_string();
+            name.push(format!(".{}", index));
+            PathBuf::from(name)
+        };
+        if self.config.keep == 0 {
+            remove_if_exists(path)?;
+        } else {
+            remove_if_exists(&numbered(self.config.keep))?;
+            for index in (1..self.config.keep).rev() {
+                rename_if_exists(&numbered(index), &numbered(index + 1))?;
+            }
+            fs::rename(path, numbered(1))?;
+        }
+        self.file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.size = 0;
+        self.opened = SystemTime::now();
+        Ok(())
+    }
+}
+
+// Delete a file that may not exist
+fn remove_if_exists(path: &PathBuf) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// Rename a file that may not exist
+fn rename_if_exists(from: &PathBuf, to: &PathBuf) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Logger writing every record at or above the configured level to a `RotatingFile`
+#[derive(Debug)]
+pub struct FileLogger {
+    level: log::LevelFilter, // Most verbose level written
+    file: Mutex<RotatingFile>, // Shared by all logging threads
+}
+
+impl FileLogger {
+    /// Opens the log file described by `config`
+    pub fn open(config: &LogFileConfig) -> io::Result<Self> {
+        Ok(FileLogger {
+            level: config.level,
+            file: Mutex::new(RotatingFile::open(config)?),
+        })
+    }
+
+    /// Opens the log file and installs the logger for the whole process, fails with `AlreadyExists`
+    /// if another logger was installed first
+    pub fn install(config: &LogFileConfig) -> io::Result<()> {
+        let logger = Self::open(config)?;
+        log::set_boxed_logger(Box::new(logger))
+            .map_err(|_| io::Error::new(ErrorKind::AlreadyExists, "A logger is already installed"))?;
+        log::set_max_level(config.level);
+        Ok(())
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            format_timestamp(SystemTime::now()),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        // Nowhere left to report a failing log file but stderr
+        if let Err(e) = self.file.lock().unwrap().write_line(&line) {
+            eprintln!("Failed to write log file: {}", e);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap().file.flush();
+    }
+}
+
+// Format a time as UTC in ISO 8601 with milliseconds, e.g. 2024-05-01T12:30:00.250Z
+fn format_timestamp(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = elapsed.as_secs();
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01, counting in 400-year eras starting on March 1st
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        elapsed.subsec_millis()
+    )
+}
@@ -0,0 +1,74 @@
+// Import necessary modules and crates
+use crate::message::client_message; // Request types
+use crate::protocol::MAX_MESSAGE_SIZE; // Largest frame, bound of the last bucket
+use std::{collections::BTreeMap, sync::Mutex}; // Statistics by message type
+
+/// Upper bounds in bytes of the request size buckets, each four times the previous; the last fits any frame
+pub const SIZE_BUCKETS: [usize; 7] = [16, 64, 256, 1024, 4096, 16 * 1024, MAX_MESSAGE_SIZE];
+
+/// Number and size distribution of the requests of one message type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageStats {
+    pub count: u64, // Requests received
+    pub total_bytes: u64, // Sum of their encoded sizes, without the length prefix
+    pub max_bytes: u64, // Largest request
+    pub buckets: [u64; SIZE_BUCKETS.len()], // Requests above the previous bound and up to the bound at the same index
+}
+
+impl MessageStats {
+    /// Mean encoded size of a request, 0 if none was received
+    pub fn mean_bytes(&self) -> u64 {
+        self.total_bytes.checked_div(self.count).unwrap_or(0)
+    }
+
+    // Add one request of `size` bytes
+    fn record(&mut self, size: usize) {
+        self.count += 1;
+        self.total_bytes += size as u64;
+        self.max_bytes = self.max_bytes.max(size as u64);
+        let bucket = SIZE_BUCKETS
+            .iter()
+            .position(|&bound| size <= bound)
+            .unwrap_or(SIZE_BUCKETS.len() - 1);
+        self.buckets[bucket] += 1;
+    }
+}
+
+// Requests received per message type, across all connections of a server
+#[derive(Debug, Default)]
+pub(crate) struct MessageCounters {
+    stats: Mutex<BTreeMap<&'static str, MessageStats>>,
+}
+
+impl MessageCounters {
+    // Count one received request of `size` bytes
+    pub(crate) fn record(&self, message: &Option<client_message::Message>, size: usize) {
+        self.stats.lock().unwrap().entry(message_type(message)).or_default().record(size);
+    }
+
+    // Copy of the current statistics
+    pub(crate) fn snapshot(&self) -> BTreeMap<&'static str, MessageStats> {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+// Name of a request type as shown on the status page and in slow request logs
+pub(crate) fn message_type(message: &Option<client_message::Message>) -> &'static str {
+    match message {
+        Some(client_message::Message::EchoMessage(_)) => "EchoMessage",
+        Some(client_message::Message::AddRequest(_)) => "AddRequest",
+        Some(client_message::Message::HealthRequest(_)) => "HealthRequest",
+        Some(client_message::Message::SelfTestRequest(_)) => "SelfTestRequest",
+        Some(client_message::Message::CommandStatusRequest(_)) => "CommandStatusRequest",
+        Some(client_message::Message::BenchRequest(_)) => "BenchRequest",
+        Some(client_message::Message::StatsRequest(_)) => "StatsRequest",
+        Some(client_message::Message::LivenessProbeAck(_)) => "LivenessProbeAck",
+        Some(client_message::Message::RecentEventsRequest(_)) => "RecentEventsRequest",
+        Some(client_message::Message::GetSchemaRequest(_)) => "GetSchemaRequest",
+        Some(client_message::Message::DynamicMessage(_)) => "DynamicMessage",
+        Some(client_message::Message::ResyncRequest(_)) => "ResyncRequest",
+        Some(client_message::Message::DeltaRequest(_)) => "DeltaRequest",
+        Some(client_message::Message::OfferRequest(_)) => "OfferRequest",
+        None => "Empty",
+    }
+}
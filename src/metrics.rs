@@ -0,0 +1,32 @@
+/// How a metric value behaves over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Gauge, // Current level, may go up and down
+    Counter, // Running total since the server started, only goes up
+}
+
+/// A single named value exported to a monitoring system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metric {
+    pub name: &'static str, // Stable name, such as `connections`
+    pub kind: MetricKind,
+    pub value: u64,
+}
+
+impl Metric {
+    /// Creates a gauge
+    pub fn gauge(name: &'static str, value: u64) -> Self {
+        Metric { name, kind: MetricKind::Gauge, value }
+    }
+
+    /// Creates a counter
+    pub fn counter(name: &'static str, value: u64) -> Self {
+        Metric { name, kind: MetricKind::Counter, value }
+    }
+}
+
+/// Receives a snapshot of the server metrics on every export, see `Server::export_metrics`
+pub trait MetricsExporter: Send {
+    /// Publishes `metrics`; always the same names in the same order, only the values change
+    fn export(&mut self, metrics: &[Metric]);
+}
@@ -0,0 +1,176 @@
+// Import necessary modules and crates
+use crate::hex::{from_hex, to_hex}; // Queue file lines
+use crate::message::{client_message, ClientMessage, Metadata}; // Queued requests
+use log::warn; // Logging macros
+use prost::Message; // Protobuf message encoding/decoding
+use std::{
+    collections::VecDeque, // Queued requests oldest first
+    fs::{self, File, OpenOptions}, // Queue file handling
+    io::{self, BufRead, BufReader, ErrorKind, Write}, // Queue file reading and writing
+    path::{Path, PathBuf}, // Queue file location
+    time::{SystemTime, UNIX_EPOCH}, // Idempotency keys unique across restarts
+};
+
+/// Requests made while the client can't reach the server, kept in a file until they are delivered. Each gets
+/// a command id as idempotency key, so a request resent after a crash between sending and dequeuing is
+/// applied by the server only once
+#[derive(Debug)]
+pub struct Outbox {
+    path: PathBuf, // Queue file
+    file: File, // Queue file opened for appending
+    pending: VecDeque<ClientMessage>, // Requests not yet delivered, oldest first
+    prefix: String, // Start of the command ids assigned by this instance
+    next_id: u64, // Counter completing the next command id
+}
+
+impl Outbox {
+    /// Opens or creates the outbox at `path`, loading the requests not delivered before a restart. A last
+    /// entry cut off by a crash is dropped, it was never queued; corruption anywhere else is an error
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut pending: VecDeque<ClientMessage> = VecDeque::new();
+        match File::open(path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+                let mut line = String::new();
+                let mut index = 0;
+                while reader.read_line(&mut line)? > 0 {
+                    index += 1;
+                    if !line.ends_with('\n') {
+                        warn!("Dropping torn entry at line {} of outbox {}", index, path.display());
+                        break;
+                    }
+                    let corrupt = || {
+                        io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("Corrupt outbox {} at line {}", path.display(), index),
+                        )
+                    };
+                    match parse_line(line.trim_end()).ok_or_else(corrupt)? {
+                        Entry::Queued(message) => pending.push_back(message),
+                        // Requests are delivered in order, so only the oldest can be marked
+                        Entry::Delivered(command_id) => {
+                            if pending.front().map(command_id_of) != Some(command_id.as_str()) {
+                                return Err(corrupt());
+                            }
+                            pending.pop_front();
+                        }
+                    }
+                    line.clear();
+                }
+            }
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        // Rewrite the file with only the pending requests, so it doesn't grow across restarts
+        let compacted = path.with_extension("compact");
+        {
+            let mut file = File::create(&compacted)?;
+            for message in &pending {
+                file.write_all(format_queued(message).as_bytes())?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&compacted, path)?;
+
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(Outbox {
+            path: path.to_path_buf(),
+            file: OpenOptions::new().append(true).open(path)?,
+            pending,
+            prefix: format!("outbox-{:x}", started.as_nanos()),
+            next_id: 0,
+        })
+    }
+
+    /// Queues `message` behind the pending requests, persisting it before returning. Returns the command id
+    /// it is sent with
+    pub fn push(&mut self, message: client_message::Message) -> io::Result<String> {
+        self.next_id += 1;
+        let command_id = format!("{}-{}", self.prefix, self.next_id);
+        let message = ClientMessage {
+            message: Some(message),
+            // The trace id comes back in the response, telling which request was answered
+            metadata: Some(Metadata {
+                trace_id: command_id.clone(),
+                command_id: command_id.clone(),
+                ..Metadata::default()
+            }),
+        };
+        self.append(&format_queued(&message))?;
+        self.pending.push_back(message);
+        Ok(command_id)
+    }
+
+    /// The oldest pending request, the next one to send
+    pub fn front(&self) -> Option<&ClientMessage> {
+        self.pending.front()
+    }
+
+    /// Pending requests, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &ClientMessage> {
+        self.pending.iter()
+    }
+
+    /// Removes the oldest pending request once the server answered it, persisting that before returning
+    pub fn pop(&mut self) -> io::Result<Option<ClientMessage>> {
+        let Some(message) = self.pending.front() else {
+            return Ok(None);
+        };
+        let line = format!("d {}\n", to_hex(command_id_of(message).as_bytes()));
+        self.append(&line)?;
+        Ok(self.pending.pop_front())
+    }
+
+    // Append `line` to the queue file and sync it. A failed write is cut off again, so later lines aren't
+    // appended behind a partial one and the queue can still be opened
+    fn append(&mut self, line: &str) -> io::Result<()> {
+        let length = self.file.metadata()?.len();
+        let written = self.file.write_all(line.as_bytes()).and_then(|()| self.file.sync_data());
+        if let Err(e) = written {
+            if let Err(truncate) = self.file.set_len(length).and_then(|()| self.file.sync_data()) {
+                warn!("Failed to cut a partial line off outbox {}: {}", self.path.display(), truncate);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Number of pending requests
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether every request was delivered
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Location of the queue file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+// A line of the queue file
+enum Entry {
+    Queued(ClientMessage), // `q` and the hex encoded request
+    Delivered(String), // `d` and the hex command id of the request answered
+}
+
+fn command_id_of(message: &ClientMessage) -> &str {
+    message.metadata.as_ref().map_or("", |metadata| metadata.command_id.as_str())
+}
+
+fn format_queued(message: &ClientMessage) -> String {
+    format!("q {}\n", to_hex(&message.encode_to_vec()))
+}
+
+// Parse a line written by `format_queued` or `pop`
+fn parse_line(line: &str) -> Option<Entry> {
+    match line.split_once(' ')? {
+        ("q", message) => Some(Entry::Queued(ClientMessage::decode(&from_hex(message)?[..]).ok()?)),
+        ("d", command_id) => Some(Entry::Delivered(String::from_utf8(from_hex(command_id)?).ok()?)),
+        _ => None,
+    }
+}
@@ -0,0 +1,307 @@
+// Import necessary modules and crates
+use crate::message::{DynamicMessage, ErrorCode, ErrorResponse}; // Plugin requests and answers
+use crate::reflect::DynamicHandler; // Plugins answer dynamic messages
+use prost::Message; // Protobuf message encoding/decoding
+use prost_types::FileDescriptorSet; // Schemas of the plugin message types
+use std::{
+    fmt, io,
+    path::Path, // Plugin directory
+    sync::Arc, // Plugins shared by their routes
+};
+
+/// Version of the plugin ABI this server implements, a plugin built against another one is refused
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Symbol every plugin library exports, an `extern "C" fn() -> *const PluginVTable`
+pub const PLUGIN_ENTRY: &str = "embedded_plugin_entry";
+
+/// Status of a plugin call that succeeded, any other value is an `ErrorCode`
+pub const PLUGIN_OK: i32 = 0;
+
+/// Bytes passed across the plugin boundary. Buffers a plugin returns in `out` are released by its `free`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PluginBytes {
+    pub data: *const u8, // Null for no bytes
+    pub len: usize,
+}
+
+impl PluginBytes {
+    /// Borrows `bytes` for the duration of one call
+    pub const fn new(bytes: &[u8]) -> Self {
+        PluginBytes {
+            data: bytes.as_ptr(),
+            len: bytes.len(),
+        }
+    }
+
+    /// No bytes, what `out` holds until a plugin sets it
+    pub const fn empty() -> Self {
+        PluginBytes {
+            data: std::ptr::null(),
+            len: 0,
+        }
+    }
+
+    /// Copies the bytes out
+    ///
+    /// # Safety
+    /// `data` must be null or point to `len` readable bytes
+    pub unsafe fn to_vec(&self) -> Vec<u8> {
+        if self.data.is_null() {
+            return Vec::new();
+        }
+        std::slice::from_raw_parts(self.data, self.len).to_vec()
+    }
+}
+
+/// Functions and data a plugin exports through its entry point, valid as long as the library is loaded.
+/// Every function may be called from several connection threads at once
+#[repr(C)]
+pub struct PluginVTable {
+    pub abi_version: u32, // `PLUGIN_ABI_VERSION` the plugin was built against
+    pub name: PluginBytes, // UTF-8 name, for logs
+    pub schema: PluginBytes, // Encoded FileDescriptorSet of the handled types, empty if they are in the server's schema
+    pub message_types: *const PluginBytes, // Full names of the dynamic message types the plugin handles
+    pub message_type_count: usize,
+    /// Handles the payload of a dynamic message of the given type. Returns `PLUGIN_OK` with the encoded
+    /// `DynamicMessage` to send back in `out`, or an `ErrorCode` with a UTF-8 message in `out`
+    pub handle: Option<extern "C" fn(type_name: PluginBytes, payload: PluginBytes, out: *mut PluginBytes) -> i32>,
+    /// Middleware run on every request before it is handled, with its type, e.g. "AddRequest", and the
+    /// encoded `ClientMessage` without metadata. Returns `PLUGIN_OK` to let it through, or an `ErrorCode`
+    /// with a UTF-8 message in `out` to answer it with that error instead
+    pub filter: Option<extern "C" fn(message_type: PluginBytes, request: PluginBytes, out: *mut PluginBytes) -> i32>,
+    /// Releases a buffer the plugin returned in `out`
+    pub free: Option<extern "C" fn(buffer: PluginBytes)>,
+}
+
+/// A loaded plugin, kept loaded while any of its routes exists
+pub struct Plugin {
+    name: String, // From the vtable, or the file name if it has none
+    vtable: &'static PluginVTable,
+    #[cfg(unix)]
+    library: Option<Library>, // Unloaded when the plugin is dropped, `None` for a plugin linked in
+}
+
+// The vtable is immutable and plugins are required to be thread-safe
+unsafe impl Send for Plugin {}
+unsafe impl Sync for Plugin {}
+
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Plugin")
+            .field("name", &self.name)
+            .field("message_types", &self.message_types())
+            .finish()
+    }
+}
+
+impl Plugin {
+    /// Wraps the vtable of a plugin linked into the program instead of loaded from a library
+    ///
+    /// # Safety
+    /// The vtable must follow the contract documented on `PluginVTable`
+    pub unsafe fn from_vtable(vtable: &'static PluginVTable) -> io::Result<Self> {
+        if vtable.abi_version != PLUGIN_ABI_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Plugin ABI version {}, the server implements {}", vtable.abi_version, PLUGIN_ABI_VERSION),
+            ));
+        }
+        Ok(Plugin {
+            name: String::from_utf8_lossy(&vtable.name.to_vec()).into_owned(),
+            vtable,
+            #[cfg(unix)]
+            library: None,
+        })
+    }
+
+    /// Loads the plugin library at `path` and checks its ABI version
+    ///
+    /// # Safety
+    /// Loading runs the library's initialisers, and it must export `PLUGIN_ENTRY` following the contract
+    /// documented on `PluginVTable`. Only load plugins you trust as much as the server itself
+    #[cfg(unix)]
+    pub unsafe fn load(path: &Path) -> io::Result<Self> {
+        let library = Library::open(path)?;
+        let entry: extern "C" fn() -> *const PluginVTable = std::mem::transmute(library.symbol(PLUGIN_ENTRY)?);
+        let vtable = entry().as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Plugin {} returned no vtable", path.display()))
+        })?;
+        let mut plugin = Self::from_vtable(vtable)?;
+        if plugin.name.is_empty() {
+            plugin.name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        }
+        plugin.library = Some(library);
+        Ok(plugin)
+    }
+
+    /// Loads a plugin library; libraries can only be loaded on Unix
+    ///
+    /// # Safety
+    /// Nothing is loaded on this platform
+    #[cfg(not(unix))]
+    pub unsafe fn load(_path: &Path) -> io::Result<Self> {
+        Err(unsupported())
+    }
+
+    /// Name of the plugin, for logs
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Full names of the dynamic message types the plugin handles
+    pub fn message_types(&self) -> Vec<String> {
+        if self.vtable.message_types.is_null() {
+            return Vec::new();
+        }
+        // Safety: the vtable contract makes this an array of `message_type_count` valid buffers
+        let types = unsafe { std::slice::from_raw_parts(self.vtable.message_types, self.vtable.message_type_count) };
+        types
+            .iter()
+            .map(|name| String::from_utf8_lossy(&unsafe { name.to_vec() }).into_owned())
+            .collect()
+    }
+
+    /// Schema of the handled message types, `None` if the plugin brings none
+    pub fn schema(&self) -> io::Result<Option<FileDescriptorSet>> {
+        // Safety: the vtable contract makes this a valid buffer
+        let schema = unsafe { self.vtable.schema.to_vec() };
+        if schema.is_empty() {
+            return Ok(None);
+        }
+        FileDescriptorSet::decode(&schema[..])
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Schema of plugin {}: {}", self.name, e)))
+    }
+
+    /// Runs the plugin's middleware on a request of type `message_type`, encoded without metadata
+    pub fn filter(&self, message_type: &str, request: &[u8]) -> Result<(), ErrorResponse> {
+        let Some(filter) = self.vtable.filter else {
+            return Ok(());
+        };
+        self.call(|out| filter(PluginBytes::new(message_type.as_bytes()), PluginBytes::new(request), out))
+            .map(|_| ())
+    }
+
+    /// Whether the plugin has middleware for every request
+    pub fn has_filter(&self) -> bool {
+        self.vtable.filter.is_some()
+    }
+
+    // Run one plugin function, taking ownership of what it returned in `out`
+    fn call(&self, function: impl FnOnce(*mut PluginBytes) -> i32) -> Result<Vec<u8>, ErrorResponse> {
+        let mut out = PluginBytes::empty();
+        let status = function(&mut out);
+        // Safety: the vtable contract makes `out` a valid buffer, released by the plugin's `free`
+        let bytes = unsafe { out.to_vec() };
+        if let (Some(free), false) = (self.vtable.free, out.data.is_null()) {
+            free(out);
+        }
+        if status == PLUGIN_OK {
+            return Ok(bytes);
+        }
+        let code = ErrorCode::try_from(status).unwrap_or(ErrorCode::Internal);
+        Err(ErrorResponse::new(code, String::from_utf8_lossy(&bytes)))
+    }
+}
+
+impl DynamicHandler for Plugin {
+    fn handle(&self, type_name: &str, payload: &[u8]) -> Result<DynamicMessage, ErrorResponse> {
+        let Some(handle) = self.vtable.handle else {
+            return Err(ErrorResponse::new(ErrorCode::Unsupported, format!("Plugin {} handles no messages", self.name)));
+        };
+        let response = self.call(|out| handle(PluginBytes::new(type_name.as_bytes()), PluginBytes::new(payload), out))?;
+        DynamicMessage::decode(&response[..]).map_err(|e| {
+            ErrorResponse::new(ErrorCode::Internal, format!("Plugin {} sent an invalid response: {}", self.name, e))
+        })
+    }
+}
+
+// A route to a plugin, which stays loaded while the route exists
+pub(crate) struct PluginRoute(pub(crate) Arc<Plugin>);
+
+impl DynamicHandler for PluginRoute {
+    fn handle(&self, type_name: &str, payload: &[u8]) -> Result<DynamicMessage, ErrorResponse> {
+        self.0.handle(type_name, payload)
+    }
+}
+
+/// Loads every plugin library in `dir`, in file name order: `.so` files, and `.dylib` files on macOS
+///
+/// # Safety
+/// See `Plugin::load`, every library in the directory is loaded
+#[cfg(unix)]
+pub unsafe fn load_plugins(dir: &Path) -> io::Result<Vec<Plugin>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    paths.retain(|path| matches!(path.extension().and_then(|extension| extension.to_str()), Some("so" | "dylib")));
+    paths.sort();
+    paths.iter().map(|path| Plugin::load(path)).collect()
+}
+
+/// Loads every plugin library in a directory; libraries can only be loaded on Unix
+///
+/// # Safety
+/// Nothing is loaded on this platform
+#[cfg(not(unix))]
+pub unsafe fn load_plugins(_dir: &Path) -> io::Result<Vec<Plugin>> {
+    Err(unsupported())
+}
+
+// Handle of a library opened with dlopen, closed on drop
+#[cfg(unix)]
+struct Library(*mut libc::c_void);
+
+#[cfg(unix)]
+impl Library {
+    // Open the library at `path`, resolving all its symbols now so a missing one fails here
+    unsafe fn open(path: &Path) -> io::Result<Self> {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+        let name = CString::new(path.as_os_str().as_bytes())?;
+        let handle = libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if handle.is_null() {
+            return Err(dl_error(&format!("Failed to load plugin {}", path.display())));
+        }
+        Ok(Library(handle))
+    }
+
+    // Address of the exported symbol `name`
+    unsafe fn symbol(&self, name: &str) -> io::Result<*mut libc::c_void> {
+        let symbol = std::ffi::CString::new(name)?;
+        let address = libc::dlsym(self.0, symbol.as_ptr());
+        if address.is_null() {
+            return Err(dl_error(&format!("Plugin doesn't export {}", name)));
+        }
+        Ok(address)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Library {
+    fn drop(&mut self) {
+        // Safety: the handle came from dlopen and nothing of the library is used after its plugin is dropped
+        unsafe {
+            libc::dlclose(self.0);
+        }
+    }
+}
+
+// The last dlopen or dlsym error, after `context`
+#[cfg(unix)]
+unsafe fn dl_error(context: &str) -> io::Error {
+    let error = libc::dlerror();
+    let detail = if error.is_null() {
+        "unknown error".into()
+    } else {
+        std::ffi::CStr::from_ptr(error).to_string_lossy()
+    };
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", context, detail))
+}
+
+// Error returned where plugin libraries can't be loaded
+#[cfg(not(unix))]
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "Plugin libraries can only be loaded on Unix")
+}
@@ -0,0 +1,248 @@
+// Import necessary modules and crates
+use lazy_static::lazy_static; // Import the lazy_static crate for static initialization
+use std::{
+    fmt,
+    ops::{Deref, DerefMut}, // Let a pooled buffer be used like a Vec<u8>
+    sync::{
+        atomic::{AtomicUsize, Ordering}, // Lock-free counters for the statistics
+        Mutex, // Mutex for the idle buffer lists
+    },
+};
+
+// Capacities of the pooled size classes, smallest first. The largest class fits a maximum size frame.
+const SIZE_CLASSES: [usize; 3] = [512, 4 * 1024, 64 * 1024 + 16];
+
+// Maximum number of idle buffers kept per size class, extra buffers are freed
+const MAX_IDLE_PER_CLASS: usize = 64;
+
+// Initialize the pool shared by every connection of every server
+lazy_static! {
+    static ref BUFFER_POOL: BufferPool = BufferPool::new();
+}
+
+/// Takes a buffer with at least `min_capacity` bytes of capacity from the shared pool
+pub fn acquire(min_capacity: usize) -> PooledBuffer<'static> {
+    BUFFER_POOL.acquire(min_capacity)
+}
+
+/// Returns a snapshot of the shared pool statistics
+pub fn stats() -> PoolStats {
+    BUFFER_POOL.stats()
+}
+
+// A single size class with its idle buffers and counters
+#[derive(Debug)]
+struct SizeClass {
+    capacity: usize, // Capacity of every buffer handed out from this class
+    idle: Mutex<Vec<Vec<u8>>>, // Buffers waiting to be reused
+    hits: AtomicUsize, // Acquisitions served from an idle buffer
+    misses: AtomicUsize, // Acquisitions that had to allocate
+}
+
+/// Pool of reusable byte buffers grouped into size classes
+#[derive(Debug)]
+pub struct BufferPool {
+    classes: Vec<SizeClass>, // Size classes, smallest first
+    in_use: AtomicUsize, // Buffers currently handed out
+    bytes_in_use: AtomicUsize, // Capacity of the buffers handed out, including growth through `PooledBuffer`
+    oversized: AtomicUsize, // Acquisitions too large for any size class
+}
+
+/// Statistics of one size class
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeClassStats {
+    pub capacity: usize, // Capacity of the buffers in this class
+    pub idle: usize, // Buffers waiting to be reused
+    pub idle_bytes: usize, // Capacity of the idle buffers, more than `capacity * idle` once buffers grew
+    pub hits: usize, // Acquisitions served from an idle buffer
+    pub misses: usize, // Acquisitions that had to allocate
+}
+
+/// Snapshot of the pool statistics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    pub classes: Vec<SizeClassStats>, // Per size class statistics, smallest first
+    pub in_use: usize, // Buffers currently handed out
+    pub bytes_in_use: usize, // Capacity of the buffers handed out, including growth through `PooledBuffer`
+    pub oversized: usize, // Acquisitions too large for any size class
+}
+
+impl PoolStats {
+    /// Total bytes held by idle buffers
+    pub fn idle_bytes(&self) -> usize {
+        self.classes.iter().map(|class| class.idle_bytes).sum()
+    }
+
+    /// Total bytes held by the pool, idle or handed out
+    pub fn total_bytes(&self) -> usize {
+        self.idle_bytes() + self.bytes_in_use
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BufferPool {
+    /// Creates an empty pool using the default size classes
+    pub fn new() -> Self {
+        BufferPool {
+            classes: SIZE_CLASSES
+                .iter()
+                .map(|&capacity| SizeClass {
+                    capacity,
+                    idle: Mutex::new(Vec::new()),
+                    hits: AtomicUsize::new(0),
+                    misses: AtomicUsize::new(0),
+                })
+                .collect(),
+            in_use: AtomicUsize::new(0),
+            bytes_in_use: AtomicUsize::new(0),
+            oversized: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes an empty buffer with at least `min_capacity` bytes of capacity, reusing an idle one if possible
+    pub fn acquire(&self, min_capacity: usize) -> PooledBuffer<'_> {
+        self.in_use.fetch_add(1, Ordering::Relaxed);
+
+        // Pick the smallest class that fits, larger requests are allocated exactly and never pooled
+        let buffer = match self.classes.iter().find(|class| class.capacity >= min_capacity) {
+            Some(class) => match class.idle.lock().unwrap().pop() {
+                Some(buffer) => {
+                    class.hits.fetch_add(1, Ordering::Relaxed);
+                    buffer
+                }
+                None => {
+                    class.misses.fetch_add(1, Ordering::Relaxed);
+                    Vec::with_capacity(class.capacity)
+                }
+            },
+            None => {
+                self.oversized.fetch_add(1, Ordering::Relaxed);
+                Vec::with_capacity(min_capacity)
+            }
+        };
+
+        let counted = buffer.capacity();
+        self.bytes_in_use.fetch_add(counted, Ordering::Relaxed);
+        PooledBuffer {
+            pool: self,
+            buffer: Some(buffer),
+            counted,
+        }
+    }
+
+    /// Returns a snapshot of the pool statistics
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            classes: self
+                .classes
+                .iter()
+                .map(|class| {
+                    let idle = class.idle.lock().unwrap();
+                    SizeClassStats {
+                        capacity: class.capacity,
+                        idle: idle.len(),
+                        idle_bytes: idle.iter().map(Vec::capacity).sum(),
+                        hits: class.hits.load(Ordering::Relaxed),
+                        misses: class.misses.load(Ordering::Relaxed),
+                    }
+                })
+                .collect(),
+            in_use: self.in_use.load(Ordering::Relaxed),
+            bytes_in_use: self.bytes_in_use.load(Ordering::Relaxed),
+            oversized: self.oversized.load(Ordering::Relaxed),
+        }
+    }
+
+    // Put a buffer back into the largest class it still satisfies
+    fn release(&self, mut buffer: Vec<u8>, counted: usize) {
+        self.in_use.fetch_sub(1, Ordering::Relaxed);
+        self.bytes_in_use.fetch_sub(counted, Ordering::Relaxed);
+
+        // Buffers that grew far past the largest class are freed so the pool doesn't hoard memory
+        let largest = SIZE_CLASSES[SIZE_CLASSES.len() - 1];
+        if buffer.capacity() > 2 * largest {
+            return;
+        }
+
+        if let Some(class) = self.classes.iter().rev().find(|class| buffer.capacity() >= class.capacity) {
+            let mut idle = class.idle.lock().unwrap();
+            if idle.len() < MAX_IDLE_PER_CLASS {
+                buffer.clear();
+                idle.push(buffer);
+            }
+        }
+    }
+}
+
+/// Buffer borrowed from a `BufferPool`, handed back to it when dropped. Grow it with its own `resize`, `reserve`
+/// or `extend_from_slice`, so the pool counts the new capacity; growth through the `Vec` itself isn't counted
+pub struct PooledBuffer<'a> {
+    pool: &'a BufferPool, // Pool the buffer returns to
+    buffer: Option<Vec<u8>>, // Always `Some` until dropped
+    counted: usize, // Capacity added to the pool's bytes in use
+}
+
+impl PooledBuffer<'_> {
+    /// Resizes the buffer like `Vec::resize`, counting any growth
+    pub fn resize(&mut self, new_len: usize, value: u8) {
+        self.buffer.as_mut().unwrap().resize(new_len, value);
+        self.count_growth();
+    }
+
+    /// Reserves capacity like `Vec::reserve`, counting any growth
+    pub fn reserve(&mut self, additional: usize) {
+        self.buffer.as_mut().unwrap().reserve(additional);
+        self.count_growth();
+    }
+
+    /// Appends `bytes` like `Vec::extend_from_slice`, counting any growth
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.buffer.as_mut().unwrap().extend_from_slice(bytes);
+        self.count_growth();
+    }
+
+    // Add the capacity the buffer grew by to the pool's bytes in use
+    fn count_growth(&mut self) {
+        let capacity = self.buffer.as_ref().unwrap().capacity();
+        if capacity > self.counted {
+            self.pool.bytes_in_use.fetch_add(capacity - self.counted, Ordering::Relaxed);
+            self.counted = capacity;
+        }
+    }
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        self.buffer.as_mut().unwrap()
+    }
+}
+
+impl fmt::Debug for PooledBuffer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledBuffer")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.pool.release(buffer, self.counted);
+        }
+    }
+}
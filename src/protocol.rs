@@ -0,0 +1,340 @@
+// Import necessary modules and crates
+use crate::health::escape_json; // JSON string escaping
+use prost::Message; // Protobuf message decoding
+use prost_types::{
+    field_descriptor_proto::{Label, Type}, // Field kinds
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorSet, // Schema descriptors
+};
+
+/// Maximum size of a single encoded message payload, excluding its length prefix
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// Longest possible length prefix, a varint of a 64-bit length
+pub const MAX_PREFIX_LEN: usize = 10;
+
+/// Descriptor of `proto/messages.proto` as compiled into this crate
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/messages.bin"));
+
+/// Returns the decoded descriptor of the wire protocol
+pub fn descriptor() -> FileDescriptorSet {
+    FileDescriptorSet::decode(FILE_DESCRIPTOR_SET).expect("Embedded descriptor set is valid")
+}
+
+/// Describes the wire format, message types and error codes as JSON, for implementing clients in other languages
+pub fn describe() -> String {
+    let set = descriptor();
+    let mut messages = Vec::new();
+    let mut enums = Vec::new();
+    let mut package = String::new();
+    for file in &set.file {
+        package = file.package().to_string();
+        for message in &file.message_type {
+            describe_message(message, "", &mut messages, &mut enums);
+        }
+        enums.extend(file.enum_type.iter().map(|e| describe_enum(e, "")));
+    }
+
+    format!(
+        concat!(
+            "{{\"wire_format\":{{",
+            "\"framing\":\"length_delimited\",",
+            "\"length_prefix\":\"unsigned protobuf varint, at most {} bytes\",",
+            "\"max_message_bytes\":{},",
+            "\"client_to_server\":\"{}.ClientMessage\",",
+            "\"server_to_client\":\"{}.ServerMessage\"",
+            "}},\"package\":\"{}\",\"messages\":[{}],\"enums\":[{}]}}"
+        ),
+        MAX_PREFIX_LEN,
+        MAX_MESSAGE_SIZE,
+        escape_json(&package),
+        escape_json(&package),
+        escape_json(&package),
+        messages.join(","),
+        enums.join(",")
+    )
+}
+
+// Describe a message and, depth first, the messages and enums nested in it
+fn describe_message(message: &DescriptorProto, scope: &str, messages: &mut Vec<String>, enums: &mut Vec<String>) {
+    let name = format!("{}{}", scope, message.name());
+    let fields: Vec<String> = message
+        .field
+        .iter()
+        .map(|field| {
+            // proto3 `optional` fields sit in a synthetic oneof that isn't part of the API
+            let oneof = field
+                .oneof_index
+                .filter(|_| !field.proto3_optional())
+                .and_then(|index| message.oneof_decl.get(index as usize))
+                .map_or("null".to_string(), |oneof| format!("\"{}\"", escape_json(oneof.name())));
+            let (label, kind) = field_type(field);
+            format!(
+                "{{\"name\":\"{}\",\"number\":{},\"type\":\"{}\",\"label\":\"{}\",\"oneof\":{}}}",
+                escape_json(field.name()),
+                field.number(),
+                escape_json(&kind),
+                label,
+                oneof
+            )
+        })
+        .collect();
+    messages.push(format!(
+        "{{\"name\":\"{}\",\"fields\":[{}]}}",
+        escape_json(&name),
+        fields.join(",")
+    ));
+
+    let scope = format!("{}.", name);
+    for nested in &message.nested_type {
+        describe_message(nested, &scope, messages, enums);
+    }
+    enums.extend(message.enum_type.iter().map(|e| describe_enum(e, &scope)));
+}
+
+// Label and type of a field, message and enum types by their full name
+fn field_type(field: &FieldDescriptorProto) -> (&'static str, String) {
+    let label = if field.label() == Label::Repeated { "repeated" } else { "singular" };
+    let kind = match field.r#type() {
+        Type::Message | Type::Enum => field.type_name().trim_start_matches('.').to_string(),
+        other => other.as_str_name().trim_start_matches("TYPE_").to_lowercase(),
+    };
+    (label, kind)
+}
+
+// Describe an enum with its values
+fn describe_enum(descriptor: &EnumDescriptorProto, scope: &str) -> String {
+    let values: Vec<String> = descriptor
+        .value
+        .iter()
+        .map(|value| format!("{{\"name\":\"{}\",\"number\":{}}}", escape_json(value.name()), value.number()))
+        .collect();
+    format!(
+        "{{\"name\":\"{}{}\",\"values\":[{}]}}",
+        escape_json(scope),
+        escape_json(descriptor.name()),
+        values.join(",")
+    )
+}
+
+/// Renders the wire-relevant parts of a schema, every field and enum value with its number, one per line
+pub fn schema_lock(set: &FileDescriptorSet) -> String {
+    let mut entries: Vec<String> = schema_entries(set)
+        .into_iter()
+        .map(|entry| entry.to_string())
+        .collect();
+    entries.sort();
+    let mut lock = String::from("# Wire schema of the last release, fields and enum values must keep their numbers\n");
+    for entry in entries {
+        lock.push_str(&entry);
+        lock.push('\n');
+    }
+    lock
+}
+
+/// Checks `current` against a lock written by `schema_lock` for an earlier release, listing every incompatibility
+///
+/// A field or enum value may only disappear if its number is reserved, and must otherwise keep its number and type.
+/// New fields, values and messages are always allowed.
+pub fn check_compatibility(lock: &str, current: &FileDescriptorSet) -> Result<(), Vec<String>> {
+    let current_entries = schema_entries(current);
+    let mut problems = Vec::new();
+    for (index, line) in lock.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some(old) = SchemaEntry::parse(line) else {
+            problems.push(format!("Unreadable lock line {}: {}", index + 1, line));
+            continue;
+        };
+
+        let same_name = current_entries
+            .iter()
+            .find(|entry| entry.kind == old.kind && entry.parent == old.parent && entry.name == old.name);
+        let same_number = current_entries
+            .iter()
+            .find(|entry| entry.kind == old.kind && entry.parent == old.parent && entry.number == old.number);
+        match (same_name, same_number) {
+            (Some(entry), _) if entry.number != old.number => problems.push(format!(
+                "{}.{} was renumbered from {} to {}",
+                old.parent, old.name, old.number, entry.number
+            )),
+            (Some(entry), _) if entry.kind == EntryKind::Field && entry.type_name != old.type_name => {
+                problems.push(format!(
+                    "{}.{} changed type from {} to {}",
+                    old.parent, old.name, old.type_name, entry.type_name
+                ))
+            }
+            (Some(_), _) => {}
+            (None, Some(entry)) => problems.push(format!(
+                "{}.{} was removed and its number {} reused by {}",
+                old.parent, old.name, old.number, entry.name
+            )),
+            (None, None) if !is_reserved(current, &old) => problems.push(format!(
+                "{}.{} was removed without reserving number {}",
+                old.parent, old.name, old.number
+            )),
+            (None, None) => {}
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+// Whether a field or enum entry is a message field or an enum value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    Field,
+    Value,
+}
+
+// One numbered element of the schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SchemaEntry {
+    kind: EntryKind,
+    parent: String, // Fully qualified message or enum name
+    name: String,
+    number: i32,
+    type_name: String, // Label and type of a field, empty for enum values
+}
+
+impl SchemaEntry {
+    // Parse a line in the format written by `Display`
+    fn parse(line: &str) -> Option<Self> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["field", parent, name, number, type_name @ ..] if !type_name.is_empty() => Some(SchemaEntry {
+                kind: EntryKind::Field,
+                parent: parent.to_string(),
+                name: name.to_string(),
+                number: number.parse().ok()?,
+                type_name: type_name.join(" "),
+            }),
+            ["value", parent, name, number] => Some(SchemaEntry {
+                kind: EntryKind::Value,
+                parent: parent.to_string(),
+                name: name.to_string(),
+                number: number.parse().ok()?,
+                type_name: String::new(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SchemaEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            EntryKind::Field => write!(f, "field {} {} {} {}", self.parent, self.name, self.number, self.type_name),
+            EntryKind::Value => write!(f, "value {} {} {}", self.parent, self.name, self.number),
+        }
+    }
+}
+
+// Flatten a schema into its numbered elements
+fn schema_entries(set: &FileDescriptorSet) -> Vec<SchemaEntry> {
+    let mut entries = Vec::new();
+    for file in &set.file {
+        let scope = file.package().to_string();
+        for message in &file.message_type {
+            message_entries(message, &scope, &mut entries);
+        }
+        for descriptor in &file.enum_type {
+            enum_entries(descriptor, &scope, &mut entries);
+        }
+    }
+    entries
+}
+
+fn message_entries(message: &DescriptorProto, scope: &str, entries: &mut Vec<SchemaEntry>) {
+    let name = qualify(scope, message.name());
+    for field in &message.field {
+        let (label, kind) = field_type(field);
+        entries.push(SchemaEntry {
+            kind: EntryKind::Field,
+            parent: name.clone(),
+            name: field.name().to_string(),
+            number: field.number(),
+            type_name: format!("{} {}", label, kind),
+        });
+    }
+    for nested in &message.nested_type {
+        message_entries(nested, &name, entries);
+    }
+    for descriptor in &message.enum_type {
+        enum_entries(descriptor, &name, entries);
+    }
+}
+
+fn enum_entries(descriptor: &EnumDescriptorProto, scope: &str, entries: &mut Vec<SchemaEntry>) {
+    let name = qualify(scope, descriptor.name());
+    for value in &descriptor.value {
+        entries.push(SchemaEntry {
+            kind: EntryKind::Value,
+            parent: name.clone(),
+            name: value.name().to_string(),
+            number: value.number(),
+            type_name: String::new(),
+        });
+    }
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+// Whether the number of a removed entry is reserved in its message or enum
+fn is_reserved(set: &FileDescriptorSet, entry: &SchemaEntry) -> bool {
+    fn find_message<'a>(messages: &'a [DescriptorProto], scope: &str, name: &str) -> Option<&'a DescriptorProto> {
+        messages.iter().find_map(|message| {
+            let qualified = qualify(scope, message.name());
+            if qualified == name {
+                Some(message)
+            } else {
+                find_message(&message.nested_type, &qualified, name)
+            }
+        })
+    }
+    fn find_enum<'a>(
+        messages: &'a [DescriptorProto],
+        enums: &'a [EnumDescriptorProto],
+        scope: &str,
+        name: &str,
+    ) -> Option<&'a EnumDescriptorProto> {
+        enums
+            .iter()
+            .find(|descriptor| qualify(scope, descriptor.name()) == name)
+            .or_else(|| {
+                messages.iter().find_map(|message| {
+                    let qualified = qualify(scope, message.name());
+                    find_enum(&message.nested_type, &message.enum_type, &qualified, name)
+                })
+            })
+    }
+
+    set.file.iter().any(|file| match entry.kind {
+        // Message reserved ranges exclude their end
+        EntryKind::Field => find_message(&file.message_type, file.package(), &entry.parent).is_some_and(|message| {
+            message
+                .reserved_range
+                .iter()
+                .any(|range| (range.start()..range.end()).contains(&entry.number))
+        }),
+        // Enum reserved ranges include their end
+        EntryKind::Value => find_enum(&file.message_type, &file.enum_type, file.package(), &entry.parent)
+            .is_some_and(|descriptor| {
+                descriptor
+                    .reserved_range
+                    .iter()
+                    .any(|range| (range.start()..=range.end()).contains(&entry.number))
+            }),
+    })
+}
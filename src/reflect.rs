@@ -0,0 +1,380 @@
+// Import necessary modules and crates
+use crate::message::{server_message, DynamicMessage, ErrorCode, ErrorResponse}; // Pass-through envelope and its errors
+use crate::protocol; // Schema compiled into the crate
+use log::warn; // Logging macros
+use prost_types::{
+    field_descriptor_proto::{Label, Type}, // Field kinds
+    DescriptorProto, FileDescriptorSet, // Schema descriptors
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    panic::{self, AssertUnwindSafe}, // A panicking handler answers with an error instead of ending the connection
+    sync::{
+        atomic::{AtomicUsize, Ordering}, // Calls still running past their timeout
+        mpsc::{self, RecvTimeoutError}, // Answer of a handler run with a timeout
+        Arc, Mutex, RwLock, // Routes shared by all connections
+    },
+    thread,
+    time::Duration, // Handler timeouts
+};
+
+// Deepest nesting of messages accepted, the same bound keeps a hostile payload from exhausting the stack
+const MAX_DEPTH: usize = 32;
+
+/// Message descriptors by full name, e.g. `messages.AddRequest`, used to validate dynamic payloads
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    messages: HashMap<String, DescriptorProto>, // Every top-level and nested message
+}
+
+impl Schema {
+    /// Creates a schema knowing no messages
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a schema with the messages of `proto/messages.proto`
+    pub fn embedded() -> Self {
+        let mut schema = Self::new();
+        schema.add(&protocol::descriptor());
+        schema
+    }
+
+    /// Adds every message described in `set`, replacing messages of the same name
+    pub fn add(&mut self, set: &FileDescriptorSet) {
+        for file in &set.file {
+            for message in &file.message_type {
+                self.add_message(file.package(), message);
+            }
+        }
+    }
+
+    // Index a message and, depth first, the messages nested in it
+    fn add_message(&mut self, scope: &str, message: &DescriptorProto) {
+        let name = if scope.is_empty() {
+            message.name().to_string()
+        } else {
+            format!("{}.{}", scope, message.name())
+        };
+        for nested in &message.nested_type {
+            self.add_message(&name, nested);
+        }
+        self.messages.insert(name, message.clone());
+    }
+
+    /// Whether a message of this full name is described, with or without a leading dot
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.messages.contains_key(type_name.trim_start_matches('.'))
+    }
+
+    /// Checks that `payload` is an encoded `type_name`: every field is described, has the wire type of
+    /// its declared type, strings are UTF-8 and nested messages are valid in turn
+    pub fn validate(&self, type_name: &str, payload: &[u8]) -> Result<(), String> {
+        self.validate_message(type_name, payload, 0)
+    }
+
+    fn validate_message(&self, type_name: &str, mut payload: &[u8], depth: usize) -> Result<(), String> {
+        let type_name = type_name.trim_start_matches('.');
+        let message = self
+            .messages
+            .get(type_name)
+            .ok_or_else(|| format!("Unknown message type {}", type_name))?;
+        if depth > MAX_DEPTH {
+            return Err(format!("{} nested more than {} messages deep", type_name, MAX_DEPTH));
+        }
+
+        while !payload.is_empty() {
+            let key = read_varint(&mut payload).ok_or_else(|| format!("Truncated field key in {}", type_name))?;
+            let (number, wire_type) = (key >> 3, (key & 0x7) as u8);
+            let field = message
+                .field
+                .iter()
+                .find(|field| field.number() as u64 == number)
+                .ok_or_else(|| format!("{} has no field {}", type_name, number))?;
+            let value = read_value(&mut payload, wire_type)
+                .ok_or_else(|| format!("Truncated or malformed field {}.{}", type_name, field.name()))?;
+
+            // Repeated scalars may arrive packed, as one length-delimited run of values
+            let expected = wire_type_of(field.r#type());
+            let packed = wire_type == WIRE_LEN
+                && field.label() == Label::Repeated
+                && matches!(expected, Some(WIRE_VARINT | WIRE_FIXED64 | WIRE_FIXED32));
+            if expected != Some(wire_type) && !packed {
+                return Err(format!(
+                    "Field {}.{} has wire type {}, its type {:?} needs {:?}",
+                    type_name,
+                    field.name(),
+                    wire_type,
+                    field.r#type(),
+                    expected
+                ));
+            }
+            match field.r#type() {
+                Type::String if std::str::from_utf8(value).is_err() => {
+                    return Err(format!("Field {}.{} is not valid UTF-8", type_name, field.name()));
+                }
+                Type::Message => self.validate_message(field.type_name(), value, depth + 1)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Answers dynamic messages of one type, see `Server::route_dynamic`
+pub trait DynamicHandler: Send + Sync {
+    /// Handles a validated `payload` of type `type_name`, returning the message to send back
+    fn handle(&self, type_name: &str, payload: &[u8]) -> Result<DynamicMessage, ErrorResponse>;
+}
+
+impl<F> DynamicHandler for F
+where
+    F: Fn(&str, &[u8]) -> Result<DynamicMessage, ErrorResponse> + Send + Sync,
+{
+    fn handle(&self, type_name: &str, payload: &[u8]) -> Result<DynamicMessage, ErrorResponse> {
+        self(type_name, payload)
+    }
+}
+
+/// Told the answer of a call that overran its timeout once its handler returns, e.g. to record it
+pub(crate) type LateAnswer = Box<dyn FnOnce(server_message::Message) + Send>;
+
+// Handler of one message type and how long it may take
+#[derive(Clone)]
+struct Route {
+    handler: Arc<dyn DynamicHandler>,
+    timeout: Option<Duration>, // Overrides the default timeout of the routes
+    overrunning: Arc<AtomicUsize>, // Calls answered as timed out whose threads still run the handler
+}
+
+impl Route {
+    fn new(handler: impl DynamicHandler + 'static, timeout: Option<Duration>) -> Self {
+        Route {
+            handler: Arc::new(handler),
+            timeout,
+            overrunning: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+// Schema and handlers of one server, keyed by full message name
+pub(crate) struct DynamicRoutes {
+    schema: RwLock<Schema>, // Known types, the embedded ones and those added by the application
+    handlers: RwLock<HashMap<String, Route>>,
+    named: RwLock<HashMap<String, Route>>, // Handlers the routing table refers to by name
+    timeout: Option<Duration>, // Longest a handler without its own timeout may take, `None` for no limit
+}
+
+impl Default for DynamicRoutes {
+    fn default() -> Self {
+        DynamicRoutes {
+            schema: RwLock::new(Schema::embedded()),
+            handlers: RwLock::new(HashMap::new()),
+            named: RwLock::new(HashMap::new()),
+            timeout: None,
+        }
+    }
+}
+
+impl DynamicRoutes {
+    pub(crate) fn with_timeout(timeout: Option<Duration>) -> Self {
+        DynamicRoutes {
+            timeout,
+            ..DynamicRoutes::default()
+        }
+    }
+
+    pub(crate) fn add_schema(&self, set: &FileDescriptorSet) {
+        self.schema.write().unwrap().add(set);
+    }
+
+    pub(crate) fn route(&self, type_name: &str, handler: impl DynamicHandler + 'static, timeout: Option<Duration>) {
+        let type_name = type_name.trim_start_matches('.').to_string();
+        self.handlers.write().unwrap().insert(type_name, Route::new(handler, timeout));
+    }
+
+    pub(crate) fn register(&self, name: &str, handler: impl DynamicHandler + 'static) {
+        self.named.write().unwrap().insert(name.to_string(), Route::new(handler, None));
+    }
+
+    pub(crate) fn clear(&self) {
+        self.handlers.write().unwrap().clear();
+    }
+
+    // Validate a dynamic message and answer it with its handler, or with the reason it can't be. A call that
+    // overruns its timeout takes `late` and tells it the answer the handler returns in the end
+    pub(crate) fn dispatch(&self, message: &DynamicMessage, late: &mut Option<LateAnswer>) -> server_message::Message {
+        let type_name = message.type_name.trim_start_matches('.');
+        // Cloned out so the handler runs without holding the lock
+        let Some(route) = self.handlers.read().unwrap().get(type_name).cloned() else {
+            return ErrorResponse::new(ErrorCode::Unsupported, format!("No handler for {}", type_name)).into();
+        };
+        self.call(route, message, late)
+    }
+
+    // Answer a dynamic message with the handler registered as `name`, whatever its type
+    pub(crate) fn dispatch_named(
+        &self,
+        name: &str,
+        message: &DynamicMessage,
+        late: &mut Option<LateAnswer>,
+    ) -> server_message::Message {
+        let Some(route) = self.named.read().unwrap().get(name).cloned() else {
+            return ErrorResponse::new(ErrorCode::Unsupported, format!("No handler named {}", name)).into();
+        };
+        self.call(route, message, late)
+    }
+
+    // Validate a dynamic message and run `route` on it
+    fn call(&self, route: Route, message: &DynamicMessage, late: &mut Option<LateAnswer>) -> server_message::Message {
+        let type_name = message.type_name.trim_start_matches('.');
+        if let Err(e) = self.schema.read().unwrap().validate(type_name, &message.payload) {
+            return ErrorResponse::new(ErrorCode::InvalidRequest, e).into();
+        }
+        let answer = match route.timeout.or(self.timeout) {
+            None => panic::catch_unwind(AssertUnwindSafe(|| route.handler.handle(type_name, &message.payload))).ok(),
+            Some(timeout) => match call_with_timeout(&route, message, timeout, late) {
+                Ok(answer) => answer,
+                Err(e) => return e.into(),
+            },
+        };
+        answer_message(type_name, answer)
+    }
+}
+
+// The message answering a call of the handler for `type_name`, `None` if the handler panicked
+fn answer_message(type_name: &str, answer: Option<Result<DynamicMessage, ErrorResponse>>) -> server_message::Message {
+    match answer {
+        Some(Ok(response)) => response.into(),
+        Some(Err(error)) => error.into(),
+        None => {
+            warn!("Dynamic handler for {} panicked", type_name);
+            ErrorResponse::new(ErrorCode::Internal, format!("Handler for {} failed", type_name)).into()
+        }
+    }
+}
+
+// Whether the caller of a handler stopped waiting for it, and who to tell the answer instead
+struct Abandoned {
+    abandoned: bool,
+    late: Option<LateAnswer>,
+}
+
+// Run a handler on a thread of its own and stop waiting for it after `timeout`. A thread can't be stopped, so a
+// handler that overruns finishes in the background, but the connection is answered and keeps serving. Until it
+// has finished, further calls of the route are refused rather than each leaving another thread behind, and its
+// answer goes to `late`. `None` if the handler panicked
+fn call_with_timeout(
+    route: &Route,
+    message: &DynamicMessage,
+    timeout: Duration,
+    late: &mut Option<LateAnswer>,
+) -> Result<Option<Result<DynamicMessage, ErrorResponse>>, ErrorResponse> {
+    let type_name = message.type_name.trim_start_matches('.').to_string();
+    if route.overrunning.load(Ordering::SeqCst) > 0 {
+        let detail = format!("Handler for {} is still running a call that timed out", type_name);
+        return Err(ErrorResponse::new(ErrorCode::HandlerBusy, detail));
+    }
+    let payload = message.payload.clone();
+    let (sender, receiver) = mpsc::channel();
+    // Set by the caller when it stops waiting, the thread then counts itself out of the overrunning calls
+    let abandoned = Arc::new(Mutex::new(Abandoned {
+        abandoned: false,
+        late: None,
+    }));
+    let handler = Arc::clone(&route.handler);
+    let overrunning = Arc::clone(&route.overrunning);
+    let thread_abandoned = Arc::clone(&abandoned);
+    thread::Builder::new()
+        .name(format!("handler-{}", type_name))
+        .spawn(move || {
+            let answer = panic::catch_unwind(AssertUnwindSafe(|| handler.handle(&type_name, &payload))).ok();
+            let mut abandoned = thread_abandoned.lock().unwrap();
+            if !abandoned.abandoned {
+                let _ = sender.send(answer);
+                return;
+            }
+            // Told before the route takes calls again
+            if let Some(late) = abandoned.late.take() {
+                late(answer_message(&type_name, answer));
+            }
+            overrunning.fetch_sub(1, Ordering::SeqCst);
+        })
+        .map_err(|e| ErrorResponse::new(ErrorCode::Internal, format!("Failed to start the handler: {}", e)))?;
+    match receiver.recv_timeout(timeout) {
+        Ok(answer) => Ok(answer),
+        // The thread ended without answering
+        Err(RecvTimeoutError::Disconnected) => Ok(None),
+        Err(RecvTimeoutError::Timeout) => {
+            let mut abandoned = abandoned.lock().unwrap();
+            // The handler may have answered between the wait ending and the lock being taken
+            if let Ok(answer) = receiver.try_recv() {
+                return Ok(answer);
+            }
+            abandoned.abandoned = true;
+            abandoned.late = late.take();
+            route.overrunning.fetch_add(1, Ordering::SeqCst);
+            let type_name = message.type_name.trim_start_matches('.');
+            warn!("Dynamic handler for {} took longer than {:?}", type_name, timeout);
+            let detail = format!("Handler for {} took longer than {:?}", type_name, timeout);
+            Err(ErrorResponse::new(ErrorCode::HandlerTimeout, detail))
+        }
+    }
+}
+
+impl fmt::Debug for DynamicRoutes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut routes: Vec<String> = self.handlers.read().unwrap().keys().cloned().collect();
+        routes.sort();
+        f.debug_struct("DynamicRoutes").field("routes", &routes).finish()
+    }
+}
+
+// Protobuf wire types
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LEN: u8 = 2;
+const WIRE_FIXED32: u8 = 5;
+
+// Wire type a field of this type is encoded with, `None` for groups, which proto3 doesn't have
+fn wire_type_of(kind: Type) -> Option<u8> {
+    match kind {
+        Type::Double | Type::Fixed64 | Type::Sfixed64 => Some(WIRE_FIXED64),
+        Type::Float | Type::Fixed32 | Type::Sfixed32 => Some(WIRE_FIXED32),
+        Type::String | Type::Bytes | Type::Message => Some(WIRE_LEN),
+        Type::Group => None,
+        _ => Some(WIRE_VARINT),
+    }
+}
+
+// Read a varint of at most ten bytes from the front of `buffer`
+fn read_varint(buffer: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (index, &byte) in buffer.iter().take(10).enumerate() {
+        value |= u64::from(byte & 0x7f) << (7 * index);
+        if byte & 0x80 == 0 {
+            *buffer = &buffer[index + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+// Take one field value off the front of `buffer`, the bytes of a length-delimited value without its length
+fn read_value<'a>(buffer: &mut &'a [u8], wire_type: u8) -> Option<&'a [u8]> {
+    let len = match wire_type {
+        WIRE_VARINT => {
+            let start = *buffer;
+            read_varint(buffer)?;
+            return Some(&start[..start.len() - buffer.len()]);
+        }
+        WIRE_FIXED64 => 8,
+        WIRE_LEN => usize::try_from(read_varint(buffer)?).ok()?,
+        WIRE_FIXED32 => 4,
+        _ => return None,
+    };
+    let value = buffer.get(..len)?;
+    *buffer = &buffer[len..];
+    Some(value)
+}
@@ -0,0 +1,93 @@
+// Import necessary modules and crates
+use std::{
+    borrow::Borrow, // Look up String keys with &str
+    collections::{hash_map::RandomState, HashMap}, // HashMap shards and their hasher
+    fmt,
+    hash::{BuildHasher, Hash},
+    sync::{Mutex, MutexGuard}, // Mutex for each shard
+};
+
+// Default number of independently locked shards
+const DEFAULT_SHARDS: usize = 16;
+
+/// HashMap split into independently locked shards, so operations on unrelated keys never contend
+pub struct ShardedMap<K, V> {
+    shards: Vec<Mutex<HashMap<K, V>>>, // Each key lives in exactly one shard
+    hasher: RandomState, // Picks the shard of a key
+}
+
+impl<K: Hash + Eq, V> Default for ShardedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq, V> ShardedMap<K, V> {
+    /// Creates an empty map with the default number of shards
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Creates an empty map with `count` shards (at least one)
+    pub fn with_shards(count: usize) -> Self {
+        ShardedMap {
+            shards: (0..count.max(1)).map(|_| Mutex::new(HashMap::new())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Locks and returns the shard holding `key`, keep the guard to make several operations on it atomic
+    pub fn shard<Q>(&self, key: &Q) -> MutexGuard<'_, HashMap<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        self.shards[index].lock().unwrap()
+    }
+
+    /// Inserts a value, returning the previous one for the key
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shard(&key).insert(key, value)
+    }
+
+    /// Removes a key, returning its value
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).remove(key)
+    }
+
+    /// Returns a clone of the value for a key
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.shard(key).get(key).cloned()
+    }
+
+    /// Total number of entries, shards are locked one after another so concurrent changes may be missed
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.lock().unwrap().len()).sum()
+    }
+
+    /// Whether the map has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for ShardedMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for shard in &self.shards {
+            let shard = shard.lock().unwrap();
+            map.entries(shard.iter());
+        }
+        map.finish()
+    }
+}
@@ -0,0 +1,134 @@
+// Import necessary modules and crates
+#[cfg(feature = "scripting")]
+use crate::config::ScriptHook; // Script rules compile like configured scripts
+#[cfg(feature = "scripting")]
+use crate::scripting::Scripts; // Scripts of the script rules
+use std::{
+    collections::HashMap, // Rules by message type
+    fs,
+    io::{self, ErrorKind}, // Routing file errors
+    path::{Path, PathBuf}, // Routing file and script locations
+};
+
+/// What the routing table does with the requests of one message type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteAction {
+    Handler(String), // Answer dynamic messages of the type with the handler registered under this name
+    Forward, // Forward to the upstream server of the gateway
+    Drop, // Read and discard without an answer
+    Script(PathBuf), // Run the script's hooks on the request and its response, needs the `scripting` feature
+}
+
+/// Deployment-specific routing rules by message type, read from a text file with one rule per line:
+///
+/// ```text
+/// # Blank lines and lines starting with '#' are ignored
+/// AddRequest          forward
+/// EchoMessage         drop
+/// telemetry.Reading   handler telemetry
+/// StatsRequest        script stats.rhai
+/// ```
+///
+/// Typed requests are named like in `GatewayConfig`, dynamic messages by their full type name. `handler`
+/// only applies to dynamic messages, and relative script paths are relative to the routing file
+#[derive(Debug, Default)]
+pub struct RoutingTable {
+    rules: HashMap<String, RouteAction>, // Action by message type
+    #[cfg(feature = "scripting")]
+    scripts: Scripts, // Compiled scripts of the script rules
+}
+
+impl RoutingTable {
+    /// Reads and checks the routing file at `path`; any invalid line, or a script that doesn't compile, is an
+    /// error, so a half-understood table is never used
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new(""))).map_err(|e| {
+            io::Error::new(e.kind(), format!("Routing file {}: {}", path.display(), e))
+        })
+    }
+
+    /// Parses routing rules, resolving relative script paths against `dir`
+    pub fn parse(text: &str, dir: &Path) -> io::Result<Self> {
+        let mut rules = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |detail: &str| {
+                io::Error::new(ErrorKind::InvalidData, format!("line {}: {}", number + 1, detail))
+            };
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (message_type, action) = match parts.as_slice() {
+                [message_type, "forward"] => (*message_type, RouteAction::Forward),
+                [message_type, "drop"] => (*message_type, RouteAction::Drop),
+                [message_type, "handler", name] if message_type.contains('.') => {
+                    (*message_type, RouteAction::Handler(name.to_string()))
+                }
+                [_, "handler", _] => return Err(invalid("handlers only answer dynamic messages, by full type name")),
+                [message_type, "script", script] => (*message_type, RouteAction::Script(dir.join(script))),
+                _ => return Err(invalid(&format!("expected `<message type> <action>`, got `{}`", line))),
+            };
+            let message_type = message_type.trim_start_matches('.').to_string();
+            if rules.insert(message_type.clone(), action).is_some() {
+                return Err(invalid(&format!("a second rule for {}", message_type)));
+            }
+        }
+        Self::with_rules(rules)
+    }
+
+    #[cfg(feature = "scripting")]
+    fn with_rules(rules: HashMap<String, RouteAction>) -> io::Result<Self> {
+        let hooks: Vec<ScriptHook> = rules
+            .iter()
+            .filter_map(|(message_type, action)| match action {
+                RouteAction::Script(path) => Some(ScriptHook {
+                    message_type: message_type.clone(),
+                    path: path.clone(),
+                }),
+                _ => None,
+            })
+            .collect();
+        let scripts = Scripts::load(&hooks)?;
+        Ok(RoutingTable { rules, scripts })
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn with_rules(rules: HashMap<String, RouteAction>) -> io::Result<Self> {
+        let script = rules.values().find(|action| matches!(action, RouteAction::Script(_)));
+        if let Some(RouteAction::Script(path)) = script {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("Script {} needs the `scripting` feature", path.display()),
+            ));
+        }
+        Ok(RoutingTable { rules })
+    }
+
+    /// Action for requests of `message_type`, `None` to handle them as without a table
+    pub fn action(&self, message_type: &str) -> Option<&RouteAction> {
+        self.rules.get(message_type)
+    }
+
+    /// Every rule, in no particular order
+    pub fn rules(&self) -> impl Iterator<Item = (&str, &RouteAction)> {
+        self.rules.iter().map(|(message_type, action)| (message_type.as_str(), action))
+    }
+
+    /// Number of rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Whether there are no rules
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Compiled scripts of the script rules, by message type
+    #[cfg(feature = "scripting")]
+    pub fn scripts(&self) -> &Scripts {
+        &self.scripts
+    }
+}
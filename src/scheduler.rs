@@ -0,0 +1,147 @@
+// Import necessary modules and crates
+use crate::clock::{self, Clock}; // Time source, replaceable in tests
+use log::error; // Logging macros
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe}, // A panicking job is logged instead of stopping the scheduler
+    sync::{
+        atomic::{AtomicBool, Ordering}, // Running flag shared with the server
+        Arc, Condvar, Mutex, // Job list and wake-ups when it changes
+    },
+    time::{Duration, Instant}, // Time handling
+};
+
+// Longest sleep between checks of the running flag, the same poll interval as the accept loop
+const MAX_IDLE: Duration = Duration::from_millis(100);
+
+/// Handle of a scheduled job, used to cancel it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobId(u64);
+
+// A periodic job and its next due time
+struct Job {
+    id: JobId,
+    interval: Duration, // Time between the starts of two runs
+    next_run: Instant, // When the job is due next
+    task: Option<Box<dyn FnMut() + Send>>, // Taken out while the job runs, so the lock isn't held
+}
+
+// Jobs together with the next id to hand out
+struct Jobs {
+    jobs: Vec<Job>,
+    next_id: u64,
+}
+
+/// Runs periodic jobs on a single thread for as long as its owner is running
+pub struct Scheduler {
+    jobs: Mutex<Jobs>, // Registered jobs
+    changed: Condvar, // Wakes the scheduler thread when a job is added
+    clock: Arc<dyn Clock>, // Decides when jobs are due
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    /// Creates a scheduler without jobs
+    pub fn new() -> Self {
+        Self::with_clock(clock::system())
+    }
+
+    /// Creates a scheduler timing its jobs by `clock`. On a `ManualClock` jobs become due as the clock
+    /// is advanced, the scheduler notices within 100 ms of real time
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Scheduler {
+            jobs: Mutex::new(Jobs {
+                jobs: Vec::new(),
+                next_id: 1,
+            }),
+            changed: Condvar::new(),
+            clock,
+        }
+    }
+
+    /// Runs `job` every `interval`, the first run happens one interval from now
+    pub fn schedule(&self, interval: Duration, job: impl FnMut() + Send + 'static) -> JobId {
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = JobId(jobs.next_id);
+        jobs.next_id += 1;
+        jobs.jobs.push(Job {
+            id,
+            interval,
+            next_run: self.clock.now() + interval,
+            task: Some(Box::new(job)),
+        });
+        self.changed.notify_all();
+        id
+    }
+
+    /// Removes a job, a run already in progress still completes; returns whether the job existed
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        let before = jobs.jobs.len();
+        jobs.jobs.retain(|job| job.id != id);
+        jobs.jobs.len() != before
+    }
+
+    /// Number of scheduled jobs
+    pub fn len(&self) -> usize {
+        self.jobs.lock().unwrap().jobs.len()
+    }
+
+    /// Whether no job is scheduled
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Runs due jobs on the current thread until `is_running` turns false
+    pub fn run(&self, is_running: &AtomicBool) {
+        while is_running.load(Ordering::SeqCst) {
+            // Take out the first due job, or sleep until one is due
+            let due = {
+                let mut jobs = self.jobs.lock().unwrap();
+                let now = self.clock.now();
+                match jobs
+                    .jobs
+                    .iter_mut()
+                    .filter(|job| job.task.is_some())
+                    .min_by_key(|job| job.next_run)
+                {
+                    Some(job) if job.next_run <= now => {
+                        // Keep the cadence unless the job fell behind by more than one interval
+                        job.next_run = (job.next_run + job.interval).max(now);
+                        job.task.take().map(|task| (job.id, task))
+                    }
+                    next => {
+                        let wait = next.map_or(MAX_IDLE, |job| (job.next_run - now).min(MAX_IDLE));
+                        drop(self.changed.wait_timeout(jobs, wait).unwrap());
+                        None
+                    }
+                }
+            };
+
+            if let Some((id, mut task)) = due {
+                if panic::catch_unwind(AssertUnwindSafe(&mut task)).is_err() {
+                    error!("Scheduled job {:?} panicked", id);
+                }
+                // Put the job back unless it was cancelled while running
+                let mut jobs = self.jobs.lock().unwrap();
+                if let Some(job) = jobs.jobs.iter_mut().find(|job| job.id == id) {
+                    job.task = Some(task);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let jobs = self.jobs.lock().unwrap();
+        f.debug_list()
+            .entries(jobs.jobs.iter().map(|job| (job.id, job.interval)))
+            .finish()
+    }
+}
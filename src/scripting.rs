@@ -0,0 +1,99 @@
+// Import necessary modules and crates
+use crate::config::ScriptHook; // Scripts by message type
+use crate::message::{client_message, server_message}; // Messages handed to scripts
+use rhai::{
+    serde::{from_dynamic, to_dynamic}, // Messages to and from script values, through their serde derives
+    Dynamic, Engine, Map, Scope, AST,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap, // Compiled scripts by message type
+    fmt, fs,
+    io::{self, ErrorKind}, // Script loading errors
+};
+
+// Most operations one script call may run, so a runaway loop can't wedge a connection thread
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// Operator-written Rhai scripts inspecting and transforming the requests and responses of selected message
+/// types. A script defines `on_request(request)`, `on_response(response)` or both; each gets the message
+/// payload as a map, e.g. `request.content` of an `EchoMessage`, and returns the payload to use instead
+#[derive(Default)]
+pub struct Scripts {
+    engine: Engine, // Shared by every script, limits the work of each call
+    hooks: HashMap<String, AST>, // Compiled scripts by request type
+}
+
+impl fmt::Debug for Scripts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scripts").field("message_types", &self.hooks.keys().collect::<Vec<_>>()).finish()
+    }
+}
+
+impl Scripts {
+    /// Compiles the script of every hook; a missing file or a syntax error is an error, so a server
+    /// doesn't start without the transformations its operator configured
+    pub fn load(hooks: &[ScriptHook]) -> io::Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let mut compiled = HashMap::new();
+        for hook in hooks {
+            let source = fs::read_to_string(&hook.path)
+                .map_err(|e| io::Error::new(e.kind(), format!("Script {}: {}", hook.path.display(), e)))?;
+            let ast = engine.compile(&source).map_err(|e| {
+                io::Error::new(ErrorKind::InvalidData, format!("Script {}: {}", hook.path.display(), e))
+            })?;
+            compiled.insert(hook.message_type.clone(), ast);
+        }
+        Ok(Scripts {
+            engine,
+            hooks: compiled,
+        })
+    }
+
+    /// Runs the `on_request` function of the script for the request type `kind`, if there is one
+    pub fn on_request(&self, kind: &str, request: client_message::Message) -> Result<client_message::Message, String> {
+        self.run(kind, "on_request", request)
+    }
+
+    /// Runs the `on_response` function of the script for the request type `kind`, if there is one
+    pub fn on_response(
+        &self,
+        kind: &str,
+        response: server_message::Message,
+    ) -> Result<server_message::Message, String> {
+        self.run(kind, "on_response", response)
+    }
+
+    /// Number of message types with a script
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    /// Whether no message type has a script
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    // Pass the payload of `message` to `function` and put what it returns back into the same oneof arm
+    fn run<T: Serialize + DeserializeOwned>(&self, kind: &str, function: &str, message: T) -> Result<T, String> {
+        let Some(ast) = self.hooks.get(kind) else {
+            return Ok(message);
+        };
+        if !ast.iter_functions().any(|defined| defined.name == function) {
+            return Ok(message);
+        }
+        // A oneof arm converts to a map with the arm name as its only key
+        let mut arm = to_dynamic(&message)
+            .map_err(|e| e.to_string())?
+            .try_cast::<Map>()
+            .ok_or_else(|| format!("{} is not a oneof arm", kind))?;
+        let (name, payload) = arm.pop_first().ok_or_else(|| format!("{} has no payload", kind))?;
+        let result: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), ast, function, (payload,))
+            .map_err(|e| format!("{} of the {} script failed: {}", function, kind, e))?;
+        arm.insert(name, result);
+        from_dynamic(&Dynamic::from_map(arm)).map_err(|e| format!("{} of the {} script returned {}", function, kind, e))
+    }
+}
@@ -0,0 +1,131 @@
+// Import necessary modules and crates
+#[cfg(feature = "storage")]
+use crate::acklog::AckLog; // Acknowledgement log probed by the storage check
+use crate::message::{SelfTestCheck, SelfTestResponse};
+use crate::pool; // Buffer pool exercised by the built-in check
+use log::{info, warn}; // Logging macros
+#[cfg(all(unix, feature = "storage"))]
+use std::{ffi::CString, io, os::unix::ffi::OsStrExt, path::Path}; // Free space of a directory
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe}, // A panicking check fails instead of taking the connection down
+    sync::{Arc, Mutex}, // Shared, mutable list of checks
+    time::Instant, // Timing each check
+};
+
+/// Outcome of a single check: a short description of what was verified, or why it failed
+pub type CheckResult = Result<String, String>;
+
+/// Free space below which the disk check fails, the acknowledgement log could fill the disk any moment
+#[cfg(all(unix, feature = "storage"))]
+pub const MIN_FREE_BYTES: u64 = 1024 * 1024;
+
+// A registered check, shared so a run doesn't hold the lock while checks execute
+type Check = Arc<dyn Fn() -> CheckResult + Send + Sync>;
+
+/// Named internal checks run on a `SelfTestRequest`, in registration order
+pub struct SelfTests {
+    checks: Mutex<Vec<(String, Check)>>, // Registered checks with their names
+}
+
+impl Default for SelfTests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelfTests {
+    /// Creates the list with the built-in checks every server supports
+    pub fn new() -> Self {
+        let tests = SelfTests {
+            checks: Mutex::new(Vec::new()),
+        };
+        tests.register("buffer_pool", check_buffer_pool);
+        tests
+    }
+
+    /// Adds a check, replacing an existing one with the same name
+    pub fn register(&self, name: &str, check: impl Fn() -> CheckResult + Send + Sync + 'static) {
+        let mut checks = self.checks.lock().unwrap();
+        let check: Check = Arc::new(check);
+        match checks.iter_mut().find(|(existing, _)| existing == name) {
+            Some(entry) => entry.1 = check,
+            None => checks.push((name.to_string(), check)),
+        }
+    }
+
+    /// Runs every check and collects the results
+    pub fn run(&self) -> SelfTestResponse {
+        let checks: Vec<(String, Check)> = self.checks.lock().unwrap().clone();
+        let checks: Vec<SelfTestCheck> = checks
+            .into_iter()
+            .map(|(name, check)| {
+                let started = Instant::now();
+                let result = panic::catch_unwind(AssertUnwindSafe(|| check()))
+                    .unwrap_or_else(|_| Err("Check panicked".to_string()));
+                let duration_us = started.elapsed().as_micros() as u64;
+                match result {
+                    Ok(detail) => {
+                        info!("Self-test {} passed: {}", name, detail);
+                        SelfTestCheck { name, passed: true, detail, duration_us }
+                    }
+                    Err(detail) => {
+                        warn!("Self-test {} failed: {}", name, detail);
+                        SelfTestCheck { name, passed: false, detail, duration_us }
+                    }
+                }
+            })
+            .collect();
+        SelfTestResponse {
+            passed: checks.iter().all(|check| check.passed),
+            checks,
+        }
+    }
+}
+
+impl fmt::Debug for SelfTests {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let checks = self.checks.lock().unwrap();
+        f.debug_list().entries(checks.iter().map(|(name, _)| name)).finish()
+    }
+}
+
+// Acquire and release a buffer to verify the shared pool still hands out usable memory
+fn check_buffer_pool() -> CheckResult {
+    let mut buffer = pool::acquire(64);
+    buffer.extend_from_slice(&[0xA5; 64]);
+    if buffer.iter().all(|&byte| byte == 0xA5) {
+        let stats = pool::stats();
+        Ok(format!("{} buffers in use, {} idle bytes", stats.in_use, stats.idle_bytes()))
+    } else {
+        Err("Pooled buffer returned corrupted data".to_string())
+    }
+}
+
+// Append to the acknowledgement log and sync it, to verify the storage it lives on is reachable and writable
+#[cfg(feature = "storage")]
+pub(crate) fn check_ack_log(acks: &mut AckLog) -> CheckResult {
+    let started = Instant::now();
+    match acks.probe() {
+        Ok(()) => Ok(format!("Appended and synced in {} us", started.elapsed().as_micros())),
+        Err(e) => Err(format!("Failed to write the acknowledgement log: {}", e)),
+    }
+}
+
+// Report the free space in `dir`, failing below `MIN_FREE_BYTES`
+#[cfg(all(unix, feature = "storage"))]
+pub(crate) fn check_free_space(dir: &Path) -> CheckResult {
+    let path = CString::new(dir.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+    // Safety: `path` is NUL-terminated and `stat` is a valid statvfs to fill in
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(format!("Failed to query {}: {}", dir.display(), io::Error::last_os_error()));
+    }
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+    let detail = format!("{} bytes free in {}", free, dir.display());
+    if free < MIN_FREE_BYTES {
+        Err(detail)
+    } else {
+        Ok(detail)
+    }
+}
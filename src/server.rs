@@ -1,131 +1,1829 @@
 // Import necessary modules and crates
-use crate::message::{ServerMessage, AddResponse, ClientMessage, client_message, server_message};
-use log::{error, info, warn}; // Logging macros
+#[cfg(feature = "delta")]
+use crate::message::DeltaRequest; // Requests sent as the difference to an earlier one
+use crate::message::{ServerMessage, AddResponse, BenchPayload, BenchResponse, ClientMessage, CommandStatus, CommandStatusResponse, DynamicMessage, ErrorCode, ErrorResponse, Metadata, GetSchemaResponse, GoAway, LivenessProbe, MaintenanceMode, OfferResponse, RecentEventsResponse, ResyncResponse, SelfTestResponse, StatsResponse, Timestamps, client_message, server_message};
+use crate::acklog::AckLog; // Completed commands, for exactly-once execution
+use crate::capture::{Captures, Direction}; // Frame dumps of single connections
+#[cfg(feature = "chaos")]
+use crate::chaos::{Fault, FaultInjector, FaultInjectors}; // Deliberate misbehaviour for testing clients
+use crate::events::{Event, EventKind, EventLog}; // Recent significant events
+use crate::health::{HealthReport, HealthStatus}; // Health checks
+use crate::affinity; // CPU pinning and thread priorities
+use crate::clock::{self, Clock}; // Time source for timeouts and periodic jobs
+use crate::config::{MemoryBudget, ServerConfig}; // Server configuration
+#[cfg(feature = "dedup")]
+use crate::dedup::ContentStore; // Large requests by content hash, for offers
+#[cfg(feature = "delta")]
+use crate::delta; // Requests rebuilt from an earlier one
+use crate::frame::{Frame, WireHeader}; // Frame header encoding and parsing
+#[cfg(feature = "gateway")]
+use crate::gateway::{ForwardError, Gateway}; // Forwarding to an upstream server
+use crate::handoff; // Listener handoff to the next server process
+#[cfg(feature = "storage")]
+use crate::lease::Lease; // Cluster-wide singleton jobs
+#[cfg(feature = "storage")]
+use crate::logfile::FileLogger; // On-disk logging
+use crate::metrics::{Metric, MetricsExporter}; // Gauges and counters for monitoring systems
+#[cfg(feature = "plugins")]
+use crate::plugin::{self, Plugin, PluginRoute}; // Handlers and middleware loaded from libraries
+use crate::pool::{self, PoolStats, PooledBuffer}; // Shared buffer pool
+use crate::protocol::{self, MAX_MESSAGE_SIZE, MAX_PREFIX_LEN}; // Frame limits and the embedded schema
+#[cfg(feature = "reflect")]
+use crate::reflect::{DynamicHandler, DynamicRoutes, LateAnswer}; // Pass-through of messages without a typed handler
+use crate::registry::ShardedMap; // Sharded map for storing server instances
+#[cfg(feature = "routing")]
+use crate::routing::{RouteAction, RoutingTable}; // Operator-declared routing rules
+use crate::scheduler::{JobId, Scheduler}; // Periodic jobs
+#[cfg(feature = "scripting")]
+use crate::scripting::Scripts; // Operator scripts transforming requests and responses
+#[cfg(feature = "wasm")]
+use crate::wasm::WasmHandler; // Sandboxed handlers of dynamic messages
+use crate::selftest::{self, CheckResult, SelfTests}; // Self-test checks
+use crate::shedding::{self, LatencyAverage}; // Early rejection of data requests under load
+use crate::socket; // Listener creation
+use crate::startup::StartupReport; // State recovered before accepting
+use crate::message_stats::{self, MessageCounters, MessageStats, SIZE_BUCKETS}; // Requests by type and size
+use crate::status::StatusReport; // Status page
+use crate::violations::{Violation, ViolationCounters, ViolationCounts, ViolationTracker}; // Protocol violation policy
+use crate::watchdog::{self, Heartbeat, Stall, Watchdog}; // Detection of wedged threads
+use log::{debug, error, info, warn}; // Logging macros
 use prost::Message; // Protobuf message encoding/decoding
-use std::collections::HashMap; // HashMap for storing server instances
+#[cfg(feature = "reflect")]
+use prost_types::FileDescriptorSet; // Schemas of dynamic message types
 use std::{
-    io::{self, ErrorKind, Read, Write}, // I/O operations
-    net::{TcpListener, TcpStream}, // Networking
+    fmt, // Debug output of the accept filter and stall callback
+    io::{self, ErrorKind, IoSlice, Read, Write}, // I/O operations
+    collections::{BTreeMap, HashMap, HashSet, VecDeque}, // Stats, registry shards, pending commands, recent errors
+    net::{SocketAddr, TcpListener, TcpStream}, // Networking
+    path::Path, // Handoff socket location
     sync::{
-        atomic::{AtomicBool, Ordering}, // Atomic operations for thread safety
-        {Arc, Mutex}, // Arc for reference counting, Mutex for mutual exclusion
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}, // Atomic operations for thread safety
+        Arc, Condvar, Mutex, // Arc for reference counting, Mutex for mutual exclusion, Condvar to wait on commands
     },
     thread, // Threading
-    time::Duration, // Time handling
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH}, // Time handling
 };
+#[cfg(any(feature = "plugins", feature = "routing"))]
+use std::sync::RwLock; // Plugins and routing rules, read on every request and replaced rarely
 use lazy_static::lazy_static; // Import the lazy_static crate for static initialization
 
+
+// Largest payload a BenchRequest may ask for, leaving room for the framing and metadata
+const MAX_BENCH_PAYLOAD: u32 = (MAX_MESSAGE_SIZE / 2) as u32;
+
+// Most payloads a single BenchRequest may ask for
+const MAX_BENCH_COUNT: u32 = 100_000;
+
+// Without `reflect` no handler can overrun its time limit, the type only keeps the dispatch signatures the same
+#[cfg(not(feature = "reflect"))]
+type LateAnswer = Box<dyn FnOnce(server_message::Message) + Send>;
+
+// Bench payloads written per vectored write, bounds the memory a stream holds
+const BENCH_BATCH: usize = 32;
+
+// First and smallest read window of a connection, the capacity of the smallest buffer pool class
+const MIN_READ_SIZE: usize = 512;
+
+// Largest read window, a maximum size frame with its length prefix
+const MAX_READ_SIZE: usize = MAX_MESSAGE_SIZE + MAX_PREFIX_LEN;
+
+// Reads between checks whether the read window can shrink
+const READ_SIZE_INTERVAL: u32 = 32;
+
+// Longest a connection thread blocks in a read, so it notices a stop and silent peers
+const READ_TICK: Duration = Duration::from_millis(100);
+
+// How long after an error the server reports itself as degraded
+const DEGRADED_WINDOW: Duration = Duration::from_secs(60);
+
+// Errors kept for the status page
+const RECENT_ERRORS: usize = 10;
+
+// Smallest request kept as a delta baseline or in the content store, below this a delta or an offer saves
+// too little to be worth the memory
+#[cfg(any(feature = "delta", feature = "dedup"))]
+const MIN_KEPT_SIZE: usize = 256;
+
+// Metric names of the request size buckets, matching `SIZE_BUCKETS`
+const REQUEST_SIZE_METRICS: [&str; SIZE_BUCKETS.len()] = [
+    "requests_up_to_16_bytes_total",
+    "requests_up_to_64_bytes_total",
+    "requests_up_to_256_bytes_total",
+    "requests_up_to_1024_bytes_total",
+    "requests_up_to_4096_bytes_total",
+    "requests_up_to_16384_bytes_total",
+    "requests_up_to_65536_bytes_total",
+];
+
+// Callback deciding whether a freshly accepted peer is served
+type AcceptFilterFn = Arc<dyn Fn(SocketAddr) -> bool + Send + Sync>;
+
+// Optional accept filter, replaceable while the server runs
+#[derive(Default)]
+struct AcceptFilter {
+    filter: Mutex<Option<AcceptFilterFn>>,
+}
+
+impl AcceptFilter {
+    // Whether `addr` may be served, a panicking filter rejects the peer
+    fn allows(&self, addr: SocketAddr) -> bool {
+        // Cloned out so the filter runs without holding the lock
+        let Some(filter) = self.filter.lock().unwrap().clone() else {
+            return true;
+        };
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| filter(addr))).unwrap_or_else(|_| {
+            error!("Accept filter panicked on {}, rejecting it", addr);
+            false
+        })
+    }
+}
+
+impl fmt::Debug for AcceptFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcceptFilter")
+            .field("installed", &self.filter.lock().unwrap().is_some())
+            .finish()
+    }
+}
+
+// Callback told which components stalled, on every watchdog check finding some
+type StallCallbackFn = Box<dyn FnMut(&[Stall]) + Send>;
+
+// Optional stall callback, replaceable while the server runs
+#[derive(Default)]
+struct StallCallback {
+    callback: Mutex<Option<StallCallbackFn>>,
+}
+
+impl fmt::Debug for StallCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StallCallback")
+            .field("installed", &self.callback.lock().unwrap().is_some())
+            .finish()
+    }
+}
+
+// State shared by the accept loops and connection threads of one server
+#[derive(Debug)]
+struct Shared {
+    is_running: AtomicBool, // Atomic flag to indicate if the server is running
+    accepting: AtomicBool, // Cleared once the listeners are handed to another process
+    draining: AtomicBool, // Set once a graceful shutdown began, clients are told to go away
+    retry_after_ms: AtomicU64, // Reconnect delay announced to clients while draining
+    maintenance: Mutex<Option<MaintenanceMode>>, // Answer to data requests while the server is serviced
+    read_only: Mutex<Option<String>>, // Why new commands are refused while storage is degraded
+    captures: Captures, // Connections whose frames are dumped to files for debugging
+    connections: AtomicUsize, // Number of currently connected clients
+    in_flight: AtomicUsize, // Requests decoded but not yet answered
+    config: ServerConfig, // Configuration the server was created with
+    started: Instant, // Creation time, for the reported uptime
+    recent_errors: Mutex<VecDeque<(Instant, String)>>, // Latest errors and when they happened, newest last
+    self_tests: SelfTests, // Checks run on a SelfTestRequest
+    scheduler: Scheduler, // Periodic jobs, run while the server is running
+    acks: Mutex<AckLog>, // Responses of completed commands by command id
+    running: Mutex<HashSet<String>>, // Commands a connection is running now, a retry elsewhere waits for them
+    command_done: Condvar, // Wakes retries waiting in `running` when a command finishes
+    pending: Mutex<HashSet<String>>, // Commands whose handler overran its time limit, recorded once it returns
+    violations: ViolationCounters, // Protocol violations across all connections
+    messages: MessageCounters, // Requests received per message type
+    errors: AtomicU64, // Errors recorded since the server started
+    events: EventLog, // Recent connects, disconnects, errors and slow requests
+    slow_requests: AtomicU64, // Requests that took longer than the configured threshold
+    shed: AtomicU64, // Connections and requests rejected over the memory budget or under load
+    handler_latency: LatencyAverage, // Moving average of the time taken to decode and handle a request
+    accept_filter: AcceptFilter, // Decides which accepted peers are served
+    clock: Arc<dyn Clock>, // Time for liveness probes, violation windows, scheduled jobs and timestamps
+    #[cfg(feature = "reflect")]
+    dynamic: DynamicRoutes, // Handlers of dynamic messages by type name, and the schema validating them
+    #[cfg(feature = "gateway")]
+    gateway: Option<Gateway>, // Forwards the configured request types upstream
+    #[cfg(feature = "dedup")]
+    content: Mutex<ContentStore>, // Large requests of every connection by content hash, for offers
+    #[cfg(feature = "plugins")]
+    plugins: RwLock<Vec<Arc<Plugin>>>, // Loaded plugins, whose middleware sees every request
+    #[cfg(feature = "routing")]
+    routes: RwLock<Arc<RoutingTable>>, // Rules of the routing file, replaced as a whole on reload
+    watchdog: Option<Watchdog>, // Last progress of the accept loops and connection threads
+    on_stall: StallCallback, // Told about stalled components
+    #[cfg(feature = "chaos")]
+    faults: FaultInjectors, // Decide which requests misbehave on purpose
+    #[cfg(feature = "scripting")]
+    scripts: Scripts, // Transform requests and responses of the configured types
+}
+
+impl Shared {
+    fn new(config: ServerConfig, acks: AckLog, clock: Arc<dyn Clock>) -> Self {
+        let events = EventLog::with_clock(config.event_capacity, Arc::clone(&clock));
+        #[cfg(feature = "gateway")]
+        let gateway = config.gateway.clone().map(|gateway| Gateway::new(gateway, Arc::clone(&clock)));
+        let watchdog = config.watchdog.as_ref().map(|watchdog| Watchdog::new(watchdog.timeout, Arc::clone(&clock)));
+        #[cfg(feature = "dedup")]
+        let content = ContentStore::new(config.content_store);
+        #[cfg(feature = "reflect")]
+        let dynamic = DynamicRoutes::with_timeout(config.handler_timeout);
+        Shared {
+            is_running: AtomicBool::new(true),
+            accepting: AtomicBool::new(true),
+            draining: AtomicBool::new(false),
+            retry_after_ms: AtomicU64::new(0),
+            maintenance: Mutex::new(None),
+            read_only: Mutex::new(None),
+            captures: Captures::default(),
+            connections: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            config,
+            started: Instant::now(),
+            recent_errors: Mutex::new(VecDeque::with_capacity(RECENT_ERRORS)),
+            self_tests: SelfTests::new(),
+            scheduler: Scheduler::with_clock(Arc::clone(&clock)),
+            acks: Mutex::new(acks),
+            running: Mutex::new(HashSet::new()),
+            command_done: Condvar::new(),
+            pending: Mutex::new(HashSet::new()),
+            violations: ViolationCounters::default(),
+            messages: MessageCounters::default(),
+            errors: AtomicU64::new(0),
+            events,
+            slow_requests: AtomicU64::new(0),
+            shed: AtomicU64::new(0),
+            handler_latency: LatencyAverage::default(),
+            accept_filter: AcceptFilter::default(),
+            clock,
+            #[cfg(feature = "reflect")]
+            dynamic,
+            #[cfg(feature = "gateway")]
+            gateway,
+            #[cfg(feature = "dedup")]
+            content: Mutex::new(content),
+            #[cfg(feature = "plugins")]
+            plugins: RwLock::new(Vec::new()),
+            #[cfg(feature = "routing")]
+            routes: RwLock::new(Arc::default()),
+            watchdog,
+            on_stall: StallCallback::default(),
+            #[cfg(feature = "chaos")]
+            faults: FaultInjectors::default(),
+            #[cfg(feature = "scripting")]
+            scripts: Scripts::default(),
+        }
+    }
+
+    // Current wall-clock time of the server's clock in microseconds since the Unix epoch, for every time
+    // sent to clients
+    fn wall_micros(&self) -> u64 {
+        self.clock
+            .wall()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros() as u64)
+            .unwrap_or(0)
+    }
+
+    // Estimated bytes held by buffers, connections and caches. The buffer pool is shared by every server
+    // in the process, so all of it counts
+    fn memory_used(&self, budget: &MemoryBudget) -> usize {
+        let used = pool::stats().total_bytes() + self.connections.load(Ordering::SeqCst) * budget.connection_cost;
+        #[cfg(feature = "dedup")]
+        let used = used + self.content.lock().unwrap().bytes();
+        #[cfg(feature = "gateway")]
+        let used = used + self.gateway.as_ref().map_or(0, Gateway::cached_bytes);
+        used
+    }
+
+    // Whether a new connection is rejected because the server is over its memory budget
+    fn sheds_connection(&self) -> bool {
+        let over = self
+            .config
+            .memory_budget
+            .as_ref()
+            .is_some_and(|budget| self.memory_used(budget) >= budget.limit);
+        if over {
+            self.shed.fetch_add(1, Ordering::Relaxed);
+        }
+        over
+    }
+
+    // Whether a frame of `len` bytes is rejected because the server is over its memory budget
+    fn sheds_request(&self, len: usize) -> bool {
+        let over = self
+            .config
+            .memory_budget
+            .as_ref()
+            .is_some_and(|budget| len >= budget.large_request && self.memory_used(budget) >= budget.limit);
+        if over {
+            self.shed.fetch_add(1, Ordering::Relaxed);
+        }
+        over
+    }
+
+    // Register a component with the watchdog, if there is one
+    fn heartbeat(&self, name: impl FnOnce() -> String) -> Option<Heartbeat> {
+        self.watchdog.as_ref().map(|watchdog| watchdog.register(name()))
+    }
+
+    // Report stalled components, or tell systemd that the server is alive
+    fn check_watchdog(&self) {
+        let (Some(watchdog), Some(config)) = (&self.watchdog, &self.config.watchdog) else {
+            return;
+        };
+        let stalled = watchdog.stalled();
+        if stalled.is_empty() {
+            if config.notify_systemd {
+                if let Err(e) = watchdog::notify_systemd("WATCHDOG=1") {
+                    warn!("Failed to notify systemd: {}", e);
+                }
+            }
+            return;
+        }
+        for stall in &stalled {
+            self.record_error(format!("Watchdog: {} made no progress for {:?}", stall.component, stall.since));
+        }
+        if let Some(callback) = self.on_stall.callback.lock().unwrap().as_mut() {
+            callback(&stalled);
+        }
+    }
+
+    // Route the message types of a plugin to it and run its middleware on every request
+    #[cfg(feature = "plugins")]
+    fn add_plugin(&self, plugin: Plugin) -> io::Result<()> {
+        let plugin = Arc::new(plugin);
+        if let Some(schema) = plugin.schema()? {
+            self.dynamic.add_schema(&schema);
+        }
+        let message_types = plugin.message_types();
+        for type_name in &message_types {
+            self.dynamic.route(type_name, PluginRoute(Arc::clone(&plugin)), None);
+        }
+        info!("Loaded plugin {} handling {:?}", plugin.name(), message_types);
+        self.plugins.write().unwrap().push(plugin);
+        Ok(())
+    }
+
+    // Use a new routing table from now on, unless it forwards requests without a gateway to forward them
+    #[cfg(feature = "routing")]
+    fn set_routes(&self, routes: RoutingTable) -> io::Result<()> {
+        let forwarded = routes.rules().find(|(_, action)| **action == RouteAction::Forward);
+        #[cfg(feature = "gateway")]
+        let gateway = self.gateway.is_some();
+        #[cfg(not(feature = "gateway"))]
+        let gateway = false;
+        if let (Some((message_type, _)), false) = (forwarded, gateway) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("The routing table forwards {} but no gateway is configured", message_type),
+            ));
+        }
+        *self.routes.write().unwrap() = Arc::new(routes);
+        Ok(())
+    }
+
+    // Run the middleware of every plugin on a request, the first refusal answers it
+    #[cfg(feature = "plugins")]
+    fn filter_request(&self, kind: &str, message: &Option<client_message::Message>) -> Result<(), ErrorResponse> {
+        let plugins = self.plugins.read().unwrap();
+        if message.is_none() || !plugins.iter().any(|plugin| plugin.has_filter()) {
+            return Ok(());
+        }
+        let encoded = ClientMessage {
+            message: message.clone(),
+            metadata: None,
+        }
+        .encode_to_vec();
+        plugins.iter().try_for_each(|plugin| plugin.filter(kind, &encoded))
+    }
+
+    // Without plugins there is no middleware, every request passes
+    #[cfg(not(feature = "plugins"))]
+    fn filter_request(&self, _kind: &str, _message: &Option<client_message::Message>) -> Result<(), ErrorResponse> {
+        Ok(())
+    }
+
+    // Answer a dynamic message with the handler registered as `handler` if the routing table named one, or
+    // by its type otherwise. A handler overrunning its time limit takes `late`, see `DynamicRoutes::dispatch`
+    #[cfg(feature = "reflect")]
+    fn dispatch_dynamic(
+        &self,
+        handler: Option<&str>,
+        message: &DynamicMessage,
+        late: &mut Option<LateAnswer>,
+    ) -> server_message::Message {
+        match handler {
+            Some(name) => self.dynamic.dispatch_named(name, message, late),
+            None => self.dynamic.dispatch(message, late),
+        }
+    }
+
+    // Without the `reflect` feature no dynamic message has a handler
+    #[cfg(not(feature = "reflect"))]
+    fn dispatch_dynamic(
+        &self,
+        _handler: Option<&str>,
+        message: &DynamicMessage,
+        _late: &mut Option<LateAnswer>,
+    ) -> server_message::Message {
+        let type_name = message.type_name.trim_start_matches('.');
+        error_response(ErrorCode::Unsupported, format!("No handler for {}", type_name))
+    }
+
+    // Keep the response of a command so a resend replays it, unless it doesn't show the command ran to
+    // completion. A failed write switches to read-only mode
+    fn record_command(&self, acks: &mut AckLog, command_id: &str, response: &server_message::Message) {
+        if let server_message::Message::ErrorResponse(error) = response {
+            let code = error.code();
+            if code.is_retryable() || matches!(code, ErrorCode::HandlerTimeout | ErrorCode::HandlerResourceLimit) {
+                debug!("Not recording command {}, it failed with {:?}", command_id, code);
+                return;
+            }
+        }
+        let stored = ServerMessage {
+            message: Some(response.clone()),
+            metadata: None,
+        };
+        if let Err(e) = acks.record(command_id, &stored) {
+            self.record_error(format!("Failed to record command {}: {}", command_id, e));
+            self.set_read_only(Some(format!("recording command {} failed: {}", command_id, e)));
+        }
+    }
+
+    // Record the answer of a command whose handler overran its time limit once the handler returns, ending the
+    // time the command is pending
+    fn late_answer(self: &Arc<Self>, command_id: &str) -> LateAnswer {
+        let shared = Arc::clone(self);
+        let command_id = command_id.to_string();
+        Box::new(move |response| {
+            let mut acks = shared.acks.lock().unwrap();
+            info!("Late handler of command {} returned, recording its response", command_id);
+            shared.record_command(&mut acks, &command_id, &response);
+            shared.pending.lock().unwrap().remove(&command_id);
+        })
+    }
+
+    // Enter read-only mode for `reason`, or leave it with `None`, recording an event when the mode changes
+    fn set_read_only(&self, reason: Option<String>) {
+        let mut read_only = self.read_only.lock().unwrap();
+        let detail = match (read_only.as_ref(), reason.as_ref()) {
+            (None, Some(reason)) => format!("Entered read-only mode: {}", reason),
+            (Some(_), None) => "Left read-only mode".to_string(),
+            _ => String::new(),
+        };
+        *read_only = reason;
+        if !detail.is_empty() {
+            warn!("{}", detail);
+            self.events.record(EventKind::ModeChanged, detail);
+        }
+    }
+
+    // Log an error and remember it for health reports
+    fn record_error(&self, message: String) {
+        error!("{}", message);
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        self.events.record(EventKind::Error, message.clone());
+        let mut recent_errors = self.recent_errors.lock().unwrap();
+        if recent_errors.len() == RECENT_ERRORS {
+            recent_errors.pop_front();
+        }
+        recent_errors.push_back((Instant::now(), message));
+    }
+
+    // Snapshot of the current health
+    fn health(&self) -> HealthReport {
+        let last_error = self.recent_errors.lock().unwrap().back().cloned();
+        let degraded = last_error
+            .as_ref()
+            .is_some_and(|(at, _)| at.elapsed() < DEGRADED_WINDOW);
+        HealthReport {
+            status: if self.draining.load(Ordering::SeqCst) {
+                HealthStatus::Draining
+            } else if self.maintenance.lock().unwrap().is_some() {
+                HealthStatus::Maintenance
+            } else if degraded || self.read_only.lock().unwrap().is_some() {
+                HealthStatus::Degraded
+            } else {
+                HealthStatus::Serving
+            },
+            connections: self.connections.load(Ordering::SeqCst),
+            queue_depth: self.in_flight.load(Ordering::SeqCst),
+            last_error: last_error.map(|(_, message)| message),
+            uptime: self.started.elapsed(),
+        }
+    }
+
+    // Key gauges and counters, new ones are only ever appended so exporters can address them by position
+    fn metrics(&self) -> Vec<Metric> {
+        let health = self.health();
+        let violations = self.violations.snapshot();
+        let messages = self.messages.snapshot();
+        let mut metrics = vec![
+            Metric::gauge("uptime_seconds", health.uptime.as_secs()),
+            Metric::gauge("connections", health.connections as u64),
+            Metric::gauge("queue_depth", health.queue_depth as u64),
+            Metric::gauge("degraded", (health.status == HealthStatus::Degraded) as u64),
+            Metric::counter("requests_total", messages.values().map(|stats| stats.count).sum()),
+            Metric::counter("errors_total", self.errors.load(Ordering::Relaxed)),
+            Metric::counter("decode_failures_total", violations.decode_failures),
+            Metric::counter("unknown_messages_total", violations.unknown_messages),
+            Metric::counter("oversized_frames_total", violations.oversized_frames),
+            Metric::counter("violation_disconnects_total", violations.disconnects),
+            Metric::counter("slow_requests_total", self.slow_requests.load(Ordering::Relaxed)),
+            Metric::counter("request_bytes_total", messages.values().map(|stats| stats.total_bytes).sum()),
+        ];
+        // Request size distribution across all message types, one counter per bucket
+        for (index, &name) in REQUEST_SIZE_METRICS.iter().enumerate() {
+            metrics.push(Metric::counter(name, messages.values().map(|stats| stats.buckets[index]).sum()));
+        }
+        // Estimated with the default connection cost when no budget is configured
+        let budget = self.config.memory_budget.clone().unwrap_or_default();
+        metrics.push(Metric::gauge("memory_bytes", self.memory_used(&budget) as u64));
+        metrics.push(Metric::counter("shed_total", self.shed.load(Ordering::Relaxed)));
+        metrics.push(Metric::gauge("handler_latency_us", self.handler_latency.get().as_micros() as u64));
+        metrics
+    }
+
+    // Snapshot of everything shown on the status page
+    fn status(&self) -> StatusReport {
+        let recent_errors = self
+            .recent_errors
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .map(|(at, message)| (at.elapsed(), message.clone()))
+            .collect();
+        StatusReport {
+            health: self.health(),
+            messages: self.messages.snapshot().into_iter().map(|(kind, stats)| (kind, stats.count)).collect(),
+            violations: self.violations.snapshot(),
+            recent_errors,
+        }
+    }
+}
+
+// A command in `Shared::running`, removed when its run ends however it ends, waking the retries waiting for it
+struct RunningCommand<'a> {
+    shared: &'a Shared,
+    command_id: &'a str,
+}
+
+impl Drop for RunningCommand<'_> {
+    fn drop(&mut self) {
+        self.shared.running.lock().unwrap().remove(self.command_id);
+        self.shared.command_done.notify_all();
+    }
+}
+
+// Traffic counters of one connection, reported on a StatsRequest
+#[derive(Debug)]
+struct ConnectionStats {
+    connected: Instant, // When the connection was accepted
+    messages_received: u64, // Requests decoded
+    messages_sent: u64, // Responses written
+    bytes_received: u64, // Bytes read from the socket
+    bytes_sent: u64, // Bytes written to the socket
+    latency_total_us: u64, // Sum of the read-to-write time of every answered request
+    latency_samples: u64, // Requests included in `latency_total_us`
+    last_error: Option<String>, // Most recent error on this connection
+}
+
+impl ConnectionStats {
+    fn new() -> Self {
+        ConnectionStats {
+            connected: Instant::now(),
+            messages_received: 0,
+            messages_sent: 0,
+            bytes_received: 0,
+            bytes_sent: 0,
+            latency_total_us: 0,
+            latency_samples: 0,
+            last_error: None,
+        }
+    }
+
+    fn to_response(&self) -> StatsResponse {
+        StatsResponse {
+            messages_received: self.messages_received,
+            messages_sent: self.messages_sent,
+            bytes_received: self.bytes_received,
+            bytes_sent: self.bytes_sent,
+            average_latency_us: self.latency_total_us.checked_div(self.latency_samples).unwrap_or(0),
+            last_error: self.last_error.clone().unwrap_or_default(),
+            connected_secs: self.connected.elapsed().as_secs(),
+            read_window: 0, // Filled in by the connection, which owns the window
+        }
+    }
+}
+
+// Read window of a connection, grown while reads fill it and shrunk back once recent frames are much smaller
+#[derive(Debug)]
+struct ReadWindow {
+    size: usize, // Bytes asked for by the next read
+    largest_frame: usize, // Largest complete frame, with its prefix, since the last shrink check
+    filled: bool, // Whether a read filled the window since the last shrink check
+    reads: u32, // Reads since the last shrink check
+}
+
+impl ReadWindow {
+    fn new() -> Self {
+        ReadWindow {
+            size: MIN_READ_SIZE,
+            largest_frame: 0,
+            filled: false,
+            reads: 0,
+        }
+    }
+
+    // Remember a complete frame of `len` bytes including its length prefix
+    fn saw_frame(&mut self, len: usize) {
+        self.largest_frame = self.largest_frame.max(len);
+    }
+
+    // Adapt the window after a read of `bytes_read` bytes
+    fn after_read(&mut self, bytes_read: usize) {
+        if bytes_read == self.size {
+            // More data is probably waiting, fetch it in fewer calls
+            self.size = (self.size * 2).min(MAX_READ_SIZE);
+            self.filled = true;
+        }
+        self.reads += 1;
+        if self.reads < READ_SIZE_INTERVAL {
+            return;
+        }
+
+        // Fall back to what recent frames need once the window is far larger and nothing filled it
+        let needed = self.largest_frame.next_power_of_two().max(MIN_READ_SIZE);
+        if !self.filled && self.size >= 4 * needed {
+            self.size = needed;
+        }
+        self.largest_frame = 0;
+        self.filled = false;
+        self.reads = 0;
+    }
+}
+
 // Define the Client struct
 #[derive(Debug)]
 pub struct Client {
     stream: TcpStream, // TCP stream for client connection
+    peer: String, // Address of the client, for logs
+    pending: Option<PooledBuffer<'static>>, // Bytes of a frame not yet fully received, pooled while held
+    shared: Arc<Shared>, // State of the server this connection belongs to
+    last_sequence: u64, // Highest sequence number accepted on this connection, for replay protection
+    stats: ConnectionStats, // Traffic counters of this connection
+    violations: ViolationTracker, // Recent protocol violations of this connection
+    discard: usize, // Bytes of an oversized frame still to be skipped
+    last_received: Instant, // When the peer last sent anything
+    probe: Option<(u64, Instant)>, // Id and send time of the unanswered liveness probe
+    read_window: ReadWindow, // Size of the next read, adapted to the frames seen
+    sent_go_away: bool, // Whether this client was told that the server is shutting down
+    shed_draws: u64, // Xorshift state deciding which requests are shed under load, never zero
+    server_sequence: u64, // Sequence number of the last message sent on this connection
+    sent: VecDeque<ServerMessage>, // Recently sent messages oldest first, replayed on a ResyncRequest
+    sent_bytes: usize, // Encoded size of the messages in `sent`
+    #[cfg(feature = "delta")]
+    baselines: VecDeque<(u64, Vec<u8>)>, // Recent large requests by fingerprint, encoded without metadata, oldest first
 }
 
 // Implement methods for the Client struct
 impl Client {
     // Create a new Client instance
     pub fn new(stream: TcpStream) -> Self {
-        Client { stream }
+        let shared = Shared::new(ServerConfig::default(), AckLog::in_memory(), clock::system());
+        Self::with_shared(stream, Arc::new(shared))
+    }
+
+    // Create a Client for a connection accepted by a server
+    fn with_shared(stream: TcpStream, shared: Arc<Shared>) -> Self {
+        let peer = stream
+            .peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|_| "unknown peer".to_string());
+        let port = stream.peer_addr().map_or(0, |addr| addr.port());
+        Client {
+            stream,
+            peer,
+            pending: None,
+            last_sequence: 0,
+            stats: ConnectionStats::new(),
+            violations: ViolationTracker::default(),
+            discard: 0,
+            last_received: shared.clock.now(),
+            probe: None,
+            read_window: ReadWindow::new(),
+            sent_go_away: false,
+            // Connections accepted in the same microsecond differ by their port
+            shed_draws: (now_micros() ^ (u64::from(port) << 32)) | 1,
+            server_sequence: 0,
+            sent: VecDeque::new(),
+            sent_bytes: 0,
+            #[cfg(feature = "delta")]
+            baselines: VecDeque::new(),
+            shared,
+        }
     }
 
     // Handle client messages
     pub fn handle(&mut self) -> io::Result<()> {
-        let mut buffer = [0; 512]; // Buffer for reading data
-        // Read data from the client
-        let bytes_read = self.stream.read(&mut buffer)?;
+        // Tell the client once that the server is shutting down, it is still served until it leaves
+        if !self.sent_go_away && self.shared.draining.load(Ordering::SeqCst) {
+            self.sent_go_away = true;
+            let retry_after_ms = self.shared.retry_after_ms.load(Ordering::SeqCst);
+            info!("Asking client {} to go away, retry after {} ms", self.peer, retry_after_ms);
+            self.write_responses(&mut [GoAway { retry_after_ms }.into()])?;
+        }
+
+        // Read straight behind the incomplete tail of the last read, in a pooled buffer
+        let read_size = self.read_window.size;
+        let mut buffer = self.pending.take().unwrap_or_else(|| pool::acquire(read_size));
+        let filled = buffer.len();
+        buffer.resize(filled + read_size, 0);
+        let read = self.stream.read(&mut buffer[filled..]);
+        buffer.truncate(filled + *read.as_ref().unwrap_or(&0));
+
+        // A read timeout only means the peer was silent
+        let bytes_read = match read {
+            Ok(bytes_read) => bytes_read,
+            Err(ref e) if socket::is_timeout(e) => {
+                if !buffer.is_empty() {
+                    self.pending = Some(buffer);
+                }
+                return self.check_liveness();
+            }
+            Err(e) => return Err(e),
+        };
         if bytes_read == 0 {
-            if bytes_read == 0 {
-                return Err(io::Error::new(ErrorKind::ConnectionAborted, "Client disconnected"));
+            return Err(io::Error::new(ErrorKind::ConnectionAborted, "Client disconnected"));
+        }
+        // Any traffic proves the peer is alive
+        self.last_received = self.shared.clock.now();
+        self.probe = None;
+        let received_us = self.shared.wall_micros(); // Receive time for requests carrying timestamps
+        self.stats.bytes_received += bytes_read as u64;
+
+        // Decode and answer every complete frame received so far
+        let mut frames = 0;
+        let result = self.process_frames(&mut buffer, received_us, &mut frames);
+        self.shared.in_flight.fetch_sub(frames, Ordering::SeqCst);
+
+        // Keep only the incomplete tail, an idle connection gives its buffer back to the pool
+        if !buffer.is_empty() {
+            self.pending = Some(buffer);
+        }
+        self.read_window.after_read(bytes_read);
+        result
+    }
+
+    // Probe a silent peer, closing the connection if an earlier probe went unanswered for too long
+    fn check_liveness(&mut self) -> io::Result<()> {
+        let liveness = &self.shared.config.liveness;
+        let Some(probe_after) = liveness.probe_after else {
+            return Ok(());
+        };
+        let now = self.shared.clock.now();
+        match self.probe {
+            Some((id, sent)) if now.duration_since(sent) > liveness.probe_timeout => {
+                warn!("Liveness probe {} unanswered for {:?}, closing the connection", id, now.duration_since(sent));
+                Err(io::Error::new(ErrorKind::ConnectionAborted, "Connection unresponsive"))
+            }
+            Some(_) => Ok(()),
+            None if now.duration_since(self.last_received) >= probe_after => {
+                static NEXT_PROBE: AtomicU64 = AtomicU64::new(1);
+                let id = NEXT_PROBE.fetch_add(1, Ordering::Relaxed);
+                self.probe = Some((id, now));
+                self.write_responses(&mut [LivenessProbe { id }.into()])
             }
+            None => Ok(()),
+        }
+    }
+
+    // Answer the complete frames at the start of `buffer` and remove them, counting them in `frames`
+    fn process_frames(&mut self, buffer: &mut Vec<u8>, received_us: u64, frames: &mut usize) -> io::Result<()> {
+        let mut responses = Vec::new();
+        // Drop what is left of an oversized frame first
+        let mut consumed = self.discard.min(buffer.len());
+        self.discard -= consumed;
+        while let Some(frame) = WireHeader::next_frame(&buffer[consumed..])? {
+            match frame {
+                Frame::Complete { start, end } => {
+                    // Counted as queued from decoding until its response has been written
+                    self.shared.in_flight.fetch_add(1, Ordering::SeqCst);
+                    *frames += 1;
+                    self.read_window.saw_frame(end);
+                    let frame = &buffer[consumed + start..consumed + end];
+                    if self.shared.sheds_request(frame.len()) {
+                        // Rejected before decoding, nothing the handler would allocate is spent on it
+                        warn!(
+                            "Over the memory budget, rejecting a request of {} bytes from {}",
+                            frame.len(),
+                            self.peer
+                        );
+                        responses.push(overloaded_response("Server is over its memory budget, retry later"));
+                    } else if self.sheds_under_load(frame) {
+                        debug!("Overloaded, rejecting a request from {}", self.peer);
+                        responses.push(overloaded_response("Server is overloaded, retry later"));
+                    } else {
+                        self.process(frame, received_us, &mut responses)?;
+                    }
+                    consumed += end;
+                }
+                Frame::Oversized { prefix_len, payload_len } => {
+                    let detail = format!(
+                        "Frame of {} bytes exceeds the {} byte limit",
+                        payload_len, MAX_MESSAGE_SIZE
+                    );
+                    self.violation(Violation::OversizedFrame, detail, &mut responses)?;
+                    // Skip the payload instead of buffering it, the rest may arrive over many reads
+                    let available = buffer.len() - consumed - prefix_len;
+                    let dropped = payload_len.min(available);
+                    self.discard = payload_len - dropped;
+                    consumed += prefix_len + dropped;
+                }
+            }
+        }
+        buffer.drain(..consumed);
+
+        // Send all responses for this read in a single batch
+        self.write_responses(&mut responses)?;
+        if *frames > 0 {
+            self.stats.latency_total_us += self.shared.wall_micros().saturating_sub(received_us) * *frames as u64;
+            self.stats.latency_samples += *frames as u64;
+        }
+        Ok(())
+    }
+
+    // Whether a data request is rejected at random because handlers are slow or requests queue up. A
+    // rejection counts as an instant request, so the average decays while requests are shed and some get
+    // through again to measure the load
+    fn sheds_under_load(&mut self, frame: &[u8]) -> bool {
+        let Some(policy) = &self.shared.config.load_shedding else {
+            return false;
+        };
+        if shedding::is_control_frame(frame) {
+            return false;
+        }
+        let queue_depth = self.shared.in_flight.load(Ordering::SeqCst);
+        let share = shedding::reject_share(policy, self.shared.handler_latency.get(), queue_depth);
+        if share == 0.0 {
+            return false;
+        }
+        self.shed_draws ^= self.shed_draws << 13;
+        self.shed_draws ^= self.shed_draws >> 7;
+        self.shed_draws ^= self.shed_draws << 17;
+        // The top 53 bits fill the mantissa of an f64
+        let draw = (self.shed_draws >> 11) as f64 / (1u64 << 53) as f64;
+        if draw >= share {
+            return false;
+        }
+        self.shared.shed.fetch_add(1, Ordering::Relaxed);
+        self.shared.handler_latency.record(Duration::ZERO);
+        true
+    }
+
+    // Count a protocol violation, closing the connection with a final error once the policy is exceeded
+    fn violation(&mut self, violation: Violation, detail: String, responses: &mut Vec<ServerMessage>) -> io::Result<()> {
+        warn!("Protocol violation ({}): {}", violation, detail);
+        self.shared.violations.record(violation);
+        self.stats.last_error = Some(detail.clone());
+        let now = self.shared.clock.now();
+        if !self.violations.record(violation, &self.shared.config.violation_policy, now) {
             return Ok(());
         }
 
+        // Answer what is already queued, then explain why the connection goes away
+        self.shared.violations.record_disconnect();
+        responses.push(ServerMessage {
+            message: Some(error_response(
+                ErrorCode::ProtocolViolation,
+                format!("Closing connection after too many violations: {} ({})", violation, detail),
+            )),
+            metadata: None,
+        });
+        self.write_responses(responses)?;
+        responses.clear();
+        Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Closed connection after too many violations: {}", detail),
+        ))
+    }
+
+    // Decode a single frame and queue the responses for it
+    fn process(&mut self, frame: &[u8], received_us: u64, responses: &mut Vec<ServerMessage>) -> io::Result<()> {
+        let started = Instant::now();
+        self.shared.captures.record(&self.peer, Direction::Received, frame, self.shared.clock.now(), received_us);
         // Decode the client message
-        if let Ok(client_message) = ClientMessage::decode(&buffer[..bytes_read]) {
-            match client_message.message {
-                // Handle EchoMessage
-                Some(client_message::Message::EchoMessage(echo_message)) => {
-                    info!("Received EchoMessage: {}", echo_message.content);
-                    // Create a ServerMessage with EchoMessage
-                    let server_message = ServerMessage {
-                        message: Some(server_message::Message::EchoMessage(echo_message)),
-                    };
-                    // Encode the ServerMessage
-                    let payload = server_message.encode_to_vec();
-                    self.stream.write_all(&payload)?; // Send the response
-                    self.stream.flush()?; // Flush the stream
-                }
-                // Handle AddRequest
-                Some(client_message::Message::AddRequest(add_request)) => {
-                    info!("Received AddRequest: {:?}", add_request);
-                    // Process the AddRequest and send back the result
-                    let result = add_request.a + add_request.b;
-                    let response = AddResponse { result };
-                    // Create a ServerMessage with AddResponse
-                    let server_message = ServerMessage {
-                        message: Some(server_message::Message::AddResponse(response)),
-                    };
-                    // Encode the ServerMessage
-                    let buf = server_message.encode_to_vec();
-                    self.stream.write_all(&buf)?; // Send the response
-                    self.stream.flush()?; // Flush the stream
-                }
-                None => {
-                    error!("Received message with no content");
+        let mut client_message = match ClientMessage::decode(frame) {
+            Ok(client_message) => client_message,
+            Err(e) => {
+                return self.violation(Violation::DecodeFailure, format!("Failed to decode message: {}", e), responses);
+            }
+        };
+        self.stats.messages_received += 1;
+        self.shared.messages.record(&client_message.message, frame.len());
+
+        // A delta request is handled as the request it rebuilds, which can be a baseline in turn
+        let mut rejected = None; // Error answering a request that can't be handled at all
+        #[cfg(feature = "delta")]
+        if let Some(client_message::Message::DeltaRequest(request)) = &client_message.message {
+            match self.expand_delta(request) {
+                Ok(message) => client_message.message = message,
+                Err(error) => {
+                    client_message.message = None;
+                    rejected = Some(error);
                 }
             }
+        }
+
+        // An offer of content the server has is handled as the stored request
+        let offered = self.take_offer(&mut client_message.message);
+        #[cfg(any(feature = "delta", feature = "dedup"))]
+        self.keep_request(&client_message.message);
+        let kind = message_stats::message_type(&client_message.message);
+
+        // Probe acks only prove liveness, which reading them already did
+        if let Some(client_message::Message::LivenessProbeAck(ack)) = &client_message.message {
+            debug!("Received LivenessProbeAck {}", ack.id);
+            return Ok(());
+        }
+        let metadata = client_message.metadata.unwrap_or_default();
+
+        // Keep the client's trace id or assign one, every log line for this request carries it
+        let trace_id = if metadata.trace_id.is_empty() {
+            next_trace_id()
         } else {
-            error!("Failed to decode message");
+            metadata.trace_id.clone()
+        };
+
+        // The operator's routing table may drop the request, and picks what handles the rest
+        #[cfg(feature = "routing")]
+        let routes = Arc::clone(&self.shared.routes.read().unwrap());
+        #[cfg(feature = "routing")]
+        let route_key = match &client_message.message {
+            Some(client_message::Message::DynamicMessage(message)) => message.type_name.trim_start_matches('.'),
+            _ => kind,
         }
+        .to_string();
+        #[cfg(feature = "routing")]
+        let (forward, handler) = match routes.action(&route_key) {
+            Some(RouteAction::Drop) => {
+                debug!("[trace {}] Dropping {} as the routing table says", trace_id, route_key);
+                return Ok(());
+            }
+            Some(RouteAction::Forward) => (true, None),
+            Some(RouteAction::Handler(name)) => (false, Some(name.as_str())),
+            Some(RouteAction::Script(_)) | None => (false, None),
+        };
+        #[cfg(not(feature = "routing"))]
+        let (forward, handler): (bool, Option<&str>) = (false, None);
 
+        // Let the operator's scripts inspect or change the request
+        #[cfg(feature = "scripting")]
+        if let Some(request) = client_message.message.take() {
+            let request = self.shared.scripts.on_request(kind, request);
+            #[cfg(feature = "routing")]
+            let request = request.and_then(|request| routes.scripts().on_request(&route_key, request));
+            match request {
+                Ok(request) => client_message.message = Some(request),
+                Err(e) => {
+                    self.shared.record_error(format!("[trace {}] {}", trace_id, e));
+                    rejected = Some(error_response(ErrorCode::Internal, e));
+                }
+            }
+        }
+
+        // Let the middleware of the plugins turn the request away
+        if rejected.is_none() {
+            if let Err(error) = self.shared.filter_request(kind, &client_message.message) {
+                debug!("[trace {}] Plugin middleware rejected the request: {}", trace_id, error.message);
+                rejected = Some(error.into());
+            }
+        }
+
+        // Misbehave on purpose when a fault injector asks for it
+        #[cfg(feature = "chaos")]
+        let injected = match client_message.message.as_ref().and_then(|request| self.shared.faults.pick(request)) {
+            Some(Fault::Drop) => {
+                warn!("[trace {}] Injected fault: dropping the request", trace_id);
+                return Ok(());
+            }
+            Some(Fault::Delay(delay)) => {
+                warn!("[trace {}] Injected fault: delaying the request by {:?}", trace_id, delay);
+                thread::sleep(delay);
+                None
+            }
+            Some(Fault::InternalError) => {
+                warn!("[trace {}] Injected fault: answering with an internal error", trace_id);
+                Some(error_response(ErrorCode::Internal, "Injected fault".to_string()))
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "chaos"))]
+        let injected: Option<server_message::Message> = None;
+
+        // Only control requests are served during maintenance
+        let maintenance = match self.shared.maintenance.lock().unwrap().as_ref() {
+            Some(maintenance) if !shedding::is_control_frame(frame) => Some(maintenance.clone().into()),
+            _ => None,
+        };
+
+        // Drop requests whose deadline passed, e.g. commands queued by a client during an outage
+        let now_us = self.shared.wall_micros();
+        let mut stale = None; // Age of a response a gateway answered from its cache
+        let message = if injected.is_some() {
+            injected
+        } else if rejected.is_some() {
+            rejected
+        } else if maintenance.is_some() {
+            debug!("[trace {}] Deferring {} during maintenance", trace_id, kind);
+            maintenance
+        } else if let Some(reason) = self.check_sequence(metadata.sequence) {
+            warn!("[trace {}] Rejecting possible replay: {}", trace_id, reason);
+            Some(error_response(ErrorCode::Replayed, reason))
+        } else if metadata.expires_at_us != 0 && now_us > metadata.expires_at_us {
+            let late_ms = (now_us - metadata.expires_at_us) / 1000;
+            warn!("[trace {}] Dropping request that expired {} ms ago", trace_id, late_ms);
+            Some(error_response(
+                ErrorCode::Expired,
+                format!("Request expired {} ms before dispatch", late_ms),
+            ))
+        } else if let Some((forwarded, age)) =
+            self.forward_upstream(kind, forward, &mut client_message.message, &metadata, &trace_id)
+        {
+            stale = age;
+            Some(forwarded)
+        } else if !metadata.command_id.is_empty()
+            // Status queries are read-only and take the log lock themselves
+            && !matches!(client_message.message, Some(client_message::Message::CommandStatusRequest(_)))
+        {
+            self.dispatch_once(&metadata.command_id, client_message.message, handler, &trace_id)
+        } else {
+            self.dispatch(client_message.message, handler, &mut None, &trace_id)
+        };
+        let Some(message) = message else {
+            let detail = "Received message of unknown type or without content".to_string();
+            return self.violation(Violation::UnknownMessage, detail, responses);
+        };
+        #[cfg(feature = "scripting")]
+        let message = self.shared.scripts.on_response(kind, message);
+        #[cfg(all(feature = "scripting", feature = "routing"))]
+        let message = message.and_then(|message| routes.scripts().on_response(&route_key, message));
+        #[cfg(feature = "scripting")]
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                self.shared.record_error(format!("[trace {}] {}", trace_id, e));
+                error_response(ErrorCode::Internal, e)
+            }
+        };
+        self.check_slow(kind, started.elapsed(), frame.len(), &trace_id);
+        self.shared.handler_latency.record(started.elapsed());
+        if let server_message::Message::ErrorResponse(error) = &message {
+            self.stats.last_error = Some(error.message.clone());
+        }
+
+        // Return the client's send time with our receive time, the respond time is added when writing
+        let timestamps = metadata.timestamps.map(|timestamps| Timestamps {
+            client_send_us: timestamps.client_send_us,
+            server_receive_us: received_us,
+            server_respond_us: 0,
+        });
+
+        // Tell the client its offer was taken before answering the stored request
+        if offered {
+            responses.push(ServerMessage {
+                message: Some(OfferResponse { have_it: true }.into()),
+                metadata: Some(Metadata {
+                    trace_id: trace_id.clone(),
+                    ..Metadata::default()
+                }),
+            });
+        }
+
+        // A bench header is followed by the payloads it announces
+        let bench = match &message {
+            server_message::Message::BenchResponse(header) => Some(*header),
+            _ => None,
+        };
+        responses.push(ServerMessage {
+            message: Some(message),
+            metadata: Some(Metadata {
+                timestamps,
+                trace_id,
+                stale: stale.is_some(),
+                age_ms: stale.map_or(0, |age| age.as_millis() as u64),
+                ..Metadata::default()
+            }),
+        });
+        match bench {
+            Some(header) => self.stream_bench(&header, responses),
+            None => Ok(()),
+        }
+    }
+
+    // Log and count a request whose decoding and handling took longer than the configured threshold
+    fn check_slow(&self, kind: &str, elapsed: Duration, size: usize, trace_id: &str) {
+        let Some(threshold) = self.shared.config.slow_request_threshold else {
+            return;
+        };
+        if elapsed < threshold {
+            return;
+        }
+        let detail = format!(
+            "{} from {} took {} us, {} bytes",
+            kind,
+            self.peer,
+            elapsed.as_micros(),
+            size
+        );
+        warn!("[trace {}] Slow request: {}", trace_id, detail);
+        self.shared.slow_requests.fetch_add(1, Ordering::Relaxed);
+        self.shared.events.record(EventKind::SlowRequest, detail);
+    }
+
+    // Queue the payloads announced by a bench header, writing them in batches so memory use stays bounded
+    fn stream_bench(&mut self, header: &BenchResponse, responses: &mut Vec<ServerMessage>) -> io::Result<()> {
+        let data: Vec<u8> = (0..header.payload_size).map(|i| (i % 251) as u8).collect();
+        for index in 0..header.count {
+            responses.push(
+                BenchPayload {
+                    index,
+                    data: data.clone(),
+                }
+                .into(),
+            );
+            if responses.len() >= BENCH_BATCH {
+                self.write_responses(responses)?;
+                responses.clear();
+            }
+        }
         Ok(())
     }
+
+    // Accept a sequence number if it is above the last one on this connection, otherwise say why not
+    fn check_sequence(&mut self, sequence: u64) -> Option<String> {
+        if sequence == 0 {
+            return self
+                .shared
+                .config
+                .require_sequence
+                .then(|| "Request has no sequence number".to_string());
+        }
+        if sequence <= self.last_sequence {
+            return Some(format!(
+                "Sequence number {} is not above {}",
+                sequence, self.last_sequence
+            ));
+        }
+        self.last_sequence = sequence;
+        None
+    }
+
+    // Forward a request upstream if the gateway forwards its type, or the routing table says so (`routed`).
+    // Returns the answer and, for one served from the cache, its age; `None` leaves the request to this server
+    #[cfg(feature = "gateway")]
+    fn forward_upstream(
+        &self,
+        kind: &str,
+        routed: bool,
+        request: &mut Option<client_message::Message>,
+        metadata: &Metadata,
+        trace_id: &str,
+    ) -> Option<(server_message::Message, Option<Duration>)> {
+        let gateway = self.shared.gateway.as_ref().filter(|gateway| routed || gateway.forwards(kind))?;
+        // The upstream server applies its own deadline and deduplication, the sequence is per connection
+        let request = ClientMessage {
+            message: request.take(),
+            metadata: Some(Metadata {
+                trace_id: trace_id.to_string(),
+                expires_at_us: metadata.expires_at_us,
+                command_id: metadata.command_id.clone(),
+                ..Metadata::default()
+            }),
+        };
+        // Answer at once rather than leave the client hanging on an unreachable upstream server
+        let message = format!("Upstream {} unavailable for {}", gateway.upstream(), kind);
+        match gateway.forward(kind, &request) {
+            Ok(forwarded) => Some((forwarded.message, forwarded.stale)),
+            Err(e @ ForwardError::CircuitOpen) => {
+                warn!("[trace {}] {}: {}", trace_id, message, e);
+                Some((error_response(ErrorCode::UpstreamUnavailable, format!("{}: {}", message, e)), None))
+            }
+            Err(e) => {
+                self.shared.record_error(format!("[trace {}] {}: {}", trace_id, message, e));
+                Some((error_response(ErrorCode::UpstreamUnavailable, format!("{}: {}", message, e)), None))
+            }
+        }
+    }
+
+    // Without the `gateway` feature every request is handled by this server
+    #[cfg(not(feature = "gateway"))]
+    fn forward_upstream(
+        &self,
+        _kind: &str,
+        _routed: bool,
+        _request: &mut Option<client_message::Message>,
+        _metadata: &Metadata,
+        _trace_id: &str,
+    ) -> Option<(server_message::Message, Option<Duration>)> {
+        None
+    }
+
+    // Run a command at most once, a retry of an applied command gets the stored response
+    fn dispatch_once(
+        &self,
+        command_id: &str,
+        message: Option<client_message::Message>,
+        handler: Option<&str>,
+        trace_id: &str,
+    ) -> Option<server_message::Message> {
+        // A concurrent retry on another connection waits for the result, other commands run in parallel
+        let mut running = self.shared.running.lock().unwrap();
+        while running.contains(command_id) {
+            debug!("[trace {}] Waiting for command {} to finish on another connection", trace_id, command_id);
+            running = self.shared.command_done.wait(running).unwrap();
+        }
+        if let Some(stored) = self.shared.acks.lock().unwrap().get(command_id) {
+            info!("[trace {}] Command {} was already applied, replaying its response", trace_id, command_id);
+            return stored.message.clone();
+        }
+        // Running the command again while its overrunning handler may still apply it could apply it twice
+        if self.shared.pending.lock().unwrap().contains(command_id) {
+            info!("[trace {}] Command {} is still running past its time limit", trace_id, command_id);
+            let status = CommandStatusResponse {
+                command_id: command_id.to_string(),
+                status: CommandStatus::Pending as i32,
+            };
+            return Some(status.into());
+        }
+        // A command that can't be recorded could run twice, so none run until the storage is back
+        if let Some(reason) = self.shared.read_only.lock().unwrap().as_ref() {
+            warn!("[trace {}] Refusing command {} in read-only mode", trace_id, command_id);
+            let detail = format!("Storage is read-only ({}), command {} was not run", reason, command_id);
+            return Some(error_response(ErrorCode::ReadOnly, detail));
+        }
+
+        running.insert(command_id.to_string());
+        drop(running);
+        let _running = RunningCommand {
+            shared: &self.shared,
+            command_id,
+        };
+
+        // Pending from here, a handler that overruns takes `late` and ends that when it returns
+        self.shared.pending.lock().unwrap().insert(command_id.to_string());
+        let mut late = Some(self.shared.late_answer(command_id));
+        let response = self.dispatch(message, handler, &mut late, trace_id);
+        if late.is_none() {
+            return response;
+        }
+        self.shared.pending.lock().unwrap().remove(command_id);
+        let response = response?;
+        self.shared.record_command(&mut self.shared.acks.lock().unwrap(), command_id, &response);
+        Some(response)
+    }
+
+    // Run the handler for a request payload, dynamic messages go to the handler named `handler` if there is one.
+    // A dynamic message handler overrunning its time limit takes `late`
+    fn dispatch(
+        &self,
+        message: Option<client_message::Message>,
+        handler: Option<&str>,
+        late: &mut Option<LateAnswer>,
+        trace_id: &str,
+    ) -> Option<server_message::Message> {
+        if let Some(message) = &message {
+            info!("[trace {}] Received {}", trace_id, message);
+        }
+        let response: server_message::Message = match message {
+            // Handle EchoMessage
+            Some(client_message::Message::EchoMessage(echo_message)) => {
+                // Create a ServerMessage with EchoMessage
+                echo_message.into()
+            }
+            // Handle AddRequest
+            Some(client_message::Message::AddRequest(add_request)) => {
+                // Process the AddRequest and send back the result
+                let result = add_request.a + add_request.b;
+                let response = AddResponse { result };
+                // Create a ServerMessage with AddResponse
+                response.into()
+            }
+            // Handle HealthRequest
+            Some(client_message::Message::HealthRequest(_)) => {
+                self.shared.health().to_response().into()
+            }
+            // Handle SelfTestRequest
+            Some(client_message::Message::SelfTestRequest(_)) => {
+                self.shared.self_tests.run().into()
+            }
+            // Handle CommandStatusRequest
+            Some(client_message::Message::CommandStatusRequest(request)) => {
+                let status = if self.shared.acks.lock().unwrap().get(&request.command_id).is_some() {
+                    CommandStatus::Applied
+                } else if self.shared.pending.lock().unwrap().contains(&request.command_id) {
+                    CommandStatus::Pending
+                } else {
+                    CommandStatus::Unknown
+                };
+                CommandStatusResponse {
+                    command_id: request.command_id,
+                    status: status as i32,
+                }
+                .into()
+            }
+            // Handle BenchRequest
+            Some(client_message::Message::BenchRequest(request)) => {
+                if request.payload_size > MAX_BENCH_PAYLOAD || request.count > MAX_BENCH_COUNT {
+                    error_response(
+                        ErrorCode::InvalidRequest,
+                        format!(
+                            "Bench is limited to {} payloads of {} bytes",
+                            MAX_BENCH_COUNT, MAX_BENCH_PAYLOAD
+                        ),
+                    )
+                } else {
+                    BenchResponse {
+                        payload_size: request.payload_size,
+                        count: request.count,
+                    }
+                    .into()
+                }
+            }
+            // Handle StatsRequest
+            Some(client_message::Message::StatsRequest(_)) => {
+                StatsResponse {
+                    read_window: self.read_window.size as u32,
+                    ..self.stats.to_response()
+                }
+                .into()
+            }
+            // Handle RecentEventsRequest
+            Some(client_message::Message::RecentEventsRequest(request)) => {
+                RecentEventsResponse {
+                    events: self.shared.events.recent(request.limit as usize),
+                    dropped: self.shared.events.dropped(),
+                }
+                .into()
+            }
+            // Handle GetSchemaRequest
+            Some(client_message::Message::GetSchemaRequest(_)) => {
+                GetSchemaResponse {
+                    file_descriptor_set: protocol::FILE_DESCRIPTOR_SET.to_vec(),
+                }
+                .into()
+            }
+            // Handle DynamicMessage
+            Some(client_message::Message::DynamicMessage(message)) => {
+                self.shared.dispatch_dynamic(handler, &message, late)
+            }
+            // Handle DeltaRequest, only reached without the `delta` feature, which rebuilds them before dispatch
+            Some(client_message::Message::DeltaRequest(_)) => {
+                error_response(ErrorCode::Unsupported, "Delta requests are not supported".to_string())
+            }
+            // Handle OfferRequest of content the server doesn't have, offers of stored content were replaced
+            Some(client_message::Message::OfferRequest(_)) => OfferResponse { have_it: false }.into(),
+            // Handle ResyncRequest
+            Some(client_message::Message::ResyncRequest(request)) => self.resync(request.last_seen_seq).into(),
+            // Unknown or empty, the caller counts it as a protocol violation; probe acks never get here
+            Some(client_message::Message::LivenessProbeAck(_)) | None => return None,
+        };
+        Some(response)
+    }
+
+    // The request a delta request rebuilds from one of the kept baselines
+    #[cfg(feature = "delta")]
+    fn expand_delta(&self, request: &DeltaRequest) -> Result<Option<client_message::Message>, server_message::Message> {
+        let Some((_, baseline)) = self.baselines.iter().find(|(fingerprint, _)| *fingerprint == request.baseline) else {
+            let detail = format!("Unknown delta baseline {:016x}", request.baseline);
+            return Err(error_response(ErrorCode::UnknownBaseline, detail));
+        };
+        let invalid = |detail: String| error_response(ErrorCode::InvalidRequest, detail);
+        let rebuilt = delta::apply(baseline, &request.delta, MAX_MESSAGE_SIZE).map_err(|e| invalid(e.to_string()))?;
+        let message = ClientMessage::decode(&rebuilt[..])
+            .map_err(|e| invalid(format!("Failed to decode rebuilt request: {}", e)))?
+            .message;
+        // Deltas of deltas would let one frame expand without bound
+        if let Some(client_message::Message::DeltaRequest(_)) = message {
+            return Err(invalid("A delta can't rebuild another delta".to_string()));
+        }
+        Ok(message)
+    }
+
+    // Replace an offer of content in the store by the stored request, telling whether it did
+    #[cfg(feature = "dedup")]
+    fn take_offer(&self, message: &mut Option<client_message::Message>) -> bool {
+        let Some(client_message::Message::OfferRequest(offer)) = message else {
+            return false;
+        };
+        let stored = self.shared.content.lock().unwrap().get(&offer.sha256).map(ClientMessage::decode);
+        let Some(Ok(stored)) = stored else {
+            return false;
+        };
+        *message = stored.message;
+        true
+    }
+
+    // Without the `dedup` feature nothing is stored, every offer is answered by asking for the request
+    #[cfg(not(feature = "dedup"))]
+    fn take_offer(&self, _message: &mut Option<client_message::Message>) -> bool {
+        false
+    }
+
+    // Keep a large request as a baseline later delta requests can refer to, forgetting the oldest ones, and
+    // in the content store for offers
+    #[cfg(any(feature = "delta", feature = "dedup"))]
+    fn keep_request(&mut self, message: &Option<client_message::Message>) {
+        #[cfg(feature = "delta")]
+        let limit = self.shared.config.delta_baselines;
+        #[cfg(not(feature = "delta"))]
+        let limit = 0;
+        #[cfg(feature = "dedup")]
+        let store = self.shared.config.content_store > 0;
+        #[cfg(not(feature = "dedup"))]
+        let store = false;
+        if (limit == 0 && !store) || message.as_ref().map_or(0, |message| message.encoded_len()) < MIN_KEPT_SIZE {
+            return;
+        }
+        let encoded = ClientMessage {
+            message: message.clone(),
+            metadata: None,
+        }
+        .encode_to_vec();
+        #[cfg(feature = "dedup")]
+        if store {
+            self.shared.content.lock().unwrap().insert(encoded.clone());
+        }
+        #[cfg(feature = "delta")]
+        if limit > 0 {
+            let fingerprint = delta::fingerprint(&encoded);
+            self.baselines.retain(|(kept, _)| *kept != fingerprint);
+            self.baselines.push_back((fingerprint, encoded));
+            if self.baselines.len() > limit {
+                self.baselines.pop_front();
+            }
+        }
+    }
+
+    // Keep copies of sent messages for a ResyncRequest, up to the configured size. Resync responses aren't
+    // kept, a client resyncing twice would otherwise get messages nested in each other
+    fn buffer_sent(&mut self, responses: &[ServerMessage]) {
+        // The replayed messages must fit in one frame together
+        let limit = self.shared.config.resync_buffer.min(MAX_MESSAGE_SIZE / 2);
+        if limit == 0 {
+            return;
+        }
+        for response in responses {
+            if !matches!(response.message, Some(server_message::Message::ResyncResponse(_))) {
+                self.sent_bytes += response.encoded_len();
+                self.sent.push_back(response.clone());
+            }
+        }
+        while self.sent_bytes > limit {
+            let Some(oldest) = self.sent.pop_front() else {
+                break;
+            };
+            self.sent_bytes -= oldest.encoded_len();
+        }
+    }
+
+    // The buffered messages sent after `last_seen`, and whether none of them is missing
+    fn resync(&self, last_seen: u64) -> ResyncResponse {
+        let sequence =
+            |message: &ServerMessage| message.metadata.as_ref().map_or(0, |metadata| metadata.server_sequence);
+        let messages: Vec<ServerMessage> = self
+            .sent
+            .iter()
+            .filter(|message| sequence(message) > last_seen)
+            .cloned()
+            .collect();
+        let complete = match messages.first() {
+            Some(first) => sequence(first) == last_seen + 1,
+            // Nothing buffered is newer, which is only complete if nothing newer was sent
+            None => last_seen >= self.server_sequence,
+        };
+        ResyncResponse { messages, complete }
+    }
+
+    // Write length-prefixed responses with vectored I/O, one syscall for the whole batch when possible
+    fn write_responses(&mut self, responses: &mut [ServerMessage]) -> io::Result<()> {
+        if responses.is_empty() {
+            return Ok(());
+        }
+
+        // Number every message and stamp the respond time as late as possible, right before encoding
+        let respond_us = self.shared.wall_micros();
+        for response in responses.iter_mut() {
+            self.server_sequence += 1;
+            let metadata = response.metadata.get_or_insert_with(Metadata::default);
+            metadata.server_sequence = self.server_sequence;
+            if let Some(timestamps) = metadata.timestamps.as_mut() {
+                timestamps.server_respond_us = respond_us;
+            }
+        }
+
+        self.buffer_sent(responses);
+
+        // Encode each payload into a pooled buffer next to its own length prefix
+        let encoded: Vec<([u8; MAX_PREFIX_LEN], usize, PooledBuffer)> = responses
+            .iter()
+            .map(|message| {
+                let mut payload = pool::acquire(message.encoded_len());
+                message
+                    .encode(&mut *payload)
+                    .expect("Pooled buffer grows to fit the payload");
+                let mut prefix = [0; MAX_PREFIX_LEN];
+                let prefix_len = WireHeader::new(payload.len()).encode(&mut prefix);
+                (prefix, prefix_len, payload)
+            })
+            .collect();
+        let now = self.shared.clock.now();
+        for (_, _, payload) in &encoded {
+            self.shared.captures.record(&self.peer, Direction::Sent, payload, now, respond_us);
+        }
+        let mut slices: Vec<IoSlice> = encoded
+            .iter()
+            .flat_map(|(prefix, prefix_len, payload)| {
+                [IoSlice::new(&prefix[..*prefix_len]), IoSlice::new(payload)]
+            })
+            .collect();
+
+        // Keep writing until every slice has been sent, the kernel may accept only part of it
+        let mut remaining = &mut slices[..];
+        while !remaining.is_empty() {
+            match self.stream.write_vectored(remaining) {
+                Ok(0) => return Err(io::Error::new(ErrorKind::WriteZero, "Failed to write response")),
+                Ok(written) => {
+                    self.stats.bytes_sent += written as u64;
+                    IoSlice::advance_slices(&mut remaining, written);
+                }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.stats.messages_sent += responses.len() as u64;
+        self.stream.flush() // Flush the stream
+    }
+}
+
+// Apply the per-connection socket settings
+fn configure_connection(stream: &TcpStream, config: &ServerConfig) -> io::Result<()> {
+    // Accepted sockets may inherit non-blocking mode from the listener
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TICK))?;
+    if let Some(keepalive) = &config.liveness.tcp_keepalive {
+        if let Err(e) = socket::set_keepalive(stream, keepalive) {
+            warn!("TCP keepalive not enabled, relying on liveness probes: {}", e);
+        }
+    }
+    Ok(())
+}
+
+// Build an error reply
+fn error_response(code: ErrorCode, message: String) -> server_message::Message {
+    ErrorResponse::new(code, message).into()
+}
+
+// Tell a connection rejected over the memory budget why it is closed, without waiting on a slow peer
+fn reject_overloaded(mut stream: &TcpStream) {
+    let mut response = overloaded_response("Server is over its memory budget, retry later");
+    // The only message ever sent on this connection
+    response.metadata = Some(Metadata {
+        server_sequence: 1,
+        ..Metadata::default()
+    });
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_write_timeout(Some(READ_TICK));
+    if let Err(e) = stream.write_all(&response.encode_length_delimited_to_vec()) {
+        debug!("Failed to tell a rejected connection why: {}", e);
+    }
+}
+
+// Error sent instead of handling a request while the server sheds load
+fn overloaded_response(reason: &str) -> ServerMessage {
+    ServerMessage {
+        message: Some(error_response(ErrorCode::Overloaded, reason.to_string())),
+        metadata: None,
+    }
 }
 
+// Current wall-clock time in microseconds since the Unix epoch
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+// Assign a trace id to a request that came without one, unique within this process
+fn next_trace_id() -> String {
+    // The start time keeps ids from different server runs apart
+    lazy_static! {
+        static ref PREFIX: u64 = now_micros();
+    }
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    format!("srv-{:x}-{:x}", *PREFIX, NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+// Loop serving an HTTP endpoint, run on its own thread next to the accept loops
+type HttpLoop<'a> = Box<dyn FnOnce() -> io::Result<()> + Send + 'a>;
+
 // Define the Server struct
 #[derive(Debug)]
 pub struct Server {
-    listener: TcpListener, // TCP listener for incoming connections
-    is_running: Arc<AtomicBool>, // Atomic flag to indicate if the server is running
-    client_count: Arc<Mutex<usize>>, // Reference counter for active clients
+    listeners: Vec<TcpListener>, // TCP listeners for incoming connections, one per acceptor thread
+    addr: String, // Address the server is registered under in SERVERS
+    client_count: AtomicUsize, // Reference counter for handles returned by `new`
+    startup: StartupReport, // What was recovered before the listeners were bound
+    shared: Arc<Shared>, // Running flag, counters and config shared with connection threads
 }
 
-// Initialize a static HashMap to store server instances
+// Initialize a static sharded map to store server instances
 lazy_static! {
-    static ref SERVERS: Arc<Mutex<HashMap<String, Arc<Server>>>> = Arc::new(Mutex::new(HashMap::new()));
+    static ref SERVERS: ShardedMap<String, Arc<Server>> = ShardedMap::new();
 }
 
 // Implement methods for the Server struct
 impl Server {
-    /// Creates a new server instance
+    /// Creates a new server instance with the default configuration
     pub fn new(addr: &str) -> io::Result<Arc<Self>> {
-        let mut servers_lock = SERVERS.lock().unwrap(); // Lock the HashMap
+        Self::with_config(addr, ServerConfig::default())
+    }
 
-        // Debugging: Print the contents of the HashMap
-        info!("Current server instances: {:?}", *servers_lock);
+    /// Creates a new server instance with the given configuration
+    pub fn with_config(addr: &str, config: ServerConfig) -> io::Result<Arc<Self>> {
+        Self::with_clock(addr, config, clock::system())
+    }
+
+    /// Creates a new server instance whose liveness probes, violation windows and scheduled jobs follow
+    /// `clock`, e.g. a `ManualClock` to test them without waiting in real time. Timestamps sent to clients
+    /// and event times come from its wall-clock time, e.g. a PTP-disciplined clock
+    pub fn with_clock(addr: &str, config: ServerConfig, clock: Arc<dyn Clock>) -> io::Result<Arc<Self>> {
+        // Debugging: Print the number of registered servers
+        info!("Current server instances: {}", SERVERS.len());
+
+        // Only the shard holding this address is locked, servers on other addresses don't contend
+        let mut servers_lock = SERVERS.shard(addr);
 
         // Check if a server instance already exists for the given address
         if let Some(server) = servers_lock.get(addr) {
             warn!("Server instance for address {} already exists.", addr); 
-            // Increment the client count
-            {
-                let mut count = server.client_count.lock().unwrap();
-                *count += 1;
+            if server.shared.config != config {
+                warn!("Ignoring new configuration for {}, the existing server keeps its own.", addr);
             }
+            // Increment the client count
+            server.client_count.fetch_add(1, Ordering::SeqCst);
             return Ok(Arc::clone(server));
         }
 
-        // Bind the TCP listener to the address
-        match TcpListener::bind(addr) {
-            Ok(listener) => {
-                let is_running = Arc::new(AtomicBool::new(false)); // Initialize the running flag
-                let client_count = Arc::new(Mutex::new(1)); // Initialize the client count
+        // Log to a file from here on if configured, only one logger can serve the process
+        #[cfg(feature = "storage")]
+        if let Some(log_file) = &config.log_file {
+            match FileLogger::install(log_file) {
+                Ok(()) => info!("Logging to {}", log_file.path.display()),
+                Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {
+                    warn!("Not logging to {}, another logger is already installed.", log_file.path.display());
+                }
+                Err(e) => {
+                    eprintln!("Failed to open log file {}: {}", log_file.path.display(), e);
+                    return Err(e);
+                }
+            }
+        }
+        #[cfg(not(feature = "storage"))]
+        if let Some(log_file) = &config.log_file {
+            warn!("Not logging to {}, log files need the `storage` feature.", log_file.path.display());
+        }
+        // Without the store every offer is answered by asking for the request, which still gets served
+        #[cfg(not(feature = "dedup"))]
+        if config.content_store > 0 {
+            warn!("Not keeping offered content, the content store needs the `dedup` feature.");
+        }
+
+        // Recover persistent state before binding, so no client connects to a half-initialised server
+        let recovery_started = Instant::now();
+        info!("Recovering state for {}", addr);
+        let acks = match &config.ack_log_path {
+            #[cfg(feature = "storage")]
+            Some(path) => AckLog::open_compacting_after(path, config.ack_log_compact_after)?,
+            // Silently forgetting acknowledgements would break exactly-once execution across restarts
+            #[cfg(not(feature = "storage"))]
+            Some(path) => {
+                return Err(io::Error::new(
+                    ErrorKind::Unsupported,
+                    format!("Acknowledgement log {} needs the `storage` feature", path.display()),
+                ))
+            }
+            None => AckLog::in_memory(),
+        };
+        // Skipping the configured transformations could forward or answer requests their operator meant to change
+        #[cfg(feature = "scripting")]
+        let scripts = Scripts::load(&config.scripts)?;
+        #[cfg(not(feature = "scripting"))]
+        if let Some(hook) = config.scripts.first() {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("Script {} needs the `scripting` feature", hook.path.display()),
+            ));
+        }
+        // A server without its site-specific handlers would answer their requests as unsupported
+        #[cfg(feature = "plugins")]
+        let plugins = match &config.plugin_dir {
+            // Safety: the operator configured the directory, its libraries are trusted like the server itself
+            Some(dir) => unsafe { plugin::load_plugins(dir)? },
+            None => Vec::new(),
+        };
+        #[cfg(not(feature = "plugins"))]
+        if let Some(dir) = &config.plugin_dir {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("Plugin directory {} needs the `plugins` feature", dir.display()),
+            ));
+        }
+        #[cfg(feature = "routing")]
+        let routes = match &config.routes_path {
+            Some(path) => RoutingTable::load(path)?,
+            None => RoutingTable::default(),
+        };
+        #[cfg(not(feature = "routing"))]
+        if let Some(path) = &config.routes_path {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("Routing table {} needs the `routing` feature", path.display()),
+            ));
+        }
+        // Answering requests meant for the upstream server here would give them the wrong handlers
+        #[cfg(not(feature = "gateway"))]
+        if let Some(gateway) = &config.gateway {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("Gateway to {} needs the `gateway` feature", gateway.upstream),
+            ));
+        }
+        #[cfg(feature = "wasm")]
+        let wasm_handlers = config
+            .wasm_handlers
+            .iter()
+            .map(|handler| Ok((handler.clone(), WasmHandler::load(handler)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        #[cfg(not(feature = "wasm"))]
+        if let Some(handler) = config.wasm_handlers.first() {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("WASM module {} needs the `wasm` feature", handler.path.display()),
+            ));
+        }
+
+        // Opening the log rewrote and synced it, so the storage is known to be writable here
+        let startup = StartupReport {
+            ack_log: config.ack_log_path.clone(),
+            commands_replayed: acks.len(),
+            entries_discarded: acks.discarded(),
+            duration: recovery_started.elapsed(),
+        };
+        info!("Recovery of {} done: {}", addr, startup);
+
+        // Bind the TCP listeners to the address, or take them over from the server being replaced
+        let listeners = match &config.inherit_listeners {
+            Some(path) => handoff::receive_listeners(path),
+            None => socket::bind_listeners(addr, &config),
+        };
+        match listeners {
+            Ok(listeners) => {
+                // The listener accepts connections as soon as it is bound, so the server counts as
+                // running from here. Setting the flag in `run` instead let a `stop` issued before the
+                // accept loop started be overwritten, leaving `run` looping forever.
+                let shared = Shared::new(config, acks, clock); // Initialize the running flag and counters
+                #[cfg(feature = "scripting")]
+                let shared = Shared { scripts, ..shared };
+                #[cfg(feature = "plugins")]
+                for plugin in plugins {
+                    shared.add_plugin(plugin)?;
+                }
+                #[cfg(feature = "routing")]
+                shared.set_routes(routes)?;
+                #[cfg(feature = "wasm")]
+                for (config, handler) in wasm_handlers {
+                    for type_name in &config.message_types {
+                        shared.dynamic.route(type_name, handler.clone(), config.timeout);
+                    }
+                }
                 let server = Arc::new(Server {
-                    listener,
-                    is_running,
-                    client_count,
+                    listeners,
+                    addr: addr.to_string(),
+                    client_count: AtomicUsize::new(1), // Initialize the client count
+                    startup,
+                    shared: Arc::new(shared),
                 });
                 servers_lock.insert(addr.to_string(), Arc::clone(&server)); // Store the server instance
+                if let Some(watchdog) = &server.shared.config.watchdog {
+                    // A weak reference, the job is owned by the scheduler inside `Shared`
+                    let shared = Arc::downgrade(&server.shared);
+                    server.schedule(watchdog.check_interval, move || {
+                        if let Some(shared) = shared.upgrade() {
+                            shared.check_watchdog();
+                        }
+                    });
+                }
+                #[cfg(feature = "storage")]
+                if let Some(path) = server.shared.config.ack_log_path.clone() {
+                    server.register_storage_checks(&path);
+                }
                 Ok(server)
             }
             Err(ref e) if e.kind() == ErrorKind::AddrInUse => {
@@ -141,67 +1839,589 @@ impl Server {
 
     /// Runs the server, listening for incoming connections and handling them
     pub fn run(&self) -> io::Result<()> {
-        self.is_running.store(true, Ordering::SeqCst); // Set the server as running
-        info!("Server is running on {}", self.listener.local_addr()?);
+        info!("Server is running on {}", self.listeners[0].local_addr()?);
+
+        // Control threads keep their own cores and priority, so busy connection threads can't starve
+        // scheduled jobs or the HTTP endpoints
+        let config = &self.shared.config;
+        let control =
+            move || affinity::configure_current_thread("control", &config.control_cores, config.control_priority);
+
+        // Extra SO_REUSEPORT listeners each get their own accept loop thread, the first one runs here
+        let results: Vec<io::Result<()>> = thread::scope(|scope| {
+            let mut extra: Vec<_> = self.listeners[1..]
+                .iter()
+                .map(|listener| scope.spawn(move || self.accept_loop(listener)))
+                .collect();
+            // The optional HTTP health, status and admin endpoints stop together with the accept loops
+            let endpoints = [self.healthz_endpoint(), self.status_endpoint(), self.admin_endpoint()];
+            for serve in endpoints.into_iter().flatten() {
+                extra.push(scope.spawn(move || {
+                    control();
+                    serve()
+                }));
+            }
+            // Scheduled jobs share the server's lifecycle
+            let shared = &self.shared;
+            extra.push(scope.spawn(move || {
+                control();
+                shared.scheduler.run(&shared.is_running);
+                Ok(())
+            }));
+            let mut results = vec![self.accept_loop(&self.listeners[0])];
+            results.extend(extra.into_iter().map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(io::Error::other("Server thread panicked")))
+            }));
+            results
+        });
+
+        info!("Server stopped.");
+        results.into_iter().collect()
+    }
+
+    // Bind the HTTP health endpoint if one is configured, returning the loop serving it
+    #[cfg(feature = "healthz")]
+    fn healthz_endpoint(&self) -> Option<HttpLoop<'_>> {
+        let addr = self.shared.config.healthz_addr.as_deref()?;
+        match TcpListener::bind(addr) {
+            Ok(listener) => {
+                let shared = &self.shared;
+                Some(Box::new(move || {
+                    crate::health::serve_http(listener, &shared.is_running, || shared.health())
+                }))
+            }
+            Err(e) => {
+                self.shared.record_error(format!("Failed to bind health endpoint {}: {}", addr, e));
+                None
+            }
+        }
+    }
+
+    // Without the `healthz` feature a configured endpoint is only reported
+    #[cfg(not(feature = "healthz"))]
+    fn healthz_endpoint(&self) -> Option<HttpLoop<'_>> {
+        if let Some(addr) = &self.shared.config.healthz_addr {
+            warn!("Health endpoint {} needs the `healthz` feature, not serving it.", addr);
+        }
+        None
+    }
+
+    // Bind the HTTP status page if one is configured, returning the loop serving it
+    #[cfg(feature = "status-page")]
+    fn status_endpoint(&self) -> Option<HttpLoop<'_>> {
+        let addr = self.shared.config.status_addr.as_deref()?;
+        match TcpListener::bind(addr) {
+            Ok(listener) => {
+                let shared = &self.shared;
+                Some(Box::new(move || {
+                    crate::status::serve_http(listener, &shared.is_running, || shared.status())
+                }))
+            }
+            Err(e) => {
+                self.shared.record_error(format!("Failed to bind status page {}: {}", addr, e));
+                None
+            }
+        }
+    }
+
+    // Without the `status-page` feature a configured page is only reported
+    #[cfg(not(feature = "status-page"))]
+    fn status_endpoint(&self) -> Option<HttpLoop<'_>> {
+        if let Some(addr) = &self.shared.config.status_addr {
+            warn!("Status page {} needs the `status-page` feature, not serving it.", addr);
+        }
+        None
+    }
+
+    // Bind the HTTP admin endpoint if one is configured, returning the loop serving it
+    #[cfg(feature = "admin")]
+    fn admin_endpoint(&self) -> Option<HttpLoop<'_>> {
+        let addr = self.shared.config.admin_addr.as_deref()?;
+        match TcpListener::bind(addr) {
+            Ok(listener) => Some(Box::new(move || crate::admin::serve_http(listener, &self.shared.is_running, self))),
+            Err(e) => {
+                self.shared.record_error(format!("Failed to bind admin endpoint {}: {}", addr, e));
+                None
+            }
+        }
+    }
+
+    // Without the `admin` feature a configured endpoint is only reported
+    #[cfg(not(feature = "admin"))]
+    fn admin_endpoint(&self) -> Option<HttpLoop<'_>> {
+        if let Some(addr) = &self.shared.config.admin_addr {
+            warn!("Admin endpoint {} needs the `admin` feature, not serving it.", addr);
+        }
+        None
+    }
+
+    // Accept connections on one listener until the server is stopped
+    fn accept_loop(&self, listener: &TcpListener) -> io::Result<()> {
+        let config = &self.shared.config;
+        affinity::configure_current_thread("acceptor", &config.acceptor_cores, config.acceptor_priority);
 
         // Set the listener to non-blocking mode
-        self.listener.set_nonblocking(true)?;
-
-        while self.is_running.load(Ordering::SeqCst) {
-            match self.listener.accept() {
-                Ok((stream, addr)) => {
-                    info!("New client connected: {}", addr);
-        
-                    // Clone the Arc to share the is_running flag with the new thread
-                    let is_running = Arc::clone(&self.is_running);
-        
-                    // Spawn a new thread to handle the client connection
-                    thread::spawn(move || {
-                        let mut client = Client::new(stream);
-                        while is_running.load(Ordering::SeqCst) {
-                            if let Err(e) = client.handle() {
-                                error!("Error handling client: {}", e);
-                                break;
-                            }
-                        }
-                    });
+        listener.set_nonblocking(true)?;
+        let heartbeat = self.shared.heartbeat(|| match listener.local_addr() {
+            Ok(addr) => format!("acceptor {}", addr),
+            Err(_) => "acceptor".to_string(),
+        });
+
+        while self.shared.is_running.load(Ordering::SeqCst) {
+            if let Some(heartbeat) = &heartbeat {
+                heartbeat.pet();
+            }
+            // During and after a handoff the next process accepts on the same sockets
+            if !self.shared.accepting.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            // Admit every connection waiting in the backlog, a burst of reconnecting devices shouldn't
+            // trickle in one per poll
+            let mut accepted = 0;
+            while self.shared.accepting.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, addr)) => {
+                        self.spawn_connection(stream, addr);
+                        accepted += 1;
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        // E.g. out of file descriptors, retrying at once would only spin
+                        self.shared.record_error(format!("Error accepting connection: {}", e));
+                        break;
+                    }
                 }
-                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
-                    // No incoming connections, sleep briefly to reduce CPU usage
-                    thread::sleep(Duration::from_millis(100));
+            }
+            if accepted > 1 {
+                debug!("Accepted {} connections in one wakeup", accepted);
+            }
+
+            // No more incoming connections, sleep briefly to reduce CPU usage unless busy-polling
+            if config.poll_interval.is_zero() {
+                std::hint::spin_loop();
+            } else {
+                thread::sleep(config.poll_interval);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Serve an accepted connection on its own thread
+    fn spawn_connection(&self, stream: TcpStream, addr: SocketAddr) {
+        // Rejected peers are closed right away, before any thread or buffer is spent on them
+        if !self.shared.accept_filter.allows(addr) {
+            info!("Connection from {} rejected by the accept filter", addr);
+            return;
+        }
+        if self.shared.sheds_connection() {
+            warn!("Over the memory budget, rejecting connection from {}", addr);
+            reject_overloaded(&stream);
+            return;
+        }
+        info!("New client connected: {}", addr);
+        self.shared.events.record(EventKind::Connected, addr.to_string());
+
+        // Clone the Arc to share the is_running flag and connection counter with the new thread
+        let shared = Arc::clone(&self.shared);
+        shared.connections.fetch_add(1, Ordering::SeqCst);
+
+        // Spawn a new thread to handle the client connection
+        thread::spawn(move || {
+            let config = &shared.config;
+            affinity::configure_current_thread("worker", &config.worker_cores, config.worker_priority);
+            if let Err(e) = configure_connection(&stream, config) {
+                shared.record_error(format!("Failed to configure connection {}: {}", addr, e));
+                shared.connections.fetch_sub(1, Ordering::SeqCst);
+                return;
+            }
+            let mut client = Client::with_shared(stream, Arc::clone(&shared));
+            let mut reason = "server stopped".to_string();
+            // `handle` returns at least every read tick, a connection thread that doesn't is stuck in a request
+            let heartbeat = shared.heartbeat(|| format!("connection {}", addr));
+            while shared.is_running.load(Ordering::SeqCst) {
+                if let Some(heartbeat) = &heartbeat {
+                    heartbeat.pet();
                 }
-                Err(e) => {
-                    error!("Error accepting connection: {}", e);
+                if let Err(e) = client.handle() {
+                    // A client hanging up is routine, anything else counts against health
+                    if socket::is_disconnect(&e) {
+                        info!("Client {} disconnected: {}", addr, e);
+                    } else {
+                        shared.record_error(format!("Error handling client: {}", e));
+                    }
+                    reason = e.to_string();
+                    break;
                 }
             }
+            shared.events.record(EventKind::Disconnected, format!("{}: {}", addr, reason));
+            shared.connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+
+    /// Passes the listeners to the next server process, which connects to the Unix socket at `path` through
+    /// `ServerConfig::inherit_listeners`. Blocks until it does, then stops accepting; connections already
+    /// accepted keep being served until they close, see `drain`
+    pub fn hand_off(self: &Arc<Self>, path: &Path) -> io::Result<()> {
+        // Stop accepting first, connections arriving meanwhile wait in the backlog for the next process
+        self.shared.accepting.store(false, Ordering::SeqCst);
+        // The address belongs to the next server now, a server created in this process must not get this one
+        self.unregister(&mut SERVERS.shard(&self.addr));
+
+        if let Err(e) = handoff::send_listeners(path, &self.listeners) {
+            self.shared.record_error(format!("Failed to hand off listeners of {}: {}", self.addr, e));
+            let mut servers_lock = SERVERS.shard(&self.addr);
+            if servers_lock.get(&self.addr).is_none() {
+                servers_lock.insert(self.addr.clone(), Arc::clone(self));
+            }
+            self.shared.accepting.store(true, Ordering::SeqCst);
+            return Err(e);
         }
+        info!("Listeners of {} handed off, draining {} connections.", self.addr, self.connection_count());
+        Ok(())
+    }
 
-        info!("Server stopped.");
+    /// Waits up to `timeout` for every connection to close, returns whether they all did
+    pub fn drain(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.connection_count() > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    /// Starts a graceful shutdown. Every client, connected now or later, gets one `GoAway` announcing
+    /// `retry_after`, and health checks report `Draining` so load balancers stop sending new clients.
+    /// Clients are still served until they leave, see `drain` and `shutdown`
+    pub fn start_draining(&self, retry_after: Duration) {
+        self.shared.retry_after_ms.store(retry_after.as_millis() as u64, Ordering::SeqCst);
+        if !self.shared.draining.swap(true, Ordering::SeqCst) {
+            info!("Draining {} connections of {}.", self.connection_count(), self.addr);
+        }
+    }
+
+    /// Whether a graceful shutdown began
+    pub fn is_draining(&self) -> bool {
+        self.shared.draining.load(Ordering::SeqCst)
+    }
+
+    /// Starts a servicing window: data requests are answered with `MaintenanceMode` announcing `retry_after`
+    /// and `reason`, while health and other control requests are still served. Entering again updates both
+    pub fn enter_maintenance(&self, retry_after: Duration, reason: &str) {
+        let maintenance = MaintenanceMode {
+            retry_after_ms: retry_after.as_millis() as u64,
+            reason: reason.to_string(),
+        };
+        if self.shared.maintenance.lock().unwrap().replace(maintenance).is_none() {
+            warn!("{} entered maintenance: {}", self.addr, reason);
+        }
+    }
+
+    /// Ends the servicing window, data requests are served again
+    pub fn leave_maintenance(&self) {
+        if self.shared.maintenance.lock().unwrap().take().is_some() {
+            info!("{} left maintenance", self.addr);
+        }
+    }
+
+    /// Whether the server is in a servicing window
+    pub fn in_maintenance(&self) -> bool {
+        self.shared.maintenance.lock().unwrap().is_some()
+    }
+
+    /// Makes the server read-only for `reason`, as it does by itself when recording a command fails: commands
+    /// already applied are replayed from memory and other requests are served, but new commands are refused
+    /// with `READ_ONLY`. Health reports `Degraded` and a `MODE_CHANGED` event is recorded
+    pub fn enter_read_only(&self, reason: &str) {
+        self.shared.set_read_only(Some(reason.to_string()));
+    }
+
+    /// Runs commands again, after the storage was repaired
+    pub fn leave_read_only(&self) {
+        self.shared.set_read_only(None);
+    }
+
+    /// Why the server is read-only, `None` if it isn't
+    pub fn read_only_reason(&self) -> Option<String> {
+        self.shared.read_only.lock().unwrap().clone()
+    }
+
+    /// Hex-dumps every frame sent or received on the connection from `peer` into `path` for `duration`, so one
+    /// misbehaving device can be debugged without verbose logging. `peer` is the client address as in the
+    /// connection events, e.g. "192.168.1.20:50312"; the file is appended to, one line per frame
+    pub fn capture_connection(&self, peer: &str, path: impl AsRef<Path>, duration: Duration) -> io::Result<()> {
+        let until = self.shared.clock.now() + duration;
+        self.shared.captures.start(peer, path.as_ref(), until)
+    }
+
+    /// Stops capturing the connection from `peer` before its time runs out, returns whether it was captured
+    pub fn stop_capture(&self, peer: &str) -> bool {
+        self.shared.captures.stop(peer)
+    }
+
+    /// Peers whose connections are being captured
+    pub fn captured_connections(&self) -> Vec<String> {
+        self.shared.captures.peers()
+    }
+
+    /// Shuts down gracefully: starts draining, waits up to `grace` for the clients to leave, then stops the
+    /// server. Returns whether every client left in time, the rest are disconnected
+    pub fn shutdown(&self, retry_after: Duration, grace: Duration) -> bool {
+        self.start_draining(retry_after);
+        let drained = self.drain(grace);
+        if !drained {
+            warn!("{} clients still connected after {:?}, disconnecting them.", self.connection_count(), grace);
+        }
+        self.stop();
+        drained
+    }
+
+    /// Returns the statistics of the buffer pool shared by all connections
+    pub fn buffer_pool_stats(&self) -> PoolStats {
+        pool::stats()
+    }
+
+    /// Returns what was recovered from disk when the server was created
+    pub fn startup_report(&self) -> &StartupReport {
+        &self.startup
+    }
+
+    /// Returns the number of currently connected clients
+    pub fn connection_count(&self) -> usize {
+        self.shared.connections.load(Ordering::SeqCst)
+    }
+
+    /// Returns the protocol violations counted across all connections
+    pub fn violation_counts(&self) -> ViolationCounts {
+        self.shared.violations.snapshot()
+    }
+
+    /// Returns a snapshot of the server's health, the same data answered to a `HealthRequest`
+    pub fn health(&self) -> HealthReport {
+        self.shared.health()
+    }
+
+    /// Returns everything shown on the status page: health, requests per message type, violations and recent errors
+    pub fn status(&self) -> StatusReport {
+        self.shared.status()
+    }
+
+    /// Returns the recent significant events, newest first, the same a `RecentEventsRequest` returns
+    pub fn recent_events(&self) -> Vec<Event> {
+        self.shared.events.recent(0)
+    }
+
+    /// Returns the number and size distribution of the requests received, by message type
+    pub fn message_stats(&self) -> BTreeMap<&'static str, MessageStats> {
+        self.shared.messages.snapshot()
+    }
+
+    /// Returns the key gauges and counters, always the same names in the same order
+    pub fn metrics(&self) -> Vec<Metric> {
+        self.shared.metrics()
+    }
+
+    /// Passes `metrics()` to `exporter` now and then every `interval` on the scheduler thread;
+    /// cancelling the returned job drops the exporter
+    pub fn export_metrics(&self, interval: Duration, mut exporter: impl MetricsExporter + 'static) -> JobId {
+        exporter.export(&self.shared.metrics());
+        // A weak reference, the job is owned by the scheduler inside `Shared`
+        let shared = Arc::downgrade(&self.shared);
+        self.schedule(interval, move || {
+            if let Some(shared) = shared.upgrade() {
+                exporter.export(&shared.metrics());
+            }
+        })
+    }
+
+    /// Registers a fault injector consulted for every request, for testing how clients cope with a
+    /// misbehaving server. Injectors are asked in registration order, the first fault returned applies
+    #[cfg(feature = "chaos")]
+    pub fn add_fault_injector(&self, injector: impl FaultInjector + 'static) {
+        self.shared.faults.add(injector);
+    }
+
+    /// Removes every fault injector, requests are handled normally again
+    #[cfg(feature = "chaos")]
+    pub fn clear_fault_injectors(&self) {
+        self.shared.faults.clear();
+    }
+
+    /// Installs `filter`, called with the peer address of every accepted connection before it is served.
+    /// Returning false closes the connection at once. Replaces an earlier filter
+    pub fn set_accept_filter(&self, filter: impl Fn(SocketAddr) -> bool + Send + Sync + 'static) {
+        *self.shared.accept_filter.filter.lock().unwrap() = Some(Arc::new(filter));
+    }
+
+    /// Removes the accept filter, every connection is served again
+    pub fn clear_accept_filter(&self) {
+        *self.shared.accept_filter.filter.lock().unwrap() = None;
+    }
+
+    /// Adds message types `DynamicMessage` payloads may carry; the types of `proto/messages.proto` are
+    /// always known
+    #[cfg(feature = "reflect")]
+    pub fn add_dynamic_schema(&self, set: &FileDescriptorSet) {
+        self.shared.dynamic.add_schema(set);
+    }
+
+    /// Routes `DynamicMessage`s of the full message name `type_name` to `handler`, after validating their
+    /// payload against the schema. The type must be known, see `add_dynamic_schema`
+    #[cfg(feature = "reflect")]
+    pub fn route_dynamic(&self, type_name: &str, handler: impl DynamicHandler + 'static) {
+        self.shared.dynamic.route(type_name, handler, None);
+    }
+
+    /// Like `route_dynamic`, with a time limit of its own instead of `ServerConfig::handler_timeout`. A call
+    /// running longer is answered with `HANDLER_TIMEOUT` and left to finish on its own thread
+    #[cfg(feature = "reflect")]
+    pub fn route_dynamic_with_timeout(
+        &self,
+        type_name: &str,
+        handler: impl DynamicHandler + 'static,
+        timeout: Duration,
+    ) {
+        self.shared.dynamic.route(type_name, handler, Some(timeout));
+    }
+
+    /// Routes the message types of `plugin` to it and runs its middleware on every request from now on,
+    /// like the plugins of `ServerConfig::plugin_dir`. An invalid plugin schema is an error
+    #[cfg(feature = "plugins")]
+    pub fn add_plugin(&self, plugin: Plugin) -> io::Result<()> {
+        self.shared.add_plugin(plugin)
+    }
+
+    /// Registers `handler` under `name` for the `handler` rules of the routing file, which route dynamic
+    /// messages to it by name. Replaces an earlier handler of the same name
+    #[cfg(feature = "reflect")]
+    pub fn register_handler(&self, name: &str, handler: impl DynamicHandler + 'static) {
+        self.shared.dynamic.register(name, handler);
+    }
+
+    /// Reads `ServerConfig::routes_path` again and routes by the new rules from the next request on. An
+    /// invalid file is an error and the rules in use stay
+    #[cfg(feature = "routing")]
+    pub fn reload_routes(&self) -> io::Result<()> {
+        let Some(path) = &self.shared.config.routes_path else {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "No routing file configured"));
+        };
+        let routes = RoutingTable::load(path)?;
+        let rules = routes.len();
+        self.shared.set_routes(routes)?;
+        info!("Reloaded {} routing rules from {}", rules, path.display());
         Ok(())
     }
 
-    /// Stops the server by setting the `is_running` flag to `false` and removing it from the HashMap
+    /// Removes every dynamic route, dynamic messages are answered as unsupported again
+    #[cfg(feature = "reflect")]
+    pub fn clear_dynamic_routes(&self) {
+        self.shared.dynamic.clear();
+    }
+
+    /// Registers a check run on every `SelfTestRequest`, such as storage reachability or certificate expiry
+    pub fn register_self_test(&self, name: &str, check: impl Fn() -> CheckResult + Send + Sync + 'static) {
+        self.shared.self_tests.register(name, check);
+    }
+
+    /// Installs `callback`, called on the scheduler thread with the stalled components on every watchdog
+    /// check finding some, e.g. to abort the process so its supervisor restarts it. Replaces an earlier
+    /// callback; needs `ServerConfig::watchdog`
+    pub fn on_watchdog_stall(&self, callback: impl FnMut(&[Stall]) + Send + 'static) {
+        if self.shared.watchdog.is_none() {
+            warn!("No watchdog configured for {}, the stall callback is never called.", self.addr);
+        }
+        *self.shared.on_stall.callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Registers an application component with the server's watchdog, which reports it stalled unless it
+    /// pets the returned heartbeat within the timeout. `None` without `ServerConfig::watchdog`
+    pub fn watchdog_heartbeat(&self, name: &str) -> Option<Heartbeat> {
+        self.shared.heartbeat(|| name.to_string())
+    }
+
+    /// Runs `job` every `interval` on the server's scheduler thread while the server is running
+    pub fn schedule(&self, interval: Duration, job: impl FnMut() + Send + 'static) -> JobId {
+        self.shared.scheduler.schedule(interval, job)
+    }
+
+    /// Runs `job` every `interval` like `schedule`, but only while this server holds `lease`, so a job
+    /// scheduled on every node of a cluster runs on exactly one of them
+    #[cfg(feature = "storage")]
+    pub fn schedule_singleton(
+        &self,
+        interval: Duration,
+        lease: Lease,
+        job: impl FnMut() + Send + 'static,
+    ) -> JobId {
+        self.schedule(interval, lease.guard(job))
+    }
+
+    /// Cancels a job added with `schedule`, returns whether it was still scheduled
+    pub fn cancel_job(&self, id: JobId) -> bool {
+        self.shared.scheduler.cancel(id)
+    }
+
+    /// Runs all self-test checks in-process, the same result a `SelfTestRequest` returns
+    pub fn self_test(&self) -> SelfTestResponse {
+        self.shared.self_tests.run()
+    }
+
+    // Check the storage of the acknowledgement log at `path` on every self-test: that the log takes writes, and
+    // on Unix that its directory has space left. A weak reference, the checks are owned by `Shared`
+    #[cfg(feature = "storage")]
+    fn register_storage_checks(&self, path: &Path) {
+        let shared = Arc::downgrade(&self.shared);
+        self.register_self_test("storage", move || match shared.upgrade() {
+            Some(shared) => selftest::check_ack_log(&mut shared.acks.lock().unwrap()),
+            None => Err("Server was dropped".to_string()),
+        });
+        #[cfg(unix)]
+        {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+                _ => Path::new(".").to_path_buf(),
+            };
+            self.register_self_test("disk_space", move || selftest::check_free_space(&dir));
+        }
+        #[cfg(not(unix))]
+        let _ = path;
+    }
+
+    // Remove this server from the registry, unless the address now belongs to another one
+    fn unregister(&self, servers_lock: &mut HashMap<String, Arc<Server>>) {
+        if servers_lock
+            .get(&self.addr)
+            .is_some_and(|server| std::ptr::eq(Arc::as_ptr(server), self))
+        {
+            servers_lock.remove(&self.addr);
+        }
+    }
+
+    /// Stops the server by setting the `is_running` flag to `false` and removing it from the registry
     pub fn stop(&self) {
-        let mut count = self.client_count.lock().unwrap();
-        if *count == 1 {
-            if self.is_running.load(Ordering::SeqCst) {
-                self.is_running.store(false, Ordering::SeqCst);
+        // Hold the shard lock while checking the count so `new` can't hand out this server meanwhile
+        let mut servers_lock = SERVERS.shard(&self.addr);
+        let count = self.client_count.load(Ordering::SeqCst);
+        if count == 1 {
+            if self.shared.is_running.load(Ordering::SeqCst) {
+                self.shared.is_running.store(false, Ordering::SeqCst);
                 info!("Shutdown signal sent.");
 
-                // Remove the server instance from the HashMap
-                let mut servers_lock: std::sync::MutexGuard<'_, HashMap<String, Arc<Server>>> = SERVERS.lock().unwrap();
-                let addr = self.listener.local_addr().unwrap().to_string();
-                servers_lock.remove(&addr);
+                self.unregister(&mut servers_lock);
             } else {
                 warn!("Server was already stopped or not running.");
             }
         } else {
             // Decrement the client count
-            {
-                *count -= 1;
-                info!("Client disconnected. Current client count: {}", *count);
-            }
-            info!("Server still has {} active clients.", *count);
+            let count = self.client_count.fetch_sub(1, Ordering::SeqCst) - 1;
+            info!("Client disconnected. Current client count: {}", count);
+            info!("Server still has {} active clients.", count);
         }
     }
-}
\ No newline at end of file
+}
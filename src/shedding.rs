@@ -0,0 +1,56 @@
+// Import necessary modules and crates
+use crate::config::LoadShedding; // Thresholds and the largest share of requests rejected
+use prost::encoding::decode_varint; // Field key at the start of a frame
+use std::{
+    sync::atomic::{AtomicU64, Ordering}, // Average updated by every connection thread without a lock
+    time::Duration, // Time handling
+};
+
+// Field numbers of the control requests in `ClientMessage`: health, self-test, command status, stats,
+// probe acks, recent events, schema and resync
+const CONTROL_FIELDS: [u64; 8] = [3, 4, 5, 7, 8, 9, 10, 12];
+
+// Weight of the newest sample in the moving average, as a power of two: 1/8
+const AVERAGE_SHIFT: u32 = 3;
+
+/// Whether an encoded `ClientMessage` is a control request, read from its first field key without decoding it.
+/// Encoders put the request before the metadata, a frame starting with anything else counts as data traffic
+pub fn is_control_frame(frame: &[u8]) -> bool {
+    let mut key = frame;
+    decode_varint(&mut key).is_ok_and(|key| CONTROL_FIELDS.contains(&(key >> 3)))
+}
+
+/// Share of data requests to reject under `policy`, from 0 when below both thresholds up to
+/// `max_reject_percent` at twice a threshold
+pub fn reject_share(policy: &LoadShedding, latency: Duration, queue_depth: usize) -> f64 {
+    let latency_load = latency.as_secs_f64() / policy.latency_threshold.as_secs_f64().max(f64::MIN_POSITIVE);
+    let queue_load = queue_depth as f64 / policy.queue_threshold.max(1) as f64;
+    let excess = (latency_load.max(queue_load) - 1.0).clamp(0.0, 1.0);
+    excess * f64::from(policy.max_reject_percent.min(100)) / 100.0
+}
+
+/// Exponential moving average of handler latency, shared by every connection of a server
+#[derive(Debug, Default)]
+pub struct LatencyAverage {
+    micros: AtomicU64, // Current average
+}
+
+impl LatencyAverage {
+    /// Adds the latency of one handled request
+    pub fn record(&self, elapsed: Duration) {
+        let sample = elapsed.as_micros().min(u128::from(u64::MAX)) as u64;
+        let _ = self.micros.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+            // Move an eighth of the way towards the sample
+            Some(if sample >= average {
+                average + ((sample - average) >> AVERAGE_SHIFT)
+            } else {
+                average - ((average - sample) >> AVERAGE_SHIFT)
+            })
+        });
+    }
+
+    /// Current average
+    pub fn get(&self) -> Duration {
+        Duration::from_micros(self.micros.load(Ordering::Relaxed))
+    }
+}
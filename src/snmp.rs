@@ -0,0 +1,332 @@
+// Import necessary modules and crates
+use crate::metrics::{Metric, MetricKind, MetricsExporter}; // Values served to SNMP managers
+use crate::socket; // Timeout classification shared with the TCP server
+use log::{debug, info, warn}; // Logging macros
+use std::{
+    io::{self, ErrorKind}, // I/O operations
+    net::{SocketAddr, UdpSocket}, // Networking
+    sync::{
+        atomic::{AtomicBool, Ordering}, // Stops the agent thread when the exporter is dropped
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration, // Time handling
+};
+
+// Longest the agent blocks in a receive, so it notices being dropped
+const RECEIVE_TICK: Duration = Duration::from_millis(100);
+
+// Largest request datagram handled, managers send far smaller ones
+const MAX_DATAGRAM: usize = 1500;
+
+// Most variable bindings answered in one request
+const MAX_VARBINDS: usize = 64;
+
+// SNMP version field of an SNMPv2c message
+const VERSION_2C: i64 = 1;
+
+// BER tags used by SNMPv2c
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+const PDU_GET: u8 = 0xa0;
+const PDU_GET_NEXT: u8 = 0xa1;
+const PDU_RESPONSE: u8 = 0xa2;
+
+/// Minimal SNMPv2c agent answering GET and GETNEXT for the exported metrics.
+/// Metric number `n` (counting from 1 in `Server::metrics` order) is the scalar `<base_oid>.n.0`;
+/// gauges are served as Gauge32, counters as Counter64. The agent answers until it is dropped
+#[derive(Debug)]
+pub struct SnmpExporter {
+    metrics: Arc<Mutex<Vec<Metric>>>, // Latest exported values, read by the agent thread
+    running: Arc<AtomicBool>, // Cleared on drop to stop the agent thread
+    local_addr: SocketAddr, // Address the agent is bound to
+}
+
+impl SnmpExporter {
+    /// Binds the agent to the UDP address `addr`, answering requests that carry `community`
+    pub fn bind(addr: &str, community: &str, base_oid: &[u32]) -> io::Result<Self> {
+        if base_oid.len() < 2 || base_oid[0] > 2 || (base_oid[0] < 2 && base_oid[1] >= 40) {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "Base OID needs two valid leading arcs"));
+        }
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_read_timeout(Some(RECEIVE_TICK))?;
+        let local_addr = socket.local_addr()?;
+        info!("SNMP agent listening on {}", local_addr);
+
+        let agent = Agent {
+            socket,
+            community: community.as_bytes().to_vec(),
+            base_oid: base_oid.to_vec(),
+            metrics: Arc::new(Mutex::new(Vec::new())),
+        };
+        let metrics = Arc::clone(&agent.metrics);
+        let running = Arc::new(AtomicBool::new(true));
+        {
+            let running = Arc::clone(&running);
+            thread::spawn(move || agent.run(&running));
+        }
+        Ok(SnmpExporter {
+            metrics,
+            running,
+            local_addr,
+        })
+    }
+
+    /// Returns the address the agent is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl MetricsExporter for SnmpExporter {
+    fn export(&mut self, metrics: &[Metric]) {
+        *self.metrics.lock().unwrap() = metrics.to_vec();
+    }
+}
+
+impl Drop for SnmpExporter {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+// State of the agent thread
+struct Agent {
+    socket: UdpSocket,
+    community: Vec<u8>,
+    base_oid: Vec<u32>,
+    metrics: Arc<Mutex<Vec<Metric>>>,
+}
+
+impl Agent {
+    // Answer requests until `running` turns false
+    fn run(&self, running: &AtomicBool) {
+        let mut request = [0u8; MAX_DATAGRAM];
+        while running.load(Ordering::SeqCst) {
+            let (len, peer) = match self.socket.recv_from(&mut request) {
+                Ok(received) => received,
+                Err(ref e) if socket::is_timeout(e) => continue,
+                // Windows reports an ICMP port unreachable for an earlier answer on the next receive
+                Err(ref e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => {
+                    warn!("Error receiving SNMP request: {}", e);
+                    continue;
+                }
+            };
+            // Malformed requests and wrong communities get no answer, like any SNMP agent
+            match self.respond(&request[..len]) {
+                Some(response) => {
+                    if let Err(e) = self.socket.send_to(&response, peer) {
+                        warn!("Failed to answer SNMP request from {}: {}", peer, e);
+                    }
+                }
+                None => debug!("Ignoring SNMP request from {}", peer),
+            }
+        }
+        info!("SNMP agent stopped.");
+    }
+
+    // Build the response to one request datagram
+    fn respond(&self, datagram: &[u8]) -> Option<Vec<u8>> {
+        let mut message = Reader::new(Reader::new(datagram).expect(TAG_SEQUENCE)?);
+        if message.integer()? != VERSION_2C || message.expect(TAG_OCTET_STRING)? != self.community.as_slice() {
+            return None;
+        }
+        let (pdu_type, pdu) = message.next()?;
+        if pdu_type != PDU_GET && pdu_type != PDU_GET_NEXT {
+            return None;
+        }
+        let mut pdu = Reader::new(pdu);
+        let request_id = pdu.integer()?;
+        pdu.integer()?; // Error status
+        pdu.integer()?; // Error index
+        let mut varbinds = Reader::new(pdu.expect(TAG_SEQUENCE)?);
+
+        let metrics = self.metrics.lock().unwrap();
+        let oids: Vec<Vec<u32>> = (1..=metrics.len() as u32)
+            .map(|number| [self.base_oid.as_slice(), &[number, 0]].concat())
+            .collect();
+        let mut bindings = Vec::new();
+        let mut count = 0;
+        while !varbinds.is_empty() {
+            count += 1;
+            if count > MAX_VARBINDS {
+                return None;
+            }
+            let oid = decode_oid(Reader::new(varbinds.expect(TAG_SEQUENCE)?).expect(TAG_OID)?)?;
+            let found = if pdu_type == PDU_GET {
+                oids.iter().position(|candidate| *candidate == oid)
+            } else {
+                oids.iter().position(|candidate| *candidate > oid)
+            };
+            let (oid, value) = match found {
+                Some(index) => (oids[index].clone(), encode_value(&metrics[index])),
+                None if pdu_type == PDU_GET => (oid, encode(TAG_NO_SUCH_OBJECT, &[])),
+                None => (oid, encode(TAG_END_OF_MIB_VIEW, &[])),
+            };
+            bindings.extend(encode(TAG_SEQUENCE, &[encode_oid(&oid), value].concat()));
+        }
+
+        let pdu = [
+            encode_integer(request_id),
+            encode_integer(0),
+            encode_integer(0),
+            encode(TAG_SEQUENCE, &bindings),
+        ]
+        .concat();
+        let message = [
+            encode_integer(VERSION_2C),
+            encode(TAG_OCTET_STRING, &self.community),
+            encode(PDU_RESPONSE, &pdu),
+        ]
+        .concat();
+        Some(encode(TAG_SEQUENCE, &message))
+    }
+}
+
+// Walks the BER elements of one constructed value
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    // Next element as its tag and content
+    fn next(&mut self) -> Option<(u8, &'a [u8])> {
+        let (&tag, rest) = self.data.split_first()?;
+        let (&first, mut rest) = rest.split_first()?;
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            // Long form, the low bits give the number of length bytes
+            let count = (first & 0x7f) as usize;
+            if count == 0 || count > 4 || rest.len() < count {
+                return None;
+            }
+            let len = rest[..count].iter().fold(0usize, |len, &byte| (len << 8) | byte as usize);
+            rest = &rest[count..];
+            len
+        };
+        if rest.len() < len {
+            return None;
+        }
+        self.data = &rest[len..];
+        Some((tag, &rest[..len]))
+    }
+
+    // Content of the next element, which must have `tag`
+    fn expect(&mut self, tag: u8) -> Option<&'a [u8]> {
+        self.next().filter(|(found, _)| *found == tag).map(|(_, content)| content)
+    }
+
+    // Value of the next element, which must be an INTEGER fitting an i64
+    fn integer(&mut self) -> Option<i64> {
+        let content = self.expect(TAG_INTEGER)?;
+        if content.is_empty() || content.len() > 8 {
+            return None;
+        }
+        // Sign-extend from the first byte
+        let initial = if content[0] & 0x80 != 0 { -1 } else { 0 };
+        Some(content.iter().fold(initial, |value, &byte| (value << 8) | byte as i64))
+    }
+}
+
+// Encode a BER element
+fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    if content.len() < 0x80 {
+        encoded.push(content.len() as u8);
+    } else {
+        let len = (content.len() as u32).to_be_bytes();
+        let skip = len.iter().take_while(|&&byte| byte == 0).count();
+        encoded.push(0x80 | (len.len() - skip) as u8);
+        encoded.extend_from_slice(&len[skip..]);
+    }
+    encoded.extend_from_slice(content);
+    encoded
+}
+
+// Encode a signed INTEGER in the fewest bytes
+fn encode_integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    // Drop leading bytes that only repeat the sign of the next one
+    while start < bytes.len() - 1
+        && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0) || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    encode(TAG_INTEGER, &bytes[start..])
+}
+
+// Encode an unsigned application value such as Gauge32 or Counter64
+fn encode_unsigned(tag: u8, value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&byte| byte == 0).count().min(bytes.len() - 1);
+    let mut content = Vec::with_capacity(9);
+    // A leading 1 bit would read as negative
+    if bytes[skip] & 0x80 != 0 {
+        content.push(0);
+    }
+    content.extend_from_slice(&bytes[skip..]);
+    encode(tag, &content)
+}
+
+// Encode a metric value by its kind
+fn encode_value(metric: &Metric) -> Vec<u8> {
+    match metric.kind {
+        MetricKind::Gauge => encode_unsigned(TAG_GAUGE32, metric.value.min(u32::MAX as u64)),
+        MetricKind::Counter => encode_unsigned(TAG_COUNTER64, metric.value),
+    }
+}
+
+// Encode an OBJECT IDENTIFIER, the first two arcs share one byte
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut content = Vec::new();
+    let arcs = std::iter::once(oid[0] * 40 + oid[1]).chain(oid[2..].iter().copied());
+    for arc in arcs {
+        // Base 128, high bit set on every byte but the last
+        let mut groups = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            groups.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        content.extend(groups.iter().rev());
+    }
+    encode(TAG_OID, &content)
+}
+
+// Decode the content of an OBJECT IDENTIFIER
+fn decode_oid(content: &[u8]) -> Option<Vec<u32>> {
+    let mut arcs = Vec::new();
+    let mut arc: u32 = 0;
+    for (index, &byte) in content.iter().enumerate() {
+        arc = arc.checked_mul(128)? | (byte & 0x7f) as u32;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.extend([first, arc - first * 40]);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        } else if index == content.len() - 1 {
+            return None;
+        }
+    }
+    (!arcs.is_empty()).then_some(arcs)
+}
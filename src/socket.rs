@@ -0,0 +1,218 @@
+// Import necessary modules and crates
+use crate::config::{ServerConfig, TcpKeepalive}; // Socket settings from the server config
+#[cfg(not(all(feature = "reuseport", unix)))]
+use log::warn; // Logging macros
+use std::{
+    io,
+    net::{TcpListener, TcpStream}, // Networking
+};
+#[cfg(all(feature = "reuseport", unix))]
+use std::net::{SocketAddr, ToSocketAddrs}; // Address resolution for raw sockets
+
+// Bind the listeners for a server, one per acceptor, honouring the configured backlog
+pub(crate) fn bind_listeners(addr: &str, config: &ServerConfig) -> io::Result<Vec<TcpListener>> {
+    let acceptors = config.acceptors.max(1);
+    if config.listen_backlog.is_none() && acceptors == 1 {
+        return Ok(vec![TcpListener::bind(addr)?]);
+    }
+
+    #[cfg(all(feature = "reuseport", unix))]
+    {
+        let backlog = config.listen_backlog.unwrap_or(DEFAULT_BACKLOG);
+        let mut last_error = None;
+        // Try every resolved address like `TcpListener::bind` does
+        for socket_addr in addr.to_socket_addrs()? {
+            match bind_listener(socket_addr, backlog, acceptors > 1) {
+                Ok(first) => {
+                    // Bind the remaining acceptors to the final address, resolving port 0 only once
+                    let local_addr = first.local_addr()?;
+                    let mut listeners = vec![first];
+                    for _ in 1..acceptors {
+                        listeners.push(bind_listener(local_addr, backlog, true)?);
+                    }
+                    return Ok(listeners);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Could not resolve to any address")
+        }))
+    }
+
+    #[cfg(not(all(feature = "reuseport", unix)))]
+    {
+        warn!(
+            "Listen backlog and multiple acceptors need the `reuseport` feature on Unix, using a single default listener for {}.",
+            addr
+        );
+        Ok(vec![TcpListener::bind(addr)?])
+    }
+}
+
+// Backlog used when only the acceptor count is configured, the same value std uses
+#[cfg(all(feature = "reuseport", unix))]
+const DEFAULT_BACKLOG: u32 = 128;
+
+/// Binds a TCP listener with an explicit backlog, with `reuse_port` several sockets can share the address
+#[cfg(all(feature = "reuseport", unix))]
+pub fn bind_listener(addr: SocketAddr, backlog: u32, reuse_port: bool) -> io::Result<TcpListener> {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    let domain = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+
+    // SAFETY: socket returns a fresh descriptor owned by nobody else, wrapping it closes it on every error path
+    let fd = unsafe { libc::socket(domain, libc::SOCK_STREAM, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    let fd = socket.as_raw_fd();
+
+    // Match std: close on exec and allow quick rebinding after a restart
+    check(unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) })?;
+    set_flag(fd, libc::SO_REUSEADDR)?;
+    if reuse_port {
+        set_flag(fd, libc::SO_REUSEPORT)?;
+    }
+
+    let (storage, len) = raw_address(&addr);
+    // SAFETY: storage holds a sockaddr of the right family and len is its exact size
+    check(unsafe { libc::bind(fd, &storage as *const _ as *const libc::sockaddr, len) })?;
+    let backlog = backlog.min(libc::c_int::MAX as u32) as libc::c_int;
+    check(unsafe { libc::listen(fd, backlog) })?;
+
+    Ok(TcpListener::from(socket))
+}
+
+/// Enables kernel keepalive on a connection with the given timing
+#[cfg(all(feature = "keepalive", target_os = "linux"))]
+pub fn set_keepalive(stream: &TcpStream, keepalive: &TcpKeepalive) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let fd = stream.as_raw_fd();
+    let seconds = |duration: std::time::Duration| duration.as_secs().clamp(1, libc::c_int::MAX as u64) as libc::c_int;
+    set_int(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+    set_int(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, seconds(keepalive.idle))?;
+    set_int(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, seconds(keepalive.interval))?;
+    set_int(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, keepalive.retries.clamp(1, i32::MAX as u32) as libc::c_int)
+}
+
+/// Enables kernel keepalive on a connection with the given timing
+#[cfg(all(feature = "keepalive", windows))]
+pub fn set_keepalive(stream: &TcpStream, keepalive: &TcpKeepalive) -> io::Result<()> {
+    use std::os::windows::io::AsRawSocket;
+    use windows_sys::Win32::Networking::WinSock::{
+        setsockopt, IPPROTO_TCP, SOCKET, SOCKET_ERROR, SOL_SOCKET, SO_KEEPALIVE, TCP_KEEPALIVE, TCP_KEEPCNT,
+        TCP_KEEPINTVL,
+    };
+
+    let socket = stream.as_raw_socket() as SOCKET;
+    let seconds = |duration: std::time::Duration| duration.as_secs().clamp(1, i32::MAX as u64) as i32;
+    // Per-option timing needs Windows 10 1709, the same options Linux has under other names
+    let set = |level: i32, option: i32, value: i32| {
+        // SAFETY: the option value points to a live i32 of the size passed
+        let result = unsafe {
+            setsockopt(socket, level, option, &value as *const i32 as *const u8, std::mem::size_of::<i32>() as i32)
+        };
+        if result == SOCKET_ERROR {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    };
+    set(SOL_SOCKET, SO_KEEPALIVE, 1)?;
+    set(IPPROTO_TCP, TCP_KEEPALIVE, seconds(keepalive.idle))?;
+    set(IPPROTO_TCP, TCP_KEEPINTVL, seconds(keepalive.interval))?;
+    set(IPPROTO_TCP, TCP_KEEPCNT, keepalive.retries.clamp(1, i32::MAX as u32) as i32)
+}
+
+/// Enables kernel keepalive on a connection with the given timing
+#[cfg(not(all(feature = "keepalive", any(target_os = "linux", windows))))]
+pub fn set_keepalive(_stream: &TcpStream, _keepalive: &TcpKeepalive) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TCP keepalive needs the `keepalive` feature on Linux or Windows",
+    ))
+}
+
+/// Whether a socket error only means that nothing arrived in time. A read timeout is `WouldBlock` on
+/// Unix but `TimedOut` on Windows, a non-blocking socket reports `WouldBlock` on both
+pub fn is_timeout(error: &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+}
+
+/// Whether a socket error means the peer went away. Windows resets a connection closed with unread
+/// data where Unix mostly reports a clean end of stream, both are a routine disconnect
+pub fn is_disconnect(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
+// Turn a -1 syscall result into the current OS error
+#[cfg(any(all(feature = "reuseport", unix), all(feature = "keepalive", target_os = "linux")))]
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+// Enable a boolean SOL_SOCKET option
+#[cfg(all(feature = "reuseport", unix))]
+fn set_flag(fd: libc::c_int, option: libc::c_int) -> io::Result<()> {
+    set_int(fd, libc::SOL_SOCKET, option, 1)
+}
+
+// Set an integer socket option
+#[cfg(any(all(feature = "reuseport", unix), all(feature = "keepalive", target_os = "linux")))]
+fn set_int(fd: libc::c_int, level: libc::c_int, option: libc::c_int, value: libc::c_int) -> io::Result<()> {
+    // SAFETY: the option value points to a live c_int of the size passed
+    check(unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            option,
+            &value as *const _ as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    })
+}
+
+// Convert a SocketAddr into the C representation expected by bind
+#[cfg(all(feature = "reuseport", unix))]
+fn raw_address(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // SAFETY: sockaddr_storage and the sockaddr structs are plain data, all-zero is a valid value;
+    // the storage is large and aligned enough for either address family
+    unsafe {
+        let mut storage: libc::sockaddr_storage = std::mem::zeroed();
+        let len = match addr {
+            SocketAddr::V4(v4) => {
+                let raw = &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in);
+                raw.sin_family = libc::AF_INET as libc::sa_family_t;
+                raw.sin_port = v4.port().to_be();
+                raw.sin_addr.s_addr = u32::from_ne_bytes(v4.ip().octets());
+                std::mem::size_of::<libc::sockaddr_in>()
+            }
+            SocketAddr::V6(v6) => {
+                let raw = &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6);
+                raw.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                raw.sin6_port = v6.port().to_be();
+                raw.sin6_addr.s6_addr = v6.ip().octets();
+                raw.sin6_flowinfo = v6.flowinfo();
+                raw.sin6_scope_id = v6.scope_id();
+                std::mem::size_of::<libc::sockaddr_in6>()
+            }
+        };
+        (storage, len as libc::socklen_t)
+    }
+}
@@ -0,0 +1,31 @@
+// Import necessary modules and crates
+use std::{
+    fmt,
+    path::PathBuf, // Location of the recovered state
+    time::Duration, // Time handling
+};
+
+/// What a server recovered from disk before it bound its listeners
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StartupReport {
+    pub ack_log: Option<PathBuf>, // Acknowledgement log replayed, `None` when acknowledgements are kept in memory
+    pub commands_replayed: usize, // Completed commands loaded from the log
+    pub entries_discarded: usize, // Torn log entries dropped after a crash mid-write
+    pub duration: Duration, // Time the recovery took
+}
+
+impl fmt::Display for StartupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.ack_log {
+            Some(path) => write!(
+                f,
+                "replayed {} commands from {}, discarded {} torn entries",
+                self.commands_replayed,
+                path.display(),
+                self.entries_discarded
+            )?,
+            None => write!(f, "no persistent state")?,
+        }
+        write!(f, " in {:?}", self.duration)
+    }
+}
@@ -0,0 +1,110 @@
+// Import necessary modules and crates
+use crate::health::{escape_json, HealthReport, HealthStatus}; // Health part of the page
+use crate::violations::ViolationCounts; // Protocol violation totals
+use std::{
+    collections::BTreeMap, // Counters sorted by message type
+    fmt::Write, // Building the page
+    time::Duration, // Time handling
+};
+
+/// Everything shown on the status page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusReport {
+    pub health: HealthReport, // Status, connections, queue depth and uptime
+    pub messages: BTreeMap<&'static str, u64>, // Requests received per message type
+    pub violations: ViolationCounts, // Protocol violations across all connections
+    pub recent_errors: Vec<(Duration, String)>, // Latest errors first, with how long ago each happened
+}
+
+impl StatusReport {
+    /// Renders the report as a JSON object, as served on `/status.json`
+    pub fn to_json(&self) -> String {
+        let messages: Vec<String> = self
+            .messages
+            .iter()
+            .map(|(kind, count)| format!("\"{}\":{}", kind, count))
+            .collect();
+        let errors: Vec<String> = self
+            .recent_errors
+            .iter()
+            .map(|(age, message)| format!("{{\"age_secs\":{},\"message\":\"{}\"}}", age.as_secs(), escape_json(message)))
+            .collect();
+        let violations = &self.violations;
+        format!(
+            "{{\"health\":{},\"messages\":{{{}}},\"violations\":{{\"decode_failures\":{},\"unknown_messages\":{},\"oversized_frames\":{},\"disconnects\":{}}},\"recent_errors\":[{}]}}",
+            self.health.to_json(),
+            messages.join(","),
+            violations.decode_failures,
+            violations.unknown_messages,
+            violations.oversized_frames,
+            violations.disconnects,
+            errors.join(",")
+        )
+    }
+
+    /// Renders the report as a self-refreshing HTML page, as served on `/`
+    pub fn to_html(&self) -> String {
+        let health = &self.health;
+        let status = match health.status {
+            HealthStatus::Serving => "serving",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Draining => "draining",
+            HealthStatus::Maintenance => "maintenance",
+            HealthStatus::Unspecified => "unspecified",
+        };
+        let mut page = String::from(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><meta http-equiv=\"refresh\" content=\"5\">\
+             <title>Server status</title></head><body>\n<h1>Server status</h1>\n<table>\n",
+        );
+        // Writing to a String cannot fail
+        let _ = writeln!(page, "<tr><th>Status</th><td>{}</td></tr>", status);
+        let _ = writeln!(page, "<tr><th>Uptime</th><td>{} s</td></tr>", health.uptime.as_secs());
+        let _ = writeln!(page, "<tr><th>Connections</th><td>{}</td></tr>", health.connections);
+        let _ = writeln!(page, "<tr><th>Queue depth</th><td>{}</td></tr>", health.queue_depth);
+        page.push_str("</table>\n<h2>Messages</h2>\n<table>\n");
+        for (kind, count) in &self.messages {
+            let _ = writeln!(page, "<tr><th>{}</th><td>{}</td></tr>", kind, count);
+        }
+        let violations = &self.violations;
+        page.push_str("</table>\n<h2>Protocol violations</h2>\n<table>\n");
+        let _ = writeln!(page, "<tr><th>Decode failures</th><td>{}</td></tr>", violations.decode_failures);
+        let _ = writeln!(page, "<tr><th>Unknown messages</th><td>{}</td></tr>", violations.unknown_messages);
+        let _ = writeln!(page, "<tr><th>Oversized frames</th><td>{}</td></tr>", violations.oversized_frames);
+        let _ = writeln!(page, "<tr><th>Connections closed</th><td>{}</td></tr>", violations.disconnects);
+        page.push_str("</table>\n<h2>Recent errors</h2>\n<ul>\n");
+        for (age, message) in &self.recent_errors {
+            let _ = writeln!(page, "<li>{} s ago: {}</li>", age.as_secs(), escape_html(message));
+        }
+        page.push_str("</ul>\n</body></html>\n");
+        page
+    }
+}
+
+// Escape text for use in HTML element content
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Serve the status page on `listener` until `is_running` turns false
+#[cfg(feature = "status-page")]
+pub(crate) fn serve_http(
+    listener: std::net::TcpListener,
+    is_running: &std::sync::atomic::AtomicBool,
+    report: impl Fn() -> StatusReport,
+) -> std::io::Result<()> {
+    crate::http::serve("Status", listener, is_running, |method, path| match (method, path) {
+        ("GET", "/") => ("200 OK", "text/html; charset=utf-8", report().to_html()),
+        ("GET", "/status.json") => ("200 OK", "application/json", report().to_json()),
+        _ => crate::http::not_found(),
+    })
+}
@@ -0,0 +1,19 @@
+// Import necessary modules and crates
+use crate::message::*; // Request and response types referenced by the generated methods
+use std::io::{self, ErrorKind}; // I/O errors
+
+// `ClientStubs`, regenerated by build.rs whenever proto/messages.proto changes
+include!(concat!(env!("OUT_DIR"), "/client_stubs.rs"));
+
+// Turn a reply of the wrong kind into an error, keeping the server's reason for an ErrorResponse
+fn unexpected_response(message: Option<server_message::Message>) -> io::Error {
+    match message {
+        Some(server_message::Message::ErrorResponse(error)) => io::Error::other(format!(
+            "Server rejected the request ({:?}): {}",
+            error.code(),
+            error.message
+        )),
+        Some(other) => io::Error::new(ErrorKind::InvalidData, format!("Unexpected response: {}", other)),
+        None => io::Error::new(ErrorKind::InvalidData, "Empty response"),
+    }
+}
@@ -0,0 +1,134 @@
+// Import necessary modules and crates
+use crate::health::escape_json; // JSON string escaping
+use crate::message::{
+    client_message, server_message, AddRequest, AddResponse, ClientMessage, EchoMessage, ErrorCode, ErrorResponse,
+    Metadata, ServerMessage,
+}; // Protobuf message types
+use crate::protocol::MAX_MESSAGE_SIZE; // Frame limit
+use prost::Message; // Protobuf message encoding
+
+/// Which side sends a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// Canonical frame bytes for a known message, for checking other client implementations byte for byte
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestVector {
+    pub name: &'static str, // Stable identifier
+    pub description: &'static str, // What the frame contains
+    pub direction: Direction, // Who sends the frame
+    pub valid: bool, // False for frames the receiver must reject
+    pub frame: Vec<u8>, // Length prefix and payload exactly as on the wire
+}
+
+/// Returns all test vectors, frames are encoded by the same code the server uses
+pub fn test_vectors() -> Vec<TestVector> {
+    let request = |message| ClientMessage {
+        message: Some(message),
+        metadata: None,
+    };
+    let response = |message| ServerMessage {
+        message: Some(message),
+        metadata: None,
+    };
+
+    vec![
+        TestVector {
+            name: "add_request",
+            description: "AddRequest { a: 10, b: 20 } without metadata",
+            direction: Direction::ClientToServer,
+            valid: true,
+            frame: request(client_message::Message::AddRequest(AddRequest { a: 10, b: 20 })).encode_length_delimited_to_vec(),
+        },
+        TestVector {
+            name: "add_request_negative",
+            description: "AddRequest { a: -1, b: 1 }, negative int32 values take ten varint bytes",
+            direction: Direction::ClientToServer,
+            valid: true,
+            frame: request(client_message::Message::AddRequest(AddRequest { a: -1, b: 1 })).encode_length_delimited_to_vec(),
+        },
+        TestVector {
+            name: "add_response",
+            description: "AddResponse { result: 30 } without metadata",
+            direction: Direction::ServerToClient,
+            valid: true,
+            frame: response(server_message::Message::AddResponse(AddResponse { result: 30 })).encode_length_delimited_to_vec(),
+        },
+        TestVector {
+            name: "echo_message",
+            description: "EchoMessage { content: \"Hello, World!\" } without metadata",
+            direction: Direction::ClientToServer,
+            valid: true,
+            frame: request(client_message::Message::EchoMessage(EchoMessage {
+                content: "Hello, World!".to_string(),
+            }))
+            .encode_length_delimited_to_vec(),
+        },
+        TestVector {
+            name: "echo_message_with_metadata",
+            description: "EchoMessage { content: \"ping\" } with trace id \"trace-1\" and sequence 1",
+            direction: Direction::ClientToServer,
+            valid: true,
+            frame: ClientMessage {
+                message: Some(client_message::Message::EchoMessage(EchoMessage {
+                    content: "ping".to_string(),
+                })),
+                metadata: Some(Metadata {
+                    trace_id: "trace-1".to_string(),
+                    sequence: 1,
+                    ..Metadata::default()
+                }),
+            }
+            .encode_length_delimited_to_vec(),
+        },
+        TestVector {
+            name: "error_expired",
+            description: "ErrorResponse { code: EXPIRED, message: \"Request expired\" }",
+            direction: Direction::ServerToClient,
+            valid: true,
+            frame: response(server_message::Message::ErrorResponse(ErrorResponse {
+                code: ErrorCode::Expired as i32,
+                message: "Request expired".to_string(),
+            }))
+            .encode_length_delimited_to_vec(),
+        },
+        TestVector {
+            name: "oversized_frame",
+            description: "Length prefix announcing one byte more than the frame limit, the server closes the connection",
+            direction: Direction::ClientToServer,
+            valid: false,
+            frame: {
+                let mut frame = Vec::new();
+                prost::encode_length_delimiter(MAX_MESSAGE_SIZE + 1, &mut frame)
+                    .expect("Vec grows to fit the prefix");
+                frame
+            },
+        },
+    ]
+}
+
+/// Renders all test vectors as a JSON array with hex encoded frames, the format of the exported fixture
+pub fn test_vectors_json() -> String {
+    let entries: Vec<String> = test_vectors()
+        .iter()
+        .map(|vector| {
+            let direction = match vector.direction {
+                Direction::ClientToServer => "client_to_server",
+                Direction::ServerToClient => "server_to_client",
+            };
+            let hex: String = vector.frame.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!(
+                "  {{\"name\":\"{}\",\"description\":\"{}\",\"direction\":\"{}\",\"valid\":{},\"frame_hex\":\"{}\"}}",
+                vector.name,
+                escape_json(vector.description),
+                direction,
+                vector.valid,
+                hex
+            )
+        })
+        .collect();
+    format!("[\n{}\n]\n", entries.join(",\n"))
+}
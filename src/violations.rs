@@ -0,0 +1,95 @@
+// Import necessary modules and crates
+use crate::config::ViolationPolicy; // Allowed violations per window
+use std::{
+    collections::VecDeque, // Recent violation times
+    fmt,
+    sync::atomic::{AtomicU64, Ordering}, // Server-wide counters
+    time::Instant, // Time handling
+};
+
+/// Kind of protocol violation committed by a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    DecodeFailure, // Frame is not a valid ClientMessage
+    UnknownMessage, // Message type unknown to this server, or no content
+    OversizedFrame, // Frame larger than the size limit
+}
+
+impl Violation {
+    // Number of violations of this kind a connection may commit within the policy window
+    fn allowed(self, policy: &ViolationPolicy) -> u32 {
+        match self {
+            Violation::DecodeFailure => policy.max_decode_failures,
+            Violation::UnknownMessage => policy.max_unknown_messages,
+            Violation::OversizedFrame => policy.max_oversized_frames,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Violation::DecodeFailure => "decode failure",
+            Violation::UnknownMessage => "unknown message",
+            Violation::OversizedFrame => "oversized frame",
+        })
+    }
+}
+
+/// Violations seen by a server across all connections
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ViolationCounts {
+    pub decode_failures: u64,
+    pub unknown_messages: u64,
+    pub oversized_frames: u64,
+    pub disconnects: u64, // Connections closed for exceeding the policy
+}
+
+// Server-wide violation counters
+#[derive(Debug, Default)]
+pub(crate) struct ViolationCounters {
+    by_kind: [AtomicU64; 3], // Indexed by `Violation::index`
+    disconnects: AtomicU64,
+}
+
+impl ViolationCounters {
+    pub(crate) fn record(&self, violation: Violation) {
+        self.by_kind[violation.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_disconnect(&self) {
+        self.disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> ViolationCounts {
+        let count = |violation: Violation| self.by_kind[violation.index()].load(Ordering::Relaxed);
+        ViolationCounts {
+            decode_failures: count(Violation::DecodeFailure),
+            unknown_messages: count(Violation::UnknownMessage),
+            oversized_frames: count(Violation::OversizedFrame),
+            disconnects: self.disconnects.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// Recent violations of one connection
+#[derive(Debug, Default)]
+pub(crate) struct ViolationTracker {
+    recent: [VecDeque<Instant>; 3], // Times of violations within the window, indexed by `Violation::index`
+}
+
+impl ViolationTracker {
+    // Record a violation committed at `now`, returns true once the connection exceeded what the policy allows
+    pub(crate) fn record(&mut self, violation: Violation, policy: &ViolationPolicy, now: Instant) -> bool {
+        let recent = &mut self.recent[violation.index()];
+        while recent.front().is_some_and(|&at| now.duration_since(at) > policy.window) {
+            recent.pop_front();
+        }
+        recent.push_back(now);
+        recent.len() > violation.allowed(policy) as usize
+    }
+}
@@ -0,0 +1,134 @@
+// Import necessary modules and crates
+use crate::config::WasmHandlerConfig; // Module files, routed types and limits
+use crate::message::{DynamicMessage, ErrorCode, ErrorResponse}; // Requests and answers of the handlers
+use crate::reflect::DynamicHandler; // Modules answer dynamic messages
+use prost::Message; // Protobuf message encoding/decoding
+use std::{
+    fmt,
+    io::{self, ErrorKind}, // Module loading errors
+};
+use wasmtime::{Config, Engine, InstancePre, Linker, Module, ResourceLimiter, Store, Trap};
+
+/// A WebAssembly module answering dynamic messages in a sandbox, for logic the gateway operator doesn't trust.
+/// Nothing is imported into the module, so it can't reach files, the network or the clock, and every call
+/// runs in a fresh instance limited in instructions and memory, so calls can't see each other's data.
+///
+/// The module exports its `memory`, `alloc(len: i32) -> i32` returning room for `len` bytes, and
+/// `handle(type_ptr: i32, type_len: i32, payload_ptr: i32, payload_len: i32) -> i64`. `handle` gets the type
+/// name and payload of the request and returns the address of its answer shifted left by 32 bits, or'ed with
+/// its length. The answer is an encoded `DynamicMessage`; a handler that fails traps, e.g. with `unreachable`
+#[derive(Clone)]
+pub struct WasmHandler {
+    name: String, // Module file, for errors
+    engine: Engine, // Compiled the module, meters fuel
+    module: InstancePre<Sandbox>, // Module checked against the (empty) imports, instantiated per call
+    fuel: u64, // Fuel of one call, about one unit per instruction
+    max_memory: usize, // Linear memory bytes of one call
+}
+
+// Store state of one call, its memory limit
+struct Sandbox {
+    max_memory: usize, // Linear memory bytes the call may use
+    refused: bool, // Set once the module asked for more, so its failure is reported as a resource limit
+}
+
+impl ResourceLimiter for Sandbox {
+    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> wasmtime::Result<bool> {
+        self.refused |= desired > self.max_memory;
+        Ok(desired <= self.max_memory)
+    }
+
+    fn table_growing(&mut self, _current: usize, _desired: usize, _maximum: Option<usize>) -> wasmtime::Result<bool> {
+        Ok(true)
+    }
+}
+
+impl fmt::Debug for WasmHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmHandler")
+            .field("name", &self.name)
+            .field("fuel", &self.fuel)
+            .field("max_memory", &self.max_memory)
+            .finish()
+    }
+}
+
+impl WasmHandler {
+    /// Compiles the module in `config.path`, binary or text format. A module that doesn't compile, imports
+    /// anything or lacks an export is an error, so a server doesn't start with a handler that can't run
+    pub fn load(config: &WasmHandlerConfig) -> io::Result<Self> {
+        let name = config.path.display().to_string();
+        let invalid = |e: wasmtime::Error| {
+            io::Error::new(ErrorKind::InvalidData, format!("WASM module {}: {}", name, e))
+        };
+        let mut engine_config = Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config).map_err(invalid)?;
+        let module = Module::from_file(&engine, &config.path).map_err(invalid)?;
+        for export in ["memory", "alloc", "handle"] {
+            if module.get_export(export).is_none() {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("WASM module {} doesn't export {}", name, export),
+                ));
+            }
+        }
+        let module = Linker::new(&engine).instantiate_pre(&module).map_err(invalid)?;
+        Ok(WasmHandler {
+            name,
+            engine,
+            module,
+            fuel: config.fuel,
+            max_memory: config.max_memory,
+        })
+    }
+
+    // Run `handle` of a fresh instance on one request and decode its answer
+    fn call(&self, store: &mut Store<Sandbox>, type_name: &str, payload: &[u8]) -> wasmtime::Result<DynamicMessage> {
+        store.set_fuel(self.fuel)?;
+        let instance = self.module.instantiate(&mut *store)?;
+        let memory = instance
+            .get_memory(&mut *store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("the `memory` export is not a memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut *store, "alloc")?;
+        let handle = instance.get_typed_func::<(i32, i32, i32, i32), i64>(&mut *store, "handle")?;
+
+        let mut pass = |bytes: &[u8]| -> wasmtime::Result<(i32, i32)> {
+            let len = i32::try_from(bytes.len())?;
+            let address = alloc.call(&mut *store, len)?;
+            memory.write(&mut *store, usize::try_from(address)?, bytes)?;
+            Ok((address, len))
+        };
+        let (type_address, type_len) = pass(type_name.as_bytes())?;
+        let (payload_address, payload_len) = pass(payload)?;
+        let answer = handle.call(&mut *store, (type_address, type_len, payload_address, payload_len))? as u64;
+
+        let (address, len) = ((answer >> 32) as usize, (answer & 0xffff_ffff) as usize);
+        let bytes = memory
+            .data(&*store)
+            .get(address..address + len)
+            .ok_or_else(|| wasmtime::Error::msg("answer outside the module's memory"))?;
+        Ok(DynamicMessage::decode(bytes)?)
+    }
+}
+
+impl DynamicHandler for WasmHandler {
+    fn handle(&self, type_name: &str, payload: &[u8]) -> Result<DynamicMessage, ErrorResponse> {
+        let sandbox = Sandbox {
+            max_memory: self.max_memory,
+            refused: false,
+        };
+        let mut store = Store::new(&self.engine, sandbox);
+        store.limiter(|sandbox| sandbox as &mut dyn ResourceLimiter);
+        // Traps, exhausted fuel and refused memory all end up here, none of them affect the server
+        self.call(&mut store, type_name, payload).map_err(|e| {
+            let out_of_fuel = e.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel);
+            let code = if out_of_fuel || store.data().refused {
+                ErrorCode::HandlerResourceLimit
+            } else {
+                ErrorCode::Internal
+            };
+            ErrorResponse::new(code, format!("WASM handler {} failed: {:#}", self.name, e))
+        })
+    }
+}
@@ -0,0 +1,139 @@
+// Import necessary modules and crates
+use crate::clock::Clock; // Stall detection, replaceable in tests
+use std::{
+    fmt, io,
+    sync::{
+        atomic::{AtomicU64, Ordering}, // Last pet, written without a lock
+        Arc, Mutex, Weak, // Components are forgotten once their heartbeat is dropped
+    },
+    time::{Duration, Instant}, // Time handling
+};
+
+/// A component that hasn't made progress for longer than the watchdog timeout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stall {
+    pub component: String, // Name given when the component registered
+    pub since: Duration, // Time since its last pet
+}
+
+/// Handle a component pets whenever it makes progress. Dropping it unregisters the component
+#[derive(Debug)]
+pub struct Heartbeat {
+    last_pet: Arc<AtomicU64>, // Milliseconds from the watchdog's start to the last pet
+    started: Instant, // The watchdog's start
+    clock: Arc<dyn Clock>, // The watchdog's clock
+}
+
+impl Heartbeat {
+    /// Records that the component is making progress
+    pub fn pet(&self) {
+        let elapsed = self.clock.now().saturating_duration_since(self.started);
+        self.last_pet.store(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Tracks when each registered component last made progress, to detect a wedged process
+pub struct Watchdog {
+    timeout: Duration, // Silence after which a component counts as stalled
+    started: Instant, // Pets are stored relative to this
+    clock: Arc<dyn Clock>, // Decides when a component stalls
+    components: Mutex<Vec<(String, Weak<AtomicU64>)>>, // Registered components by name
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("timeout", &self.timeout)
+            .field("components", &self.components.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl Watchdog {
+    /// Creates a watchdog reporting components silent for longer than `timeout` on `clock`
+    pub fn new(timeout: Duration, clock: Arc<dyn Clock>) -> Self {
+        Watchdog {
+            timeout,
+            started: clock.now(),
+            clock,
+            components: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Registers a component under `name`, counting it as having just made progress
+    pub fn register(&self, name: impl Into<String>) -> Heartbeat {
+        let heartbeat = Heartbeat {
+            last_pet: Arc::new(AtomicU64::new(0)),
+            started: self.started,
+            clock: Arc::clone(&self.clock),
+        };
+        heartbeat.pet();
+        self.components
+            .lock()
+            .unwrap()
+            .push((name.into(), Arc::downgrade(&heartbeat.last_pet)));
+        heartbeat
+    }
+
+    /// Components silent for longer than the timeout, forgetting those whose heartbeat was dropped
+    pub fn stalled(&self) -> Vec<Stall> {
+        let now = self.clock.now().saturating_duration_since(self.started);
+        let mut components = self.components.lock().unwrap();
+        components.retain(|(_, last_pet)| last_pet.strong_count() > 0);
+        components
+            .iter()
+            .filter_map(|(name, last_pet)| {
+                let last_pet = Duration::from_millis(last_pet.upgrade()?.load(Ordering::Relaxed));
+                let since = now.saturating_sub(last_pet);
+                (since > self.timeout).then(|| Stall {
+                    component: name.clone(),
+                    since,
+                })
+            })
+            .collect()
+    }
+
+    /// Number of registered components
+    pub fn len(&self) -> usize {
+        let mut components = self.components.lock().unwrap();
+        components.retain(|(_, last_pet)| last_pet.strong_count() > 0);
+        components.len()
+    }
+
+    /// Whether no component is registered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Sends `state`, e.g. `WATCHDOG=1`, to the systemd notification socket. Returns false when the process
+/// wasn't started by systemd with one
+#[cfg(unix)]
+pub fn notify_systemd(state: &str) -> io::Result<bool> {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let socket = UnixDatagram::unbound()?;
+    let path = path.to_string_lossy();
+    match path.strip_prefix('@') {
+        // A leading @ names a socket in the abstract namespace
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), path.as_ref())?;
+        }
+    }
+    Ok(true)
+}
+
+/// Sends `state` to the systemd notification socket, there is none on this platform
+#[cfg(not(unix))]
+pub fn notify_systemd(_state: &str) -> io::Result<bool> {
+    Ok(false)
+}
@@ -0,0 +1,141 @@
+#![cfg(feature = "storage")]
+
+use embedded_recruitment_task::{
+    acklog::AckLog,
+    config::ServerConfig,
+    message::{server_message, AddResponse, ServerMessage},
+    server::Server,
+};
+use std::io::Write;
+
+fn response(result: i32) -> ServerMessage {
+    ServerMessage {
+        message: Some(server_message::Message::AddResponse(AddResponse { result })),
+        metadata: None,
+    }
+}
+
+#[test]
+fn test_log_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("acks.log");
+
+    {
+        let mut log = AckLog::open(&path).expect("Failed to create log");
+        assert!(log.is_empty(), "New log should be empty");
+        log.record("cmd-1", &response(3)).unwrap();
+        log.record("cmd with spaces", &response(7)).unwrap();
+    }
+
+    // Reopening loads every recorded command
+    let log = AckLog::open(&path).expect("Failed to reopen log");
+    assert_eq!(log.len(), 2, "Both commands should have been persisted");
+    assert_eq!(log.get("cmd-1"), Some(&response(3)));
+    assert_eq!(log.get("cmd with spaces"), Some(&response(7)));
+    assert_eq!(log.get("cmd-2"), None);
+}
+
+#[test]
+fn test_corrupt_log_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("acks.log");
+    std::fs::write(&path, "not a log line\n").unwrap();
+
+    let error = AckLog::open(&path).expect_err("Corrupt log should not load");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_torn_last_entry_is_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("acks.log");
+    {
+        let mut log = AckLog::open(&path).unwrap();
+        log.record("cmd-1", &response(3)).unwrap();
+        log.record("cmd-2", &response(5)).unwrap();
+    }
+    // A crash while appending leaves half an entry behind
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"636d642d33 0a").unwrap();
+    drop(file);
+
+    let mut log = AckLog::open(&path).expect("Torn last entry should not stop the log from loading");
+    assert_eq!(log.len(), 2);
+    assert_eq!(log.discarded(), 1);
+    assert_eq!(log.get("cmd-3"), None);
+
+    // The compacted file no longer has the torn entry, later appends start on a line of their own
+    log.record("cmd-3", &response(8)).unwrap();
+    drop(log);
+    let log = AckLog::open(&path).unwrap();
+    assert_eq!((log.len(), log.discarded()), (3, 0));
+    assert_eq!(log.get("cmd-3"), Some(&response(8)));
+}
+
+#[test]
+fn test_server_reports_recovery() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("acks.log");
+    {
+        let mut log = AckLog::open(&path).unwrap();
+        log.record("cmd-1", &response(3)).unwrap();
+    }
+    std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"63").unwrap();
+
+    let config = ServerConfig {
+        ack_log_path: Some(path.clone()),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2477", config).expect("Failed to create server");
+    let report = server.startup_report().clone();
+    server.stop();
+
+    assert_eq!(report.ack_log, Some(path));
+    assert_eq!(report.commands_replayed, 1);
+    assert_eq!(report.entries_discarded, 1);
+}
+
+#[test]
+fn test_log_is_compacted_while_running() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("acks.log");
+    let lines = || std::fs::read_to_string(&path).unwrap().lines().count();
+
+    // Every record after the first leaves the previous response of the command behind as a stale entry
+    let mut log = AckLog::open_compacting_after(&path, 3).unwrap();
+    for result in 0..10 {
+        log.record("cmd-1", &response(result)).unwrap();
+        log.record("cmd-2", &response(result)).unwrap();
+        assert!(log.file_entries() <= 2 + 3, "The file should be compacted past 3 stale entries");
+        assert_eq!(lines(), log.file_entries());
+    }
+    drop(log);
+
+    // Compaction kept the latest response of each command
+    let log = AckLog::open(&path).unwrap();
+    assert_eq!(log.len(), 2);
+    assert_eq!(log.get("cmd-1"), Some(&response(9)));
+    assert_eq!(log.get("cmd-2"), Some(&response(9)));
+    assert_eq!(lines(), 2);
+}
+
+#[test]
+fn test_self_test_checks_the_log_storage() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("acks.log");
+    let config = ServerConfig {
+        ack_log_path: Some(path.clone()),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2506", config).expect("Failed to create server");
+    let response = server.self_test();
+    server.stop();
+
+    let passed = |name: &str| response.checks.iter().find(|check| check.name == name).map(|check| check.passed);
+    assert_eq!(passed("storage"), Some(true), "The log should take writes: {:?}", response.checks);
+    #[cfg(unix)]
+    assert_eq!(passed("disk_space"), Some(true), "Free space should be reported: {:?}", response.checks);
+
+    // The probe was synced to the file
+    assert_eq!(AckLog::open(&path).expect("Failed to reopen log").len(), 1);
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "admin")]
+
+use embedded_recruitment_task::{config::ServerConfig, server::Server};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Send `method` `target` to the admin endpoint on `port` and return the whole response
+fn request(port: u16, method: &str, target: &str) -> String {
+    // The endpoint is bound by `run`, give it a moment to come up
+    let deadline = Instant::now() + Duration::from_secs(1);
+    let mut stream = loop {
+        match TcpStream::connect(("localhost", port)) {
+            Ok(stream) => break stream,
+            Err(e) if Instant::now() > deadline => panic!("Admin endpoint unreachable: {}", e),
+            Err(_) => thread::sleep(Duration::from_millis(20)),
+        }
+    };
+    write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n", method, target).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_admin_endpoint_toggles_maintenance() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        admin_addr: Some("localhost:2508".to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2507", config).expect("Failed to start server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let response = request(2508, "POST", "/maintenance?retry_after_ms=5000&reason=pump+service");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "Unexpected response: {}", response);
+    assert!(response.contains("\"maintenance\":true"), "Unexpected body: {}", response);
+    assert!(server.in_maintenance(), "The server should be in maintenance");
+
+    let response = request(2508, "DELETE", "/maintenance");
+    assert!(response.contains("\"maintenance\":false"), "Unexpected body: {}", response);
+    assert!(!server.in_maintenance(), "The server should have left maintenance");
+
+    // Invalid parameters and unknown requests change nothing
+    let response = request(2508, "POST", "/maintenance?retry_after_ms=soon");
+    assert!(response.starts_with("HTTP/1.1 400"), "Invalid parameters should be 400: {}", response);
+    let response = request(2508, "GET", "/maintenance");
+    assert!(response.starts_with("HTTP/1.1 404"), "Unknown requests should be 404: {}", response);
+    assert!(!server.in_maintenance());
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_admin_endpoint_toggles_read_only_mode() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        admin_addr: Some("localhost:2510".to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2509", config).expect("Failed to start server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let response = request(2510, "POST", "/read-only?reason=replacing+the+SD+card");
+    assert!(response.contains("\"read_only\":\"replacing the SD card\""), "Unexpected body: {}", response);
+    assert_eq!(server.read_only_reason().as_deref(), Some("replacing the SD card"));
+
+    let response = request(2510, "DELETE", "/read-only");
+    assert!(response.contains("\"read_only\":null"), "Unexpected body: {}", response);
+    assert_eq!(server.read_only_reason(), None);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_admin_endpoint_starts_and_stops_captures() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        admin_addr: Some("localhost:2512".to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2511", config).expect("Failed to start server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+    let device = TcpStream::connect("localhost:2511").expect("Failed to connect to the server");
+    let peer = device.local_addr().unwrap().to_string();
+
+    let response = request(2512, "POST", &format!("/capture?peer={}&duration_ms=60000", peer));
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "Unexpected response: {}", response);
+    assert!(response.contains(&format!("\"captures\":[\"{}\"]", peer)), "Unexpected body: {}", response);
+    assert_eq!(server.captured_connections(), [peer.clone()]);
+
+    let response = request(2512, "DELETE", &format!("/capture?peer={}", peer));
+    assert!(response.contains("\"captures\":[]"), "Unexpected body: {}", response);
+    let response = request(2512, "POST", "/capture");
+    assert!(response.starts_with("HTTP/1.1 400"), "A capture needs a peer: {}", response);
+
+    drop(device);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,88 @@
+use embedded_recruitment_task::{affinity, config::ThreadPriority};
+use std::thread;
+
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+#[test]
+fn test_pin_and_prioritize_thread() {
+    // Run on a scratch thread so the test harness thread keeps its settings
+    let handle = thread::spawn(|| {
+        assert!(affinity::pin_current_thread(&[0]).is_ok(), "Failed to pin thread to core 0");
+        // Raising the nice value never needs extra privileges
+        assert!(
+            affinity::set_current_thread_priority(ThreadPriority::Nice(10)).is_ok(),
+            "Failed to lower thread priority"
+        );
+    });
+    assert!(handle.join().is_ok(), "Pinned thread panicked");
+}
+
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+#[test]
+fn test_pin_to_invalid_core() {
+    let result = affinity::pin_current_thread(&[usize::MAX]);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput,
+        "Out of range core should be rejected"
+    );
+}
+
+#[cfg(not(all(feature = "affinity", target_os = "linux")))]
+#[test]
+fn test_affinity_unsupported_without_feature() {
+    let handle = thread::spawn(|| {
+        assert_eq!(
+            affinity::pin_current_thread(&[0]).unwrap_err().kind(),
+            std::io::ErrorKind::Unsupported
+        );
+        assert_eq!(
+            affinity::set_current_thread_priority(ThreadPriority::Nice(10))
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::Unsupported
+        );
+    });
+    assert!(handle.join().is_ok(), "Test thread panicked");
+}
+
+// Nice value of the calling thread, field 19 of its stat line
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+fn current_thread_nice() -> i32 {
+    let stat = std::fs::read_to_string("/proc/thread-self/stat").expect("Failed to read thread stat");
+    // The command name may contain spaces, the fields after it are counted from its closing parenthesis
+    let fields: Vec<&str> = stat[stat.rfind(')').unwrap() + 2..].split(' ').collect();
+    fields[16].parse().expect("Invalid nice value")
+}
+
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+#[test]
+fn test_scheduled_jobs_run_with_control_priority() {
+    use embedded_recruitment_task::{config::ServerConfig, server::Server};
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    let config = ServerConfig {
+        control_priority: Some(ThreadPriority::Nice(7)),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2475", config).expect("Failed to create server");
+    let nice = Arc::new(Mutex::new(None));
+    {
+        let nice = Arc::clone(&nice);
+        server.schedule(Duration::from_millis(10), move || *nice.lock().unwrap() = Some(current_thread_nice()));
+    }
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while nice.lock().unwrap().is_none() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+    assert_eq!(*nice.lock().unwrap(), Some(7), "Scheduled jobs should run on the control thread's priority");
+}
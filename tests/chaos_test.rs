@@ -0,0 +1,56 @@
+#![cfg(feature = "chaos")]
+
+use embedded_recruitment_task::{
+    chaos::{Fault, FaultInjector, FaultRates, RandomFaults},
+    message::{client_message, AddRequest},
+};
+use std::time::Duration;
+
+fn request() -> client_message::Message {
+    client_message::Message::AddRequest(AddRequest { a: 1, b: 2 })
+}
+
+#[test]
+fn test_random_fault_rates() {
+    let rates = FaultRates {
+        drop: 0.1,
+        error: 0.2,
+        delay: 0.3,
+        delay_for: Duration::from_millis(5),
+    };
+    let faults = RandomFaults::new(rates, 42);
+    let (mut drops, mut errors, mut delays, mut normal) = (0, 0, 0, 0);
+    for _ in 0..10_000 {
+        match faults.inject(&request()) {
+            Some(Fault::Drop) => drops += 1,
+            Some(Fault::InternalError) => errors += 1,
+            Some(Fault::Delay(delay)) => {
+                assert_eq!(delay, Duration::from_millis(5));
+                delays += 1;
+            }
+            None => normal += 1,
+        }
+    }
+    // Within a few percent of the configured shares
+    for (count, expected) in [(drops, 1000), (errors, 2000), (delays, 3000), (normal, 4000)] {
+        assert!((count as i32 - expected).abs() < 300, "Got {} where {} was expected", count, expected);
+    }
+}
+
+#[test]
+fn test_random_faults_reproducible() {
+    let rates = FaultRates {
+        drop: 0.5,
+        ..FaultRates::default()
+    };
+    let draws = |seed| {
+        let faults = RandomFaults::new(rates, seed);
+        (0..100).map(|_| faults.inject(&request())).collect::<Vec<_>>()
+    };
+    assert_eq!(draws(7), draws(7), "Same seed, same faults");
+    assert_ne!(draws(7), draws(8), "Different seeds should differ");
+
+    // Zero rates never inject
+    let none = RandomFaults::new(FaultRates::default(), 1);
+    assert!((0..1000).all(|_| none.inject(&request()).is_none()));
+}
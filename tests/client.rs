@@ -1,22 +1,92 @@
 // Import necessary modules and crates
-use embedded_recruitment_task::message::{client_message, ServerMessage, server_message}; // Protobuf message types
+use embedded_recruitment_task::message::{client_message, BenchRequest, ClientMessage, LivenessProbeAck, Metadata, ServerMessage, Timestamps, server_message}; // Protobuf message types
+#[cfg(feature = "delta")]
+use embedded_recruitment_task::message::ErrorCode; // Lost delta baselines
+use embedded_recruitment_task::frame::{Frame, WireHeader}; // Frame header parsing shared with the server
+#[cfg(feature = "storage")]
+use embedded_recruitment_task::outbox::Outbox; // Requests kept on disk while the server can't be reached
+use embedded_recruitment_task::stubs::ClientStubs; // Generated typed request methods
+use log::debug; // Logging macros for per-message details
 use log::error; // Logging macros for error messages
 use log::info; // Logging macros for informational messages
 use prost::Message; // Protobuf message encoding/decoding
 use std::io::Read; // Trait for reading from streams
 use std::io::Write; // Trait for writing to streams
 use std::{
+    collections::HashMap, // Cached responses by request
     io, // Standard I/O library
     net::{SocketAddr, TcpStream, ToSocketAddrs}, // Networking types and traits
-    time::Duration, // Time handling
+    sync::{Arc, Mutex}, // Write handle shared by the halves of a split client
+    thread, // Waiting out a GoAway retry delay
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH}, // Time handling
 };
 
+// Latency of the last request split into time spent in the network and inside the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyBreakdown {
+    pub total: Duration, // Round trip measured on the client clock
+    pub processing: Duration, // Time between the server reading the request and writing the response
+    pub network: Duration, // Remaining time, spent in transit and in the kernels
+}
+
+// Link quality measured by a bench run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    pub received: u32, // Payloads received
+    pub bytes: u64, // Payload bytes received
+    pub elapsed: Duration, // From sending the request to the last payload
+    pub throughput: f64, // Payload bytes per second
+    pub jitter: Duration, // Mean difference between consecutive payload inter-arrival times
+}
+
+// Something the client handled on its own that the application may want to know about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    Reconnected { from: String, to: String }, // The server at `from` sent GoAway, later requests go to `to`
+    MissedMessages { after: u64, next: u64 }, // Server sequence numbers skipped between two received messages
+}
+
+// Most responses the client caches, the oldest one is evicted first
+const MAX_CACHED: usize = 256;
+
+// Wait before resending durable requests after the first retryable error, doubled for every further one
+#[cfg(feature = "storage")]
+const OUTBOX_RETRY_MIN: Duration = Duration::from_millis(100);
+// Longest wait between resends of durable requests
+#[cfg(feature = "storage")]
+const OUTBOX_RETRY_MAX: Duration = Duration::from_secs(10);
+
+// Responses of read-only requests by encoded request payload, with the time they were received
+struct ResponseCache {
+    ttl: Duration, // Age up to which a response is returned without asking the server
+    entries: HashMap<Vec<u8>, (Instant, server_message::Message)>,
+}
+
+// Callback applying socket options right after connecting, an error aborts the connect
+type ConnectHook = Box<dyn FnMut(&TcpStream) -> io::Result<()> + Send>;
+
 // TCP/IP Client
 pub struct Client {
     ip: String, // IP address of the server
     port: u32, // Port number of the server
     timeout: Duration, // Connection timeout duration
     stream: Option<TcpStream>, // Optional TCP stream for the connection
+    buffer: Vec<u8>, // Bytes received but not yet decoded into a complete frame
+    last_latency: Option<LatencyBreakdown>, // Breakdown computed from the last timestamped response
+    last_trace_id: Option<String>, // Trace id of the last response
+    connect_hook: Option<ConnectHook>, // Run on every new stream before it is used
+    endpoints: Vec<(String, u32)>, // Servers to use in turn when one goes away, the current one first
+    current: usize, // Index of the endpoint in `ip` and `port`
+    going_away: Option<Duration>, // Retry delay of a GoAway received, the client reconnects before sending again
+    events: Vec<ClientEvent>, // Events not yet taken by the application
+    server_sequence: u64, // Sequence number of the last message received on this connection
+    #[cfg(feature = "storage")]
+    outbox: Option<Outbox>, // Durable requests not yet answered, resent on every connect
+    #[cfg(feature = "storage")]
+    outbox_retry: Option<Instant>, // When to resend the durable requests after a retryable error
+    #[cfg(feature = "storage")]
+    outbox_backoff: Duration, // Wait before the next resend, grows with every retryable error in a row
+    cache: Option<ResponseCache>, // Responses of `call_cached`, `None` until a TTL is set
 }
 impl Client {
     pub fn new(ip: &str, port: u32, timeout_ms: u64) -> Self {
@@ -25,9 +95,45 @@ impl Client {
             port,
             timeout: Duration::from_millis(timeout_ms),
             stream: None,
+            buffer: Vec::new(),
+            last_latency: None,
+            last_trace_id: None,
+            connect_hook: None,
+            endpoints: vec![(ip.to_string(), port)],
+            current: 0,
+            going_away: None,
+            events: Vec::new(),
+            server_sequence: 0,
+            #[cfg(feature = "storage")]
+            outbox: None,
+            #[cfg(feature = "storage")]
+            outbox_retry: None,
+            #[cfg(feature = "storage")]
+            outbox_backoff: OUTBOX_RETRY_MIN,
+            cache: None,
         }
     }
 
+    // client of several servers, connecting to the first and moving to the next one whenever a server
+    // sends GoAway; a single endpoint, e.g. a load balancer, is reconnected to after the retry delay
+    pub fn with_endpoints(endpoints: &[(&str, u32)], timeout_ms: u64) -> Self {
+        let (ip, port) = endpoints[0];
+        let mut client = Self::new(ip, port, timeout_ms);
+        client.endpoints = endpoints.iter().map(|&(ip, port)| (ip.to_string(), port)).collect();
+        client
+    }
+
+    // events since the last call, such as reconnections after a GoAway, oldest first
+    pub fn take_events(&mut self) -> Vec<ClientEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // run `hook` on the raw stream after every connect, before any message is sent, e.g. to set
+    // platform-specific socket options such as SO_BINDTODEVICE
+    pub fn on_connect(&mut self, hook: impl FnMut(&TcpStream) -> io::Result<()> + Send + 'static) {
+        self.connect_hook = Some(Box::new(hook));
+    }
+
     // connect the client to the server
     pub fn connect(&mut self) -> io::Result<()> {
         println!("Connecting to {}:{}", self.ip, self.port);
@@ -45,12 +151,123 @@ impl Client {
 
         // Connect to the server with a timeout
         let stream = TcpStream::connect_timeout(&socket_addrs[0], self.timeout)?;
+        // A failing hook drops the stream, closing the connection
+        if let Some(hook) = self.connect_hook.as_mut() {
+            hook(&stream)?;
+        }
         self.stream = Some(stream);
+        self.buffer.clear();
+        // Every connection numbers its messages from 1
+        self.server_sequence = 0;
 
         println!("Connected to the server!");
+        #[cfg(feature = "storage")]
+        self.flush_outbox()?;
+        Ok(())
+    }
+
+    // keep durable requests in `outbox` until the server answers them, requests left from a previous run
+    // are sent on the next connect
+    #[cfg(feature = "storage")]
+    pub fn set_outbox(&mut self, outbox: Outbox) {
+        self.outbox = Some(outbox);
+    }
+
+    // number of durable requests the server hasn't answered yet
+    #[cfg(feature = "storage")]
+    pub fn outbox_len(&self) -> usize {
+        self.outbox.as_ref().map_or(0, Outbox::len)
+    }
+
+    // queue a request on disk and send it if connected; while disconnected it waits for the next connect.
+    // Returns its command id, the server applies it once however often it is resent
+    #[cfg(feature = "storage")]
+    pub fn send_durable(&mut self, message: impl Into<client_message::Message>) -> io::Result<String> {
+        let Some(outbox) = self.outbox.as_mut() else {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "No outbox set"));
+        };
+        let command_id = outbox.push(message.into())?;
+        // The older requests went out on connect or when they were queued. While they wait to be resent,
+        // the new one waits too, so the server still gets them in order
+        if self.outbox_retry.is_some() {
+            return Ok(command_id);
+        }
+        if let (Some(stream), Some(message)) = (self.stream.as_mut(), outbox.iter().last()) {
+            if let Err(e) = write_message(stream, message) {
+                error!("Failed to send durable request {}, keeping it for the next connect: {}", command_id, e);
+                self.stream = None;
+            }
+        }
+        Ok(command_id)
+    }
+
+    // send every queued durable request in order, their responses arrive through `receive`
+    #[cfg(feature = "storage")]
+    fn flush_outbox(&mut self) -> io::Result<()> {
+        let (Some(stream), Some(outbox)) = (self.stream.as_mut(), self.outbox.as_ref()) else {
+            return Ok(());
+        };
+        if !outbox.is_empty() {
+            info!("Sending {} queued requests from outbox {}", outbox.len(), outbox.path().display());
+        }
+        self.outbox_retry = None;
+        for message in outbox.iter() {
+            write_message(stream, message)?;
+        }
         Ok(())
     }
 
+    // resend the durable requests once the wait after a retryable error is over, sleeping until then.
+    // Requests behind the oldest one may have been applied already, their command ids keep that to once
+    #[cfg(feature = "storage")]
+    fn retry_outbox(&mut self) -> io::Result<()> {
+        let Some(retry_at) = self.outbox_retry else {
+            return Ok(());
+        };
+        thread::sleep(retry_at.saturating_duration_since(Instant::now()));
+        self.flush_outbox()
+    }
+
+    // dequeue the oldest durable request once `server_message` answered it for good; after a retryable
+    // error or a MaintenanceMode reply it stays queued and is resent after a backoff
+    #[cfg(feature = "storage")]
+    fn settle_outbox(&mut self, server_message: &ServerMessage) -> io::Result<()> {
+        let Some(outbox) = self.outbox.as_mut() else {
+            return Ok(());
+        };
+        let answered = outbox
+            .front()
+            .and_then(|message| message.metadata.as_ref())
+            .is_some_and(|metadata| metadata.trace_id == trace_id(server_message));
+        if !answered {
+            return Ok(());
+        }
+        let retry_after = match &server_message.message {
+            Some(server_message::Message::ErrorResponse(error)) if error.code().is_retryable() => {
+                Some(self.outbox_backoff)
+            }
+            // Not run during maintenance, sent again once the server expects to be back
+            Some(server_message::Message::MaintenanceMode(maintenance)) => {
+                Some(Duration::from_millis(maintenance.retry_after_ms).max(self.outbox_backoff))
+            }
+            _ => None,
+        };
+        if let Some(retry_after) = retry_after {
+            info!("Durable request {} was not applied, resending in {:?}", trace_id(server_message), retry_after);
+            self.outbox_retry = Some(Instant::now() + retry_after);
+            self.outbox_backoff = (self.outbox_backoff * 2).min(OUTBOX_RETRY_MAX);
+        } else {
+            outbox.pop()?;
+            self.outbox_backoff = OUTBOX_RETRY_MIN;
+        }
+        Ok(())
+    }
+
+    // Local address of the connection, the peer address the server knows this client by
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.stream.as_ref()?.local_addr().ok()
+    }
+
     // disconnect the client
     pub fn disconnect(&mut self) -> io::Result<()> {
         if let Some(stream) = self.stream.take() {
@@ -61,12 +278,94 @@ impl Client {
         Ok(())
     }
 
-    // generic message to send message to the server
-    pub fn send(&mut self, message: client_message::Message) -> io::Result<()> {
+    // generic message to send message to the server, the server assigns the trace id
+    pub fn send(&mut self, message: impl Into<client_message::Message>) -> io::Result<()> {
+        self.send_traced(message.into(), "")
+    }
+
+    // send a message with a client-chosen trace id, an empty id lets the server assign one
+    pub fn send_traced(&mut self, message: client_message::Message, trace_id: &str) -> io::Result<()> {
+        self.send_message(Self::wrap(message, trace_id))
+    }
+
+    // send a message the server rejects as expired unless it is dispatched before `deadline`
+    pub fn send_with_deadline(&mut self, message: client_message::Message, deadline: SystemTime) -> io::Result<()> {
+        let mut client_message = Self::wrap(message, "");
+        if let Some(metadata) = client_message.metadata.as_mut() {
+            metadata.expires_at_us = deadline
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_micros() as u64)
+                .unwrap_or(0);
+        }
+        self.send_message(client_message)
+    }
+
+    // send a command the server applies at most once, retries with the same id get the stored response
+    pub fn send_command(&mut self, message: client_message::Message, command_id: &str) -> io::Result<()> {
+        let mut client_message = Self::wrap(message, "");
+        if let Some(metadata) = client_message.metadata.as_mut() {
+            metadata.command_id = command_id.to_string();
+        }
+        self.send_message(client_message)
+    }
+
+    // send a message with a sequence number, which must grow with every message on the connection
+    pub fn send_sequenced(&mut self, message: client_message::Message, sequence: u64) -> io::Result<()> {
+        let mut client_message = Self::wrap(message, "");
+        if let Some(metadata) = client_message.metadata.as_mut() {
+            metadata.sequence = sequence;
+        }
+        self.send_message(client_message)
+    }
+
+    // send bytes as they are, without framing, for exercising the server's protocol checks
+    pub fn send_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self.stream {
+            Some(ref mut stream) => {
+                stream.write_all(bytes)?;
+                stream.flush()
+            }
+            None => Err(io::Error::new(io::ErrorKind::NotConnected, "No active connection")),
+        }
+    }
+
+    // send a fully built message, including its metadata
+    pub fn send_message(&mut self, client_message: ClientMessage) -> io::Result<()> {
+        self.follow_go_away()?;
         if let Some(ref mut stream) = self.stream {
-            // Encode the message to a buffer
+            let trace_id = client_message
+                .metadata
+                .as_ref()
+                .map(|metadata| metadata.trace_id.as_str())
+                .unwrap_or_default();
+            info!("[trace {}] Sending message", trace_id);
+            // Encode the message to a length-prefixed buffer
+            let buffer = client_message.encode_length_delimited_to_vec();
+
+            // Send the buffer to the server
+            stream.write_all(&buffer)?;
+            stream.flush()?;
+
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "No active connection",
+            ))
+        }
+    }
+
+    // send several messages to the server in a single write
+    pub fn send_all(&mut self, messages: Vec<client_message::Message>) -> io::Result<()> {
+        self.follow_go_away()?;
+        if let Some(ref mut stream) = self.stream {
+            // Encode every message back to back into one length-prefixed buffer
             let mut buffer = Vec::new();
-            message.encode(&mut buffer);
+            for message in messages {
+                Self::wrap(message, "")
+                    .encode_length_delimited(&mut buffer)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            }
 
             // Send the buffer to the server
             stream.write_all(&buffer)?;
@@ -80,47 +379,33 @@ impl Client {
             ))
         }
     }
+
     // Receive a message from the server
     pub fn receive(&mut self) -> io::Result<ServerMessage> {
+        #[cfg(feature = "storage")]
+        self.retry_outbox()?;
         if let Some(ref mut stream) = self.stream {
-            info!("Receiving message from the server");
-            let mut buffer = vec![0u8; 1024]; // Create a buffer to hold the received data
-            let bytes_read = stream.read(&mut buffer)?; // Read data from the stream
-            if bytes_read == 0 {
-                info!("Server disconnected.");
-                return Err(io::Error::new(
-                    io::ErrorKind::ConnectionAborted,
-                    "Server disconnected",
-                ));
-            }
-
-            info!("Received {} bytes from the server", bytes_read);
-
-            // Decode the received message
-            match ServerMessage::decode(&buffer[..bytes_read]) {
-                Ok(server_message) => {
-                    if let Some(ref message) = server_message.message {
-                        match message {
-                            server_message::Message::AddResponse(add_response) => {
-                                info!("Received AddResponse: result = {}", add_response.result);
-                            }
-                            server_message::Message::EchoMessage(echo_response) => {
-                                info!("Received EchoResponse: content = {}", echo_response.content);
-                            }
-                        }
-                    } else {
-                        error!("Received empty server message");
-                    }
-                    Ok(server_message)
-                }
-                Err(e) => {
-                    error!("Failed to decode ServerMessage: {}", e);
-                    Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Failed to decode ServerMessage: {}", e),
-                    ))
+            let (server_message, received_us) = read_message(stream, &mut self.buffer)?;
+            self.check_sequence(&server_message);
+            #[cfg(feature = "storage")]
+            self.settle_outbox(&server_message)?;
+            self.record_latency(&server_message, received_us);
+            log_received(&server_message);
+
+            // Answer liveness probes transparently and wait for the real reply
+            if let Some(server_message::Message::LivenessProbe(probe)) = &server_message.message {
+                if let Some(stream) = self.stream.as_mut() {
+                    write_message(stream, &probe_ack(probe.id))?;
                 }
+                return self.receive();
             }
+            // The server still answers what was sent before its GoAway, so keep waiting for the reply
+            if let Some(server_message::Message::GoAway(go_away)) = &server_message.message {
+                self.going_away = Some(Duration::from_millis(go_away.retry_after_ms));
+                return self.receive();
+            }
+            self.last_trace_id = Some(trace_id(&server_message));
+            Ok(server_message)
         } else {
             error!("No active connection");
             Err(io::Error::new(
@@ -129,4 +414,489 @@ impl Client {
             ))
         }
     }
+
+    // cache the responses of `call_cached` for `ttl`, meant for read-only requests such as stats or health
+    pub fn set_cache(&mut self, ttl: Duration) {
+        self.cache = Some(ResponseCache {
+            ttl,
+            entries: HashMap::new(),
+        });
+    }
+
+    // send a read-only request, answered from the cache while the last response to it is within the TTL.
+    // If the server can't be reached and `allow_stale` is set, an older cached response is returned with
+    // `stale` and `age_ms` set in its metadata, like a gateway answering from its cache
+    pub fn call_cached(
+        &mut self,
+        message: impl Into<client_message::Message>,
+        allow_stale: bool,
+    ) -> io::Result<ServerMessage> {
+        let message = message.into();
+        let Some(cache) = self.cache.as_ref() else {
+            return self.call(message);
+        };
+        // Requests are cached by payload, the metadata differs between otherwise identical requests
+        let key = ClientMessage {
+            message: Some(message.clone()),
+            metadata: None,
+        }
+        .encode_to_vec();
+        let cached = cache.entries.get(&key).cloned();
+        if let Some((received, response)) = &cached {
+            if received.elapsed() <= cache.ttl {
+                return Ok(ServerMessage {
+                    message: Some(response.clone()),
+                    metadata: None,
+                });
+            }
+        }
+
+        let result = match self.stream {
+            Some(_) => self.call(message),
+            None => self.connect().and_then(|()| self.call(message)),
+        };
+        match (result, cached) {
+            (Ok(response), _) => {
+                // Errors are not worth repeating during an outage
+                if let (Some(cache), Some(message)) = (self.cache.as_mut(), response.message.as_ref()) {
+                    if !matches!(message, server_message::Message::ErrorResponse(_)) {
+                        cache.insert(key, message.clone());
+                    }
+                }
+                Ok(response)
+            }
+            (Err(e), Some((received, response))) if allow_stale => {
+                info!("Server unreachable ({}), answering from the cache", e);
+                // The connection is unusable, the next call reconnects
+                self.stream = None;
+                Ok(ServerMessage {
+                    message: Some(response),
+                    metadata: Some(Metadata {
+                        stale: true,
+                        age_ms: received.elapsed().as_millis() as u64,
+                        ..Metadata::default()
+                    }),
+                })
+            }
+            (Err(e), _) => {
+                self.stream = None;
+                Err(e)
+            }
+        }
+    }
+
+    // send `message` as the difference to `baseline`, an earlier request the server answered, and return the
+    // reply. A server no longer keeping the baseline gets the full request instead
+    #[cfg(feature = "delta")]
+    pub fn call_delta(
+        &mut self,
+        baseline: &client_message::Message,
+        message: client_message::Message,
+    ) -> io::Result<ServerMessage> {
+        let delta = ClientMessage::delta(baseline, &message);
+        let response = self.call(delta.message.expect("A delta request has a payload"))?;
+        match &response.message {
+            Some(server_message::Message::ErrorResponse(error)) if error.code() == ErrorCode::UnknownBaseline => {
+                info!("Server lost the delta baseline, sending the full request");
+                self.call(message)
+            }
+            _ => Ok(response),
+        }
+    }
+
+    // offer `message` by its hash and return the reply to it. Only a server that hasn't got the same request
+    // from any client gets the message itself
+    #[cfg(feature = "dedup")]
+    pub fn call_offered(&mut self, message: client_message::Message) -> io::Result<ServerMessage> {
+        let offer = ClientMessage::offer(&message);
+        match self.call(offer.message.expect("An offer has a payload"))?.message {
+            Some(server_message::Message::OfferResponse(response)) if response.have_it => self.receive(),
+            Some(server_message::Message::OfferResponse(_)) => {
+                info!("Server asked for the offered request, sending it");
+                self.call(message)
+            }
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected OfferResponse, got {:?}", other),
+            )),
+        }
+    }
+
+    // iterate over messages as they arrive, blocking for each, until the server closes the connection
+    pub fn incoming(&mut self) -> impl Iterator<Item = io::Result<ServerMessage>> + '_ {
+        incoming(move || self.receive())
+    }
+
+    // split the connection into halves that can be used from different threads, e.g. one receiving
+    // pushed messages while another sends requests
+    pub fn split(mut self) -> io::Result<(ClientReader, ClientWriter)> {
+        let stream = self
+            .stream
+            .take()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "No active connection"))?;
+        // Only writes are shared, the reader acknowledges liveness probes through the same lock
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+        let reader = ClientReader {
+            stream,
+            buffer: std::mem::take(&mut self.buffer),
+            writer: Arc::clone(&writer),
+        };
+        Ok((reader, ClientWriter { stream: writer }))
+    }
+
+    // ask the server to stream `count` payloads of `payload_size` bytes and measure the link
+    pub fn bench(&mut self, payload_size: u32, count: u32) -> io::Result<BenchResult> {
+        let started = Instant::now();
+        self.send(client_message::Message::BenchRequest(BenchRequest { payload_size, count }))?;
+        match self.receive()?.message {
+            Some(server_message::Message::BenchResponse(_)) => {}
+            Some(server_message::Message::ErrorResponse(error)) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, error.message));
+            }
+            _ => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Expected BenchResponse"));
+            }
+        }
+
+        let mut bytes = 0;
+        let mut last_arrival = started;
+        let mut last_gap: Option<Duration> = None;
+        let mut gap_changes = Duration::ZERO;
+        for expected in 0..count {
+            let payload = match self.receive()?.message {
+                Some(server_message::Message::BenchPayload(payload)) if payload.index == expected => payload,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Expected BenchPayload {}", expected),
+                    ))
+                }
+            };
+            if payload.data.len() != payload_size as usize
+                || payload.data.iter().enumerate().any(|(i, &byte)| byte != (i % 251) as u8)
+            {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Corrupted bench payload"));
+            }
+            bytes += payload.data.len() as u64;
+
+            // Jitter as in RFC 3550, without smoothing: how much consecutive gaps differ
+            let arrival = Instant::now();
+            let gap = arrival - last_arrival;
+            if let Some(previous) = last_gap {
+                gap_changes += gap.abs_diff(previous);
+            }
+            last_gap = Some(gap);
+            last_arrival = arrival;
+        }
+
+        let elapsed = started.elapsed();
+        Ok(BenchResult {
+            received: count,
+            bytes,
+            elapsed,
+            throughput: bytes as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            jitter: gap_changes / count.saturating_sub(1).max(1),
+        })
+    }
+
+    // Latency breakdown of the last response that carried timestamps
+    pub fn last_latency_breakdown(&self) -> Option<LatencyBreakdown> {
+        self.last_latency
+    }
+
+    // Trace id of the last response, as chosen by the client or assigned by the server
+    pub fn last_trace_id(&self) -> Option<&str> {
+        self.last_trace_id.as_deref()
+    }
+
+    // Reconnect if the server sent GoAway: to the next endpoint, or to the only one after its retry delay
+    fn follow_go_away(&mut self) -> io::Result<()> {
+        self.poll_go_away()?;
+        let Some(retry_after) = self.going_away.take() else {
+            return Ok(());
+        };
+        let from = format!("{}:{}", self.ip, self.port);
+        // The server may have closed the connection already
+        let _ = self.disconnect();
+        self.current = (self.current + 1) % self.endpoints.len();
+        if self.endpoints.len() == 1 {
+            thread::sleep(retry_after);
+        }
+        (self.ip, self.port) = self.endpoints[self.current].clone();
+        self.connect()?;
+
+        let to = format!("{}:{}", self.ip, self.port);
+        info!("Server {} is going away, reconnected to {}", from, to);
+        self.events.push(ClientEvent::Reconnected { from, to });
+        Ok(())
+    }
+
+    // Take a GoAway the server sent while the client was idle, without blocking
+    fn poll_go_away(&mut self) -> io::Result<()> {
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(());
+        };
+        stream.set_nonblocking(true)?;
+        let mut chunk = [0u8; 1024];
+        let read = loop {
+            match stream.read(&mut chunk) {
+                // A closed connection is reported by the next receive
+                Ok(0) => break Ok(()),
+                Ok(bytes_read) => self.buffer.extend_from_slice(&chunk[..bytes_read]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        stream.set_nonblocking(false)?;
+        read?;
+
+        // Only a GoAway at the front is taken, anything else stays buffered for `receive`
+        while let Some(Frame::Complete { start, end }) = WireHeader::next_frame(&self.buffer)? {
+            match ServerMessage::decode(&self.buffer[start..end]) {
+                Ok(go_away) if matches!(go_away.message, Some(server_message::Message::GoAway(_))) => {
+                    self.check_sequence(&go_away);
+                    if let Some(server_message::Message::GoAway(go_away)) = go_away.message {
+                        self.going_away = Some(Duration::from_millis(go_away.retry_after_ms));
+                    }
+                    self.buffer.drain(..end);
+                }
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    // Wrap a message with the send timestamp and trace id
+    fn wrap(message: client_message::Message, trace_id: &str) -> ClientMessage {
+        ClientMessage {
+            message: Some(message),
+            metadata: Some(Metadata {
+                timestamps: Some(Timestamps {
+                    client_send_us: now_micros(),
+                    ..Timestamps::default()
+                }),
+                trace_id: trace_id.to_string(),
+                ..Metadata::default()
+            }),
+        }
+    }
+
+    // Record an event if server sequence numbers were skipped, messages were lost on the way
+    fn check_sequence(&mut self, server_message: &ServerMessage) {
+        let sequence = server_message.metadata.as_ref().map_or(0, |metadata| metadata.server_sequence);
+        if sequence == 0 {
+            return;
+        }
+        if sequence > self.server_sequence + 1 {
+            error!("Missed server messages {} to {}", self.server_sequence + 1, sequence - 1);
+            self.events.push(ClientEvent::MissedMessages {
+                after: self.server_sequence,
+                next: sequence,
+            });
+        }
+        self.server_sequence = sequence;
+    }
+
+    // Split the round trip of a timestamped response into network and processing time
+    fn record_latency(&mut self, server_message: &ServerMessage, received_us: u64) {
+        let timestamps = match server_message
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.timestamps.as_ref())
+        {
+            Some(timestamps) => timestamps,
+            None => return,
+        };
+
+        // Each difference uses a single clock, so the client and server clocks need not agree
+        let total = received_us.saturating_sub(timestamps.client_send_us);
+        let processing = timestamps
+            .server_respond_us
+            .saturating_sub(timestamps.server_receive_us)
+            .min(total);
+        self.last_latency = Some(LatencyBreakdown {
+            total: Duration::from_micros(total),
+            processing: Duration::from_micros(processing),
+            network: Duration::from_micros(total - processing),
+        });
+    }
+
+    // Take the next complete length-delimited frame out of the receive buffer
+    fn take_frame(buffer: &mut Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        match WireHeader::next_frame(buffer)? {
+            Some(Frame::Complete { start, end }) => {
+                let frame = buffer[start..end].to_vec();
+                buffer.drain(..end);
+                Ok(Some(frame))
+            }
+            Some(Frame::Oversized { payload_len, .. }) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Frame of {} bytes exceeds the size limit", payload_len),
+            )),
+            // Wait for the rest of the frame
+            None => Ok(None),
+        }
+    }
+}
+
+// Receiving half of a split client
+pub struct ClientReader {
+    stream: TcpStream, // Own handle of the connection, only read from
+    buffer: Vec<u8>, // Bytes received but not yet decoded into a complete frame
+    writer: Arc<Mutex<TcpStream>>, // Write handle shared with the `ClientWriter`, for probe acks
+}
+
+impl ClientReader {
+    // Receive the next message from the server, answering liveness probes on the way
+    pub fn receive(&mut self) -> io::Result<ServerMessage> {
+        loop {
+            let (server_message, _) = read_message(&mut self.stream, &mut self.buffer)?;
+            log_received(&server_message);
+            match &server_message.message {
+                Some(server_message::Message::LivenessProbe(probe)) => {
+                    write_message(&mut self.writer.lock().unwrap(), &probe_ack(probe.id))?;
+                }
+                _ => return Ok(server_message),
+            }
+        }
+    }
+
+    // iterate over messages as they arrive, blocking for each, until the server closes the connection
+    pub fn incoming(&mut self) -> impl Iterator<Item = io::Result<ServerMessage>> + '_ {
+        incoming(move || self.receive())
+    }
+}
+
+// Sending half of a split client
+pub struct ClientWriter {
+    stream: Arc<Mutex<TcpStream>>, // Locked only while a frame is written, so frames never interleave
+}
+
+impl ClientWriter {
+    // send a message to the server, the server assigns the trace id
+    pub fn send(&mut self, message: impl Into<client_message::Message>) -> io::Result<()> {
+        self.send_message(Client::wrap(message.into(), ""))
+    }
+
+    // send a fully built message, including its metadata
+    pub fn send_message(&mut self, client_message: ClientMessage) -> io::Result<()> {
+        write_message(&mut self.stream.lock().unwrap(), &client_message)
+    }
+}
+
+// Messages from `receive` until the connection closes, a failed receive is yielded once and ends the iteration
+fn incoming(mut receive: impl FnMut() -> io::Result<ServerMessage>) -> impl Iterator<Item = io::Result<ServerMessage>> {
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        match receive() {
+            Ok(server_message) => Some(Ok(server_message)),
+            Err(e) => {
+                done = true;
+                (e.kind() != io::ErrorKind::ConnectionAborted).then_some(Err(e))
+            }
+        }
+    })
+}
+
+// Read from `stream` until `buffer` holds a complete frame, returning its message and receive time
+fn read_message(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> io::Result<(ServerMessage, u64)> {
+    info!("Receiving message from the server");
+    let frame = loop {
+        // Return a frame already buffered from an earlier read
+        if let Some(frame) = Client::take_frame(buffer)? {
+            break frame;
+        }
+
+        let mut chunk = vec![0u8; 1024]; // Create a buffer to hold the received data
+        let bytes_read = stream.read(&mut chunk)?; // Read data from the stream
+        if bytes_read == 0 {
+            info!("Server disconnected.");
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "Server disconnected",
+            ));
+        }
+
+        info!("Received {} bytes from the server", bytes_read);
+        buffer.extend_from_slice(&chunk[..bytes_read]);
+    };
+
+    let received_us = now_micros();
+
+    // Decode the received message
+    match ServerMessage::decode(&frame[..]) {
+        Ok(server_message) => Ok((server_message, received_us)),
+        Err(e) => {
+            error!("Failed to decode ServerMessage: {}", e);
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to decode ServerMessage: {}", e),
+            ))
+        }
+    }
+}
+
+// Encode a message with its length prefix and write it in one go
+fn write_message(stream: &mut TcpStream, client_message: &ClientMessage) -> io::Result<()> {
+    stream.write_all(&client_message.encode_length_delimited_to_vec())?;
+    stream.flush()
+}
+
+// Log a received message, payload streams and probes only at debug level
+fn log_received(server_message: &ServerMessage) {
+    let trace_id = trace_id(server_message);
+    match server_message.message {
+        Some(server_message::Message::BenchPayload(_)) | Some(server_message::Message::LivenessProbe(_)) => {
+            debug!("[trace {}] Received {}", trace_id, server_message);
+        }
+        Some(server_message::Message::ErrorResponse(_)) | None => {
+            error!("[trace {}] Received {}", trace_id, server_message);
+        }
+        Some(_) => info!("[trace {}] Received {}", trace_id, server_message),
+    }
+}
+
+// Trace id of a received message, empty if it carries none
+fn trace_id(server_message: &ServerMessage) -> String {
+    server_message
+        .metadata
+        .as_ref()
+        .map(|metadata| metadata.trace_id.clone())
+        .unwrap_or_default()
+}
+
+// Answer to the liveness probe with `id`
+fn probe_ack(id: u64) -> ClientMessage {
+    LivenessProbeAck { id }.into()
+}
+
+impl ResponseCache {
+    fn insert(&mut self, key: Vec<u8>, response: server_message::Message) {
+        if self.entries.len() >= MAX_CACHED && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter().min_by_key(|(_, (received, _))| *received).map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (Instant::now(), response));
+    }
+}
+
+// Typed request methods on top of send and receive
+impl ClientStubs for Client {
+    fn call(&mut self, message: client_message::Message) -> io::Result<ServerMessage> {
+        self.send(message)?;
+        self.receive()
+    }
+}
+
+// Current wall-clock time in microseconds since the Unix epoch
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_micros() as u64)
+        .unwrap_or(0)
 }
@@ -1,11 +1,32 @@
+// The baseline tests are kept as written upstream
+#![allow(clippy::field_reassign_with_default, clippy::single_component_path_imports, clippy::useless_vec)]
+
 use embedded_recruitment_task::{
-    message::{client_message, server_message, AddRequest, EchoMessage},
+    clock::{Clock, ManualClock},
+    config::{LivenessConfig, MemoryBudget, ServerConfig, ViolationPolicy},
+    message::{
+        client_message, server_message, AddRequest, AddResponse, BenchRequest, ClientMessage, CommandStatus,
+        CommandStatusRequest, CommandStatusResponse, EchoMessage, ErrorCode, ErrorResponse, EventKind, GetSchemaRequest,
+        GoAway, HealthRequest, HealthStatus, MaintenanceMode, Metadata, RecentEventsRequest, ResyncRequest,
+        SelfTestRequest, ServerMessage, StatsRequest,
+    },
+    metrics::{Metric, MetricKind, MetricsExporter},
+    protocol::FILE_DESCRIPTOR_SET,
     server::Server,
+    stubs::ClientStubs,
 };
+#[cfg(feature = "gateway")]
+use embedded_recruitment_task::config::{CachePolicy, GatewayConfig};
+#[cfg(feature = "reflect")]
+use embedded_recruitment_task::{config::LoadShedding, message::DynamicMessage};
 use std::{
-    sync::Arc,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
     thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use prost::Message;
+use prost_types::FileDescriptorSet;
 use env_logger;
 mod client;
 
@@ -19,6 +40,18 @@ pub fn create_server(addr: &str) -> Arc<Server> {
     Server::new(addr).expect("Failed to create server")
 }
 
+// Poll `condition` until it holds or a second has passed
+fn wait_until(condition: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    condition()
+}
+
 #[test]
 fn test_client_connection() {
     let _ = env_logger::builder().is_test(true).try_init();
@@ -399,3 +432,2564 @@ fn test_multiple_clients_with_different_message_types() {
         "Server2 thread panicked or failed to join"
     );
 }
+
+#[test]
+fn test_pipelined_requests() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2060");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2060, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // Send several requests in a single write so the server reads them together
+    let add_requests = [(1, 2), (10, 20), (100, 200)];
+    let messages = add_requests
+        .iter()
+        .map(|&(a, b)| client_message::Message::AddRequest(AddRequest { a, b }))
+        .collect();
+    assert!(client.send_all(messages).is_ok(), "Failed to send messages");
+
+    // Every request must get its own response, in order
+    for (a, b) in add_requests {
+        let response = client.receive();
+        assert!(
+            response.is_ok(),
+            "Failed to receive response for AddRequest"
+        );
+
+        match response.unwrap().message {
+            Some(server_message::Message::AddResponse(add_response)) => {
+                assert_eq!(add_response.result, a + b, "AddResponse result does not match");
+            }
+            _ => panic!("Expected AddResponse, but received a different message"),
+        }
+    }
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_large_echo_message() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2070");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2070, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // Prepare a message larger than a single read on either side
+    let echo_message = EchoMessage {
+        content: "x".repeat(5000),
+    };
+    let message = client_message::Message::EchoMessage(echo_message.clone());
+
+    // Send the message to the server
+    assert!(client.send(message).is_ok(), "Failed to send message");
+
+    // Receive the echoed message
+    let response = client.receive();
+    assert!(
+        response.is_ok(),
+        "Failed to receive response for EchoMessage"
+    );
+
+    match response.unwrap().message {
+        Some(server_message::Message::EchoMessage(echo)) => {
+            assert_eq!(
+                echo.content, echo_message.content,
+                "Echoed message content does not match"
+            );
+        }
+        _ => panic!("Expected EchoMessage, but received a different message"),
+    }
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_connection_count() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2080");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect multiple clients
+    let mut clients = [
+        client::Client::new("localhost", 2080, 1000),
+        client::Client::new("localhost", 2080, 1000),
+    ];
+    for client in clients.iter_mut() {
+        assert!(client.connect().is_ok(), "Failed to connect to the server");
+    }
+    assert!(
+        wait_until(|| server.connection_count() == 2),
+        "Server should count both connected clients"
+    );
+
+    // Disconnect the clients
+    for client in clients.iter_mut() {
+        assert!(
+            client.disconnect().is_ok(),
+            "Failed to disconnect from the server"
+        );
+    }
+    assert!(
+        wait_until(|| server.connection_count() == 0),
+        "Server should count the clients as disconnected"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_server_with_pinned_threads() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Pin every server thread to the first core, without the feature this only logs a warning
+    let config = ServerConfig {
+        acceptor_cores: vec![0],
+        worker_cores: vec![0],
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2090", config).expect("Failed to create server");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2090, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // The pinned server must still answer requests
+    let message = client_message::Message::AddRequest(AddRequest { a: 2, b: 3 });
+    assert!(client.send(message).is_ok(), "Failed to send message");
+    match client.receive().expect("Failed to receive response").message {
+        Some(server_message::Message::AddResponse(add_response)) => {
+            assert_eq!(add_response.result, 5, "AddResponse result does not match");
+        }
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_server_with_multiple_acceptors() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Several SO_REUSEPORT acceptors with a small backlog, without the feature this falls back to one listener
+    let config = ServerConfig {
+        listen_backlog: Some(16),
+        acceptors: 4,
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2100", config).expect("Failed to create server");
+    let handle = setup_server_thread(server.clone());
+
+    // Whichever acceptor takes a connection, every client must be served
+    let mut clients: Vec<_> = (0..8).map(|_| client::Client::new("localhost", 2100, 1000)).collect();
+    for client in clients.iter_mut() {
+        assert!(client.connect().is_ok(), "Failed to connect to the server");
+    }
+    for (i, client) in clients.iter_mut().enumerate() {
+        let message = client_message::Message::AddRequest(AddRequest { a: i as i32, b: 1 });
+        assert!(client.send(message).is_ok(), "Failed to send message");
+        match client.receive().expect("Failed to receive response").message {
+            Some(server_message::Message::AddResponse(add_response)) => {
+                assert_eq!(add_response.result, i as i32 + 1, "AddResponse result does not match");
+            }
+            _ => panic!("Expected AddResponse, but received a different message"),
+        }
+    }
+
+    // Disconnect the clients
+    for client in clients.iter_mut() {
+        assert!(
+            client.disconnect().is_ok(),
+            "Failed to disconnect from the server"
+        );
+    }
+
+    // Stop the server and wait for every accept loop to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_latency_breakdown() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2110");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2110, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    assert!(
+        client.last_latency_breakdown().is_none(),
+        "No breakdown should exist before the first response"
+    );
+
+    // Every request is timestamped, the response carries the server side of the timing
+    let message = client_message::Message::AddRequest(AddRequest { a: 1, b: 1 });
+    assert!(client.send(message).is_ok(), "Failed to send message");
+    assert!(client.receive().is_ok(), "Failed to receive response");
+
+    let latency = client
+        .last_latency_breakdown()
+        .expect("Response should carry timestamps");
+    assert_eq!(
+        latency.network + latency.processing,
+        latency.total,
+        "Network and processing time should add up to the round trip"
+    );
+    assert!(latency.total < Duration::from_secs(1), "Loopback round trip took too long");
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_trace_ids() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2120");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2120, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // A client-chosen trace id comes back unchanged
+    let message = client_message::Message::AddRequest(AddRequest { a: 1, b: 2 });
+    assert!(client.send_traced(message, "device-7-txn-42").is_ok(), "Failed to send message");
+    assert!(client.receive().is_ok(), "Failed to receive response");
+    assert_eq!(client.last_trace_id(), Some("device-7-txn-42"));
+
+    // Without one the server assigns a fresh id to every request
+    let mut assigned = Vec::new();
+    for _ in 0..2 {
+        let message = client_message::Message::AddRequest(AddRequest { a: 1, b: 2 });
+        assert!(client.send(message).is_ok(), "Failed to send message");
+        assert!(client.receive().is_ok(), "Failed to receive response");
+        let trace_id = client.last_trace_id().expect("Response should carry a trace id");
+        assert!(trace_id.starts_with("srv-"), "Unexpected server trace id {}", trace_id);
+        assigned.push(trace_id.to_string());
+    }
+    assert_ne!(assigned[0], assigned[1], "Server trace ids must be unique");
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_health_check() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2130");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2130, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // The health response reports the server as serving, including this connection
+    let message = client_message::Message::HealthRequest(HealthRequest {});
+    assert!(client.send(message).is_ok(), "Failed to send message");
+    let response = client.receive();
+    assert!(response.is_ok(), "Failed to receive response for HealthRequest");
+    match response.unwrap().message {
+        Some(server_message::Message::HealthResponse(health)) => {
+            assert_eq!(health.status(), HealthStatus::Serving, "Server should be serving");
+            assert_eq!(health.connections, 1, "Health should count the connected client");
+            assert!(health.last_error.is_empty(), "No error should have been recorded");
+        }
+        _ => panic!("Expected HealthResponse, but received a different message"),
+    }
+
+    // The same report is available in-process
+    let report = server.health();
+    assert_eq!(report.status, HealthStatus::Serving);
+    assert_eq!(report.queue_depth, 0, "No request should be left in flight");
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_self_test() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2150");
+    let handle = setup_server_thread(server.clone());
+
+    // Application checks run next to the built-in ones, a failing check fails the whole run
+    server.register_self_test("storage", || Ok("journal directory writable".to_string()));
+    server.register_self_test("tls_certificate", || Err("certificate expires in 3 days".to_string()));
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2150, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    let message = client_message::Message::SelfTestRequest(SelfTestRequest {});
+    assert!(client.send(message).is_ok(), "Failed to send message");
+    let response = client.receive();
+    assert!(response.is_ok(), "Failed to receive response for SelfTestRequest");
+    match response.unwrap().message {
+        Some(server_message::Message::SelfTestResponse(self_test)) => {
+            let names: Vec<&str> = self_test.checks.iter().map(|check| check.name.as_str()).collect();
+            assert_eq!(names, ["buffer_pool", "storage", "tls_certificate"], "Checks should run in registration order");
+            assert!(self_test.checks[0].passed, "Built-in buffer pool check should pass");
+            assert!(self_test.checks[1].passed, "Storage check should pass");
+            assert!(!self_test.checks[2].passed, "Certificate check should fail");
+            assert_eq!(self_test.checks[2].detail, "certificate expires in 3 days");
+            assert!(!self_test.passed, "One failing check should fail the self-test");
+        }
+        _ => panic!("Expected SelfTestResponse, but received a different message"),
+    }
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_scheduled_jobs() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2160");
+    let handle = setup_server_thread(server.clone());
+
+    // Jobs run periodically on the server's scheduler thread
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&runs);
+    let job = server.schedule(Duration::from_millis(20), move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+    assert!(
+        wait_until(|| runs.load(Ordering::SeqCst) >= 3),
+        "Scheduled job should have run repeatedly"
+    );
+
+    // A cancelled job stops running
+    assert!(server.cancel_job(job), "Job should still have been scheduled");
+    assert!(!server.cancel_job(job), "Job can only be cancelled once");
+    let after_cancel = runs.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(100));
+    assert!(
+        runs.load(Ordering::SeqCst) <= after_cancel + 1,
+        "Cancelled job kept running"
+    );
+
+    // Stop the server and wait for thread to finish, the scheduler stops with it
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_expired_request() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2170");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2170, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // A request whose deadline already passed is rejected instead of being handled
+    let deadline = SystemTime::now() - Duration::from_secs(3600);
+    let message = client_message::Message::AddRequest(AddRequest { a: 1, b: 2 });
+    assert!(client.send_with_deadline(message, deadline).is_ok(), "Failed to send message");
+    let response = client.receive();
+    assert!(response.is_ok(), "Failed to receive response for expired request");
+    match response.unwrap().message {
+        Some(server_message::Message::ErrorResponse(error)) => {
+            assert_eq!(error.code(), ErrorCode::Expired, "Request should be reported as expired");
+        }
+        _ => panic!("Expected ErrorResponse, but received a different message"),
+    }
+
+    // A request within its deadline is handled as usual
+    let deadline = SystemTime::now() + Duration::from_secs(60);
+    let message = client_message::Message::AddRequest(AddRequest { a: 1, b: 2 });
+    assert!(client.send_with_deadline(message, deadline).is_ok(), "Failed to send message");
+    let response = client.receive();
+    assert!(response.is_ok(), "Failed to receive response for AddRequest");
+    match response.unwrap().message {
+        Some(server_message::Message::AddResponse(add_response)) => {
+            assert_eq!(add_response.result, 3, "AddResponse result does not match");
+        }
+        _ => panic!("Expected AddResponse, but received a different message"),
+    }
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_command_applied_once() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2180");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2180, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    let status = |client: &mut client::Client| {
+        let message = client_message::Message::CommandStatusRequest(CommandStatusRequest {
+            command_id: "valve-3-open".to_string(),
+        });
+        assert!(client.send(message).is_ok(), "Failed to send message");
+        match client.receive().expect("Failed to receive status").message {
+            Some(server_message::Message::CommandStatusResponse(response)) => response.status(),
+            _ => panic!("Expected CommandStatusResponse, but received a different message"),
+        }
+    };
+    assert_eq!(status(&mut client), CommandStatus::Unknown, "Command was never sent");
+
+    // A retried command is answered with the original response
+    for b in [2, 40] {
+        let message = client_message::Message::AddRequest(AddRequest { a: 1, b });
+        assert!(client.send_command(message, "valve-3-open").is_ok(), "Failed to send command");
+        match client.receive().expect("Failed to receive response").message {
+            Some(server_message::Message::AddResponse(add_response)) => {
+                assert_eq!(add_response.result, 3, "Retry must replay the first result");
+            }
+            _ => panic!("Expected AddResponse, but received a different message"),
+        }
+    }
+    assert_eq!(status(&mut client), CommandStatus::Applied, "Command should be applied");
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[cfg(feature = "reflect")]
+#[test]
+fn test_commands_run_in_parallel_and_retries_wait() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2505");
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    {
+        let calls = Arc::clone(&calls);
+        server.route_dynamic("messages.EchoMessage", move |type_name: &str, payload: &[u8]| {
+            calls.lock().unwrap().push(payload.to_vec());
+            thread::sleep(Duration::from_millis(300));
+            Ok(DynamicMessage::new(type_name, payload.to_vec()))
+        });
+    }
+    let handle = setup_server_thread(server.clone());
+
+    // Each client sends its command with the id given, all at once
+    let run = |commands: &[(&str, &str)]| {
+        let started = Instant::now();
+        let clients: Vec<_> = commands
+            .iter()
+            .map(|&(command_id, content)| {
+                let (command_id, content) = (command_id.to_string(), content.to_string());
+                thread::spawn(move || {
+                    let mut client = client::Client::new("localhost", 2505, 2000);
+                    assert!(client.connect().is_ok(), "Failed to connect to the server");
+                    let payload = EchoMessage { content }.encode_to_vec();
+                    let message = DynamicMessage::new("messages.EchoMessage", payload).into();
+                    assert!(client.send_command(message, &command_id).is_ok(), "Failed to send command");
+                    client.receive().expect("Failed to receive response").message
+                })
+            })
+            .collect();
+        let responses: Vec<_> = clients.into_iter().map(|client| client.join().unwrap()).collect();
+        (started.elapsed(), responses)
+    };
+
+    // Different commands don't wait for each other
+    let (elapsed, _) = run(&[("fan-1-on", "one"), ("fan-2-on", "two")]);
+    assert!(elapsed < Duration::from_millis(550), "Commands ran one after the other: {:?}", elapsed);
+    assert_eq!(calls.lock().unwrap().len(), 2);
+
+    // The same command sent twice at once runs once, the retry waits and gets the same response
+    let (_, responses) = run(&[("fan-3-on", "three"), ("fan-3-on", "three")]);
+    assert_eq!(calls.lock().unwrap().len(), 3, "The retry should not run the command again");
+    assert_eq!(responses[0], responses[1]);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_replayed_sequence_rejected() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up a server that requires sequence numbers
+    let config = ServerConfig {
+        require_sequence: true,
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2190", config).expect("Failed to start server");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2190, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    let add = |client: &mut client::Client, sequence: u64| {
+        let message = client_message::Message::AddRequest(AddRequest { a: 1, b: 2 });
+        assert!(client.send_sequenced(message, sequence).is_ok(), "Failed to send message");
+        client.receive().expect("Failed to receive response").message
+    };
+
+    // Increasing sequence numbers are accepted, gaps are allowed
+    for sequence in [1, 2, 5] {
+        assert!(
+            matches!(add(&mut client, sequence), Some(server_message::Message::AddResponse(_))),
+            "Sequence {} should be accepted",
+            sequence
+        );
+    }
+
+    // Repeated, lower and missing sequence numbers are rejected
+    for sequence in [5, 3, 0] {
+        match add(&mut client, sequence) {
+            Some(server_message::Message::ErrorResponse(error)) => {
+                assert_eq!(error.code(), ErrorCode::Replayed, "Sequence {} should be a replay", sequence);
+            }
+            _ => panic!("Expected ErrorResponse for sequence {}", sequence),
+        }
+    }
+
+    // A new connection starts its own sequence
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    assert!(client.connect().is_ok(), "Failed to reconnect to the server");
+    assert!(
+        matches!(add(&mut client, 1), Some(server_message::Message::AddResponse(_))),
+        "New connection should accept sequence 1"
+    );
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_generated_client_stubs() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2200");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2200, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // Each request has a typed method returning its own response type
+    let sum = client.add_request(AddRequest { a: 20, b: 22 }).expect("AddRequest failed");
+    assert_eq!(sum.result, 42, "AddResponse result does not match");
+    let echo = client
+        .echo_message(EchoMessage { content: "stub".to_string() })
+        .expect("EchoMessage failed");
+    assert_eq!(echo.content, "stub", "Echoed message content does not match");
+    let health = client.health_request(HealthRequest {}).expect("HealthRequest failed");
+    assert_eq!(health.status(), HealthStatus::Serving, "Server should be serving");
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_bench_stream() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2210");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2210, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // The server streams every payload back, in order and intact
+    let result = client.bench(1024, 200).expect("Bench failed");
+    assert_eq!(result.received, 200, "All payloads should arrive");
+    assert_eq!(result.bytes, 200 * 1024, "Payload bytes do not match");
+    assert!(result.throughput > 0.0, "Throughput should be measured");
+
+    // Requests beyond the limits are refused, the connection stays usable
+    let refused = client.bench(1024 * 1024, 1);
+    assert_eq!(
+        refused.map_err(|e| e.kind()).unwrap_err(),
+        std::io::ErrorKind::InvalidInput,
+        "Oversized bench should be refused"
+    );
+    let result = client.bench(0, 3).expect("Bench after refusal failed");
+    assert_eq!(result.received, 3, "Connection should still work");
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_connection_stats() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Set up the server in a separate thread
+    let server = create_server("localhost:2220");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2220, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // Two answered requests, then one rejected as out of range
+    for _ in 0..2 {
+        client.add_request(AddRequest { a: 1, b: 2 }).expect("AddRequest failed");
+    }
+    assert!(client.bench(1024 * 1024, 1).is_err(), "Oversized bench should be refused");
+
+    // The counters cover exactly this connection
+    let stats = client.stats_request(StatsRequest {}).expect("StatsRequest failed");
+    assert_eq!(stats.messages_received, 4, "Three requests and the stats request itself");
+    assert_eq!(stats.messages_sent, 3, "The stats response is not counted yet");
+    assert!(stats.bytes_received > 0 && stats.bytes_sent > 0, "Byte counters should grow");
+    assert!(
+        stats.last_error.starts_with("Bench is limited"),
+        "Unexpected last error: {}",
+        stats.last_error
+    );
+
+    // A second connection starts from zero
+    let mut other = client::Client::new("localhost", 2220, 1000);
+    assert!(other.connect().is_ok(), "Failed to connect to the server");
+    let stats = other.stats_request(StatsRequest {}).expect("StatsRequest failed");
+    assert_eq!(stats.messages_received, 1, "New connection should only count its own request");
+    assert!(stats.last_error.is_empty(), "New connection has no error");
+    assert!(other.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Disconnect the client
+    assert!(
+        client.disconnect().is_ok(),
+        "Failed to disconnect from the server"
+    );
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_protocol_violations_close_connection() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Allow two undecodable frames and one oversized frame per window
+    let config = ServerConfig {
+        violation_policy: ViolationPolicy {
+            max_decode_failures: 2,
+            max_oversized_frames: 1,
+            ..ViolationPolicy::default()
+        },
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2230", config).expect("Failed to start server");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2230, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // An oversized frame within the allowance is skipped, the next request is answered
+    let mut oversized = vec![0x81, 0x80, 0x04]; // Announces 65537 bytes
+    oversized.extend(std::iter::repeat_n(0x55, 65537));
+    assert!(client.send_raw(&oversized).is_ok(), "Failed to send oversized frame");
+    let sum = client.add_request(AddRequest { a: 2, b: 3 }).expect("Request after skipped frame failed");
+    assert_eq!(sum.result, 5, "AddResponse result does not match");
+
+    // Invalid frames within the allowance get no reply, one more closes the connection
+    let garbage = [0x02, 0xff, 0xff];
+    for _ in 0..3 {
+        assert!(client.send_raw(&garbage).is_ok(), "Failed to send invalid frame");
+    }
+    let response = client.receive().expect("Expected a final error before the close");
+    match response.message {
+        Some(server_message::Message::ErrorResponse(error)) => {
+            assert_eq!(error.code(), ErrorCode::ProtocolViolation, "Final error has the wrong code");
+        }
+        _ => panic!("Expected ErrorResponse, but received a different message"),
+    }
+    assert!(client.receive().is_err(), "Connection should be closed");
+
+    let counts = server.violation_counts();
+    assert_eq!(counts.decode_failures, 3, "Every invalid frame should be counted");
+    assert_eq!(counts.oversized_frames, 1, "The oversized frame should be counted");
+    assert_eq!(counts.disconnects, 1, "One connection should have been closed");
+
+    // The status page reports the same totals, undecodable frames never reach the per-type counters
+    let status = server.status();
+    assert_eq!(status.violations, counts, "Status should report the violation totals");
+    assert_eq!(status.messages.get("AddRequest"), Some(&1), "The answered request should be counted");
+    assert_eq!(status.messages.len(), 1, "Only decoded requests are counted by type");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_liveness_probes_close_unresponsive_connection() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Probe after 200 ms of silence and give up 300 ms after an unanswered probe
+    let config = ServerConfig {
+        liveness: LivenessConfig {
+            probe_after: Some(Duration::from_millis(200)),
+            probe_timeout: Duration::from_millis(300),
+            ..LivenessConfig::default()
+        },
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2240", config).expect("Failed to start server");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2240, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // A probe sent while the client was idle is acknowledged by the client and does not end the connection
+    thread::sleep(Duration::from_millis(500));
+    let sum = client.add_request(AddRequest { a: 4, b: 5 }).expect("Request after idle period failed");
+    assert_eq!(sum.result, 9, "AddResponse result does not match");
+    assert_eq!(server.connection_count(), 1, "Answered probes should keep the connection open");
+
+    // A client that stops reading never answers the next probe and is disconnected
+    thread::sleep(Duration::from_millis(300));
+    assert!(wait_until(|| server.connection_count() == 0), "Unresponsive connection was not closed");
+    assert!(client.receive().is_err(), "Connection should be closed");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[cfg(all(feature = "handoff", unix))]
+#[test]
+fn test_listener_handoff() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let old_server = create_server("localhost:2250");
+    let old_handle = setup_server_thread(old_server.clone());
+
+    // A client connected before the handoff stays with the old server
+    let mut old_client = client::Client::new("localhost", 2250, 1000);
+    assert!(old_client.connect().is_ok(), "Failed to connect to the server");
+
+    // The next server takes the listeners over the control socket instead of binding the port
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("handoff.sock");
+    let sender = {
+        let (old_server, path) = (old_server.clone(), path.clone());
+        thread::spawn(move || old_server.hand_off(&path))
+    };
+    // Once the control socket exists the old server has given up the address
+    assert!(wait_until(|| path.exists()), "Old server did not open the control socket");
+    let config = ServerConfig {
+        inherit_listeners: Some(path),
+        ..ServerConfig::default()
+    };
+    let new_server = Server::with_config("localhost:2250", config).expect("Failed to take over the listeners");
+    assert!(sender.join().unwrap().is_ok(), "Handoff failed");
+    assert!(!Arc::ptr_eq(&old_server, &new_server), "Handoff should create a separate server");
+    let new_handle = setup_server_thread(new_server.clone());
+
+    // New connections on the same port reach the new server
+    let mut new_client = client::Client::new("localhost", 2250, 1000);
+    assert!(new_client.connect().is_ok(), "Failed to connect after the handoff");
+    let sum = new_client.add_request(AddRequest { a: 1, b: 2 }).expect("Request to the new server failed");
+    assert_eq!(sum.result, 3, "AddResponse result does not match");
+    assert!(wait_until(|| new_server.connection_count() == 1), "New server should serve the new client");
+
+    // The old server keeps serving its connection until it closes
+    let sum = old_client.add_request(AddRequest { a: 3, b: 4 }).expect("Request to the old server failed");
+    assert_eq!(sum.result, 7, "AddResponse result does not match");
+    assert_eq!(old_server.connection_count(), 1, "Old server should only keep its own client");
+    assert!(old_client.disconnect().is_ok(), "Failed to disconnect from the server");
+    assert!(old_server.drain(Duration::from_secs(1)), "Old server did not drain");
+
+    // Stop both servers and wait for their threads to finish
+    old_server.stop();
+    assert!(old_handle.join().is_ok(), "Old server thread panicked or failed to join");
+    assert!(new_client.disconnect().is_ok(), "Failed to disconnect from the server");
+    new_server.stop();
+    assert!(new_handle.join().is_ok(), "New server thread panicked or failed to join");
+}
+
+// Exporter keeping every snapshot it was given
+struct RecordingExporter(Arc<Mutex<Vec<Vec<Metric>>>>);
+
+impl MetricsExporter for RecordingExporter {
+    fn export(&mut self, metrics: &[Metric]) {
+        self.0.lock().unwrap().push(metrics.to_vec());
+    }
+}
+
+#[test]
+fn test_metrics_export() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2270");
+    let handle = setup_server_thread(server.clone());
+    let exports = Arc::new(Mutex::new(Vec::new()));
+    let job = server.export_metrics(Duration::from_millis(20), RecordingExporter(Arc::clone(&exports)));
+    assert_eq!(exports.lock().unwrap().len(), 1, "Metrics should be exported right away");
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2270, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let sum = client.add_request(AddRequest { a: 1, b: 1 }).expect("Request failed");
+    assert_eq!(sum.result, 2, "AddResponse result does not match");
+
+    let value = |metrics: &[Metric], name: &str| metrics.iter().find(|metric| metric.name == name).map(|metric| metric.value);
+    let metrics = server.metrics();
+    assert_eq!(value(&metrics, "connections"), Some(1), "Connected client should be counted");
+    assert_eq!(value(&metrics, "requests_total"), Some(1), "Answered request should be counted");
+    assert!(
+        metrics.iter().filter(|metric| metric.name.ends_with("_total")).all(|metric| metric.kind == MetricKind::Counter),
+        "Totals should be counters"
+    );
+
+    // Later exports carry the current values under the same names
+    assert!(
+        wait_until(|| exports.lock().unwrap().last().is_some_and(|last| value(last, "requests_total") == Some(1))),
+        "Periodic export did not pick up the request"
+    );
+    let names = |metrics: &[Metric]| metrics.iter().map(|metric| metric.name).collect::<Vec<_>>();
+    let first = names(&exports.lock().unwrap()[0]);
+    assert_eq!(first, names(&metrics), "Exports should keep the same metrics in the same order");
+    assert!(server.cancel_job(job), "Export job should be cancellable");
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_recent_events() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2280");
+    let handle = setup_server_thread(server.clone());
+
+    // A client that connects and leaves again
+    let mut first = client::Client::new("localhost", 2280, 1000);
+    assert!(first.connect().is_ok(), "Failed to connect to the server");
+    assert!(first.disconnect().is_ok(), "Failed to disconnect from the server");
+    assert!(wait_until(|| server.connection_count() == 0), "First client did not disconnect");
+
+    // The events are queryable over the protocol, newest first
+    let mut client = client::Client::new("localhost", 2280, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let response = client
+        .recent_events_request(RecentEventsRequest { limit: 0 })
+        .expect("RecentEventsRequest failed");
+    let kinds: Vec<_> = response.events.iter().map(|event| event.kind()).collect();
+    assert_eq!(
+        kinds,
+        [EventKind::Connected, EventKind::Disconnected, EventKind::Connected],
+        "Unexpected events: {:?}",
+        response.events
+    );
+    assert!(response.events.windows(2).all(|pair| pair[0].at_us >= pair[1].at_us), "Events should be newest first");
+    assert_eq!(response.dropped, 0, "Nothing should have been overwritten");
+
+    // A limit returns only the newest events, the same ones the server reports in-process
+    let response = client
+        .recent_events_request(RecentEventsRequest { limit: 1 })
+        .expect("RecentEventsRequest failed");
+    assert_eq!(response.events.len(), 1, "Limit should cap the events returned");
+    assert_eq!(server.recent_events()[0], response.events[0], "In-process and protocol views should agree");
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_slow_requests_reported() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // A zero threshold makes every request count as slow
+    let config = ServerConfig {
+        slow_request_threshold: Some(Duration::ZERO),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2290", config).expect("Failed to start server");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2290, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let sum = client.add_request(AddRequest { a: 6, b: 7 }).expect("Request failed");
+    assert_eq!(sum.result, 13, "AddResponse result does not match");
+
+    // The request is counted and recorded with its type and size
+    let slow = server.metrics().into_iter().find(|metric| metric.name == "slow_requests_total");
+    assert_eq!(slow.map(|metric| metric.value), Some(1), "Slow request should be counted");
+    let event = server
+        .recent_events()
+        .into_iter()
+        .find(|event| event.kind() == EventKind::SlowRequest)
+        .expect("Slow request should be recorded as an event");
+    assert!(event.detail.starts_with("AddRequest from "), "Unexpected detail: {}", event.detail);
+    assert!(event.detail.ends_with(" bytes"), "Unexpected detail: {}", event.detail);
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_message_size_stats() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2300");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2300, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // One tiny and one 2000 byte echo, metadata adds a few dozen bytes to each
+    for content in ["tiny".to_string(), "x".repeat(2000)] {
+        let echo = client.echo_message(EchoMessage { content: content.clone() }).expect("Echo failed");
+        assert_eq!(echo.content, content, "Echoed content does not match");
+    }
+
+    let stats = server.message_stats();
+    let echo = stats.get("EchoMessage").expect("Echo requests should be tracked");
+    assert_eq!(echo.count, 2, "Both echoes should be counted");
+    assert!(echo.max_bytes > 2000 && echo.max_bytes <= 4096, "Unexpected largest size: {}", echo.max_bytes);
+    assert_eq!(echo.buckets[..2].iter().sum::<u64>(), 1, "Tiny echo belongs in the smallest buckets: {:?}", echo.buckets);
+    assert_eq!(echo.buckets[4], 1, "Large echo belongs in the up to 4 KiB bucket: {:?}", echo.buckets);
+    assert_eq!(echo.mean_bytes(), echo.total_bytes / 2);
+
+    // The metrics carry the same distribution across all types
+    let metrics = server.metrics();
+    let value = |name: &str| metrics.iter().find(|metric| metric.name == name).map(|metric| metric.value);
+    assert_eq!(value("request_bytes_total"), Some(echo.total_bytes), "Byte total should match");
+    assert_eq!(value("requests_up_to_4096_bytes_total"), Some(1), "Bucket counter should match");
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_read_window_adapts() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2310");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2310, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // A new connection starts with the smallest window
+    let stats = client.stats_request(StatsRequest {}).expect("StatsRequest failed");
+    assert_eq!(stats.read_window, 512, "Window should start small");
+
+    // A large frame arrives in reads that fill the window, so it grows
+    let content = "x".repeat(20_000);
+    let echo = client.echo_message(EchoMessage { content: content.clone() }).expect("Echo failed");
+    assert_eq!(echo.content, content, "Echoed content does not match");
+    let stats = client.stats_request(StatsRequest {}).expect("StatsRequest failed");
+    assert!(stats.read_window > 512, "Window should grow, got {}", stats.read_window);
+
+    // Enough small requests shrink it back
+    for _ in 0..64 {
+        client.add_request(AddRequest { a: 1, b: 2 }).expect("AddRequest failed");
+    }
+    let stats = client.stats_request(StatsRequest {}).expect("StatsRequest failed");
+    assert_eq!(stats.read_window, 512, "Window should shrink back");
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_accept_burst() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2320");
+    let handle = setup_server_thread(server.clone());
+
+    // A burst of devices reconnecting at once, admitted one per poll they would take seconds
+    let mut clients: Vec<client::Client> = (0..50).map(|_| client::Client::new("localhost", 2320, 1000)).collect();
+    for client in &mut clients {
+        assert!(client.connect().is_ok(), "Failed to connect to the server");
+    }
+    assert!(
+        wait_until(|| server.connection_count() == 50),
+        "Burst not admitted in time, {} connected",
+        server.connection_count()
+    );
+
+    // Disconnect the clients
+    for client in &mut clients {
+        assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    }
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_busy_poll_accept() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // A zero interval never sleeps between accept polls
+    let config = ServerConfig {
+        poll_interval: Duration::ZERO,
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2330", config).expect("Failed to start server");
+    let handle = setup_server_thread(server.clone());
+
+    // One connection after another, each waiting a full poll interval would take two seconds
+    let started = Instant::now();
+    let mut clients = Vec::new();
+    for count in 1..=20 {
+        let mut client = client::Client::new("localhost", 2330, 1000);
+        assert!(client.connect().is_ok(), "Failed to connect to the server");
+        assert!(wait_until(|| server.connection_count() == count), "Connection {} not accepted", count);
+        clients.push(client);
+    }
+    assert!(started.elapsed() < Duration::from_secs(1), "Accepting took {:?}", started.elapsed());
+
+    // Requests are served as usual
+    let sum = clients[0].add_request(AddRequest { a: 1, b: 2 }).expect("AddRequest failed");
+    assert_eq!(sum.result, 3, "AddResponse result does not match");
+
+    // Disconnect the clients
+    for client in &mut clients {
+        assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    }
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_accept_filter() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2340");
+    let handle = setup_server_thread(server.clone());
+
+    // Reject every peer, remembering who asked
+    let seen: Arc<Mutex<Vec<SocketAddr>>> = Arc::new(Mutex::new(Vec::new()));
+    {
+        let seen = Arc::clone(&seen);
+        server.set_accept_filter(move |addr| {
+            seen.lock().unwrap().push(addr);
+            false
+        });
+    }
+    let mut client = client::Client::new("localhost", 2340, 1000);
+    assert!(client.connect().is_ok(), "TCP connect succeeds before the filter runs");
+    assert!(wait_until(|| !seen.lock().unwrap().is_empty()), "Filter was not called");
+    assert!(seen.lock().unwrap()[0].ip().is_loopback(), "Filter should see the peer address");
+    assert!(client.add_request(AddRequest { a: 1, b: 2 }).is_err(), "Rejected connection should be closed");
+    assert_eq!(server.connection_count(), 0, "Rejected connection should not be counted");
+
+    // Without the filter the next connection is served
+    server.clear_accept_filter();
+    let mut client = client::Client::new("localhost", 2340, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let sum = client.add_request(AddRequest { a: 1, b: 2 }).expect("AddRequest failed");
+    assert_eq!(sum.result, 3, "AddResponse result does not match");
+    assert_eq!(seen.lock().unwrap().len(), 1, "Removed filter should not be called");
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_client_connect_hook() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2350");
+    let handle = setup_server_thread(server.clone());
+
+    // The hook sees the raw stream and can configure it
+    let mut client = client::Client::new("localhost", 2350, 1000);
+    let configured = Arc::new(Mutex::new(None));
+    {
+        let configured = Arc::clone(&configured);
+        client.on_connect(move |stream| {
+            stream.set_nodelay(true)?;
+            *configured.lock().unwrap() = Some((stream.nodelay()?, stream.peer_addr()?.port()));
+            Ok(())
+        });
+    }
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    assert_eq!(*configured.lock().unwrap(), Some((true, 2350)), "Hook should configure the new stream");
+    let sum = client.add_request(AddRequest { a: 1, b: 2 }).expect("AddRequest failed");
+    assert_eq!(sum.result, 3, "AddResponse result does not match");
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // A failing hook aborts the connect
+    let mut client = client::Client::new("localhost", 2350, 1000);
+    client.on_connect(|_| Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "No such device")));
+    let error = client.connect().expect_err("Connect should fail with the hook");
+    assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+    assert!(client.add_request(AddRequest { a: 1, b: 2 }).is_err(), "Client should not be connected");
+    assert!(wait_until(|| server.connection_count() == 0), "Dropped stream should be closed");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_split_client() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2360");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client, then split it
+    let mut client = client::Client::new("localhost", 2360, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let (mut reader, mut writer) = client.split().expect("Split failed");
+
+    // One thread only sends while this one only receives
+    let sender = thread::spawn(move || {
+        for a in 0..100 {
+            writer.send(AddRequest { a, b: 1 }).expect("Send failed");
+        }
+        writer
+    });
+    for a in 0..100 {
+        match reader.receive().expect("Receive failed").message {
+            Some(server_message::Message::AddResponse(sum)) => assert_eq!(sum.result, a + 1, "Responses out of order"),
+            other => panic!("Expected AddResponse, got {:?}", other),
+        }
+    }
+    let _writer = sender.join().expect("Sender thread panicked");
+
+    // An unconnected client cannot be split
+    let client = client::Client::new("localhost", 2360, 1000);
+    assert!(client.split().is_err(), "Split needs a connection");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_incoming_messages() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2370");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2370, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // A bench request makes the server push its payloads after the header
+    client
+        .send(BenchRequest { payload_size: 16, count: 5 })
+        .expect("Send failed");
+    let messages: Vec<_> = client
+        .incoming()
+        .take(6)
+        .map(|message| message.expect("Receive failed").message)
+        .collect();
+    assert!(matches!(messages[0], Some(server_message::Message::BenchResponse(_))), "Header comes first");
+    for (index, message) in messages[1..].iter().enumerate() {
+        match message {
+            Some(server_message::Message::BenchPayload(payload)) => assert_eq!(payload.index, index as u32),
+            other => panic!("Expected BenchPayload, got {:?}", other),
+        }
+    }
+
+    // Stop the server, which closes the connection and ends the iteration
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+    assert!(client.incoming().next().is_none(), "Iteration should end when the server closes the connection");
+}
+
+#[cfg(feature = "chaos")]
+#[test]
+fn test_fault_injection() {
+    use embedded_recruitment_task::chaos::{FaultRates, RandomFaults};
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2380");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2380, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // Every request fails with an internal error
+    let always_fail = FaultRates {
+        error: 1.0,
+        ..FaultRates::default()
+    };
+    server.add_fault_injector(RandomFaults::new(always_fail, 1));
+    let error = client.add_request(AddRequest { a: 1, b: 2 }).expect_err("Request should fail");
+    assert!(error.to_string().contains("Internal"), "Unexpected error: {}", error);
+
+    // Every request is answered late
+    server.clear_fault_injectors();
+    let always_late = FaultRates {
+        delay: 1.0,
+        delay_for: Duration::from_millis(200),
+        ..FaultRates::default()
+    };
+    server.add_fault_injector(RandomFaults::new(always_late, 1));
+    let started = Instant::now();
+    let sum = client.add_request(AddRequest { a: 1, b: 2 }).expect("Delayed request should succeed");
+    assert_eq!(sum.result, 3, "AddResponse result does not match");
+    assert!(started.elapsed() >= Duration::from_millis(200), "Response came too early");
+
+    // Without injectors requests are handled normally
+    server.clear_fault_injectors();
+    let sum = client.add_request(AddRequest { a: 2, b: 2 }).expect("AddRequest failed");
+    assert_eq!(sum.result, 4, "AddResponse result does not match");
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_liveness_on_manual_clock() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Timeouts far longer than the test, they only pass on the virtual clock
+    let config = ServerConfig {
+        liveness: LivenessConfig {
+            probe_after: Some(Duration::from_secs(30)),
+            probe_timeout: Duration::from_secs(10),
+            ..LivenessConfig::default()
+        },
+        ..ServerConfig::default()
+    };
+    let clock = Arc::new(ManualClock::new());
+    let server = Server::with_clock("localhost:2390", config, clock.clone()).expect("Failed to start server");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client, which then stops reading
+    let mut client = client::Client::new("localhost", 2390, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    assert!(wait_until(|| server.connection_count() == 1), "Connection was not accepted");
+
+    // Half a minute of silence sends a probe, the connection stays open until it times out
+    clock.advance(Duration::from_secs(31));
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(server.connection_count(), 1, "A pending probe should not close the connection yet");
+
+    // The unanswered probe times out without any real waiting
+    clock.advance(Duration::from_secs(11));
+    assert!(wait_until(|| server.connection_count() == 0), "Unresponsive connection was not closed");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_get_schema() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2400");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2400, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // The server answers with exactly the descriptor it was built with
+    let response = client.get_schema_request(GetSchemaRequest {}).expect("GetSchemaRequest failed");
+    assert_eq!(response.file_descriptor_set, FILE_DESCRIPTOR_SET, "Schema differs from the embedded one");
+    let set = FileDescriptorSet::decode(&response.file_descriptor_set[..]).expect("Schema should decode");
+    let names: Vec<_> = set.file.iter().flat_map(|file| &file.message_type).map(|message| message.name()).collect();
+    assert!(names.contains(&"GetSchemaRequest"), "Schema should describe itself: {:?}", names);
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[cfg(feature = "reflect")]
+#[test]
+fn test_dynamic_message_routing() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2410");
+    let handle = setup_server_thread(server.clone());
+
+    // A gateway handler for a type the server was not compiled with, answering with the payload reversed
+    let mut set = FileDescriptorSet::decode(FILE_DESCRIPTOR_SET).unwrap();
+    set.file[0].package = Some("gateway".to_string());
+    server.add_dynamic_schema(&set);
+    server.route_dynamic("gateway.EchoMessage", |type_name: &str, payload: &[u8]| {
+        if payload.is_empty() {
+            return Err(ErrorResponse::new(ErrorCode::InvalidRequest, "Empty reading"));
+        }
+        let content: String = EchoMessage::decode(payload).unwrap().content.chars().rev().collect();
+        Ok(DynamicMessage::new(type_name, EchoMessage { content }.encode_to_vec()))
+    });
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2410, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // A valid payload reaches the handler and its answer comes back
+    let payload = EchoMessage { content: "abc".to_string() }.encode_to_vec();
+    let response = client
+        .dynamic_message(DynamicMessage::new("gateway.EchoMessage", payload))
+        .expect("Routed dynamic message failed");
+    assert_eq!(response.type_name, "gateway.EchoMessage");
+    assert_eq!(EchoMessage::decode(&response.payload[..]).unwrap().content, "cba");
+
+    // Handler errors, invalid payloads and unrouted types are answered with an ErrorResponse
+    let error = client.dynamic_message(DynamicMessage::new("gateway.EchoMessage", Vec::new())).unwrap_err();
+    assert!(error.to_string().contains("Empty reading"), "Unexpected error: {}", error);
+    let error = client.dynamic_message(DynamicMessage::new("gateway.EchoMessage", vec![0x18, 0x01])).unwrap_err();
+    assert!(error.to_string().contains("InvalidRequest"), "Unexpected error: {}", error);
+    let error = client.dynamic_message(DynamicMessage::new("gateway.AddRequest", Vec::new())).unwrap_err();
+    assert!(error.to_string().contains("Unsupported"), "Unexpected error: {}", error);
+
+    // Without routes every dynamic message is unsupported
+    server.clear_dynamic_routes();
+    let payload = EchoMessage { content: "abc".to_string() }.encode_to_vec();
+    let error = client.dynamic_message(DynamicMessage::new("gateway.EchoMessage", payload)).unwrap_err();
+    assert!(error.to_string().contains("Unsupported"), "Unexpected error: {}", error);
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    // Stop the server and wait for thread to finish
+    server.stop();
+    assert!(
+        handle.join().is_ok(),
+        "Server thread panicked or failed to join"
+    );
+}
+
+#[cfg(feature = "gateway")]
+#[test]
+fn test_gateway_forwards_to_upstream() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let upstream = create_server("localhost:2420");
+    let upstream_handle = setup_server_thread(upstream.clone());
+
+    // A gateway forwarding additions upstream and answering everything else itself
+    let config = ServerConfig {
+        gateway: Some(GatewayConfig {
+            timeout: Duration::from_millis(500),
+            ..GatewayConfig::new("localhost:2420", &["AddRequest"])
+        }),
+        ..ServerConfig::default()
+    };
+    let gateway = Server::with_config("localhost:2421", config).expect("Failed to start gateway");
+    let gateway_handle = setup_server_thread(gateway.clone());
+
+    // Create and connect the client to the gateway
+    let mut client = client::Client::new("localhost", 2421, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the gateway");
+
+    // Forwarded requests are answered by the upstream server, over one reused connection
+    for (a, b) in [(1, 2), (3, 4)] {
+        let sum = client.add_request(AddRequest { a, b }).expect("Forwarded AddRequest failed");
+        assert_eq!(sum.result, a + b, "AddResponse result does not match");
+    }
+    let echo = client
+        .echo_message(EchoMessage { content: "local".to_string() })
+        .expect("Local EchoMessage failed");
+    assert_eq!(echo.content, "local");
+    let upstream_stats = upstream.message_stats();
+    assert_eq!(upstream_stats.get("AddRequest").map(|stats| stats.count), Some(2), "Additions should go upstream");
+    assert!(!upstream_stats.contains_key("EchoMessage"), "Echo should be handled by the gateway");
+    assert_eq!(upstream.connection_count(), 1, "The upstream connection should be reused");
+
+    // Without the upstream server forwarded requests fail with an error response, local ones still work
+    upstream.stop();
+    assert!(upstream_handle.join().is_ok(), "Upstream server thread panicked or failed to join");
+    assert!(wait_until(|| upstream.connection_count() == 0), "Upstream connection was not closed");
+    let error = client.add_request(AddRequest { a: 1, b: 1 }).unwrap_err();
+    assert!(error.to_string().contains("UpstreamUnavailable"), "Unexpected error: {}", error);
+    assert!(client.echo_message(EchoMessage { content: "still here".to_string() }).is_ok());
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the gateway");
+
+    // Stop the gateway and wait for thread to finish
+    gateway.stop();
+    assert!(
+        gateway_handle.join().is_ok(),
+        "Gateway thread panicked or failed to join"
+    );
+}
+
+#[cfg(feature = "gateway")]
+#[test]
+fn test_gateway_circuit_breaker() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Nothing listens upstream yet; two failures open the breaker for 30 virtual seconds
+    let config = ServerConfig {
+        gateway: Some(GatewayConfig {
+            timeout: Duration::from_millis(500),
+            failure_threshold: 2,
+            open_for: Duration::from_secs(30),
+            ..GatewayConfig::new("localhost:2430", &["AddRequest"])
+        }),
+        ..ServerConfig::default()
+    };
+    let clock = Arc::new(ManualClock::new());
+    let gateway = Server::with_clock("localhost:2431", config, clock.clone()).expect("Failed to start gateway");
+    let gateway_handle = setup_server_thread(gateway.clone());
+
+    // Create and connect the client to the gateway
+    let mut client = client::Client::new("localhost", 2431, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the gateway");
+
+    // Failed connection attempts are reported as unavailable
+    for _ in 0..2 {
+        let error = client.add_request(AddRequest { a: 1, b: 2 }).unwrap_err();
+        assert!(error.to_string().contains("UpstreamUnavailable"), "Unexpected error: {}", error);
+        assert!(!error.to_string().contains("circuit"), "Breaker should still be closed: {}", error);
+    }
+
+    // The open breaker rejects without trying, even once the upstream server is back
+    let upstream = create_server("localhost:2430");
+    let upstream_handle = setup_server_thread(upstream.clone());
+    let error = client.add_request(AddRequest { a: 1, b: 2 }).unwrap_err();
+    assert!(error.to_string().contains("circuit breaker open"), "Unexpected error: {}", error);
+    assert!(upstream.message_stats().is_empty(), "Nothing should reach the upstream server");
+
+    // After cooling down a probe gets through and closes the breaker again
+    clock.advance(Duration::from_secs(31));
+    let sum = client.add_request(AddRequest { a: 1, b: 2 }).expect("Probe request failed");
+    assert_eq!(sum.result, 3, "AddResponse result does not match");
+    let sum = client.add_request(AddRequest { a: 2, b: 2 }).expect("Request after recovery failed");
+    assert_eq!(sum.result, 4, "AddResponse result does not match");
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the gateway");
+
+    // Stop both servers and wait for their threads to finish
+    gateway.stop();
+    upstream.stop();
+    assert!(gateway_handle.join().is_ok(), "Gateway thread panicked or failed to join");
+    assert!(upstream_handle.join().is_ok(), "Upstream server thread panicked or failed to join");
+}
+
+#[cfg(feature = "gateway")]
+#[test]
+fn test_gateway_serves_cached_responses_during_outage() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let upstream = create_server("localhost:2440");
+    let upstream_handle = setup_server_thread(upstream.clone());
+
+    // Additions are read-only, their responses may be served for a minute after the upstream server went away
+    let config = ServerConfig {
+        gateway: Some(GatewayConfig {
+            timeout: Duration::from_millis(500),
+            cache: vec![CachePolicy {
+                message_type: "AddRequest".to_string(),
+                ttl: Duration::from_secs(60),
+            }],
+            ..GatewayConfig::new("localhost:2440", &["AddRequest"])
+        }),
+        ..ServerConfig::default()
+    };
+    let clock = Arc::new(ManualClock::new());
+    let gateway = Server::with_clock("localhost:2441", config, clock.clone()).expect("Failed to start gateway");
+    let gateway_handle = setup_server_thread(gateway.clone());
+
+    // Create and connect the client to the gateway
+    let mut client = client::Client::new("localhost", 2441, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the gateway");
+    let add = |client: &mut client::Client, a: i32, b: i32| {
+        client.call(client_message::Message::AddRequest(AddRequest { a, b })).expect("Request failed")
+    };
+
+    // Fresh upstream responses are not marked stale
+    let response = add(&mut client, 1, 2);
+    assert_eq!(response.message, Some(server_message::Message::AddResponse(AddResponse { result: 3 })));
+    assert!(!response.metadata.unwrap().stale, "Upstream response should not be stale");
+
+    // During the outage the cached response is served with its age
+    upstream.stop();
+    assert!(upstream_handle.join().is_ok(), "Upstream server thread panicked or failed to join");
+    assert!(wait_until(|| upstream.connection_count() == 0), "Upstream connection was not closed");
+    clock.advance(Duration::from_secs(20));
+    let response = add(&mut client, 1, 2);
+    assert_eq!(response.message, Some(server_message::Message::AddResponse(AddResponse { result: 3 })));
+    let metadata = response.metadata.unwrap();
+    assert!(metadata.stale, "Cached response should be marked stale");
+    assert_eq!(metadata.age_ms, 20_000, "Age should follow the gateway clock");
+
+    // Requests never answered before, and responses past their TTL, fail as unavailable
+    let unavailable = |response: &ServerMessage| match &response.message {
+        Some(server_message::Message::ErrorResponse(error)) => error.code() == ErrorCode::UpstreamUnavailable,
+        _ => false,
+    };
+    let response = add(&mut client, 2, 2);
+    assert!(unavailable(&response), "Unexpected response: {:?}", response);
+    clock.advance(Duration::from_secs(41));
+    let response = add(&mut client, 1, 2);
+    assert!(unavailable(&response), "Expired response should not be served: {:?}", response);
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the gateway");
+
+    // Stop the gateway and wait for thread to finish
+    gateway.stop();
+    assert!(
+        gateway_handle.join().is_ok(),
+        "Gateway thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_graceful_shutdown_sends_go_away() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2450");
+    let handle = setup_server_thread(server.clone());
+
+    // Create and connect the client
+    let mut client = client::Client::new("localhost", 2450, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    assert!(client.add_request(AddRequest { a: 1, b: 2 }).is_ok(), "AddRequest failed");
+
+    // Draining tells the connected client to go away and reports it to health checks
+    server.start_draining(Duration::from_secs(5));
+    assert!(server.is_draining());
+    assert_eq!(server.health().status, HealthStatus::Draining);
+    let (mut reader, mut writer) = client.split().expect("Failed to split the client");
+    let go_away = reader.receive().expect("Failed to receive GoAway");
+    assert_eq!(
+        go_away.message,
+        Some(server_message::Message::GoAway(GoAway { retry_after_ms: 5000 }))
+    );
+
+    // The client is still served until it leaves
+    assert!(writer.send(HealthRequest {}).is_ok(), "Failed to send HealthRequest while draining");
+    match reader.receive().expect("HealthRequest failed while draining").message {
+        Some(server_message::Message::HealthResponse(health)) => assert_eq!(health.status(), HealthStatus::Draining),
+        other => panic!("Unexpected response: {:?}", other),
+    }
+    drop((reader, writer));
+
+    // With every client gone the shutdown completes within the grace period
+    assert!(server.shutdown(Duration::from_secs(5), Duration::from_secs(1)), "Clients did not drain in time");
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_client_follows_go_away_to_next_endpoint() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let first = create_server("localhost:2460");
+    let first_handle = setup_server_thread(first.clone());
+    let second = create_server("localhost:2461");
+    let second_handle = setup_server_thread(second.clone());
+
+    // Create and connect the client to the first server
+    let mut client = client::Client::with_endpoints(&[("localhost", 2460), ("localhost", 2461)], 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the first server");
+    assert!(client.add_request(AddRequest { a: 1, b: 2 }).is_ok(), "AddRequest failed");
+
+    // Requests keep succeeding while the first server drains, the client moves on without an error
+    first.start_draining(Duration::ZERO);
+    for a in 0..3 {
+        let sum = client.add_request(AddRequest { a, b: 1 }).expect("AddRequest failed during the move");
+        assert_eq!(sum.result, a + 1);
+        thread::sleep(Duration::from_millis(150));
+    }
+    assert_eq!(
+        client.take_events(),
+        vec![client::ClientEvent::Reconnected {
+            from: "localhost:2460".to_string(),
+            to: "localhost:2461".to_string(),
+        }]
+    );
+    assert!(second.message_stats().contains_key("AddRequest"), "Later requests should reach the second server");
+
+    // The first server is left without clients and shuts down at once
+    assert!(first.shutdown(Duration::ZERO, Duration::from_secs(1)), "The client did not leave the first server");
+    assert!(first_handle.join().is_ok(), "First server thread panicked or failed to join");
+
+    // Disconnect the client
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the second server");
+
+    // Stop the second server and wait for thread to finish
+    second.stop();
+    assert!(
+        second_handle.join().is_ok(),
+        "Second server thread panicked or failed to join"
+    );
+}
+
+#[test]
+fn test_sheds_load_over_memory_budget() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // One connection alone fills the budget, the shared buffer pool is far below it
+    let config = ServerConfig {
+        memory_budget: Some(MemoryBudget {
+            limit: 1 << 30,
+            connection_cost: 1 << 30,
+            large_request: 1024,
+        }),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2480", config).expect("Failed to create server");
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2480, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let is_overloaded = |response: &ServerMessage| match &response.message {
+        Some(server_message::Message::ErrorResponse(error)) => error.code() == ErrorCode::Overloaded,
+        _ => false,
+    };
+
+    // Small requests are still served, large ones are shed
+    client.send(EchoMessage { content: "small".to_string() }).unwrap();
+    let response = client.receive().expect("Failed to receive echo");
+    assert!(
+        matches!(response.message, Some(server_message::Message::EchoMessage(ref echo)) if echo.content == "small"),
+        "Small request should be served: {}",
+        response
+    );
+    client.send(EchoMessage { content: "x".repeat(2048) }).unwrap();
+    let response = client.receive().expect("Failed to receive rejection");
+    assert!(is_overloaded(&response), "Large request should be shed: {}", response);
+
+    // A second connection is told why before it is closed
+    let mut rejected = client::Client::new("localhost", 2480, 1000);
+    assert!(rejected.connect().is_ok(), "TCP connect should still succeed");
+    let response = rejected.receive().expect("Failed to receive rejection");
+    assert!(is_overloaded(&response), "New connection should be shed: {}", response);
+
+    let shed = server.metrics().into_iter().find(|metric| metric.name == "shed_total").map(|metric| metric.value);
+    assert_eq!(shed, Some(2), "Both rejections should be counted");
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(feature = "reflect")]
+#[test]
+fn test_sheds_data_requests_while_handlers_are_slow() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Rejects every data request once the average latency reaches twice the threshold
+    let config = ServerConfig {
+        load_shedding: Some(LoadShedding {
+            latency_threshold: Duration::from_millis(1),
+            queue_threshold: 1000,
+            max_reject_percent: 100,
+        }),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2481", config).expect("Failed to create server");
+    server.route_dynamic("messages.EchoMessage", |type_name: &str, payload: &[u8]| {
+        thread::sleep(Duration::from_millis(40));
+        Ok(DynamicMessage::new(type_name, payload.to_vec()))
+    });
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2481, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // One slow request lifts the average far above the threshold
+    let payload = EchoMessage { content: "slow".to_string() }.encode_to_vec();
+    client
+        .dynamic_message(DynamicMessage::new("messages.EchoMessage", payload))
+        .expect("Slow request should be served before the server is loaded");
+
+    // Control traffic is still answered, data traffic is rejected
+    client.send(HealthRequest {}).unwrap();
+    let response = client.receive().expect("Failed to receive health");
+    assert!(
+        matches!(response.message, Some(server_message::Message::HealthResponse(_))),
+        "Health checks must never be shed: {}",
+        response
+    );
+    client.send(EchoMessage { content: "data".to_string() }).unwrap();
+    let response = client.receive().expect("Failed to receive rejection");
+    let shed = match &response.message {
+        Some(server_message::Message::ErrorResponse(error)) => error.code() == ErrorCode::Overloaded,
+        _ => false,
+    };
+    assert!(shed, "Data request should be shed: {}", response);
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_timestamps_follow_the_server_clock() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // A simulated clock a day ahead of real time stands in for a disciplined one
+    let clock = Arc::new(ManualClock::new());
+    clock.advance(Duration::from_secs(24 * 3600));
+    let server = Server::with_clock("localhost:2482", ServerConfig::default(), clock.clone())
+        .expect("Failed to create server");
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2482, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    client.send(AddRequest { a: 1, b: 1 }).unwrap();
+    let response = client.receive().expect("Failed to receive response");
+    let timestamps = response
+        .metadata
+        .and_then(|metadata| metadata.timestamps)
+        .expect("Response should carry timestamps");
+
+    // The manual clock doesn't move on its own, both stamps are its wall-clock time
+    let expected = clock.wall().duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+    assert_eq!(timestamps.server_receive_us, expected);
+    assert_eq!(timestamps.server_respond_us, expected);
+    let events = server.recent_events();
+    assert!(events.iter().all(|event| event.at_us == expected), "Events should follow the clock too");
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_server_numbers_every_message() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2483");
+    let handle = setup_server_thread(server.clone());
+
+    // Responses, errors and streamed payloads all count, starting from 1 on each connection
+    for _ in 0..2 {
+        let mut client = client::Client::new("localhost", 2483, 1000);
+        assert!(client.connect().is_ok(), "Failed to connect to the server");
+        client.send(AddRequest { a: 1, b: 2 }).unwrap();
+        client
+            .send(BenchRequest {
+                payload_size: u32::MAX,
+                count: 1,
+            })
+            .unwrap();
+        client
+            .send(BenchRequest {
+                payload_size: 8,
+                count: 2,
+            })
+            .unwrap();
+        let sequences: Vec<u64> = (0..5)
+            .map(|_| client.receive().expect("Failed to receive").metadata.unwrap_or_default().server_sequence)
+            .collect();
+        assert_eq!(sequences, [1, 2, 3, 4, 5]);
+        assert!(client.take_events().is_empty(), "Nothing was missed");
+        assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    }
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_client_reports_missed_messages() {
+    use std::{io::Write, net::TcpListener};
+
+    // A stand-in server whose second message got lost
+    let listener = TcpListener::bind("localhost:2484").expect("Failed to bind");
+    let fake = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        for server_sequence in [1, 3] {
+            let mut message = ServerMessage::add(server_sequence as i32);
+            message.metadata = Some(Metadata {
+                server_sequence,
+                ..Metadata::default()
+            });
+            stream.write_all(&message.encode_length_delimited_to_vec()).unwrap();
+        }
+    });
+
+    let mut client = client::Client::new("localhost", 2484, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the stand-in server");
+    assert!(client.receive().is_ok() && client.receive().is_ok(), "Failed to receive");
+    assert_eq!(client.take_events(), [client::ClientEvent::MissedMessages { after: 1, next: 3 }]);
+    assert!(fake.join().is_ok(), "Stand-in server panicked");
+}
+
+#[test]
+fn test_resync_replays_buffered_messages() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        resync_buffer: 64,
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2485", config).expect("Failed to create server");
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2485, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    for a in 1..=10 {
+        client.send(AddRequest { a, b: 0 }).unwrap();
+        assert!(client.receive().is_ok(), "Failed to receive");
+    }
+    let mut resync = |last_seen_seq| {
+        client.send(ResyncRequest { last_seen_seq }).unwrap();
+        match client.receive().expect("Failed to receive").message {
+            Some(server_message::Message::ResyncResponse(response)) => response,
+            other => panic!("Expected a resync response, got {:?}", other),
+        }
+    };
+
+    // A short gap is replayed in full, with the original sequence numbers
+    let response = resync(8);
+    let sequences: Vec<u64> = response
+        .messages
+        .iter()
+        .map(|message| message.metadata.clone().unwrap_or_default().server_sequence)
+        .collect();
+    assert_eq!(sequences, [9, 10]);
+    assert_eq!(response.messages[1].message, Some(ServerMessage::add(10).message.unwrap()));
+    assert!(response.complete);
+
+    // The oldest messages were evicted to stay within the buffer size
+    let response = resync(0);
+    assert!(!response.messages.is_empty() && response.messages.len() < 10);
+    assert!(!response.complete, "Evicted messages can't be replayed");
+
+    // Resync responses aren't buffered themselves
+    let response = resync(10);
+    assert!(response.messages.is_empty() && response.complete);
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(feature = "storage")]
+#[test]
+fn test_outbox_delivers_requests_made_while_disconnected() {
+    use embedded_recruitment_task::outbox::Outbox;
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("outbox.log");
+    let server = create_server("localhost:2486");
+    let handle = setup_server_thread(server.clone());
+
+    // Requests made before the link comes up wait on disk
+    let mut client = client::Client::new("localhost", 2486, 1000);
+    client.set_outbox(Outbox::open(&path).expect("Failed to open outbox"));
+    let first = client.send_durable(AddRequest { a: 1, b: 1 }).unwrap();
+    client.send_durable(AddRequest { a: 2, b: 2 }).unwrap();
+    assert_eq!(client.outbox_len(), 2);
+
+    // Connecting sends them in order, and a request made while connected goes out at once
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    client.send_durable(AddRequest { a: 3, b: 3 }).unwrap();
+    let results: Vec<_> = (0..3).map(|_| client.receive().expect("Failed to receive").message).collect();
+    assert_eq!(
+        results,
+        [2, 4, 6].map(|result| Some(server_message::Message::AddResponse(AddResponse { result })))
+    );
+    assert_eq!(client.outbox_len(), 0, "Answered requests leave the outbox");
+    assert!(Outbox::open(&path).unwrap().is_empty(), "Delivery should have been persisted");
+
+    // A request resent after a crash is applied once, the stored response comes back
+    client.send_command(AddRequest { a: 5, b: 5 }.into(), &first).unwrap();
+    assert_eq!(
+        client.receive().expect("Failed to receive").message,
+        Some(server_message::Message::AddResponse(AddResponse { result: 2 }))
+    );
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(all(feature = "storage", feature = "gateway"))]
+#[test]
+fn test_outbox_keeps_requests_failing_with_retryable_errors() {
+    use embedded_recruitment_task::outbox::Outbox;
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let dir = tempfile::tempdir().unwrap();
+    // A gateway whose upstream server isn't up yet
+    let config = ServerConfig {
+        gateway: Some(GatewayConfig {
+            timeout: Duration::from_millis(500),
+            failure_threshold: 100,
+            ..GatewayConfig::new("localhost:2500", &["AddRequest"])
+        }),
+        ..ServerConfig::default()
+    };
+    let gateway = Server::with_config("localhost:2499", config).expect("Failed to start gateway");
+    let gateway_handle = setup_server_thread(gateway.clone());
+
+    let mut client = client::Client::new("localhost", 2499, 1000);
+    client.set_outbox(Outbox::open(&dir.path().join("outbox.log")).expect("Failed to open outbox"));
+    assert!(client.connect().is_ok(), "Failed to connect to the gateway");
+    client.send_durable(AddRequest { a: 2, b: 3 }).unwrap();
+
+    // The failure is reported, but the request stays queued
+    let response = client.receive().expect("Failed to receive");
+    let unavailable = matches!(&response.message,
+        Some(server_message::Message::ErrorResponse(error)) if error.code() == ErrorCode::UpstreamUnavailable);
+    assert!(unavailable, "Expected the upstream to be unavailable, got {}", response);
+    assert_eq!(client.outbox_len(), 1, "A retryable error should not dequeue the request");
+
+    // Once the upstream is up the request is resent after the backoff, and delivered
+    let upstream = create_server("localhost:2500");
+    let upstream_handle = setup_server_thread(upstream.clone());
+    assert_eq!(
+        client.receive().expect("Failed to receive").message,
+        Some(server_message::Message::AddResponse(AddResponse { result: 5 }))
+    );
+    assert_eq!(client.outbox_len(), 0, "Delivered request should leave the outbox");
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the gateway");
+    for (server, handle) in [(gateway, gateway_handle), (upstream, upstream_handle)] {
+        server.stop();
+        assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+    }
+}
+
+#[test]
+fn test_cached_responses_outlive_the_server() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2487");
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2487, 1000);
+    client.set_cache(Duration::from_millis(200));
+    let fresh = client.call_cached(HealthRequest {}, false).expect("Failed to reach the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    // Within the TTL the server isn't asked at all
+    let cached = client.call_cached(HealthRequest {}, false).expect("Cached response expected");
+    assert_eq!(cached.message, fresh.message);
+    assert!(!cached.metadata.unwrap_or_default().stale);
+
+    // Past it, an unreachable server is an error unless stale data will do
+    thread::sleep(Duration::from_millis(250));
+    assert!(client.call_cached(HealthRequest {}, false).is_err(), "Expired entry must not be served");
+    let stale = client.call_cached(HealthRequest {}, true).expect("Stale response expected");
+    assert_eq!(stale.message, fresh.message);
+    let metadata = stale.metadata.unwrap_or_default();
+    assert!(metadata.stale, "Response should be marked stale");
+    assert!(metadata.age_ms >= 250, "Age {} ms too low", metadata.age_ms);
+
+    // Requests never answered have nothing to fall back on
+    assert!(client.call_cached(StatsRequest {}, true).is_err());
+}
+
+#[cfg(feature = "delta")]
+#[test]
+fn test_delta_requests_rebuild_large_payloads() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        delta_baselines: 4,
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2488", config).expect("Failed to create server");
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2488, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    // A config blob of 100 settings, most of which stay the same between versions
+    let blob = |version: i32| -> String {
+        (0..100).map(|line| format!("setting_{} = {}\n", line, line * version)).collect()
+    };
+    let echo = |content: &str| client_message::Message::from(EchoMessage { content: content.to_string() });
+    let echoed = |response: ServerMessage| match response.message {
+        Some(server_message::Message::EchoMessage(echo)) => echo.content,
+        other => panic!("Expected an echo, got {:?}", other),
+    };
+
+    // The first version goes in full, the next ones as differences to the one before
+    let mut baseline = echo(&blob(1));
+    client.call(baseline.clone()).expect("Failed to send the blob");
+    for version in [2, 3] {
+        let next = echo(&blob(version));
+        let delta = ClientMessage::delta(&baseline, &next);
+        assert!(delta.encoded_len() < next.encoded_len() / 2, "The delta should be much smaller than the blob");
+        let response = client.call_delta(&baseline, next.clone()).expect("Failed to send the delta");
+        assert_eq!(echoed(response), blob(version));
+        baseline = next;
+    }
+
+    // A baseline the server never saw is rejected, the client falls back to the full request
+    let unknown = client.call(ClientMessage::delta(&echo(&blob(7)), &echo(&blob(8))).message.unwrap()).unwrap();
+    match unknown.message {
+        Some(server_message::Message::ErrorResponse(error)) => assert_eq!(error.code(), ErrorCode::UnknownBaseline),
+        other => panic!("Expected an unknown baseline error, got {:?}", other),
+    }
+    let response = client.call_delta(&echo(&blob(7)), echo(&blob(8))).expect("Failed to send the full request");
+    assert_eq!(echoed(response), blob(8));
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(feature = "dedup")]
+#[test]
+fn test_offered_content_is_uploaded_once() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        content_store: 64 * 1024,
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2489", config).expect("Failed to create server");
+    let handle = setup_server_thread(server.clone());
+    let image = "firmware image ".repeat(200);
+    let upload = client_message::Message::from(EchoMessage { content: image.clone() });
+
+    // The first device has to send the image, the next ones only offer it
+    let mut responses = Vec::new();
+    for _ in 0..2 {
+        let mut client = client::Client::new("localhost", 2489, 1000);
+        assert!(client.connect().is_ok(), "Failed to connect to the server");
+        let offer = client.offer_request(match ClientMessage::offer(&upload).message {
+            Some(client_message::Message::OfferRequest(offer)) => offer,
+            other => panic!("Expected an offer, got {:?}", other),
+        });
+        let have_it = offer.expect("Offer failed").have_it;
+        let response = if have_it { client.receive() } else { client.call(upload.clone()) };
+        responses.push((have_it, response.expect("Failed to upload").message));
+        assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    }
+    let echo = Some(server_message::Message::EchoMessage(EchoMessage { content: image }));
+    assert_eq!(responses, [(false, echo.clone()), (true, echo.clone())]);
+
+    // The client helper does the same in one call
+    let mut client = client::Client::new("localhost", 2489, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    assert_eq!(client.call_offered(upload).expect("Failed to upload").message, echo);
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(feature = "reflect")]
+#[test]
+fn test_slow_handlers_time_out() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        handler_timeout: Some(Duration::from_millis(50)),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2494", config).expect("Failed to create server");
+    let slow = |type_name: &str, payload: &[u8]| -> Result<DynamicMessage, ErrorResponse> {
+        thread::sleep(Duration::from_millis(200));
+        Ok(DynamicMessage::new(type_name, payload.to_vec()))
+    };
+    server.route_dynamic("messages.EchoMessage", slow);
+    server.route_dynamic_with_timeout("messages.AddRequest", slow, Duration::from_secs(2));
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2494, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // The default timeout answers the slow handler without waiting for it
+    let started = Instant::now();
+    let error = client.dynamic_message(DynamicMessage::new("messages.EchoMessage", Vec::new())).unwrap_err();
+    assert!(error.to_string().contains("HandlerTimeout"), "Unexpected error: {}", error);
+    assert!(started.elapsed() < Duration::from_millis(200), "The connection waited for the handler");
+
+    // A route's own limit replaces the default, and the connection keeps serving
+    let response = client.dynamic_message(DynamicMessage::new("messages.AddRequest", Vec::new()));
+    assert!(response.is_ok(), "Handler within its own limit failed: {:?}", response);
+    assert_eq!(client.call(AddRequest { a: 1, b: 2 }.into()).unwrap().message, Some(AddResponse { result: 3 }.into()));
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(feature = "reflect")]
+#[test]
+fn test_timed_out_handler_is_not_started_again_while_running() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        handler_timeout: Some(Duration::from_millis(50)),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2503", config).expect("Failed to create server");
+    let calls = Arc::new(Mutex::new(0));
+    {
+        let calls = Arc::clone(&calls);
+        server.route_dynamic("messages.EchoMessage", move |type_name: &str, payload: &[u8]| {
+            *calls.lock().unwrap() += 1;
+            thread::sleep(Duration::from_millis(300));
+            Ok(DynamicMessage::new(type_name, payload.to_vec()))
+        });
+    }
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2503, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let mut call = || client.dynamic_message(DynamicMessage::new("messages.EchoMessage", Vec::new())).unwrap_err();
+
+    // The first call times out, the ones made while it still runs are refused without starting the handler
+    let error = call();
+    assert!(error.to_string().contains("HandlerTimeout"), "Unexpected error: {}", error);
+    for _ in 0..5 {
+        let error = call();
+        assert!(error.to_string().contains("HandlerBusy"), "Unexpected error: {}", error);
+    }
+    assert_eq!(*calls.lock().unwrap(), 1, "The handler should run once while it overruns");
+
+    // Once the overrunning call has finished the route takes calls again
+    thread::sleep(Duration::from_millis(400));
+    let error = call();
+    assert!(error.to_string().contains("HandlerTimeout"), "Unexpected error: {}", error);
+    assert_eq!(*calls.lock().unwrap(), 2);
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(feature = "reflect")]
+#[test]
+fn test_timed_out_command_stays_pending_until_its_handler_returns() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        handler_timeout: Some(Duration::from_millis(50)),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2504", config).expect("Failed to create server");
+    let calls = Arc::new(Mutex::new(0));
+    {
+        let calls = Arc::clone(&calls);
+        server.route_dynamic("messages.EchoMessage", move |type_name: &str, payload: &[u8]| {
+            *calls.lock().unwrap() += 1;
+            thread::sleep(Duration::from_millis(300));
+            Ok(DynamicMessage::new(type_name, payload.to_vec()))
+        });
+    }
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2504, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let payload = EchoMessage { content: "late".to_string() }.encode_to_vec();
+    let request = DynamicMessage::new("messages.EchoMessage", payload);
+    let command = |client: &mut client::Client| {
+        let message = client_message::Message::DynamicMessage(request.clone());
+        assert!(client.send_command(message, "pump-2-start").is_ok(), "Failed to send command");
+        client.receive().expect("Failed to receive response").message
+    };
+    let status = |client: &mut client::Client| {
+        let message = CommandStatusRequest {
+            command_id: "pump-2-start".to_string(),
+        };
+        client.command_status_request(message).expect("Failed to query the status").status()
+    };
+    let code = |message: Option<server_message::Message>| match message {
+        Some(server_message::Message::ErrorResponse(error)) => error.code(),
+        other => panic!("Expected an error, got {:?}", other),
+    };
+
+    // The timeout isn't the command's answer, a resend is told the command is pending while the handler still runs
+    assert_eq!(code(command(&mut client)), ErrorCode::HandlerTimeout);
+    assert_eq!(status(&mut client), CommandStatus::Pending);
+    let pending = CommandStatusResponse {
+        command_id: "pump-2-start".to_string(),
+        status: CommandStatus::Pending as i32,
+    };
+    assert_eq!(command(&mut client), Some(pending.into()));
+
+    // What the handler returned in the end is recorded, and a resend replays it
+    thread::sleep(Duration::from_millis(400));
+    assert_eq!(status(&mut client), CommandStatus::Applied);
+    assert_eq!(command(&mut client), Some(request.clone().into()));
+    assert_eq!(*calls.lock().unwrap(), 1, "The command should run once");
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_maintenance_mode_defers_data_requests() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2496");
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2496, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    server.enter_maintenance(Duration::from_secs(600), "Replacing the modem");
+    assert!(server.in_maintenance());
+
+    // Data requests are deferred, health checks are still answered and report the maintenance
+    let response = client.call(AddRequest { a: 1, b: 2 }.into()).expect("Failed to send the request");
+    let expected = MaintenanceMode {
+        retry_after_ms: 600_000,
+        reason: "Replacing the modem".to_string(),
+    };
+    assert_eq!(response.message, Some(expected.into()));
+    let response = client.call(HealthRequest {}.into()).expect("Failed to check health");
+    match response.message {
+        Some(server_message::Message::HealthResponse(health)) => {
+            assert_eq!(health.status(), HealthStatus::Maintenance)
+        }
+        other => panic!("Expected a health response, got {:?}", other),
+    }
+
+    server.leave_maintenance();
+    let response = client.call(AddRequest { a: 1, b: 2 }.into()).expect("Failed to send the request");
+    assert_eq!(response.message, Some(AddResponse { result: 3 }.into()));
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(feature = "storage")]
+#[test]
+fn test_outbox_resends_after_maintenance() {
+    use embedded_recruitment_task::outbox::Outbox;
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let server = create_server("localhost:2501");
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2501, 1000);
+    client.set_outbox(Outbox::open(&dir.path().join("outbox.log")).expect("Failed to open outbox"));
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // A durable request deferred by maintenance stays queued
+    server.enter_maintenance(Duration::from_millis(200), "Replacing the modem");
+    client.send_durable(AddRequest { a: 2, b: 3 }).unwrap();
+    let response = client.receive().expect("Failed to receive");
+    assert!(matches!(response.message, Some(server_message::Message::MaintenanceMode(_))), "Got {}", response);
+    assert_eq!(client.outbox_len(), 1, "A deferred request should stay in the outbox");
+
+    // It is sent again after the announced time, once the server is back
+    server.leave_maintenance();
+    let started = Instant::now();
+    let response = client.receive().expect("Failed to receive");
+    assert_eq!(response.message, Some(AddResponse { result: 5 }.into()));
+    assert!(started.elapsed() >= Duration::from_millis(150), "The request was resent before the retry time");
+    assert_eq!(client.outbox_len(), 0, "Delivered request should leave the outbox");
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_read_only_mode_refuses_new_commands() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = create_server("localhost:2497");
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2497, 1000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let command = |client: &mut client::Client, command_id: &str| {
+        let message = client_message::Message::AddRequest(AddRequest { a: 1, b: 2 });
+        assert!(client.send_command(message, command_id).is_ok(), "Failed to send command");
+        client.receive().expect("Failed to receive response").message
+    };
+    let applied = Some(AddResponse { result: 3 }.into());
+    assert_eq!(command(&mut client, "before"), applied);
+
+    server.enter_read_only("disk replaced");
+    assert_eq!(server.read_only_reason().as_deref(), Some("disk replaced"));
+    assert_eq!(server.health().status, HealthStatus::Degraded);
+    let changed = server.recent_events().into_iter().find(|event| event.kind() == EventKind::ModeChanged);
+    assert!(changed.is_some_and(|event| event.detail.contains("disk replaced")), "Mode change not recorded");
+
+    // Applied commands are replayed and plain requests served, new commands are refused
+    assert_eq!(command(&mut client, "before"), applied);
+    assert_eq!(client.call(AddRequest { a: 2, b: 2 }.into()).unwrap().message, Some(AddResponse { result: 4 }.into()));
+    match command(&mut client, "during") {
+        Some(server_message::Message::ErrorResponse(error)) => assert_eq!(error.code(), ErrorCode::ReadOnly),
+        other => panic!("Expected a read-only error, got {:?}", other),
+    }
+
+    server.leave_read_only();
+    assert_eq!(command(&mut client, "during"), applied, "Commands should run again");
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(feature = "storage")]
+#[test]
+fn test_outbox_resends_commands_refused_while_read_only() {
+    use embedded_recruitment_task::outbox::Outbox;
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let server = create_server("localhost:2502");
+    let handle = setup_server_thread(server.clone());
+
+    let mut client = client::Client::new("localhost", 2502, 1000);
+    client.set_outbox(Outbox::open(&dir.path().join("outbox.log")).expect("Failed to open outbox"));
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+
+    // The refused command was not run, so it stays queued
+    server.enter_read_only("disk replaced");
+    client.send_durable(AddRequest { a: 2, b: 3 }).unwrap();
+    let response = client.receive().expect("Failed to receive");
+    let refused = matches!(&response.message,
+        Some(server_message::Message::ErrorResponse(error)) if error.code() == ErrorCode::ReadOnly);
+    assert!(refused, "Expected a read-only refusal, got {}", response);
+    assert_eq!(client.outbox_len(), 1, "A refused command should stay in the outbox");
+
+    // Once the storage is back the command is resent and applied
+    server.leave_read_only();
+    assert_eq!(client.receive().expect("Failed to receive").message, Some(AddResponse { result: 5 }.into()));
+    assert_eq!(client.outbox_len(), 0, "Applied command should leave the outbox");
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_capture_dumps_one_connection() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let clock = Arc::new(ManualClock::new());
+    let server = Server::with_clock("localhost:2498", ServerConfig::default(), clock.clone()).unwrap();
+    let handle = setup_server_thread(server.clone());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("capture.hex");
+
+    let mut client = client::Client::new("localhost", 2498, 1000);
+    let mut other = client::Client::new("localhost", 2498, 1000);
+    assert!(client.connect().is_ok() && other.connect().is_ok(), "Failed to connect to the server");
+    let peer = client.local_addr().expect("Client should be connected").to_string();
+    server.capture_connection(&peer, &path, Duration::from_secs(60)).expect("Failed to start the capture");
+    assert_eq!(server.captured_connections(), [peer.clone()]);
+
+    // Both directions of the captured connection are dumped, nothing of the other one
+    client.call(ClientMessage::echo("captured").message.unwrap()).unwrap();
+    other.call(ClientMessage::echo("not captured").message.unwrap()).unwrap();
+    let dump = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<Vec<&str>> = dump.lines().map(|line| line.split(' ').collect()).collect();
+    assert_eq!(lines.len(), 2, "Expected a request and a response: {}", dump);
+    assert_eq!((lines[0][1], lines[1][1]), ("in", "out"));
+    let hex = |text: &str| -> String { text.bytes().map(|byte| format!("{:02x}", byte)).collect() };
+    assert!(lines.iter().all(|line| line[2].contains(&hex("captured"))), "Frames should be dumped: {}", dump);
+
+    // The capture ends by itself once its time is up
+    clock.advance(Duration::from_secs(61));
+    client.call(ClientMessage::echo("too late").message.unwrap()).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), dump, "Capture should have ended");
+    assert!(server.captured_connections().is_empty());
+    assert!(!server.stop_capture(&peer), "Nothing left to stop");
+
+    assert!(client.disconnect().is_ok() && other.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,28 @@
+use embedded_recruitment_task::clock::{Clock, ManualClock, SystemClock};
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_manual_clock_moves_only_when_advanced() {
+    let clock = ManualClock::new();
+    let (start, wall_start) = (clock.now(), clock.wall());
+    assert_eq!(clock.now(), start, "Time must stand still until advanced");
+
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(clock.now(), start + Duration::from_secs(30));
+    assert_eq!(clock.wall(), wall_start + Duration::from_secs(30), "Wall-clock time should advance with it");
+    assert_eq!(clock.elapsed(), Duration::from_secs(30));
+
+    // Sleeping advances the clock instead of blocking
+    let real = Instant::now();
+    clock.sleep(Duration::from_secs(3600));
+    assert!(real.elapsed() < Duration::from_secs(1), "Sleeping on a manual clock should not block");
+    assert_eq!(clock.elapsed(), Duration::from_secs(3630));
+}
+
+#[test]
+fn test_system_clock_follows_real_time() {
+    let clock = SystemClock;
+    let before = Instant::now();
+    clock.sleep(Duration::from_millis(20));
+    assert!(clock.now().duration_since(before) >= Duration::from_millis(20));
+}
@@ -0,0 +1,33 @@
+#![cfg(feature = "conformance")]
+
+use embedded_recruitment_task::{conformance, server::Server};
+use std::{thread, time::Duration};
+
+#[test]
+fn test_server_passes_conformance() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = Server::new("localhost:2470").expect("Failed to create server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let report = conformance::run("localhost:2470", Duration::from_secs(2));
+    assert!(report.passed(), "Server failed conformance:\n{}", report);
+    assert_eq!(report.checks.len(), 11);
+    assert!(report.to_string().ends_with("11 of 11 checks passed"), "Unexpected report:\n{}", report);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[test]
+fn test_unreachable_server_fails_every_check() {
+    let report = conformance::run("localhost:2471", Duration::from_millis(200));
+    assert!(!report.passed());
+    for check in &report.checks {
+        let reason = check.result.as_ref().unwrap_err();
+        assert!(reason.starts_with("Failed to connect"), "{} failed for another reason: {}", check.name, reason);
+    }
+    assert!(report.to_string().contains("FAIL echo"), "Unexpected report:\n{}", report);
+}
@@ -0,0 +1,66 @@
+use embedded_recruitment_task::message::{
+    client_message, server_message, AddRequest, AddResponse, ClientMessage, EchoMessage, ErrorCode, ErrorResponse,
+    RecentEventsRequest, ServerMessage,
+};
+
+#[test]
+fn test_client_message_constructors() {
+    assert_eq!(
+        ClientMessage::add(2, 3),
+        ClientMessage {
+            message: Some(client_message::Message::AddRequest(AddRequest { a: 2, b: 3 })),
+            metadata: None,
+        }
+    );
+    assert_eq!(
+        ClientMessage::echo("hi").message,
+        Some(client_message::Message::EchoMessage(EchoMessage { content: "hi".to_string() }))
+    );
+    assert_eq!(
+        ClientMessage::recent_events(5).message,
+        Some(client_message::Message::RecentEventsRequest(RecentEventsRequest { limit: 5 }))
+    );
+}
+
+#[test]
+fn test_from_conversions() {
+    // Payloads convert into their oneof and into the enclosing message
+    let payload: client_message::Message = AddRequest { a: 1, b: 1 }.into();
+    assert_eq!(payload, client_message::Message::AddRequest(AddRequest { a: 1, b: 1 }));
+    let response = ServerMessage::from(AddResponse { result: 4 });
+    assert_eq!(response, ServerMessage::add(4));
+    assert_eq!(response.metadata, None, "Conversions carry no metadata");
+
+    // EchoMessage belongs to both oneofs
+    let request: ClientMessage = EchoMessage { content: "x".to_string() }.into();
+    let reply: ServerMessage = EchoMessage { content: "x".to_string() }.into();
+    assert!(matches!(request.message, Some(client_message::Message::EchoMessage(_))));
+    assert!(matches!(reply.message, Some(server_message::Message::EchoMessage(_))));
+}
+
+#[test]
+fn test_error_constructors() {
+    let error = ErrorResponse::new(ErrorCode::Replayed, "Sequence 3 is not above 5");
+    assert_eq!(error.code(), ErrorCode::Replayed);
+    assert_eq!(
+        ServerMessage::error(ErrorCode::Replayed, "Sequence 3 is not above 5").message,
+        Some(server_message::Message::ErrorResponse(error))
+    );
+}
+
+#[test]
+fn test_retryable_error_codes() {
+    let retryable = [
+        ErrorCode::Internal,
+        ErrorCode::UpstreamUnavailable,
+        ErrorCode::Overloaded,
+        ErrorCode::ReadOnly,
+        ErrorCode::HandlerBusy,
+    ];
+    for code in retryable {
+        assert!(code.is_retryable(), "{:?} should be retried", code);
+    }
+    for code in [ErrorCode::InvalidRequest, ErrorCode::Unsupported, ErrorCode::Expired, ErrorCode::Replayed] {
+        assert!(!code.is_retryable(), "{:?} is a final answer", code);
+    }
+}
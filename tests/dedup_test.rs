@@ -0,0 +1,37 @@
+#![cfg(feature = "dedup")]
+
+use embedded_recruitment_task::dedup::{content_hash, ContentStore};
+
+#[test]
+fn test_content_hash_is_sha256() {
+    let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    let hash: String = content_hash(b"abc").iter().map(|byte| format!("{:02x}", byte)).collect();
+    assert_eq!(hash, expected);
+}
+
+#[test]
+fn test_least_recently_used_content_is_evicted() {
+    let mut store = ContentStore::new(250);
+    let first = store.insert(vec![1; 100]);
+    let second = store.insert(vec![2; 100]);
+    assert_eq!(store.get(&first), Some(&[1; 100][..]));
+
+    // Over the limit, the content not used for longest goes
+    let third = store.insert(vec![3; 100]);
+    assert_eq!(store.get(&second), None);
+    assert!(store.get(&first).is_some() && store.get(&third).is_some());
+    assert_eq!((store.len(), store.bytes()), (2, 200));
+
+    // Storing the same content again adds nothing
+    store.insert(vec![3; 100]);
+    assert_eq!((store.len(), store.bytes()), (2, 200));
+}
+
+#[test]
+fn test_oversized_or_unknown_content() {
+    let mut store = ContentStore::new(10);
+    let hash = store.insert(vec![0; 11]);
+    assert!(store.is_empty(), "Content over the whole limit is not kept");
+    assert_eq!(store.get(&hash), None);
+    assert_eq!(store.get(b"not a hash"), None);
+}
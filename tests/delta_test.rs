@@ -0,0 +1,60 @@
+#![cfg(feature = "delta")]
+
+use embedded_recruitment_task::delta::{apply, diff, fingerprint};
+
+// A config blob of `lines` numbered settings
+fn config(lines: usize) -> Vec<u8> {
+    (0..lines)
+        .map(|line| format!("setting_{:04} = value {}\n", line, line * 7))
+        .collect::<String>()
+        .into_bytes()
+}
+
+#[test]
+fn test_small_change_gives_small_delta() {
+    let baseline = config(200);
+    let mut target = baseline.clone();
+    target[1000..1005].copy_from_slice(b"XXXXX");
+    target.extend_from_slice(b"setting_new = 1\n");
+
+    let delta = diff(&baseline, &target);
+    assert!(delta.len() < 64, "Delta of {} bytes for a {} byte blob", delta.len(), target.len());
+    assert_eq!(apply(&baseline, &delta, usize::MAX).unwrap(), target);
+}
+
+#[test]
+fn test_moved_and_unrelated_content() {
+    let baseline = config(100);
+    // The second half moved in front of the first, with new bytes between them
+    let half = baseline.len() / 2;
+    let mut target = baseline[half..].to_vec();
+    target.extend_from_slice(b"inserted in the middle");
+    target.extend_from_slice(&baseline[..half]);
+    let delta = diff(&baseline, &target);
+    assert!(delta.len() < 64, "Moved content should be copied, delta is {} bytes", delta.len());
+    assert_eq!(apply(&baseline, &delta, usize::MAX).unwrap(), target);
+
+    // Nothing in common, or no baseline at all, still round trips
+    let cases: [(&[u8], &[u8]); 3] = [(b"abc", b"completely different content"), (b"", b"x"), (b"x", b"")];
+    for (baseline, target) in cases {
+        assert_eq!(apply(baseline, &diff(baseline, target), usize::MAX).unwrap(), target);
+    }
+}
+
+#[test]
+fn test_invalid_deltas_are_rejected() {
+    let baseline = config(10);
+    let delta = diff(&baseline, &baseline);
+    assert!(apply(&baseline, &delta, baseline.len() - 1).is_err(), "Target over the limit");
+    assert!(apply(&baseline[..16], &delta, usize::MAX).is_err(), "Copy outside the baseline");
+    assert!(apply(&baseline, &[0x80], usize::MAX).is_err(), "Truncated instruction");
+    assert!(apply(&baseline, &[10, b'a'], usize::MAX).is_err(), "Truncated insert");
+}
+
+#[test]
+fn test_fingerprint() {
+    // FNV-1a reference values
+    assert_eq!(fingerprint(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(fingerprint(b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_ne!(fingerprint(&config(10)), fingerprint(&config(11)));
+}
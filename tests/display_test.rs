@@ -0,0 +1,62 @@
+use embedded_recruitment_task::{
+    display::{Truncated, MAX_SHOWN_CHARS},
+    message::{
+        client_message, server_message, AddRequest, BenchPayload, ClientMessage, EchoMessage, ErrorCode,
+        ErrorResponse, ServerMessage,
+    },
+};
+
+#[test]
+fn test_truncated_strings() {
+    assert_eq!(Truncated("short").to_string(), "\"short\"");
+    let exact = "x".repeat(MAX_SHOWN_CHARS);
+    assert_eq!(Truncated(&exact).to_string(), format!("{:?}", exact), "A string at the limit is shown in full");
+
+    let long = "y".repeat(10_000);
+    let shown = Truncated(&long).to_string();
+    assert_eq!(shown, format!("{:?}... (10000 bytes)", "y".repeat(MAX_SHOWN_CHARS)));
+
+    // Cuts fall on character boundaries
+    let wide = "é".repeat(100);
+    assert!(Truncated(&wide).to_string().ends_with("... (200 bytes)"));
+}
+
+#[test]
+fn test_client_message_display() {
+    let add = ClientMessage {
+        message: Some(client_message::Message::AddRequest(AddRequest { a: 1, b: 2 })),
+        metadata: None,
+    };
+    assert_eq!(add.to_string(), "AddRequest { a: 1, b: 2 }");
+
+    let echo = ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage { content: "z".repeat(5000) })),
+        metadata: None,
+    };
+    let shown = echo.to_string();
+    assert!(shown.starts_with("EchoMessage { content: \"zzz"), "Unexpected display: {}", shown);
+    assert!(shown.contains("(5000 bytes)") && shown.len() < 200, "Echo content should be cut: {}", shown);
+
+    assert_eq!(ClientMessage::default().to_string(), "empty ClientMessage");
+}
+
+#[test]
+fn test_server_message_display() {
+    let payload = ServerMessage {
+        message: Some(server_message::Message::BenchPayload(BenchPayload { index: 3, data: vec![7; 4096] })),
+        metadata: None,
+    };
+    assert_eq!(payload.to_string(), "BenchPayload { index: 3, data: <4096 bytes> }");
+
+    let error = ServerMessage {
+        message: Some(server_message::Message::ErrorResponse(ErrorResponse {
+            code: ErrorCode::Expired as i32,
+            message: "Request expired 5 ms before dispatch".to_string(),
+        })),
+        metadata: None,
+    };
+    assert_eq!(
+        error.to_string(),
+        "ErrorResponse { code: Expired, message: \"Request expired 5 ms before dispatch\" }"
+    );
+}
@@ -0,0 +1,47 @@
+use embedded_recruitment_task::events::{EventKind, EventLog};
+
+#[test]
+fn test_event_log_overwrites_oldest() {
+    let log = EventLog::new(3);
+    for index in 0..5 {
+        log.record(EventKind::Connected, format!("client {}", index));
+    }
+
+    // Only the newest three are kept, newest first
+    let details: Vec<_> = log.recent(0).into_iter().map(|event| event.detail).collect();
+    assert_eq!(details, ["client 4", "client 3", "client 2"]);
+    assert_eq!(log.dropped(), 2, "Two events should have been overwritten");
+
+    let latest = log.recent(1);
+    assert_eq!(latest.len(), 1, "Limit should cap the events returned");
+    assert_eq!(latest[0].kind(), EventKind::Connected);
+    assert!(latest[0].at_us > 0, "Events carry their time");
+}
+
+#[test]
+fn test_event_log_without_capacity_keeps_nothing() {
+    let log = EventLog::new(0);
+    log.record(EventKind::Error, "ignored");
+    assert!(log.recent(0).is_empty());
+    assert_eq!(log.dropped(), 0);
+}
+
+#[test]
+fn test_events_are_stamped_by_the_clock() {
+    use embedded_recruitment_task::clock::{Clock, ManualClock};
+    use std::{
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    let clock = Arc::new(ManualClock::new());
+    let log = EventLog::with_clock(2, clock.clone());
+    log.record(EventKind::Connected, "first");
+    clock.advance(Duration::from_secs(3600));
+    log.record(EventKind::Connected, "second");
+
+    let events = log.recent(0);
+    assert_eq!(events[0].at_us - events[1].at_us, 3_600_000_000, "Events follow the clock, not real time");
+    let expected = clock.wall().duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+    assert_eq!(events[0].at_us, expected);
+}
@@ -0,0 +1,78 @@
+use embedded_recruitment_task::{
+    frame::{Frame, Header, WireHeader},
+    protocol::{MAX_MESSAGE_SIZE, MAX_PREFIX_LEN},
+};
+
+// Lengths at every varint size boundary
+const BOUNDARIES: [usize; 10] = [0, 1, 127, 128, 16_383, 16_384, 2_097_151, 2_097_152, 268_435_455, 268_435_456];
+
+#[test]
+fn test_header_round_trip() {
+    for payload_len in BOUNDARIES.into_iter().chain([usize::MAX]) {
+        let header = Header::<{ usize::MAX }>::new(payload_len);
+        let mut out = [0; MAX_PREFIX_LEN];
+        let len = header.encode(&mut out);
+        assert_eq!(len, header.encoded_len(), "Length of {}", payload_len);
+        assert_eq!(len, prost::length_delimiter_len(payload_len), "Matches prost for {}", payload_len);
+        assert_eq!(Header::<{ usize::MAX }>::parse(&out[..len]).unwrap(), Some((header, len)), "Round trip of {}", payload_len);
+    }
+}
+
+#[test]
+fn test_encoded_sizes() {
+    let sizes: Vec<usize> = BOUNDARIES.iter().map(|&len| WireHeader::new(len).encoded_len()).collect();
+    assert_eq!(sizes, [1, 1, 1, 2, 2, 3, 3, 4, 4, 5]);
+    assert_eq!(Header::<{ usize::MAX }>::new(usize::MAX).encoded_len(), MAX_PREFIX_LEN);
+}
+
+#[test]
+fn test_incomplete_header() {
+    let mut out = [0; MAX_PREFIX_LEN];
+    let len = WireHeader::new(300_000).encode(&mut out);
+    for cut in 0..len {
+        assert_eq!(WireHeader::parse(&out[..cut]).unwrap(), None, "Header cut after {} bytes", cut);
+        assert_eq!(WireHeader::next_frame(&out[..cut]).unwrap(), None, "Frame cut after {} bytes", cut);
+    }
+}
+
+#[test]
+fn test_overlong_header() {
+    // Ten continuation bytes can never end a valid prefix
+    let overlong = [0x80; MAX_PREFIX_LEN];
+    assert!(WireHeader::parse(&overlong).is_err());
+    assert!(WireHeader::next_frame(&overlong).is_err());
+    assert_eq!(WireHeader::parse(&overlong[..MAX_PREFIX_LEN - 1]).unwrap(), None, "Might still end");
+}
+
+#[test]
+fn test_next_frame() {
+    // A frame is only complete once its whole payload is there, trailing bytes belong to the next one
+    let mut buffer = vec![3, b'a', b'b'];
+    assert_eq!(WireHeader::next_frame(&buffer).unwrap(), None);
+    buffer.extend_from_slice(&[b'c', 0]);
+    assert_eq!(WireHeader::next_frame(&buffer).unwrap(), Some(Frame::Complete { start: 1, end: 4 }));
+    assert_eq!(WireHeader::next_frame(&buffer[4..]).unwrap(), Some(Frame::Complete { start: 1, end: 1 }));
+}
+
+#[test]
+fn test_size_limit() {
+    let mut out = [0; MAX_PREFIX_LEN];
+
+    // The limit itself is allowed and waits for the payload
+    let len = WireHeader::new(MAX_MESSAGE_SIZE).encode(&mut out);
+    assert_eq!(WireHeader::next_frame(&out[..len]).unwrap(), None);
+
+    // One byte more is reported right after the header
+    let len = WireHeader::new(MAX_MESSAGE_SIZE + 1).encode(&mut out);
+    assert_eq!(
+        WireHeader::next_frame(&out[..len]).unwrap(),
+        Some(Frame::Oversized { prefix_len: len, payload_len: MAX_MESSAGE_SIZE + 1 })
+    );
+
+    // The limit is a type parameter
+    let len = Header::<4>::new(5).encode(&mut out);
+    assert_eq!(
+        Header::<4>::next_frame(&out[..len]).unwrap(),
+        Some(Frame::Oversized { prefix_len: 1, payload_len: 5 })
+    );
+}
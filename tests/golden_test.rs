@@ -0,0 +1,113 @@
+use embedded_recruitment_task::{
+    frame::{Frame, WireHeader},
+    message::{
+        client_message, AddRequest, BenchRequest, ClientMessage, CommandStatusRequest, DynamicMessage, EchoMessage,
+        Metadata,
+    },
+    server::Server,
+};
+use prost::Message;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+// Snapshot of the responses, one `name hex` line per request with the frame exactly as received
+const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/golden_responses.txt");
+
+const HEADER: &str = "\
+# Server responses to the golden request corpus, each frame hex encoded with its length prefix
+# Re-record with UPDATE_FIXTURES=1 cargo test --test golden_test after an intended wire change
+";
+
+// Requests sent in order over one connection. Each carries its own trace id and no timestamps, so the
+// responses are the same on every run
+fn corpus() -> Vec<(&'static str, client_message::Message, u64)> {
+    vec![
+        ("echo", EchoMessage { content: "golden".to_string() }.into(), 0),
+        ("echo_empty", EchoMessage { content: String::new() }.into(), 0),
+        ("add", AddRequest { a: 2, b: 3 }.into(), 0),
+        ("add_negative", AddRequest { a: -10, b: 3 }.into(), 0),
+        (
+            "bench_too_large",
+            BenchRequest {
+                payload_size: u32::MAX,
+                count: 1,
+            }
+            .into(),
+            0,
+        ),
+        (
+            "command_status_unknown",
+            CommandStatusRequest {
+                command_id: "golden-1".to_string(),
+            }
+            .into(),
+            0,
+        ),
+        ("dynamic_unrouted", DynamicMessage::new("messages.AddRequest", Vec::new()).into(), 0),
+        ("sequenced", EchoMessage { content: "first".to_string() }.into(), 5),
+        ("replayed", EchoMessage { content: "again".to_string() }.into(), 5),
+    ]
+}
+
+// Read one complete frame, prefix included
+fn read_frame(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> Vec<u8> {
+    loop {
+        if let Some(Frame::Complete { end, .. }) = WireHeader::next_frame(buffer).expect("Invalid frame") {
+            return buffer.drain(..end).collect();
+        }
+        let mut chunk = [0u8; 1024];
+        let bytes_read = stream.read(&mut chunk).expect("Failed to read response");
+        assert!(bytes_read > 0, "Server closed the connection");
+        buffer.extend_from_slice(&chunk[..bytes_read]);
+    }
+}
+
+#[test]
+fn test_responses_match_snapshot() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = Server::new("localhost:2472").expect("Failed to create server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let mut stream = TcpStream::connect("localhost:2472").expect("Failed to connect to the server");
+    stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let mut buffer = Vec::new();
+    let mut recorded = HEADER.to_string();
+    for (name, message, sequence) in corpus() {
+        let request = ClientMessage {
+            message: Some(message),
+            metadata: Some(Metadata {
+                trace_id: format!("golden-{}", name),
+                sequence,
+                ..Metadata::default()
+            }),
+        };
+        stream.write_all(&request.encode_length_delimited_to_vec()).unwrap();
+        let frame = read_frame(&mut stream, &mut buffer);
+        let hex: String = frame.iter().map(|byte| format!("{:02x}", byte)).collect();
+        recorded.push_str(&format!("{} {}\n", name, hex));
+    }
+    drop(stream);
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    if std::env::var_os("UPDATE_FIXTURES").is_some() {
+        std::fs::write(SNAPSHOT, &recorded).expect("Failed to write snapshot");
+    }
+    let snapshot = std::fs::read_to_string(SNAPSHOT).expect("Failed to read snapshot");
+    // Name the responses that changed, the whole files are too long to compare by eye
+    let changed: Vec<&str> = recorded
+        .lines()
+        .zip(snapshot.lines())
+        .filter(|(recorded, expected)| recorded != expected)
+        .map(|(recorded, _)| recorded.split(' ').next().unwrap_or_default())
+        .collect();
+    assert!(changed.is_empty(), "Responses changed: {:?}", changed);
+    assert_eq!(recorded, snapshot, "Snapshot and corpus differ in length or header");
+}
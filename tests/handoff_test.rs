@@ -0,0 +1,38 @@
+#![cfg(all(feature = "handoff", unix))]
+
+use embedded_recruitment_task::handoff::{receive_listeners, send_listeners};
+use std::{
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+#[test]
+fn test_listeners_survive_handoff() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("handoff.sock");
+    let listeners = vec![
+        TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener"),
+        TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener"),
+    ];
+    let addrs: Vec<_> = listeners.iter().map(|listener| listener.local_addr().unwrap()).collect();
+
+    let sender = {
+        let path = path.clone();
+        thread::spawn(move || send_listeners(&path, &listeners))
+    };
+    let received = receive_listeners(&path).expect("Failed to receive listeners");
+    assert!(sender.join().unwrap().is_ok(), "Failed to send listeners");
+
+    // The received sockets are the same listeners, in the same order, and still accept connections
+    let received_addrs: Vec<_> = received.iter().map(|listener| listener.local_addr().unwrap()).collect();
+    assert_eq!(received_addrs, addrs, "Received listeners should keep their addresses");
+    let _stream = TcpStream::connect(addrs[0]).expect("Failed to connect");
+    assert!(received[0].accept().is_ok(), "Received listener should accept");
+}
+
+#[test]
+fn test_handoff_rejects_no_listeners() {
+    let dir = tempfile::tempdir().unwrap();
+    let error = send_listeners(&dir.path().join("handoff.sock"), &[]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,91 @@
+use embedded_recruitment_task::health::{HealthReport, HealthStatus};
+use std::time::Duration;
+
+#[test]
+fn test_health_report_json() {
+    let report = HealthReport {
+        status: HealthStatus::Degraded,
+        connections: 3,
+        queue_depth: 1,
+        last_error: Some("Failed to decode \"frame\"".to_string()),
+        uptime: Duration::from_secs(42),
+    };
+    assert_eq!(
+        report.to_json(),
+        r#"{"status":"degraded","connections":3,"queue_depth":1,"last_error":"Failed to decode \"frame\"","uptime_secs":42}"#
+    );
+
+    // Without an error the field is null rather than an empty string
+    let report = HealthReport { last_error: None, ..report };
+    assert!(report.to_json().contains("\"last_error\":null"), "Missing error should be null");
+
+    let report = HealthReport {
+        status: HealthStatus::Draining,
+        ..report
+    };
+    assert!(report.to_json().contains("\"status\":\"draining\""), "Draining should be reported");
+}
+
+#[test]
+fn test_health_report_to_response() {
+    let report = HealthReport {
+        status: HealthStatus::Serving,
+        connections: 2,
+        queue_depth: 0,
+        last_error: None,
+        uptime: Duration::from_millis(2500),
+    };
+    let response = report.to_response();
+    assert_eq!(response.status(), HealthStatus::Serving);
+    assert_eq!(response.connections, 2);
+    assert_eq!(response.uptime_secs, 2, "Uptime is reported in whole seconds");
+    assert!(response.last_error.is_empty());
+}
+
+#[cfg(feature = "healthz")]
+#[test]
+fn test_healthz_endpoint() {
+    use embedded_recruitment_task::{config::ServerConfig, server::Server};
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+    };
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        healthz_addr: Some("localhost:2141".to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2140", config).expect("Failed to start server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    // The endpoint is bound by `run`, give it a moment to come up
+    let get = |path: &str| -> String {
+        let deadline = std::time::Instant::now() + Duration::from_secs(1);
+        let mut stream = loop {
+            match TcpStream::connect("localhost:2141") {
+                Ok(stream) => break stream,
+                Err(e) if std::time::Instant::now() > deadline => panic!("Health endpoint unreachable: {}", e),
+                Err(_) => thread::sleep(Duration::from_millis(20)),
+            }
+        };
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let response = get("/healthz");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "Unexpected response: {}", response);
+    assert!(response.contains("\"status\":\"serving\""), "Unexpected body: {}", response);
+
+    let response = get("/other");
+    assert!(response.starts_with("HTTP/1.1 404"), "Unknown paths should be 404: {}", response);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,21 @@
+#![cfg(all(feature = "keepalive", any(target_os = "linux", windows)))]
+
+use embedded_recruitment_task::{config::TcpKeepalive, socket::set_keepalive};
+use std::{
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+#[test]
+fn test_keepalive_applies_to_connection() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener");
+    let stream = TcpStream::connect(listener.local_addr().unwrap()).expect("Failed to connect");
+
+    // Sub-second timings are rounded up to the kernel's one second resolution
+    let keepalive = TcpKeepalive {
+        idle: Duration::from_millis(500),
+        interval: Duration::from_secs(2),
+        retries: 3,
+    };
+    assert!(set_keepalive(&stream, &keepalive).is_ok(), "Failed to enable keepalive");
+}
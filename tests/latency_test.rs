@@ -0,0 +1,103 @@
+use embedded_recruitment_task::{
+    frame::{Frame, WireHeader},
+    message::{server_message, ClientMessage, ServerMessage},
+    server::Server,
+};
+use prost::Message;
+use std::{
+    env,
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Defaults for the environment variables tuning the run
+const DEFAULT_BUDGET_US: u64 = 5_000; // LATENCY_BUDGET_US, allowed p99 round trip
+const DEFAULT_CLIENTS: usize = 8; // LATENCY_CLIENTS, concurrent connections
+const DEFAULT_REQUESTS: usize = 500; // LATENCY_REQUESTS, echoes sent by each client
+
+// Echoes sent by each client before measuring, so connection setup and cold caches don't count
+const WARMUP: usize = 20;
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+// Round trips of `requests` echoes on one connection, after the warm-up. The server has no in-memory
+// transport, so this goes over loopback TCP, the same kernel path devices use
+fn measure_client(requests: usize) -> Vec<Duration> {
+    let mut stream = TcpStream::connect("localhost:2474").expect("Failed to connect to the server");
+    stream.set_nodelay(true).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let request = ClientMessage::echo("x".repeat(64)).encode_length_delimited_to_vec();
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    let mut round_trips = Vec::with_capacity(requests);
+    for index in 0..WARMUP + requests {
+        let sent = Instant::now();
+        stream.write_all(&request).unwrap();
+        let (start, end) = loop {
+            if let Some(Frame::Complete { start, end }) = WireHeader::next_frame(&buffer).unwrap() {
+                break (start, end);
+            }
+            let bytes_read = stream.read(&mut chunk).expect("Failed to read the echo");
+            assert!(bytes_read > 0, "Server closed the connection");
+            buffer.extend_from_slice(&chunk[..bytes_read]);
+        };
+        let elapsed = sent.elapsed();
+        let response = ServerMessage::decode(&buffer[start..end]).expect("Undecodable response");
+        assert!(
+            matches!(response.message, Some(server_message::Message::EchoMessage(_))),
+            "Expected an echo, got {}",
+            response
+        );
+        buffer.drain(..end);
+        if index >= WARMUP {
+            round_trips.push(elapsed);
+        }
+    }
+    round_trips
+}
+
+// Asserts the p99 loopback round trip of echoes under concurrent clients. Timing depends on the machine
+// and its load, so the test is ignored by default and tuned through the environment, e.g. in CI:
+// `LATENCY_BUDGET_US=2000 LATENCY_CLIENTS=16 cargo test --release --test latency_test -- --ignored`
+#[test]
+#[ignore]
+fn test_echo_p99_within_budget() {
+    let budget = Duration::from_micros(env_or("LATENCY_BUDGET_US", DEFAULT_BUDGET_US));
+    let clients = env_or("LATENCY_CLIENTS", DEFAULT_CLIENTS);
+    let requests = env_or("LATENCY_REQUESTS", DEFAULT_REQUESTS);
+    // Without a single round trip there is no percentile to check
+    assert!(clients > 0 && requests > 0, "LATENCY_CLIENTS and LATENCY_REQUESTS must be at least 1");
+
+    let server = Server::new("localhost:2474").expect("Failed to create server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let workers: Vec<_> = (0..clients).map(|_| thread::spawn(move || measure_client(requests))).collect();
+    let mut round_trips: Vec<Duration> = workers
+        .into_iter()
+        .flat_map(|worker| worker.join().expect("Client thread panicked"))
+        .collect();
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    round_trips.sort();
+    let percentile = |p: usize| round_trips[(round_trips.len() * p / 100).min(round_trips.len() - 1)];
+    let p99 = percentile(99);
+    println!(
+        "{} echoes from {} clients: p50 {:?}, p99 {:?}, max {:?}, budget {:?}",
+        round_trips.len(),
+        clients,
+        percentile(50),
+        p99,
+        round_trips[round_trips.len() - 1],
+        budget
+    );
+    assert!(p99 <= budget, "p99 round trip {:?} is over the {:?} budget", p99, budget);
+}
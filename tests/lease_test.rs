@@ -0,0 +1,129 @@
+#![cfg(feature = "storage")]
+
+use embedded_recruitment_task::{clock::ManualClock, lease::Lease, scheduler::Scheduler};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Barrier,
+    },
+    thread,
+    time::Duration,
+};
+
+#[test]
+fn test_lease_fails_over_after_expiry() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("compaction.lease");
+    let clock = Arc::new(ManualClock::new());
+    let ttl = Duration::from_secs(30);
+    let node_a = Lease::with_clock(&path, "node-a", ttl, clock.clone());
+    let node_b = Lease::with_clock(&path, "node-b", ttl, clock.clone());
+
+    assert_eq!(node_a.holder().unwrap(), None, "A new lease should be free");
+    assert!(node_a.try_acquire().unwrap(), "A free lease should be acquired");
+    assert!(!node_b.try_acquire().unwrap(), "A held lease must not be taken over");
+    assert_eq!(node_b.holder().unwrap().as_deref(), Some("node-a"));
+
+    // Renewing pushes the expiry out again
+    clock.advance(Duration::from_secs(20));
+    assert!(node_a.try_acquire().unwrap(), "The holder should renew its lease");
+    clock.advance(Duration::from_secs(20));
+    assert!(!node_b.try_acquire().unwrap(), "A renewed lease must not have expired yet");
+
+    // Node A stops renewing
+    clock.advance(Duration::from_secs(11));
+    assert_eq!(node_b.holder().unwrap(), None, "An unrenewed lease should expire");
+    assert!(node_b.try_acquire().unwrap(), "An expired lease should be taken over");
+    assert!(!node_a.try_acquire().unwrap(), "The old holder should have lost the lease");
+}
+
+#[test]
+fn test_contending_nodes_acquire_lease_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journal.lease");
+    let clock = Arc::new(ManualClock::new());
+    let ttl = Duration::from_secs(30);
+    let nodes: Vec<Lease> = (0..8)
+        .map(|node| Lease::with_clock(&path, &format!("node-{}", node), ttl, clock.clone()))
+        .collect();
+
+    // The first round finds the lease free, every later one finds it expired
+    for round in 0..20 {
+        let barrier = Arc::new(Barrier::new(nodes.len()));
+        let contenders: Vec<_> = nodes
+            .iter()
+            .cloned()
+            .map(|lease| {
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    lease.try_acquire().expect("Failed to try the lease")
+                })
+            })
+            .collect();
+        let acquired = contenders.into_iter().map(|contender| contender.join().unwrap()).filter(|&held| held).count();
+        assert_eq!(acquired, 1, "Exactly one node should hold the lease in round {}", round);
+        clock.advance(ttl + Duration::from_secs(1));
+    }
+}
+
+#[test]
+fn test_release_frees_lease() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("quota.lease");
+    let node_a = Lease::new(&path, "node-a", Duration::from_secs(60));
+    let node_b = Lease::new(&path, "node-b", Duration::from_secs(60));
+
+    assert!(node_a.try_acquire().unwrap());
+    node_b.release().unwrap();
+    assert_eq!(node_a.holder().unwrap().as_deref(), Some("node-a"), "Only the holder may release");
+
+    node_a.release().unwrap();
+    assert_eq!(node_b.holder().unwrap(), None);
+    assert!(node_b.try_acquire().unwrap(), "A released lease should be free at once");
+}
+
+#[test]
+fn test_corrupt_lease_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("job.lease");
+    std::fs::write(&path, "no expiry").unwrap();
+    assert!(Lease::new(&path, "node-a", Duration::from_secs(60)).try_acquire().is_err());
+}
+
+#[test]
+fn test_guarded_job_runs_on_one_node() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("singleton.lease");
+    let is_running = Arc::new(AtomicBool::new(true));
+    let runs = [Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0))];
+
+    // Node A already leads, then both nodes schedule the same job against the lease
+    assert!(Lease::new(&path, "node-a", Duration::from_secs(60)).try_acquire().unwrap());
+    let handles: Vec<_> = ["node-a", "node-b"]
+        .iter()
+        .zip(&runs)
+        .map(|(holder, counter)| {
+            let scheduler = Arc::new(Scheduler::new());
+            let lease = Lease::new(&path, holder, Duration::from_secs(60));
+            let counter = Arc::clone(counter);
+            scheduler.schedule(
+                Duration::from_millis(10),
+                lease.guard(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }),
+            );
+            let is_running = Arc::clone(&is_running);
+            thread::spawn(move || scheduler.run(&is_running))
+        })
+        .collect();
+    thread::sleep(Duration::from_millis(200));
+    is_running.store(false, Ordering::SeqCst);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let (a, b) = (runs[0].load(Ordering::SeqCst), runs[1].load(Ordering::SeqCst));
+    assert!(a >= 5, "The job should have run repeatedly on the leader, ran {} times", a);
+    assert_eq!(b, 0, "The job must not run on the other node");
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "storage")]
+
+use embedded_recruitment_task::{
+    config::LogFileConfig,
+    logfile::{FileLogger, RotatingFile},
+};
+use log::{Level, LevelFilter, Log, Record};
+use std::fs;
+
+#[test]
+fn test_rotation_by_size_keeps_newest_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("logs").join("server.log");
+    let config = LogFileConfig {
+        max_size: Some(20),
+        keep: 2,
+        ..LogFileConfig::new(&path)
+    };
+
+    // Each 10-byte line fills half a file, so every third line starts a new one
+    let mut file = RotatingFile::open(&config).expect("Failed to open log file");
+    for index in 0..7 {
+        file.write_line(&format!("line {:03}\n", index)).expect("Failed to write line");
+    }
+
+    let read = |suffix: &str| fs::read_to_string(format!("{}{}", path.display(), suffix)).ok();
+    assert_eq!(read("").as_deref(), Some("line 006\n"), "Current file holds the newest line");
+    assert_eq!(read(".1").as_deref(), Some("line 004\nline 005\n"), "First rotated file holds the previous lines");
+    assert_eq!(read(".2").as_deref(), Some("line 002\nline 003\n"), "Second rotated file holds older lines");
+    assert_eq!(read(".3"), None, "Files beyond `keep` are deleted");
+}
+
+#[test]
+fn test_file_logger_filters_and_formats() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("server.log");
+    let config = LogFileConfig {
+        level: LevelFilter::Info,
+        ..LogFileConfig::new(&path)
+    };
+    let logger = FileLogger::open(&config).expect("Failed to open logger");
+
+    let log = |level: Level, message: &str| {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .target("server")
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    };
+    log(Level::Warn, "Client stalled");
+    log(Level::Debug, "Too verbose");
+    logger.flush();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let lines: Vec<_> = contents.lines().collect();
+    assert_eq!(lines.len(), 1, "Debug records are below the configured level: {:?}", lines);
+    // e.g. "2024-05-01T12:30:00.250Z WARN  server: Client stalled"
+    let (timestamp, rest) = lines[0].split_once(' ').unwrap();
+    assert_eq!(timestamp.len(), 24, "Unexpected timestamp: {}", timestamp);
+    assert!(timestamp.ends_with('Z') && timestamp.as_bytes()[10] == b'T', "Unexpected timestamp: {}", timestamp);
+    assert_eq!(rest, "WARN  server: Client stalled");
+}
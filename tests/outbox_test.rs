@@ -0,0 +1,75 @@
+#![cfg(feature = "storage")]
+
+use embedded_recruitment_task::{
+    message::{client_message, AddRequest},
+    outbox::Outbox,
+};
+use std::{collections::HashSet, io::Write};
+
+fn add(a: i32) -> client_message::Message {
+    AddRequest { a, b: 0 }.into()
+}
+
+// The first operand of each pending add request, oldest first
+fn pending(outbox: &Outbox) -> Vec<i32> {
+    outbox
+        .iter()
+        .map(|message| match &message.message {
+            Some(client_message::Message::AddRequest(request)) => request.a,
+            other => panic!("Expected an add request, got {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_outbox_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("outbox.log");
+
+    let mut command_ids = HashSet::new();
+    {
+        let mut outbox = Outbox::open(&path).expect("Failed to create outbox");
+        assert!(outbox.is_empty(), "New outbox should be empty");
+        for a in 1..=3 {
+            command_ids.insert(outbox.push(add(a)).unwrap());
+        }
+        assert_eq!(pending(&outbox)[0], 1);
+        outbox.pop().unwrap();
+    }
+
+    // Delivered requests stay delivered, the rest come back in order with their command ids
+    let mut outbox = Outbox::open(&path).expect("Failed to reopen outbox");
+    assert_eq!(pending(&outbox), [2, 3]);
+    for message in outbox.iter() {
+        let command_id = &message.metadata.as_ref().unwrap().command_id;
+        assert!(command_ids.contains(command_id), "Command id {} changed across the restart", command_id);
+    }
+
+    // Command ids stay unique after a restart
+    let command_id = outbox.push(add(4)).unwrap();
+    assert!(command_ids.insert(command_id), "Command id reused after a restart");
+    assert_eq!(pending(&outbox), [2, 3, 4]);
+}
+
+#[test]
+fn test_torn_entry_is_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("outbox.log");
+    {
+        let mut outbox = Outbox::open(&path).expect("Failed to create outbox");
+        outbox.push(add(1)).unwrap();
+    }
+    // A crash in the middle of queueing the second request
+    std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"q 0a02").unwrap();
+
+    let outbox = Outbox::open(&path).expect("A torn last entry should not stop the outbox");
+    assert_eq!(pending(&outbox), [1]);
+}
+
+#[test]
+fn test_corrupt_outbox_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("outbox.log");
+    std::fs::write(&path, "not an entry\n").unwrap();
+    assert!(Outbox::open(&path).is_err(), "Corrupt outbox should not be opened");
+}
@@ -0,0 +1,36 @@
+use embedded_recruitment_task::socket::{is_disconnect, is_timeout};
+use std::{
+    io::{self, ErrorKind, Read},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+#[test]
+fn test_read_timeout_is_a_timeout_on_this_platform() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener");
+    let mut stream = TcpStream::connect(listener.local_addr().unwrap()).expect("Failed to connect");
+    let (_peer, _) = listener.accept().expect("Failed to accept");
+
+    // Whatever kind the platform reports for a silent peer, the server treats it as a timeout
+    stream.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+    let error = stream.read(&mut [0u8; 16]).expect_err("Read should time out");
+    assert!(is_timeout(&error), "Read timeout reported as {:?}", error.kind());
+    assert!(!is_disconnect(&error), "A timeout is not a disconnect");
+}
+
+#[test]
+fn test_error_kinds_are_classified() {
+    for kind in [ErrorKind::WouldBlock, ErrorKind::TimedOut] {
+        assert!(is_timeout(&io::Error::from(kind)), "{:?} should be a timeout", kind);
+    }
+    for kind in [
+        ErrorKind::ConnectionAborted,
+        ErrorKind::ConnectionReset,
+        ErrorKind::BrokenPipe,
+        ErrorKind::UnexpectedEof,
+    ] {
+        assert!(is_disconnect(&io::Error::from(kind)), "{:?} should be a disconnect", kind);
+        assert!(!is_timeout(&io::Error::from(kind)), "{:?} is not a timeout", kind);
+    }
+    assert!(!is_disconnect(&io::Error::from(ErrorKind::InvalidData)), "Bad data is not a disconnect");
+}
@@ -0,0 +1,148 @@
+#![cfg(feature = "plugins")]
+
+use embedded_recruitment_task::{
+    config::ServerConfig,
+    message::{client_message, server_message, AddRequest, ClientMessage, DynamicMessage, EchoMessage, ErrorCode},
+    plugin::{Plugin, PluginBytes, PluginVTable, PLUGIN_ABI_VERSION, PLUGIN_OK},
+    reflect::DynamicHandler,
+    server::Server,
+    stubs::ClientStubs,
+};
+use prost::Message;
+use std::thread;
+#[allow(dead_code)]
+mod client;
+
+// Hand `bytes` to the server in `out`, released by `free`
+fn reply(out: *mut PluginBytes, bytes: Vec<u8>, status: i32) -> i32 {
+    let bytes: &'static [u8] = Box::leak(bytes.into_boxed_slice());
+    unsafe { *out = PluginBytes::new(bytes) };
+    status
+}
+
+extern "C" fn free(buffer: PluginBytes) {
+    let bytes = std::ptr::slice_from_raw_parts_mut(buffer.data as *mut u8, buffer.len);
+    drop(unsafe { Box::from_raw(bytes) });
+}
+
+// Answer an echo with its content reversed
+extern "C" fn handle(type_name: PluginBytes, payload: PluginBytes, out: *mut PluginBytes) -> i32 {
+    let type_name = String::from_utf8(unsafe { type_name.to_vec() }).unwrap();
+    let echo = EchoMessage::decode(&unsafe { payload.to_vec() }[..]).unwrap();
+    if echo.content.is_empty() {
+        return reply(out, b"Empty echo".to_vec(), ErrorCode::InvalidRequest as i32);
+    }
+    let content = echo.content.chars().rev().collect();
+    let response = DynamicMessage::new(type_name, EchoMessage { content }.encode_to_vec());
+    reply(out, response.encode_to_vec(), PLUGIN_OK)
+}
+
+// Middleware turning away additions with an unlucky operand
+extern "C" fn filter(message_type: PluginBytes, request: PluginBytes, out: *mut PluginBytes) -> i32 {
+    if unsafe { message_type.to_vec() } != b"AddRequest" {
+        return PLUGIN_OK;
+    }
+    match ClientMessage::decode(&unsafe { request.to_vec() }[..]).unwrap().message {
+        Some(client_message::Message::AddRequest(add)) if add.a == 13 => {
+            reply(out, b"Unlucky operand".to_vec(), ErrorCode::InvalidRequest as i32)
+        }
+        _ => PLUGIN_OK,
+    }
+}
+
+// A plugin linked into the test, as a library would export it
+fn vtable(abi_version: u32) -> &'static PluginVTable {
+    let types: &'static [PluginBytes] = Box::leak(Box::new([PluginBytes::new(b"messages.EchoMessage")]));
+    Box::leak(Box::new(PluginVTable {
+        abi_version,
+        name: PluginBytes::new(b"reverse"),
+        schema: PluginBytes::empty(),
+        message_types: types.as_ptr(),
+        message_type_count: types.len(),
+        handle: Some(handle),
+        filter: Some(filter),
+        free: Some(free),
+    }))
+}
+
+#[test]
+fn test_plugin_calls() {
+    let plugin = unsafe { Plugin::from_vtable(vtable(PLUGIN_ABI_VERSION)) }.expect("Failed to wrap the plugin");
+    assert_eq!(plugin.name(), "reverse");
+    assert_eq!(plugin.message_types(), ["messages.EchoMessage"]);
+    assert!(plugin.schema().unwrap().is_none(), "The plugin brings no schema");
+
+    let payload = EchoMessage { content: "abc".to_string() }.encode_to_vec();
+    let response = plugin.handle("messages.EchoMessage", &payload).expect("Plugin handler failed");
+    assert_eq!(EchoMessage::decode(&response.payload[..]).unwrap().content, "cba");
+    let error = plugin.handle("messages.EchoMessage", &[]).unwrap_err();
+    assert_eq!((error.code(), error.message.as_str()), (ErrorCode::InvalidRequest, "Empty echo"));
+
+    let request = |a| ClientMessage::add(a, 1).encode_to_vec();
+    assert!(plugin.filter("AddRequest", &request(1)).is_ok());
+    assert!(plugin.filter("AddRequest", &request(13)).is_err(), "Middleware should reject the request");
+
+    // A plugin built against another ABI is refused
+    assert!(unsafe { Plugin::from_vtable(vtable(PLUGIN_ABI_VERSION + 1)) }.is_err());
+}
+
+#[test]
+fn test_server_runs_plugins() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = Server::new("localhost:2491").expect("Failed to create server");
+    let plugin = unsafe { Plugin::from_vtable(vtable(PLUGIN_ABI_VERSION)) }.unwrap();
+    server.add_plugin(plugin).expect("Failed to add the plugin");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let mut client = client::Client::new("localhost", 2491, 2000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let mut call = |request: client_message::Message| client.call(request).expect("Failed to call the server");
+
+    // The plugin handles its message type
+    let payload = EchoMessage { content: "plugin".to_string() }.encode_to_vec();
+    let response = call(DynamicMessage::new("messages.EchoMessage", payload).into());
+    let Some(server_message::Message::DynamicMessage(echo)) = response.message else {
+        panic!("Expected a dynamic message, got {}", response);
+    };
+    assert_eq!(EchoMessage::decode(&echo.payload[..]).unwrap().content, "nigulp");
+
+    // Its middleware sees every request
+    let response = call(AddRequest { a: 13, b: 1 }.into());
+    let rejected = matches!(&response.message,
+        Some(server_message::Message::ErrorResponse(error)) if error.code() == ErrorCode::InvalidRequest);
+    assert!(rejected, "Middleware should reject the request: {}", response);
+    let response = call(AddRequest { a: 2, b: 1 }.into());
+    assert!(matches!(response.message, Some(server_message::Message::AddResponse(_))), "Unexpected {}", response);
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_invalid_plugin_library() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("broken.so"), b"not a library").unwrap();
+    std::fs::write(dir.path().join("README"), b"ignored").unwrap();
+    let config = ServerConfig {
+        plugin_dir: Some(dir.path().to_path_buf()),
+        ..ServerConfig::default()
+    };
+    let error = Server::with_config("localhost:2492", config).unwrap_err();
+    assert!(error.to_string().contains("broken.so"), "Error should name the library: {}", error);
+}
+
+#[cfg(not(unix))]
+#[test]
+fn test_plugin_libraries_need_unix() {
+    let config = ServerConfig {
+        plugin_dir: Some(std::env::temp_dir()),
+        ..ServerConfig::default()
+    };
+    let error = Server::with_config("localhost:2492", config).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+}
@@ -0,0 +1,102 @@
+use embedded_recruitment_task::pool::BufferPool;
+
+#[test]
+fn test_buffer_is_reused_after_release() {
+    let pool = BufferPool::new();
+
+    // The first acquisition has to allocate
+    {
+        let mut buffer = pool.acquire(100);
+        buffer.extend_from_slice(b"Hello, World!");
+        assert_eq!(pool.stats().in_use, 1, "Buffer should be counted as in use");
+    }
+
+    // Dropping the buffer returns it, the next acquisition of the same class reuses it
+    let buffer = pool.acquire(200);
+    assert!(buffer.is_empty(), "Reused buffer should be cleared");
+    let stats = pool.stats();
+    assert_eq!(stats.classes[0].misses, 1, "Only the first acquisition should allocate");
+    assert_eq!(stats.classes[0].hits, 1, "Second acquisition should reuse the idle buffer");
+}
+
+#[test]
+fn test_size_class_selection() {
+    let pool = BufferPool::new();
+
+    // Each request is served from the smallest class that fits it
+    let small = pool.acquire(1);
+    let medium = pool.acquire(1000);
+    let large = pool.acquire(10_000);
+    assert!(small.capacity() >= 512, "Small buffer has the wrong capacity");
+    assert!(medium.capacity() >= 4 * 1024, "Medium buffer has the wrong capacity");
+    assert!(large.capacity() >= 64 * 1024, "Large buffer has the wrong capacity");
+    drop((small, medium, large));
+
+    let stats = pool.stats();
+    assert_eq!(stats.in_use, 0, "All buffers should have been returned");
+    for class in &stats.classes {
+        assert_eq!(class.idle, 1, "Each class should hold exactly one idle buffer");
+    }
+    assert_eq!(stats.idle_bytes(), 512 + 4 * 1024 + 64 * 1024 + 16);
+}
+
+#[test]
+fn test_oversized_buffers_are_not_pooled() {
+    let pool = BufferPool::new();
+
+    // Requests larger than every class are allocated exactly and freed on release
+    drop(pool.acquire(1024 * 1024));
+
+    let stats = pool.stats();
+    assert_eq!(stats.oversized, 1, "Oversized acquisition should be counted");
+    assert_eq!(stats.idle_bytes(), 0, "Oversized buffer must not be kept");
+}
+
+#[test]
+fn test_grown_buffer_moves_to_larger_class() {
+    let pool = BufferPool::new();
+
+    // A small buffer that grew past the next class is pooled in the larger class
+    {
+        let mut buffer = pool.acquire(1);
+        buffer.resize(5000, 0);
+    }
+
+    let stats = pool.stats();
+    assert_eq!(stats.classes[0].idle, 0, "Grown buffer should leave the small class");
+    assert_eq!(stats.classes[1].idle, 1, "Grown buffer should join the medium class");
+}
+
+#[test]
+fn test_grown_buffer_is_counted_with_its_capacity() {
+    let pool = BufferPool::new();
+    let mut buffer = pool.acquire(1);
+    assert_eq!(pool.stats().bytes_in_use, 512, "Buffer should count with its size class");
+
+    // A buffer that grew past its size class counts with the capacity it has now, also while it is in use
+    buffer.resize(5000, 0);
+    let capacity = buffer.capacity();
+    assert!(capacity >= 5000, "Buffer should have grown");
+    assert_eq!(pool.stats().bytes_in_use, capacity, "Growth should be counted when it happens");
+    assert_eq!(pool.stats().total_bytes(), capacity);
+
+    // Once released it counts with the same capacity as an idle buffer
+    drop(buffer);
+    let stats = pool.stats();
+    assert_eq!(stats.bytes_in_use, 0, "Released buffer should no longer be in use");
+    assert_eq!(stats.idle_bytes(), capacity, "Idle buffer should count with its grown capacity");
+}
+
+#[test]
+fn test_reads_leave_the_count_alone() {
+    let pool = BufferPool::new();
+    let mut buffer = pool.acquire(1);
+    buffer.reserve(10_000);
+    let counted = pool.stats().bytes_in_use;
+    assert_eq!(counted, buffer.capacity(), "Reserved capacity should be counted");
+
+    // Writing within the capacity and reading the buffer don't change what is counted
+    buffer.extend_from_slice(b"Hello");
+    assert_eq!(buffer.len(), 5);
+    assert_eq!(pool.stats().bytes_in_use, counted);
+}
@@ -0,0 +1,130 @@
+use embedded_recruitment_task::protocol::{check_compatibility, describe, descriptor, schema_lock, MAX_MESSAGE_SIZE};
+use prost_types::{descriptor_proto::ReservedRange, field_descriptor_proto::Type, DescriptorProto, FileDescriptorSet};
+
+#[test]
+fn test_descriptor_matches_generated_types() {
+    let set = descriptor();
+    let file = set.file.iter().find(|file| file.package() == "messages").expect("messages package");
+    let client_message = file
+        .message_type
+        .iter()
+        .find(|message| message.name() == "ClientMessage")
+        .expect("ClientMessage should be described");
+
+    // Field numbers are the wire contract, they must match the proto definition
+    let number = |name: &str| {
+        client_message
+            .field
+            .iter()
+            .find(|field| field.name() == name)
+            .map(|field| field.number())
+    };
+    assert_eq!(number("echo_message"), Some(1));
+    assert_eq!(number("add_request"), Some(2));
+    assert_eq!(number("metadata"), Some(15));
+}
+
+#[test]
+fn test_describe_json() {
+    let json = describe();
+    assert!(json.starts_with("{\"wire_format\":"), "Unexpected start: {}", json);
+    assert!(
+        json.contains(&format!("\"max_message_bytes\":{}", MAX_MESSAGE_SIZE)),
+        "Frame limit missing"
+    );
+    assert!(
+        json.contains("{\"name\":\"a\",\"number\":1,\"type\":\"int32\",\"label\":\"singular\",\"oneof\":null}"),
+        "AddRequest.a missing"
+    );
+    assert!(
+        json.contains(
+            "{\"name\":\"add_request\",\"number\":2,\"type\":\"messages.AddRequest\",\"label\":\"singular\",\"oneof\":\"message\"}"
+        ),
+        "ClientMessage.add_request missing"
+    );
+    assert!(
+        json.contains("{\"name\":\"ERROR_CODE_EXPIRED\",\"number\":1}"),
+        "Error codes missing"
+    );
+
+    // Braces and brackets outside strings must balance
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in json.chars() {
+        match (in_string, escaped, c) {
+            (true, true, _) => escaped = false,
+            (true, false, '\\') => escaped = true,
+            (_, false, '"') => in_string = !in_string,
+            (false, _, '{' | '[') => depth += 1,
+            (false, _, '}' | ']') => depth -= 1,
+            _ => {}
+        }
+        assert!(depth >= 0, "Unbalanced JSON");
+    }
+    assert_eq!(depth, 0, "Unbalanced JSON");
+}
+
+#[test]
+fn test_schema_is_compatible_with_release() {
+    // Set UPDATE_SCHEMA_LOCK=1 when cutting a release to record the shipped schema
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/proto/messages.lock");
+    let current = descriptor();
+    if std::env::var_os("UPDATE_SCHEMA_LOCK").is_some() {
+        std::fs::write(path, schema_lock(&current)).expect("Failed to write schema lock");
+    }
+
+    let lock = std::fs::read_to_string(path).expect("Failed to read schema lock");
+    if let Err(problems) = check_compatibility(&lock, &current) {
+        panic!("Schema breaks fielded clients:\n{}", problems.join("\n"));
+    }
+}
+
+// Mutable access to a top-level message of the schema
+fn message<'a>(set: &'a mut FileDescriptorSet, name: &str) -> &'a mut DescriptorProto {
+    set.file[0]
+        .message_type
+        .iter_mut()
+        .find(|message| message.name() == name)
+        .unwrap()
+}
+
+#[test]
+fn test_incompatible_changes_are_reported() {
+    let mut set = descriptor();
+    let lock = schema_lock(&set);
+
+    // Renumbering a field
+    message(&mut set, "AddRequest").field[0].number = Some(7);
+    let problems = check_compatibility(&lock, &set).expect_err("Renumbering must be rejected");
+    assert_eq!(problems, ["messages.AddRequest.a was renumbered from 1 to 7"]);
+
+    // Removing a field without reserving its number
+    message(&mut set, "AddRequest").field.remove(0);
+    let problems = check_compatibility(&lock, &set).expect_err("Removal must be rejected");
+    assert_eq!(problems, ["messages.AddRequest.a was removed without reserving number 1"]);
+
+    // Reserving the number makes the removal safe
+    message(&mut set, "AddRequest").reserved_range.push(ReservedRange {
+        start: Some(1),
+        end: Some(2),
+    });
+    assert_eq!(check_compatibility(&lock, &set), Ok(()));
+
+    // Changing a field's type
+    message(&mut set, "AddRequest").field[0].r#type = Some(Type::String as i32);
+    let problems = check_compatibility(&lock, &set).expect_err("Type change must be rejected");
+    assert_eq!(problems, ["messages.AddRequest.b changed type from singular int32 to singular string"]);
+}
+
+#[test]
+fn test_new_fields_are_compatible() {
+    let mut set = descriptor();
+    let lock = schema_lock(&set);
+    let echo = message(&mut set, "EchoMessage");
+    let mut added = echo.field[0].clone();
+    added.name = Some("sender".to_string());
+    added.number = Some(2);
+    echo.field.push(added);
+    assert_eq!(check_compatibility(&lock, &set), Ok(()), "Adding fields must stay compatible");
+}
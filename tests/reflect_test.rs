@@ -0,0 +1,92 @@
+#![cfg(feature = "reflect")]
+
+use embedded_recruitment_task::{
+    message::{AddRequest, SelfTestCheck, SelfTestResponse},
+    reflect::Schema,
+};
+use prost::Message;
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+};
+
+// A schema unknown to the server: `telemetry.Reading { string sensor = 1; repeated sint32 samples = 2; }`
+fn telemetry() -> FileDescriptorSet {
+    let field = |name: &str, number: i32, kind: Type, label: Label| FieldDescriptorProto {
+        name: Some(name.to_string()),
+        number: Some(number),
+        r#type: Some(kind as i32),
+        label: Some(label as i32),
+        ..FieldDescriptorProto::default()
+    };
+    FileDescriptorSet {
+        file: vec![FileDescriptorProto {
+            name: Some("telemetry.proto".to_string()),
+            package: Some("telemetry".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Reading".to_string()),
+                field: vec![
+                    field("sensor", 1, Type::String, Label::Optional),
+                    field("samples", 2, Type::Sint32, Label::Repeated),
+                ],
+                ..DescriptorProto::default()
+            }],
+            ..FileDescriptorProto::default()
+        }],
+    }
+}
+
+#[test]
+fn test_embedded_messages_validate() {
+    let schema = Schema::embedded();
+    assert!(schema.contains("messages.AddRequest"));
+    assert!(schema.contains(".messages.AddRequest"), "A leading dot should be accepted");
+
+    let add = AddRequest { a: 1, b: -2 }.encode_to_vec();
+    assert_eq!(schema.validate("messages.AddRequest", &add), Ok(()));
+    assert_eq!(schema.validate("messages.AddRequest", &[]), Ok(()), "An empty message is valid");
+
+    // Nested messages are validated as well
+    let response = SelfTestResponse {
+        checks: vec![SelfTestCheck {
+            name: "pool".to_string(),
+            passed: true,
+            ..SelfTestCheck::default()
+        }],
+        passed: true,
+    };
+    assert_eq!(schema.validate("messages.SelfTestResponse", &response.encode_to_vec()), Ok(()));
+}
+
+#[test]
+fn test_invalid_payloads_are_rejected() {
+    let schema = Schema::embedded();
+    let invalid = |type_name: &str, payload: &[u8]| schema.validate(type_name, payload).unwrap_err();
+
+    assert!(invalid("telemetry.Reading", &[]).contains("Unknown message type"));
+    // Field 3 does not exist in AddRequest
+    assert!(invalid("messages.AddRequest", &[0x18, 0x01]).contains("no field 3"));
+    // Field 1 of AddRequest is an int32, not length-delimited
+    assert!(invalid("messages.AddRequest", &[0x0a, 0x01, 0x00]).contains("wire type"));
+    // A length running past the end of the payload
+    assert!(invalid("messages.EchoMessage", &[0x0a, 0x05, b'h', b'i']).contains("Truncated"));
+    // Echo content must be UTF-8
+    assert!(invalid("messages.EchoMessage", &[0x0a, 0x01, 0xff]).contains("UTF-8"));
+    // A nested SelfTestCheck with an unknown field
+    assert!(invalid("messages.SelfTestResponse", &[0x0a, 0x02, 0x78, 0x01]).contains("no field 15"));
+}
+
+#[test]
+fn test_added_schema_validates_packed_fields() {
+    let mut schema = Schema::new();
+    assert!(!schema.contains("telemetry.Reading"));
+    schema.add(&telemetry());
+    assert!(schema.contains("telemetry.Reading"));
+
+    // sensor = "t1", samples = [1, -1] packed as zigzag varints 2 and 1
+    let packed = [0x0a, 0x02, b't', b'1', 0x12, 0x02, 0x02, 0x01];
+    assert_eq!(schema.validate("telemetry.Reading", &packed), Ok(()));
+    // The same samples unpacked
+    let unpacked = [0x10, 0x02, 0x10, 0x01];
+    assert_eq!(schema.validate("telemetry.Reading", &unpacked), Ok(()));
+}
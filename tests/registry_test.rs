@@ -0,0 +1,50 @@
+use embedded_recruitment_task::registry::ShardedMap;
+use std::{sync::Arc, thread};
+
+#[test]
+fn test_insert_get_remove() {
+    let map: ShardedMap<String, usize> = ShardedMap::new();
+    assert!(map.is_empty(), "New map should be empty");
+
+    // Values can be looked up by &str and removed again
+    assert_eq!(map.insert("localhost:8080".to_string(), 1), None);
+    assert_eq!(map.insert("localhost:8080".to_string(), 2), Some(1));
+    assert_eq!(map.get_cloned("localhost:8080"), Some(2));
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.remove("localhost:8080"), Some(2));
+    assert_eq!(map.get_cloned("localhost:8080"), None);
+    assert!(map.is_empty(), "Map should be empty after removal");
+}
+
+#[test]
+fn test_single_shard_map() {
+    // A shard count of zero is rounded up so every key still has a home
+    let map: ShardedMap<u32, u32> = ShardedMap::with_shards(0);
+    for key in 0..100 {
+        map.insert(key, key * 2);
+    }
+    assert_eq!(map.len(), 100);
+    assert_eq!(map.get_cloned(&42), Some(84));
+}
+
+#[test]
+fn test_shard_guard_makes_updates_atomic() {
+    let map: Arc<ShardedMap<&'static str, usize>> = Arc::new(ShardedMap::new());
+
+    // Read-modify-write through the shard guard from many threads must not lose updates
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    *map.shard("counter").entry("counter").or_insert(0) += 1;
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        assert!(handle.join().is_ok(), "Worker thread panicked");
+    }
+
+    assert_eq!(map.get_cloned("counter"), Some(8000));
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "routing")]
+
+use embedded_recruitment_task::{
+    config::ServerConfig,
+    message::{client_message, server_message, AddRequest, DynamicMessage, EchoMessage},
+    routing::{RouteAction, RoutingTable},
+    server::Server,
+    stubs::ClientStubs,
+};
+use prost::Message;
+use std::{io::ErrorKind, path::Path, thread, time::Duration};
+#[allow(dead_code)]
+mod client;
+
+#[test]
+fn test_routing_file_parses() {
+    let text = "
+        # Site rules
+        AddRequest            forward
+        EchoMessage           drop
+
+        .telemetry.Reading    handler telemetry
+    ";
+    let table = RoutingTable::parse(text, Path::new("/etc/gateway")).expect("Failed to parse routes");
+    assert_eq!(table.len(), 3);
+    assert_eq!(table.action("AddRequest"), Some(&RouteAction::Forward));
+    assert_eq!(table.action("EchoMessage"), Some(&RouteAction::Drop));
+    assert_eq!(table.action("telemetry.Reading"), Some(&RouteAction::Handler("telemetry".to_string())));
+    assert_eq!(table.action("StatsRequest"), None);
+}
+
+#[test]
+fn test_invalid_routing_files() {
+    for text in [
+        "AddRequest",
+        "AddRequest teleport",
+        "AddRequest handler adder",
+        "AddRequest forward\nAddRequest drop",
+    ] {
+        let error = RoutingTable::parse(text, Path::new("")).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData, "{:?} should be rejected", text);
+    }
+    let error = RoutingTable::parse("EchoMessage drop\nbad", Path::new("")).unwrap_err();
+    assert!(error.to_string().contains("line 2"), "Error should name the line: {}", error);
+
+    // Script rules need the scripts to compile, or the feature to run them
+    let error = RoutingTable::parse("EchoMessage script missing.rhai", Path::new("")).unwrap_err();
+    assert!(error.to_string().contains("missing.rhai"), "Error should name the script: {}", error);
+}
+
+#[test]
+fn test_server_routes_by_file() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("routes");
+    std::fs::write(&path, "EchoMessage drop\nmessages.AddRequest handler adder\n").unwrap();
+    let config = ServerConfig {
+        routes_path: Some(path.clone()),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2495", config).expect("Failed to create server");
+    server.register_handler("adder", |type_name: &str, payload: &[u8]| {
+        let request = AddRequest::decode(payload).unwrap();
+        Ok(DynamicMessage::new(type_name, AddRequest { a: request.a + request.b, b: 0 }.encode_to_vec()))
+    });
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let mut client = client::Client::new("localhost", 2495, 2000);
+    // Dropped requests get no answer, so stop waiting for one after a while
+    client.on_connect(|stream| stream.set_read_timeout(Some(Duration::from_millis(300))));
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let mut call = |request: client_message::Message| client.call(request).ok();
+    let echo = |content: &str| client_message::Message::from(EchoMessage { content: content.into() });
+
+    // Dropped requests get no answer, the named handler answers its type
+    assert!(call(echo("hello")).is_none(), "Dropped request was answered");
+    let payload = AddRequest { a: 2, b: 3 }.encode_to_vec();
+    let response = call(DynamicMessage::new("messages.AddRequest", payload).into());
+    let Some(server_message::Message::DynamicMessage(sum)) = response.and_then(|response| response.message) else {
+        panic!("Expected the named handler to answer");
+    };
+    assert_eq!(AddRequest::decode(&sum.payload[..]).unwrap().a, 5);
+
+    // A reload takes effect for the next request, an invalid table leaves the rules in use
+    std::fs::write(&path, "AddRequest forward\n").unwrap();
+    assert!(server.reload_routes().is_err(), "Forwarding without a gateway should be rejected");
+    assert!(call(echo("still dropped")).is_none(), "Rejected reload changed the rules");
+    std::fs::write(&path, "# No rules\n").unwrap();
+    server.reload_routes().expect("Failed to reload the routes");
+    let response = call(echo("hello")).expect("Echo should be answered after the reload");
+    assert_eq!(response.message, Some(server_message::Message::EchoMessage(EchoMessage { content: "hello".into() })));
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
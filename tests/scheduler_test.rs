@@ -0,0 +1,84 @@
+use embedded_recruitment_task::{clock::ManualClock, scheduler::Scheduler};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+#[test]
+fn test_jobs_run_until_stopped() {
+    let scheduler = Arc::new(Scheduler::new());
+    let is_running = Arc::new(AtomicBool::new(true));
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    let counter = Arc::clone(&runs);
+    scheduler.schedule(Duration::from_millis(10), move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+    // A panicking job must not stop the others
+    scheduler.schedule(Duration::from_millis(10), || panic!("job failed"));
+    assert_eq!(scheduler.len(), 2);
+
+    let handle = {
+        let (scheduler, is_running) = (Arc::clone(&scheduler), Arc::clone(&is_running));
+        thread::spawn(move || scheduler.run(&is_running))
+    };
+    thread::sleep(Duration::from_millis(200));
+    is_running.store(false, Ordering::SeqCst);
+    assert!(handle.join().is_ok(), "Scheduler thread should stop with the flag");
+
+    let runs = runs.load(Ordering::SeqCst);
+    assert!(runs >= 5, "Job should have run repeatedly, ran {} times", runs);
+    assert_eq!(scheduler.len(), 2, "Panicking job should stay scheduled");
+}
+
+#[test]
+fn test_first_run_waits_one_interval() {
+    let scheduler = Arc::new(Scheduler::new());
+    let is_running = Arc::new(AtomicBool::new(true));
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    let counter = Arc::clone(&runs);
+    scheduler.schedule(Duration::from_secs(60), move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let handle = {
+        let (scheduler, is_running) = (Arc::clone(&scheduler), Arc::clone(&is_running));
+        thread::spawn(move || scheduler.run(&is_running))
+    };
+    thread::sleep(Duration::from_millis(50));
+    is_running.store(false, Ordering::SeqCst);
+    handle.join().unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 0, "Job must not run before its interval elapsed");
+}
+
+#[test]
+fn test_jobs_follow_manual_clock() {
+    let clock = Arc::new(ManualClock::new());
+    let scheduler = Arc::new(Scheduler::with_clock(clock.clone()));
+    let is_running = Arc::new(AtomicBool::new(true));
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    let counter = Arc::clone(&runs);
+    scheduler.schedule(Duration::from_secs(3600), move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let handle = {
+        let (scheduler, is_running) = (Arc::clone(&scheduler), Arc::clone(&is_running));
+        thread::spawn(move || scheduler.run(&is_running))
+    };
+    thread::sleep(Duration::from_millis(150));
+    assert_eq!(runs.load(Ordering::SeqCst), 0, "Job must wait for virtual time, not real time");
+
+    // An hour of virtual time passes at once, the scheduler notices within its idle interval
+    clock.advance(Duration::from_secs(3600));
+    thread::sleep(Duration::from_millis(300));
+    is_running.store(false, Ordering::SeqCst);
+    handle.join().unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 1, "Job should run once per advanced interval");
+}
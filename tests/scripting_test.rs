@@ -0,0 +1,99 @@
+#![cfg(feature = "scripting")]
+
+use embedded_recruitment_task::{
+    config::{ScriptHook, ServerConfig},
+    message::{client_message, server_message, AddRequest, AddResponse, EchoMessage},
+    scripting::Scripts,
+    server::Server,
+    stubs::ClientStubs,
+};
+use std::{path::Path, thread};
+#[allow(dead_code)]
+mod client;
+
+// Write `source` as a script for `message_type` in `dir`
+fn hook(dir: &Path, message_type: &str, source: &str) -> ScriptHook {
+    let path = dir.join(format!("{}.rhai", message_type));
+    std::fs::write(&path, source).unwrap();
+    ScriptHook {
+        message_type: message_type.to_string(),
+        path,
+    }
+}
+
+#[test]
+fn test_scripts_transform_by_message_type() {
+    let dir = tempfile::tempdir().unwrap();
+    let shout = "fn on_request(request) { request.content = request.content.to_upper(); request }";
+    let scripts = Scripts::load(&[
+        hook(dir.path(), "EchoMessage", shout),
+        hook(dir.path(), "AddRequest", "fn on_response(response) { response.result += 1000; response }"),
+    ])
+    .expect("Failed to load scripts");
+    assert_eq!(scripts.len(), 2);
+
+    let echo = client_message::Message::from(EchoMessage { content: "quiet".to_string() });
+    let loud = client_message::Message::from(EchoMessage { content: "QUIET".to_string() });
+    assert_eq!(scripts.on_request("EchoMessage", echo), Ok(loud));
+    // Types and directions without a function pass unchanged
+    let add = client_message::Message::from(AddRequest { a: 2, b: 3 });
+    let sum = server_message::Message::from(AddResponse { result: 5 });
+    assert_eq!(scripts.on_request("AddRequest", add.clone()), Ok(add));
+    assert_eq!(scripts.on_response("EchoMessage", sum.clone()), Ok(sum.clone()));
+    assert_eq!(scripts.on_response("AddRequest", sum), Ok(AddResponse { result: 1005 }.into()));
+}
+
+#[test]
+fn test_broken_scripts() {
+    let dir = tempfile::tempdir().unwrap();
+    let error = Scripts::load(&[hook(dir.path(), "EchoMessage", "fn on_request(request) {")]).unwrap_err();
+    assert!(error.to_string().contains("EchoMessage.rhai"), "Error should name the script: {}", error);
+    let missing = ScriptHook {
+        message_type: "EchoMessage".to_string(),
+        path: dir.path().join("missing.rhai"),
+    };
+    assert!(Scripts::load(&[missing]).is_err(), "A missing script should fail loading");
+
+    // A runaway script is stopped, and one returning the wrong shape is an error
+    let scripts = Scripts::load(&[
+        hook(dir.path(), "EchoMessage", "fn on_request(request) { loop {} }"),
+        hook(dir.path(), "AddRequest", "fn on_request(request) { 42 }"),
+    ])
+    .unwrap();
+    assert!(scripts.on_request("EchoMessage", EchoMessage::default().into()).is_err());
+    assert!(scripts.on_request("AddRequest", AddRequest::default().into()).is_err());
+}
+
+#[test]
+fn test_server_runs_scripts() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let config = ServerConfig {
+        scripts: vec![hook(
+            dir.path(),
+            "EchoMessage",
+            "fn on_request(request) { request.content = `<${request.content}>`; request }",
+        )],
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2490", config).expect("Failed to create server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let mut client = client::Client::new("localhost", 2490, 2000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let request = EchoMessage { content: "hello".to_string() };
+    let response = client.call(request.into()).expect("Failed to call the server");
+    assert_eq!(
+        response.message,
+        Some(server_message::Message::EchoMessage(EchoMessage {
+            content: "<hello>".to_string()
+        }))
+    );
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,23 @@
+use embedded_recruitment_task::selftest::SelfTests;
+
+#[test]
+fn test_registering_same_name_replaces_check() {
+    let tests = SelfTests::new();
+    tests.register("storage", || Err("unreachable".to_string()));
+    tests.register("storage", || Ok("reachable".to_string()));
+
+    let response = tests.run();
+    assert_eq!(response.checks.len(), 2, "Replacing a check must not add another one");
+    assert!(response.passed, "Replacement check should be the one that ran");
+}
+
+#[test]
+fn test_panicking_check_fails() {
+    let tests = SelfTests::new();
+    tests.register("broken", || panic!("sensor missing"));
+
+    let response = tests.run();
+    let broken = response.checks.iter().find(|check| check.name == "broken").unwrap();
+    assert!(!broken.passed, "A panicking check should be reported as failed");
+    assert!(!response.passed, "The run should fail as a whole");
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "serde")]
+
+use embedded_recruitment_task::message::{
+    client_message, AddRequest, ClientMessage, EchoMessage, Event, EventKind, Metadata,
+};
+
+#[test]
+fn test_message_json_round_trip() {
+    let message = ClientMessage {
+        message: Some(client_message::Message::EchoMessage(EchoMessage {
+            content: "hello".to_string(),
+        })),
+        metadata: Some(Metadata {
+            trace_id: "trace-1".to_string(),
+            sequence: 7,
+            ..Metadata::default()
+        }),
+    };
+    let json = serde_json::to_string(&message).expect("Serialize failed");
+    assert!(json.contains("\"content\":\"hello\""), "Unexpected JSON: {}", json);
+    let decoded: ClientMessage = serde_json::from_str(&json).expect("Deserialize failed");
+    assert_eq!(decoded, message);
+}
+
+#[test]
+fn test_missing_fields_take_defaults() {
+    // Only the fields that differ from the protobuf defaults need to be written
+    let request: AddRequest = serde_json::from_str("{\"a\":2}").expect("Deserialize failed");
+    assert_eq!(request, AddRequest { a: 2, b: 0 });
+
+    // Enumerations are stored by number, like on the wire
+    let event: Event = serde_json::from_str("{\"kind\":1,\"detail\":\"127.0.0.1:5000\"}").expect("Deserialize failed");
+    assert_eq!(event.kind(), EventKind::Connected);
+    assert_eq!(event.at_us, 0);
+}
@@ -0,0 +1,53 @@
+use embedded_recruitment_task::{
+    config::LoadShedding,
+    message::{AddRequest, ClientMessage, HealthRequest, Metadata, StatsRequest},
+    shedding::{is_control_frame, reject_share, LatencyAverage},
+};
+use prost::Message;
+use std::time::Duration;
+
+#[test]
+fn test_control_frames_are_recognised() {
+    assert!(is_control_frame(&ClientMessage::from(HealthRequest {}).encode_to_vec()));
+    assert!(is_control_frame(&ClientMessage::from(StatsRequest {}).encode_to_vec()));
+    assert!(!is_control_frame(&ClientMessage::echo("data".to_string()).encode_to_vec()));
+    assert!(!is_control_frame(&ClientMessage::from(AddRequest { a: 1, b: 2 }).encode_to_vec()));
+
+    // Metadata first or no request at all can't be classified without decoding, it counts as data
+    let metadata_only = ClientMessage {
+        message: None,
+        metadata: Some(Metadata::default()),
+    };
+    assert!(!is_control_frame(&metadata_only.encode_to_vec()));
+    assert!(!is_control_frame(&[]));
+}
+
+#[test]
+fn test_reject_share_grows_with_load() {
+    let policy = LoadShedding {
+        latency_threshold: Duration::from_millis(10),
+        queue_threshold: 100,
+        max_reject_percent: 80,
+    };
+    assert_eq!(reject_share(&policy, Duration::from_millis(5), 10), 0.0, "Below both thresholds");
+    assert_eq!(reject_share(&policy, Duration::from_millis(10), 100), 0.0, "At the thresholds");
+    assert!((reject_share(&policy, Duration::from_millis(15), 0) - 0.4).abs() < 1e-9, "Halfway to twice the latency");
+    assert!((reject_share(&policy, Duration::ZERO, 150) - 0.4).abs() < 1e-9, "Halfway to twice the queue");
+    assert_eq!(reject_share(&policy, Duration::from_secs(1), 0), 0.8, "Capped at the maximum share");
+}
+
+#[test]
+fn test_latency_average_follows_samples() {
+    let average = LatencyAverage::default();
+    assert_eq!(average.get(), Duration::ZERO);
+    average.record(Duration::from_micros(800));
+    assert_eq!(average.get(), Duration::from_micros(100), "An eighth of the way towards the sample");
+    for _ in 0..100 {
+        average.record(Duration::from_micros(800));
+    }
+    assert!(average.get() > Duration::from_micros(790), "Steady samples are approached");
+    for _ in 0..100 {
+        average.record(Duration::ZERO);
+    }
+    assert!(average.get() < Duration::from_micros(10), "The average decays again");
+}
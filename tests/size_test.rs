@@ -0,0 +1,95 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+// Modules of the optional subsystems, each behind a feature of its own that the minimal build leaves out
+const OPTIONAL_MODULES: [&str; 9] =
+    ["conformance", "dedup", "delta", "gateway", "outbox", "plugin", "reflect", "routing", "vectors"];
+
+// Flash budget for the server binary on the gateways, which have 8 MiB in total
+const MAX_BINARY_BYTES: u64 = 2 * 1024 * 1024;
+
+// Builds the server the way the gateway image does and fails when it outgrows its budget. Ignored by
+// default because it runs a full release build: `cargo test --test size_test -- --ignored`
+#[test]
+#[ignore]
+fn test_minimal_binary_fits_flash_budget() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // A separate target directory, so the build doesn't wait on the lock of the running test build
+    let target_dir = root.join("target").join("size-check");
+    let build_args = [
+        "--profile",
+        "release-small",
+        "--no-default-features",
+        "--features",
+        "minimal",
+        "--bin",
+        "server",
+    ];
+
+    let status = Command::new(env!("CARGO"))
+        .arg("build")
+        .args(build_args)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .current_dir(&root)
+        .status()
+        .expect("Failed to run cargo build");
+    assert!(status.success(), "Minimal server build failed");
+
+    let binary = target_dir
+        .join("release-small")
+        .join(format!("server{}", env::consts::EXE_SUFFIX));
+    let size = fs::metadata(&binary).expect("Minimal server binary missing").len();
+    println!("Minimal server binary: {} bytes of {} allowed", size, MAX_BINARY_BYTES);
+    if size <= MAX_BINARY_BYTES {
+        return;
+    }
+
+    // Show where the space went when cargo-bloat is installed
+    let bloat = Command::new(env!("CARGO"))
+        .arg("bloat")
+        .args(build_args)
+        .args(["--crates", "-n", "20", "--target-dir"])
+        .arg(&target_dir)
+        .current_dir(&root)
+        .output();
+    match bloat {
+        Ok(output) if output.status.success() => println!("{}", String::from_utf8_lossy(&output.stdout)),
+        _ => println!("Install cargo-bloat for a per-crate size report"),
+    }
+    panic!("Minimal server binary is {} bytes, over the {} byte budget", size, MAX_BINARY_BYTES);
+}
+
+// Checks a crate importing every optional module against the minimal library, which must refuse each of the
+// imports. Ignored by default like the size check: `cargo test --test size_test -- --ignored`
+#[test]
+#[ignore]
+fn test_minimal_build_leaves_out_optional_subsystems() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let probe = tempfile::tempdir().expect("Failed to create the probe crate directory");
+    let manifest = format!(
+        "[package]\nname = \"probe\"\nversion = \"0.0.0\"\nedition = \"2021\"\n\n[workspace]\n\n[dependencies]\n\
+         embedded-recruitment-task = {{ path = {:?}, default-features = false, features = [\"minimal\"] }}\n",
+        root
+    );
+    fs::write(probe.path().join("Cargo.toml"), manifest).unwrap();
+    fs::create_dir(probe.path().join("src")).unwrap();
+    let imports: String = OPTIONAL_MODULES
+        .iter()
+        .map(|module| format!("pub use embedded_recruitment_task::{};\n", module))
+        .collect();
+    fs::write(probe.path().join("src").join("lib.rs"), imports).unwrap();
+
+    let output = Command::new(env!("CARGO"))
+        .arg("check")
+        .arg("--target-dir")
+        .arg(root.join("target").join("size-check"))
+        .current_dir(probe.path())
+        .output()
+        .expect("Failed to run cargo check");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "The minimal build should not have the optional modules");
+    for module in OPTIONAL_MODULES {
+        let missing = format!("`embedded_recruitment_task::{}`", module);
+        assert!(stderr.contains(&missing), "The minimal build has the `{}` module:\n{}", module, stderr);
+    }
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "snmp")]
+
+use embedded_recruitment_task::{
+    metrics::{Metric, MetricsExporter},
+    snmp::SnmpExporter,
+};
+use std::{net::UdpSocket, time::Duration};
+
+// Enterprise subtree the test agent serves, 1.3.6.1.4.1.99999
+const BASE_OID: [u32; 7] = [1, 3, 6, 1, 4, 1, 99999];
+const BASE_OID_BER: [u8; 8] = [0x2b, 6, 1, 4, 1, 0x86, 0x8d, 0x1f];
+
+// Encode a short BER element
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    assert!(content.len() < 0x80, "Test elements use the short length form");
+    [&[tag, content.len() as u8][..], content].concat()
+}
+
+// OID below the base, as an encoded element
+fn oid(suffix: &[u8]) -> Vec<u8> {
+    tlv(0x06, &[&BASE_OID_BER[..], suffix].concat())
+}
+
+// SNMPv2c message around a PDU
+fn message(community: &str, pdu_type: u8, varbinds: &[Vec<u8>]) -> Vec<u8> {
+    let pdu = [tlv(0x02, &[42]), tlv(0x02, &[0]), tlv(0x02, &[0]), tlv(0x30, &varbinds.concat())].concat();
+    let body = [tlv(0x02, &[1]), tlv(0x04, community.as_bytes()), tlv(pdu_type, &pdu)].concat();
+    tlv(0x30, &body)
+}
+
+// Variable binding of an OID to a value
+fn varbind(oid: Vec<u8>, value: Vec<u8>) -> Vec<u8> {
+    tlv(0x30, &[oid, value].concat())
+}
+
+#[test]
+fn test_snmp_get_and_get_next() {
+    let mut exporter = SnmpExporter::bind("127.0.0.1:0", "public", &BASE_OID).expect("Failed to bind agent");
+    exporter.export(&[Metric::gauge("connections", 3), Metric::counter("requests_total", 300)]);
+
+    let manager = UdpSocket::bind("127.0.0.1:0").unwrap();
+    manager.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+    manager.connect(exporter.local_addr()).unwrap();
+    let query = |request: Vec<u8>| -> Option<Vec<u8>> {
+        manager.send(&request).unwrap();
+        let mut response = [0u8; 1500];
+        manager.recv(&mut response).ok().map(|len| response[..len].to_vec())
+    };
+    let null = tlv(0x05, &[]);
+
+    // GET of both metrics, a gauge as Gauge32 and a counter as Counter64
+    let request = message("public", 0xa0, &[varbind(oid(&[1, 0]), null.clone()), varbind(oid(&[2, 0]), null.clone())]);
+    let expected = message(
+        "public",
+        0xa2,
+        &[varbind(oid(&[1, 0]), tlv(0x42, &[3])), varbind(oid(&[2, 0]), tlv(0x46, &[0x01, 0x2c]))],
+    );
+    assert_eq!(query(request), Some(expected), "Unexpected GET response");
+
+    // GETNEXT walks from the base to the first metric, and past the last one to the end of the view
+    let request = message("public", 0xa1, &[varbind(tlv(0x06, &BASE_OID_BER), null.clone())]);
+    let expected = message("public", 0xa2, &[varbind(oid(&[1, 0]), tlv(0x42, &[3]))]);
+    assert_eq!(query(request), Some(expected), "Unexpected GETNEXT response");
+    let request = message("public", 0xa1, &[varbind(oid(&[2, 0]), null.clone())]);
+    let expected = message("public", 0xa2, &[varbind(oid(&[2, 0]), tlv(0x82, &[]))]);
+    assert_eq!(query(request), Some(expected), "GETNEXT past the last metric should end the view");
+
+    // Unknown objects are reported per binding, a wrong community gets no answer at all
+    let request = message("public", 0xa0, &[varbind(oid(&[9, 0]), null.clone())]);
+    let expected = message("public", 0xa2, &[varbind(oid(&[9, 0]), tlv(0x80, &[]))]);
+    assert_eq!(query(request), Some(expected), "Unknown object should be noSuchObject");
+    let request = message("private", 0xa0, &[varbind(oid(&[1, 0]), null)]);
+    assert_eq!(query(request), None, "Wrong community should be ignored");
+}
+
+#[test]
+fn test_snmp_rejects_invalid_base_oid() {
+    assert!(SnmpExporter::bind("127.0.0.1:0", "public", &[1]).is_err(), "Base OID needs two arcs");
+}
@@ -0,0 +1,30 @@
+#![cfg(all(feature = "reuseport", unix))]
+
+use embedded_recruitment_task::socket::bind_listener;
+use std::net::SocketAddr;
+
+#[test]
+fn test_reuse_port_listeners_share_address() {
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+    // With SO_REUSEPORT a second listener can bind the first one's address
+    let first = bind_listener(addr, 16, true).expect("Failed to bind first listener");
+    let local_addr = first.local_addr().unwrap();
+    let second = bind_listener(local_addr, 16, true);
+    assert!(second.is_ok(), "Second SO_REUSEPORT listener should bind");
+}
+
+#[test]
+fn test_exclusive_listener_rejects_second_bind() {
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+    // Without SO_REUSEPORT the address stays exclusive
+    let first = bind_listener(addr, 16, false).expect("Failed to bind first listener");
+    let local_addr = first.local_addr().unwrap();
+    let second = bind_listener(local_addr, 16, false);
+    assert_eq!(
+        second.unwrap_err().kind(),
+        std::io::ErrorKind::AddrInUse,
+        "Second exclusive listener should fail"
+    );
+}
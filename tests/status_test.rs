@@ -0,0 +1,99 @@
+use embedded_recruitment_task::{
+    health::{HealthReport, HealthStatus},
+    status::StatusReport,
+    violations::ViolationCounts,
+};
+use std::{collections::BTreeMap, time::Duration};
+
+fn report() -> StatusReport {
+    StatusReport {
+        health: HealthReport {
+            status: HealthStatus::Degraded,
+            connections: 2,
+            queue_depth: 0,
+            last_error: Some("Failed <to> bind".to_string()),
+            uptime: Duration::from_secs(90),
+        },
+        messages: BTreeMap::from([("AddRequest", 3), ("EchoMessage", 5)]),
+        violations: ViolationCounts {
+            decode_failures: 1,
+            ..ViolationCounts::default()
+        },
+        recent_errors: vec![(Duration::from_secs(4), "Failed <to> bind".to_string())],
+    }
+}
+
+#[test]
+fn test_status_report_json() {
+    let json = report().to_json();
+    assert!(json.starts_with("{\"health\":{\"status\":\"degraded\""), "Unexpected JSON: {}", json);
+    assert!(json.contains("\"messages\":{\"AddRequest\":3,\"EchoMessage\":5}"), "Unexpected JSON: {}", json);
+    assert!(json.contains("\"decode_failures\":1"), "Unexpected JSON: {}", json);
+    assert!(
+        json.ends_with("\"recent_errors\":[{\"age_secs\":4,\"message\":\"Failed <to> bind\"}]}"),
+        "Unexpected JSON: {}",
+        json
+    );
+}
+
+#[test]
+fn test_status_report_html() {
+    let html = report().to_html();
+    assert!(html.contains("<tr><th>Uptime</th><td>90 s</td></tr>"), "Missing uptime: {}", html);
+    assert!(html.contains("<tr><th>EchoMessage</th><td>5</td></tr>"), "Missing message counter: {}", html);
+    // Error text must not be able to inject markup
+    assert!(html.contains("<li>4 s ago: Failed &lt;to&gt; bind</li>"), "Error not escaped: {}", html);
+}
+
+#[cfg(feature = "status-page")]
+#[test]
+fn test_status_page_endpoint() {
+    use embedded_recruitment_task::{config::ServerConfig, server::Server};
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+    };
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        status_addr: Some("localhost:2261".to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2260", config).expect("Failed to start server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    // The page is bound by `run`, give it a moment to come up
+    let get = |path: &str| -> String {
+        let deadline = std::time::Instant::now() + Duration::from_secs(1);
+        let mut stream = loop {
+            match TcpStream::connect("localhost:2261") {
+                Ok(stream) => break stream,
+                Err(e) if std::time::Instant::now() > deadline => panic!("Status page unreachable: {}", e),
+                Err(_) => thread::sleep(Duration::from_millis(20)),
+            }
+        };
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let response = get("/");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "Unexpected response: {}", response);
+    assert!(response.contains("Content-Type: text/html"), "Page should be HTML: {}", response);
+    assert!(response.contains("<h1>Server status</h1>"), "Unexpected body: {}", response);
+
+    let response = get("/status.json");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "Unexpected response: {}", response);
+    assert!(response.contains("\"status\":\"serving\""), "Unexpected body: {}", response);
+
+    let response = get("/other");
+    assert!(response.starts_with("HTTP/1.1 404"), "Unknown paths should be 404: {}", response);
+
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "vectors")]
+
+use embedded_recruitment_task::{
+    message::{client_message, server_message, ClientMessage, ServerMessage},
+    vectors::{test_vectors, test_vectors_json, Direction},
+};
+use prost::Message;
+
+#[test]
+fn test_vectors_match_fixture() {
+    // Set UPDATE_FIXTURES=1 to re-export after an intended wire change
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/interop_vectors.json");
+    if std::env::var_os("UPDATE_FIXTURES").is_some() {
+        std::fs::write(path, test_vectors_json()).expect("Failed to write fixture");
+    }
+    let fixture = std::fs::read_to_string(path).expect("Failed to read fixture");
+    assert_eq!(fixture, test_vectors_json(), "Encoding changed, the exported vectors no longer match");
+}
+
+#[test]
+fn test_known_frame_bytes() {
+    let vectors = test_vectors();
+    let frame = |name: &str| &vectors.iter().find(|vector| vector.name == name).unwrap().frame;
+
+    // Length 6, field 2 (add_request) of length 4 holding a = 10 and b = 20
+    assert_eq!(frame("add_request"), &[0x06, 0x12, 0x04, 0x08, 0x0a, 0x10, 0x14]);
+    // Length 4, field 2 (add_response) of length 2 holding result = 30
+    assert_eq!(frame("add_response"), &[0x04, 0x12, 0x02, 0x08, 0x1e]);
+    // A 65537 byte length as a three byte varint
+    assert_eq!(frame("oversized_frame"), &[0x81, 0x80, 0x04]);
+}
+
+#[test]
+fn test_valid_vectors_decode() {
+    for vector in test_vectors().iter().filter(|vector| vector.valid) {
+        let mut bytes = &vector.frame[..];
+        match vector.direction {
+            Direction::ClientToServer => {
+                let message = ClientMessage::decode_length_delimited(&mut bytes)
+                    .unwrap_or_else(|e| panic!("{} does not decode: {}", vector.name, e));
+                assert!(
+                    matches!(
+                        message.message,
+                        Some(client_message::Message::AddRequest(_) | client_message::Message::EchoMessage(_))
+                    ),
+                    "{} decodes to an unexpected message",
+                    vector.name
+                );
+            }
+            Direction::ServerToClient => {
+                let message = ServerMessage::decode_length_delimited(&mut bytes)
+                    .unwrap_or_else(|e| panic!("{} does not decode: {}", vector.name, e));
+                assert!(
+                    matches!(
+                        message.message,
+                        Some(server_message::Message::AddResponse(_) | server_message::Message::ErrorResponse(_))
+                    ),
+                    "{} decodes to an unexpected message",
+                    vector.name
+                );
+            }
+        }
+        assert!(bytes.is_empty(), "{} has trailing bytes", vector.name);
+    }
+}
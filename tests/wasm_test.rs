@@ -0,0 +1,148 @@
+#![cfg(feature = "wasm")]
+
+use embedded_recruitment_task::{
+    config::{ServerConfig, WasmHandlerConfig},
+    message::{server_message, DynamicMessage, EchoMessage, ErrorCode},
+    reflect::DynamicHandler,
+    server::Server,
+    stubs::ClientStubs,
+    wasm::WasmHandler,
+};
+use prost::Message;
+use std::{path::Path, thread};
+#[allow(dead_code)]
+mod client;
+
+// Answer with the request as it came, a DynamicMessage of the same type. Fields must be under 128 bytes
+const ECHO: &str = r#"
+    (local $out i32) (local $at i32)
+    (local.set $out (global.get $next))
+    (i32.store8 (local.get $out) (i32.const 0x0a))
+    (i32.store8 offset=1 (local.get $out) (local.get $type_len))
+    (memory.copy (i32.add (local.get $out) (i32.const 2)) (local.get $type) (local.get $type_len))
+    (local.set $at (i32.add (i32.add (local.get $out) (i32.const 2)) (local.get $type_len)))
+    (i32.store8 (local.get $at) (i32.const 0x12))
+    (i32.store8 offset=1 (local.get $at) (local.get $payload_len))
+    (memory.copy (i32.add (local.get $at) (i32.const 2)) (local.get $payload) (local.get $payload_len))
+    (i64.or
+        (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+        (i64.extend_i32_u (i32.add (i32.add (local.get $type_len) (local.get $payload_len)) (i32.const 4))))
+"#;
+
+// Never return
+const SPIN: &str = "(loop $spin (br $spin)) (unreachable)";
+
+// Ask for 4 MiB more memory and fail without it
+const HOG: &str = "(if (i32.eq (memory.grow (i32.const 64)) (i32.const -1)) (then unreachable)) (i64.const 0)";
+
+// Write a module with a bump allocator and the given body of `handle` to `dir`
+fn module(dir: &Path, name: &str, handle: &str, message_types: &[&str]) -> WasmHandlerConfig {
+    let source = format!(
+        r#"(module
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 1024))
+            (func (export "alloc") (param $len i32) (result i32)
+                (global.get $next)
+                (global.set $next (i32.add (global.get $next) (local.get $len))))
+            (func (export "handle")
+                (param $type i32) (param $type_len i32) (param $payload i32) (param $payload_len i32)
+                (result i64)
+                {}))"#,
+        handle
+    );
+    let path = dir.join(format!("{}.wat", name));
+    std::fs::write(&path, source).unwrap();
+    WasmHandlerConfig {
+        path,
+        message_types: message_types.iter().map(|name| name.to_string()).collect(),
+        fuel: 1_000_000,
+        max_memory: 1 << 20,
+        timeout: None,
+    }
+}
+
+#[test]
+fn test_wasm_handlers_stay_in_their_limits() {
+    let dir = tempfile::tempdir().unwrap();
+    let echo = WasmHandler::load(&module(dir.path(), "echo", ECHO, &[])).expect("Failed to load the echo module");
+    let payload = EchoMessage { content: "sandboxed".to_string() }.encode_to_vec();
+    let response = echo.handle("messages.EchoMessage", &payload).expect("Echo module failed");
+    assert_eq!(response, DynamicMessage::new("messages.EchoMessage", payload));
+
+    // Running out of fuel or memory fails the call, and only the call
+    let spin = WasmHandler::load(&module(dir.path(), "spin", SPIN, &[])).unwrap();
+    let error = spin.handle("messages.EchoMessage", &[]).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::HandlerResourceLimit);
+    assert!(error.message.contains("spin.wat"), "Error should name the module: {}", error.message);
+    let hog = WasmHandler::load(&module(dir.path(), "hog", HOG, &[])).unwrap();
+    assert_eq!(hog.handle("messages.EchoMessage", &[]).unwrap_err().code(), ErrorCode::HandlerResourceLimit);
+
+    // Other failures of the module are internal errors
+    let trap = WasmHandler::load(&module(dir.path(), "trap", "(unreachable)", &[])).unwrap();
+    assert_eq!(trap.handle("messages.EchoMessage", &[]).unwrap_err().code(), ErrorCode::Internal);
+
+    // Every call starts from a fresh instance
+    assert!(echo.handle("messages.EchoMessage", &payload).is_ok(), "Echo module should still work");
+}
+
+#[test]
+fn test_invalid_wasm_modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.wat");
+    std::fs::write(&path, "(module").unwrap();
+    let broken = WasmHandlerConfig {
+        path,
+        message_types: Vec::new(),
+        fuel: 1_000,
+        max_memory: 1 << 16,
+        timeout: None,
+    };
+    assert!(WasmHandler::load(&broken).is_err(), "A module that doesn't compile should fail loading");
+
+    // Modules get no access to the host
+    let importing = module(dir.path(), "importing", "(i64.const 0)", &[]);
+    let source = std::fs::read_to_string(&importing.path).unwrap();
+    let source = source.replacen("(module", r#"(module (import "env" "open" (func (param i32)))"#, 1);
+    std::fs::write(&importing.path, source).unwrap();
+    assert!(WasmHandler::load(&importing).is_err(), "A module with imports should fail loading");
+}
+
+#[test]
+fn test_server_routes_to_wasm_handlers() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let config = ServerConfig {
+        wasm_handlers: vec![
+            module(dir.path(), "echo", ECHO, &["messages.EchoMessage"]),
+            module(dir.path(), "spin", SPIN, &["messages.AddRequest"]),
+        ],
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2493", config).expect("Failed to create server");
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    let mut client = client::Client::new("localhost", 2493, 5000);
+    assert!(client.connect().is_ok(), "Failed to connect to the server");
+    let mut call = |message: DynamicMessage| client.call(message.into()).expect("Failed to call the server");
+
+    let payload = EchoMessage { content: "hi".to_string() }.encode_to_vec();
+    let response = call(DynamicMessage::new("messages.EchoMessage", payload.clone()));
+    assert_eq!(
+        response.message,
+        Some(server_message::Message::DynamicMessage(DynamicMessage::new("messages.EchoMessage", payload)))
+    );
+    // A runaway module is answered with an error and the connection keeps working
+    let response = call(DynamicMessage::new("messages.AddRequest", Vec::new()));
+    let limited = matches!(&response.message,
+        Some(server_message::Message::ErrorResponse(error)) if error.code() == ErrorCode::HandlerResourceLimit);
+    assert!(limited, "Expected a resource limit error, got {}", response);
+    let response = call(DynamicMessage::new("messages.EchoMessage", Vec::new()));
+    assert!(matches!(response.message, Some(server_message::Message::DynamicMessage(_))), "Unexpected {}", response);
+
+    assert!(client.disconnect().is_ok(), "Failed to disconnect from the server");
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+}
@@ -0,0 +1,127 @@
+use embedded_recruitment_task::{
+    clock::ManualClock,
+    watchdog::{Stall, Watchdog},
+};
+use std::{sync::Arc, time::Duration};
+
+#[test]
+fn test_silent_components_are_stalled() {
+    let clock = Arc::new(ManualClock::new());
+    let watchdog = Watchdog::new(Duration::from_secs(5), clock.clone());
+    let acceptor = watchdog.register("acceptor");
+    let worker = watchdog.register("worker");
+    assert!(watchdog.stalled().is_empty(), "Freshly registered components count as alive");
+
+    // Only the worker keeps petting
+    clock.advance(Duration::from_secs(4));
+    worker.pet();
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(
+        watchdog.stalled(),
+        vec![Stall {
+            component: "acceptor".to_string(),
+            since: Duration::from_secs(6),
+        }]
+    );
+
+    // A pet ends the stall
+    acceptor.pet();
+    assert!(watchdog.stalled().is_empty());
+}
+
+#[test]
+fn test_dropped_heartbeat_unregisters() {
+    let clock = Arc::new(ManualClock::new());
+    let watchdog = Watchdog::new(Duration::from_secs(1), clock.clone());
+    let heartbeat = watchdog.register("connection");
+    assert_eq!(watchdog.len(), 1);
+
+    // A connection that closed is never reported, however long ago it was last seen
+    drop(heartbeat);
+    clock.advance(Duration::from_secs(10));
+    assert!(watchdog.stalled().is_empty());
+    assert!(watchdog.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_notify_systemd() {
+    use embedded_recruitment_task::watchdog::notify_systemd;
+    use std::os::unix::net::UnixDatagram;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("notify.sock");
+    let socket = UnixDatagram::bind(&path).unwrap();
+    // The only test in this binary touching the variable
+    std::env::set_var("NOTIFY_SOCKET", &path);
+    assert!(notify_systemd("WATCHDOG=1").expect("Failed to notify"));
+    std::env::remove_var("NOTIFY_SOCKET");
+
+    let mut buffer = [0u8; 64];
+    let received = socket.recv(&mut buffer).unwrap();
+    assert_eq!(&buffer[..received], b"WATCHDOG=1");
+    assert!(!notify_systemd("WATCHDOG=1").unwrap(), "Nothing is sent without a socket");
+}
+
+#[cfg(feature = "reflect")]
+#[test]
+fn test_wedged_connection_triggers_callback() {
+    use embedded_recruitment_task::{
+        config::{ServerConfig, WatchdogConfig},
+        message::{ClientMessage, DynamicMessage, EchoMessage},
+        server::Server,
+    };
+    use prost::Message;
+    use std::{io::Write, net::TcpStream, sync::Mutex, thread, time::Instant};
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let config = ServerConfig {
+        watchdog: Some(WatchdogConfig {
+            timeout: Duration::from_millis(200),
+            check_interval: Duration::from_millis(50),
+            notify_systemd: false,
+        }),
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config("localhost:2476", config).expect("Failed to create server");
+    // A handler hanging far beyond the timeout, like one stuck on a lock
+    server.route_dynamic("messages.EchoMessage", |type_name: &str, payload: &[u8]| {
+        thread::sleep(Duration::from_millis(800));
+        Ok(DynamicMessage::new(type_name, payload.to_vec()))
+    });
+    let stalls = Arc::new(Mutex::new(Vec::new()));
+    {
+        let stalls = Arc::clone(&stalls);
+        server.on_watchdog_stall(move |stalled: &[Stall]| stalls.lock().unwrap().extend_from_slice(stalled));
+    }
+    let handle = {
+        let server = server.clone();
+        thread::spawn(move || server.run().expect("Server encountered an error"))
+    };
+
+    // An idle connection keeps petting through its read timeouts
+    let _idle = TcpStream::connect("localhost:2476").expect("Failed to connect to the server");
+    thread::sleep(Duration::from_millis(300));
+    assert!(stalls.lock().unwrap().is_empty(), "Idle components must not stall");
+
+    let mut stream = TcpStream::connect("localhost:2476").expect("Failed to connect to the server");
+    let payload = EchoMessage { content: "slow".to_string() }.encode_to_vec();
+    let request = ClientMessage::from(DynamicMessage::new("messages.EchoMessage", payload));
+    stream.write_all(&request.encode_length_delimited_to_vec()).unwrap();
+    let local = stream.local_addr().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while stalls.lock().unwrap().is_empty() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(20));
+    }
+    server.stop();
+    assert!(handle.join().is_ok(), "Server thread panicked or failed to join");
+
+    let stalls = stalls.lock().unwrap();
+    assert!(!stalls.is_empty(), "The wedged connection should have been reported");
+    assert!(
+        stalls.iter().all(|stall| stall.component == format!("connection {}", local)),
+        "Only the wedged connection should stall: {:?}",
+        stalls
+    );
+}
@@ -0,0 +1,92 @@
+// Import necessary modules and crates
+use std::{
+    env,
+    path::{Path, PathBuf}, // Repository root
+    process::{self, Command}, // Cargo invocations
+};
+
+const USAGE: &str = "\
+Usage: cargo xtask <task>
+
+Tasks:
+    proto         Regenerate the prost types and client stubs from proto/messages.proto
+    vectors       Re-export the interop vectors and golden responses in tests/fixtures after an intended wire change
+    schema-check  Check the schema against proto/messages.lock, the last released schema
+    schema-lock   Record the current schema in proto/messages.lock, only when cutting a release
+    message       Everything needed after adding a message: proto, vectors and schema-check
+";
+
+// Run one task, the first argument names it
+fn main() {
+    let task = env::args().nth(1).unwrap_or_default();
+    let result = match task.as_str() {
+        "proto" => proto(),
+        "vectors" => vectors(),
+        "schema-check" => schema_check(),
+        "schema-lock" => schema_lock(),
+        "message" => proto().and_then(|()| vectors()).and_then(|()| schema_check()),
+        "" | "help" | "-h" | "--help" => {
+            print!("{}", USAGE);
+            return;
+        }
+        other => Err(format!("Unknown task `{}`\n\n{}", other, USAGE)),
+    };
+    if let Err(e) = result {
+        eprintln!("xtask {}: {}", task, e);
+        process::exit(1);
+    }
+}
+
+// The build script runs prost-build whenever the schema changed; building every feature checks that
+// all generated code, the serde derives included, still compiles
+fn proto() -> Result<(), String> {
+    if env::var_os("PROTOC").is_none() && Command::new("protoc").arg("--version").output().is_err() {
+        return Err("protoc not found, install it or point PROTOC at it".to_string());
+    }
+    cargo(&["build", "--package", "embedded-recruitment-task", "--all-features"], &[])
+}
+
+// The fixture tests rewrite their fixtures before comparing when UPDATE_FIXTURES is set
+fn vectors() -> Result<(), String> {
+    cargo(
+        &["test", "--features", "vectors", "--test", "vectors_test", "--test", "golden_test"],
+        &[("UPDATE_FIXTURES", "1")],
+    )
+}
+
+fn schema_check() -> Result<(), String> {
+    cargo(&["test", "--test", "protocol_test", "test_schema_is_compatible_with_release"], &[])
+}
+
+// The compatibility test records the lock first when UPDATE_SCHEMA_LOCK is set
+fn schema_lock() -> Result<(), String> {
+    cargo(
+        &["test", "--test", "protocol_test", "test_schema_is_compatible_with_release"],
+        &[("UPDATE_SCHEMA_LOCK", "1")],
+    )
+}
+
+// Run cargo in the repository root with extra environment variables
+fn cargo(args: &[&str], envs: &[(&str, &str)]) -> Result<(), String> {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    println!("Running cargo {}", args.join(" "));
+    let status = Command::new(cargo)
+        .args(args)
+        .envs(envs.iter().copied())
+        .current_dir(root())
+        .status()
+        .map_err(|e| format!("Failed to run cargo: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("cargo {} failed", args.join(" ")))
+    }
+}
+
+// The repository root, one level above this crate
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives inside the repository")
+        .to_path_buf()
+}